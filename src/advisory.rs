@@ -0,0 +1,120 @@
+// Security advisories: check the dependency tree of the version being built
+// against the RustSec advisory database, via cargo-audit.
+
+use std::process::Command;
+
+use serde_json::Value;
+
+use crate::{Installer, InstallerError};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum AdvisoryCheck {
+    /// Don't check (the default; cargo-audit is an extra tool).
+    #[default]
+    Off,
+    /// Report known-vulnerable dependencies but install anyway.
+    Warn,
+    /// Refuse to install a version with known-vulnerable dependencies.
+    Deny,
+}
+
+#[derive(Debug, Clone)]
+pub struct Advisory {
+    pub id: String,
+    pub package: String,
+    pub version: String,
+    pub title: String,
+    /// Version requirements that fix the issue, e.g. `>=1.2.3`.
+    pub patched: Vec<String>,
+}
+
+fn parse_report(report: &Value) -> Vec<Advisory> {
+    let list = report["vulnerabilities"]["list"].as_array().cloned().unwrap_or_default();
+    list.iter()
+        .map(|entry| {
+            let text = |value: &Value| value.as_str().unwrap_or_default().to_string();
+            Advisory {
+                id: text(&entry["advisory"]["id"]),
+                package: text(&entry["package"]["name"]),
+                version: text(&entry["package"]["version"]),
+                title: text(&entry["advisory"]["title"]),
+                patched: entry["versions"]["patched"]
+                    .as_array()
+                    .map(|p| p.iter().filter_map(|v| v.as_str().map(str::to_string)).collect())
+                    .unwrap_or_default(),
+            }
+        })
+        .collect()
+}
+
+impl Installer {
+    pub fn with_advisory_check(mut self, check: AdvisoryCheck) -> Self {
+        self.advisories = check;
+        self
+    }
+
+    /// Run cargo-audit on the scratch checkout's Cargo.lock. Only fails when
+    /// advisories are denied and either some were found or the check couldn't run.
+    pub(crate) fn check_advisories(&self) -> Result<(), InstallerError> {
+        if self.advisories == AdvisoryCheck::Off {
+            return Ok(());
+        }
+        let deny = self.advisories == AdvisoryCheck::Deny;
+
+        let unavailable = |reason: String| {
+            if deny {
+                self.log_error(&reason);
+                Err(InstallerError::Cargo("Advisory check could not run".to_string()))
+            } else {
+                self.log_warning(&format!("{}; skipping the advisory check", reason));
+                Ok(())
+            }
+        };
+
+        self.log_info("Checking dependencies against the RustSec advisory database...");
+        let clone_dir = self.temp_dir.join("kopi-lang");
+        let output = match self.run_command(
+            Command::new("cargo")
+                .args(["audit", "--json", "--file", "Cargo.lock"])
+                .current_dir(&clone_dir),
+        ) {
+            Ok(output) => output,
+            Err(InstallerError::Cancelled) => return Err(InstallerError::Cancelled),
+            Err(e) => return unavailable(format!("Could not run cargo audit: {:?}", e)),
+        };
+
+        // cargo-audit exits non-zero when it finds something, so judge by the report
+        let Ok(report) = serde_json::from_slice::<Value>(&output.stdout) else {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            if stderr.contains("no such command") {
+                let result = unavailable("cargo-audit is not installed".to_string());
+                self.log_info("Install it with: cargo install cargo-audit --locked");
+                return result;
+            }
+            return unavailable(format!("cargo audit failed: {}", stderr.trim()));
+        };
+
+        let advisories = parse_report(&report);
+        if advisories.is_empty() {
+            self.log_success("No known vulnerabilities in the dependency tree");
+            return Ok(());
+        }
+
+        let log = |msg: &str| if deny { self.log_error(msg) } else { self.log_warning(msg) };
+        log(&format!("{} known-vulnerable dependenc{}:", advisories.len(), if advisories.len() == 1 { "y" } else { "ies" }));
+        for advisory in &advisories {
+            self.print_line(&format!("  {} {} {}: {}", advisory.id, advisory.package, advisory.version, advisory.title));
+            if !advisory.patched.is_empty() {
+                self.print_line(&format!("      fixed in {}", advisory.patched.join(", ")));
+            }
+        }
+
+        if deny {
+            return Err(InstallerError::Cargo(format!(
+                "{} known-vulnerable dependencies (--deny-advisories)",
+                advisories.len()
+            )));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,130 @@
+// Announcement mode: progress as a few whole lines, for screen readers and
+// terminals that can't redraw. Each install phase is announced when it
+// starts and when it ends, and a phase that has run here before (so its
+// usual length is known, see `timing`) and takes a while is also announced
+// at 25, 50 and 75% of that. Nothing is redrawn: there is no cursor movement
+// and no carriage return, so every line is read out once and the scrollback
+// stays readable.
+//
+// Picked with `--announce` or `[theme] progress = "announce"`; when neither
+// is set it is on for TERM=dumb and when the desktop says assistive
+// technology is running (ACCESSIBILITY_ENABLED=1, as GNOME sets it).
+// `progress = "standard"` keeps the usual output everywhere.
+
+use std::env;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use crate::capabilities::dumb_terminal;
+use crate::timing::{Phase, format_duration};
+use crate::{Installer, InstallerError};
+
+/// Percentages of a phase's usual length that are announced.
+const MILESTONES: [u32; 3] = [25, 50, 75];
+/// Phases usually shorter than this get no milestones; they'd only be noise.
+const MILESTONE_MIN: Duration = Duration::from_secs(20);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ProgressMode {
+    /// The usual messages, with an estimate before the install starts.
+    Standard,
+    /// One line per phase transition and milestone.
+    Announce,
+}
+
+impl fmt::Display for ProgressMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ProgressMode::Standard => "standard",
+            ProgressMode::Announce => "announce",
+        })
+    }
+}
+
+impl FromStr for ProgressMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "standard" => Ok(ProgressMode::Standard),
+            "announce" => Ok(ProgressMode::Announce),
+            _ => Err(format!("unknown progress mode '{}' (expected standard or announce)", s)),
+        }
+    }
+}
+
+/// The mode to use when none was chosen.
+pub fn detect() -> ProgressMode {
+    let dumb = dumb_terminal();
+    let assistive = env::var("ACCESSIBILITY_ENABLED").is_ok_and(|value| value == "1");
+    if dumb || assistive { ProgressMode::Announce } else { ProgressMode::Standard }
+}
+
+/// What a phase is called in announcements.
+fn describe(phase: Phase) -> &'static str {
+    match phase {
+        Phase::Fetch => "Fetching the source",
+        Phase::Build => "Building",
+        Phase::Install => "Installing",
+    }
+}
+
+impl Installer {
+    /// Show progress as announcements (or not) whatever the settings say.
+    pub fn with_progress_mode(mut self, mode: ProgressMode) -> Self {
+        self.progress_mode = Some(mode);
+        self
+    }
+
+    /// The chosen progress mode, else the one in the settings, else the
+    /// detected one.
+    pub fn effective_progress_mode(&self) -> ProgressMode {
+        self.progress_mode.or(self.config.theme.progress).unwrap_or_else(detect)
+    }
+
+    /// Run `phase`, announcing its start, its milestones when its usual
+    /// length is known, and its end.
+    pub(crate) fn announced<T>(
+        &self,
+        phase: Phase,
+        run: impl FnOnce() -> Result<T, InstallerError>,
+    ) -> Result<T, InstallerError> {
+        let step = Phase::ALL.iter().position(|p| *p == phase).unwrap_or(0) + 1;
+        let estimate = self.phase_estimate(phase);
+        let about = estimate.map_or(String::new(), |estimate| format!(", usually {}", format_duration(estimate)));
+        self.log_info(&format!("{}: step {} of {}{}", describe(phase), step, Phase::ALL.len(), about));
+
+        let started = Instant::now();
+        let (done, finished) = mpsc::channel::<()>();
+        let result = thread::scope(|scope| {
+            if let Some(estimate) = estimate.filter(|estimate| *estimate >= MILESTONE_MIN) {
+                scope.spawn(move || {
+                    for percent in MILESTONES {
+                        let due = estimate.mul_f64(f64::from(percent) / 100.0);
+                        let wait = due.saturating_sub(started.elapsed());
+                        // Anything but a timeout means the phase is over
+                        if finished.recv_timeout(wait) != Err(mpsc::RecvTimeoutError::Timeout) {
+                            return;
+                        }
+                        self.log_info(&format!("{}: {}%", describe(phase), percent));
+                    }
+                });
+            }
+            let result = run();
+            drop(done);
+            result
+        });
+        let took = format_duration(started.elapsed());
+        match &result {
+            Ok(_) => self.log_info(&format!("{}: done in {}", describe(phase), took)),
+            Err(_) => self.log_info(&format!("{}: stopped after {}", describe(phase), took)),
+        }
+        result
+    }
+}
@@ -0,0 +1,103 @@
+// Async front for the installer, used by the daemon and HTTP API paths
+//
+// The heavy lifting is still git and cargo child processes, so each operation
+// runs on tokio's blocking pool while callers await it and consume progress
+// from a broadcast channel.
+
+use std::io;
+use std::sync::Arc;
+
+use tokio::sync::{Mutex, broadcast};
+use tokio::task;
+
+use crate::toolchain::{self, exe_name};
+use crate::{CancellationToken, Installer, InstallerError, ProgressEvent, Toolchain};
+
+/// Events buffered per subscriber before slow receivers start lagging.
+const EVENT_CAPACITY: usize = 256;
+
+#[derive(Clone)]
+pub struct AsyncInstaller {
+    installer: Arc<Installer>,
+    events: broadcast::Sender<ProgressEvent>,
+    // Install and update write to the same directories, so only one runs at a time
+    write_lock: Arc<Mutex<()>>,
+}
+
+impl AsyncInstaller {
+    pub fn new(installer: Installer) -> Self {
+        let (events, _) = broadcast::channel(EVENT_CAPACITY);
+        let sender = events.clone();
+        let installer = installer.with_observer(Arc::new(move |event: &ProgressEvent| {
+            let _ = sender.send(event.clone());
+        }));
+
+        AsyncInstaller {
+            installer: Arc::new(installer),
+            events,
+            write_lock: Arc::new(Mutex::new(())),
+        }
+    }
+
+    /// Stream of progress messages from every operation started after subscribing.
+    pub fn subscribe(&self) -> broadcast::Receiver<ProgressEvent> {
+        self.events.subscribe()
+    }
+
+    pub fn is_installed(&self) -> bool {
+        self.installer.is_installed()
+    }
+
+    /// Whether an install or update is currently running.
+    pub fn is_busy(&self) -> bool {
+        self.write_lock.try_lock().is_err()
+    }
+
+    pub fn cancellation_token(&self) -> CancellationToken {
+        self.installer.cancellation_token()
+    }
+
+    /// Install `version` without prompting; does nothing if it is already installed.
+    pub async fn install(&self, version: String) -> Result<(), InstallerError> {
+        let _guard = self.write_lock.lock().await;
+        self.run_blocking(move |installer| {
+            // Other processes, such as the CLI, may be changing the install too
+            toolchain::check_version_name(&version)?;
+            let _lock = installer.lock_install(None)?;
+            if installer.version_dir(&version).join(exe_name("kopi")).exists() {
+                installer.log_warning(&format!("Kopi {} is already installed, use update to rebuild it", version));
+                return Ok(());
+            }
+            let result = installer.install_version(&version);
+            installer.cleanup()?;
+            result
+        })
+        .await
+    }
+
+    pub async fn update(&self) -> Result<(), InstallerError> {
+        let _guard = self.write_lock.lock().await;
+        self.run_blocking(|installer| {
+            let _lock = installer.lock_install(None)?;
+            let result = installer.update();
+            installer.cleanup()?;
+            result
+        })
+        .await
+    }
+
+    pub async fn list(&self) -> Result<Vec<Toolchain>, InstallerError> {
+        self.run_blocking(|installer| installer.list()).await
+    }
+
+    async fn run_blocking<T, F>(&self, op: F) -> Result<T, InstallerError>
+    where
+        T: Send + 'static,
+        F: FnOnce(&Installer) -> Result<T, InstallerError> + Send + 'static,
+    {
+        let installer = Arc::clone(&self.installer);
+        task::spawn_blocking(move || op(&installer))
+            .await
+            .map_err(|e| InstallerError::Io(io::Error::other(e)))?
+    }
+}
@@ -0,0 +1,404 @@
+// `kipper auth`: the credentials kipper sends on its own requests, kept in
+// the OS keyring rather than in a config file:
+//
+//     remote-cache  bearer token for the remote build cache (`[cache] remote`)
+//     github        token for downloads from GitHub (`--source github:...`)
+//     proxy         USER:PASSWORD for the proxy in https_proxy / http_proxy
+//
+// `kipper auth login NAME` reads the secret from the terminal without echoing
+// it (or from stdin when piped), `logout` removes it and `status` tells which
+// are set and where from. Each credential is its own keyring item, service
+// "kipper" and account NAME, so it can be inspected or revoked in the
+// keyring's own tools, and each is looked up only by the request it is for:
+// the cache token never goes to GitHub, and none of them is passed on to git
+// or cargo. An environment variable of the same credential wins over the
+// keyring, which keeps CI configurations working unchanged.
+//
+// The keyrings are reached through their command-line front ends, so no
+// system library is linked: `secret-tool` (libsecret's, for the Secret
+// Service of GNOME Keyring or KWallet), `security` on macOS, whose items
+// only the kipper executable that stored them may read without asking, and
+// PowerShell's PasswordVault for the Windows Credential Manager. Where none
+// is available, `login` fails rather than falling back to a plaintext file.
+
+use std::env;
+use std::io::{self, IsTerminal, Write};
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+use serde::Serialize;
+
+use crate::redact;
+use crate::toolchain::exe_name;
+use crate::{Installer, InstallerError};
+
+/// The keyring service every credential is stored under.
+const SERVICE: &str = "kipper";
+
+/// A credential kipper knows how to use.
+#[derive(Debug, Clone, Copy)]
+pub struct Credential {
+    pub name: &'static str,
+    /// The environment variables that set it instead, first one set wins.
+    pub env: &'static [&'static str],
+    pub description: &'static str,
+}
+
+pub const CREDENTIALS: [Credential; 3] = [
+    Credential {
+        name: "remote-cache",
+        env: &[crate::remote_cache::REMOTE_CACHE_TOKEN_ENV],
+        description: "bearer token for the remote build cache",
+    },
+    Credential {
+        name: "github",
+        env: &["GITHUB_TOKEN"],
+        description: "token for downloads from GitHub",
+    },
+    Credential {
+        name: "proxy",
+        env: &["KIPPER_PROXY_AUTH"],
+        description: "USER:PASSWORD for the HTTP(S) proxy",
+    },
+];
+
+/// The credential called `name`.
+pub fn credential(name: &str) -> Result<&'static Credential, InstallerError> {
+    CREDENTIALS.iter().find(|c| c.name == name).ok_or_else(|| {
+        let names: Vec<&str> = CREDENTIALS.iter().map(|c| c.name).collect();
+        InstallerError::PathError(format!("unknown credential '{}' (use {})", name, names.join(", ")))
+    })
+}
+
+/// The OS keyrings kipper can store credentials in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Keyring {
+    SecretService,
+    Keychain,
+    CredentialManager,
+}
+
+impl Keyring {
+    /// This system's keyring, if its front end is installed.
+    pub fn detect() -> Option<Keyring> {
+        let keyring = if cfg!(windows) {
+            Keyring::CredentialManager
+        } else if cfg!(target_os = "macos") {
+            Keyring::Keychain
+        } else {
+            Keyring::SecretService
+        };
+        on_path(keyring.program()).then_some(keyring)
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Keyring::SecretService => "the Secret Service",
+            Keyring::Keychain => "the login keychain",
+            Keyring::CredentialManager => "the Windows Credential Manager",
+        }
+    }
+
+    fn program(self) -> &'static str {
+        match self {
+            Keyring::SecretService => "secret-tool",
+            Keyring::Keychain => "security",
+            Keyring::CredentialManager => "powershell",
+        }
+    }
+
+    /// The command storing the secret it is given on stdin as `account`.
+    fn store(self, account: &str) -> Command {
+        let mut command = Command::new(self.program());
+        match self {
+            Keyring::SecretService => {
+                command.args(["store", "--label", &format!("kipper: {}", account)]);
+                command.args(["service", SERVICE, "account", account]);
+            }
+            // Commands on stdin keep the secret out of the process list
+            Keyring::Keychain => {
+                command.arg("-i");
+            }
+            Keyring::CredentialManager => {
+                command.args(["-NoProfile", "-NonInteractive", "-Command"]);
+                command.arg(format!(
+                    "{} $vault.Add((New-Object Windows.Security.Credentials.PasswordCredential('{}', '{}', [Console]::In.ReadToEnd())))",
+                    VAULT, SERVICE, account
+                ));
+            }
+        }
+        command
+    }
+
+    /// What to write to the store command's stdin.
+    fn store_input(self, account: &str, secret: &str) -> Result<String, InstallerError> {
+        match self {
+            Keyring::Keychain => {
+                if secret.contains(['"', '\\']) {
+                    return Err(InstallerError::PathError(
+                        "The keychain can't be given a secret with \" or \\ in it".to_string(),
+                    ));
+                }
+                let program = env::current_exe()?;
+                Ok(format!(
+                    "add-generic-password -U -s {} -a {} -T \"{}\" -w \"{}\"\n",
+                    SERVICE,
+                    account,
+                    program.display(),
+                    secret
+                ))
+            }
+            _ => Ok(secret.to_string()),
+        }
+    }
+
+    /// The command printing the secret stored as `account`.
+    fn lookup(self, account: &str) -> Command {
+        let mut command = Command::new(self.program());
+        match self {
+            Keyring::SecretService => {
+                command.args(["lookup", "service", SERVICE, "account", account]);
+            }
+            Keyring::Keychain => {
+                command.args(["find-generic-password", "-s", SERVICE, "-a", account, "-w"]);
+            }
+            Keyring::CredentialManager => {
+                command.args(["-NoProfile", "-NonInteractive", "-Command"]);
+                command.arg(format!(
+                    "{} $c = $vault.Retrieve('{}', '{}'); $c.RetrievePassword(); [Console]::Out.Write($c.Password)",
+                    VAULT, SERVICE, account
+                ));
+            }
+        }
+        command
+    }
+
+    /// The command removing what is stored as `account`.
+    fn remove(self, account: &str) -> Command {
+        let mut command = Command::new(self.program());
+        match self {
+            Keyring::SecretService => {
+                command.args(["clear", "service", SERVICE, "account", account]);
+            }
+            Keyring::Keychain => {
+                command.args(["delete-generic-password", "-s", SERVICE, "-a", account]);
+            }
+            Keyring::CredentialManager => {
+                command.args(["-NoProfile", "-NonInteractive", "-Command"]);
+                command.arg(format!("{} $vault.Remove($vault.Retrieve('{}', '{}'))", VAULT, SERVICE, account));
+            }
+        }
+        command
+    }
+}
+
+/// PowerShell that loads the WinRT PasswordVault into `$vault`.
+const VAULT: &str = "[void][Windows.Security.Credentials.PasswordVault,Windows.Security.Credentials,ContentType=WindowsRuntime]; $vault = New-Object Windows.Security.Credentials.PasswordVault;";
+
+/// Whether `program` is in a dir on PATH.
+fn on_path(program: &str) -> bool {
+    env::var_os("PATH").is_some_and(|path| env::split_paths(&path).any(|dir| is_file(&dir.join(exe_name(program)))))
+}
+
+fn is_file(path: &Path) -> bool {
+    path.metadata().is_ok_and(|metadata| metadata.is_file())
+}
+
+/// Where a credential is set, for `kipper auth status`.
+#[derive(Debug, Clone, Serialize)]
+pub struct CredentialStatus {
+    pub name: String,
+    pub description: String,
+    /// The environment variable or keyring it comes from, None when unset.
+    pub source: Option<String>,
+}
+
+/// Read a secret for `kipper auth login`: typed at the terminal without
+/// echo, or everything on stdin when it is piped.
+pub fn read_secret(prompt: &str) -> io::Result<String> {
+    let stdin = io::stdin();
+    if !stdin.is_terminal() {
+        return Ok(io::read_to_string(stdin)?.trim_end_matches(['\r', '\n']).to_string());
+    }
+    eprint!("{}: ", prompt);
+    io::stderr().flush()?;
+    let echo_off = cfg!(unix) && stty("-echo");
+    let mut secret = String::new();
+    let read = stdin.read_line(&mut secret);
+    if echo_off {
+        stty("echo");
+        eprintln!();
+    }
+    read?;
+    Ok(secret.trim_end_matches(['\r', '\n']).to_string())
+}
+
+/// Change the terminal's settings with stty.
+fn stty(setting: &str) -> bool {
+    Command::new("stty").arg(setting).stdin(Stdio::inherit()).status().is_ok_and(|status| status.success())
+}
+
+impl Installer {
+    /// The secret of credential `name`: its environment variable if set,
+    /// else what the keyring holds, else None.
+    pub fn credential(&self, name: &str) -> Option<String> {
+        let credential = credential(name).ok()?;
+        if let Some(value) = credential.env.iter().find_map(|var| env::var(var).ok().filter(|v| !v.is_empty())) {
+            return Some(value);
+        }
+        let keyring = Keyring::detect()?;
+        match self.keyring_lookup(keyring, name) {
+            Ok(secret) => secret,
+            Err(e) => {
+                self.log_debug("auth", &format!("Could not read {} from {}: {:?}", name, keyring.name(), e));
+                None
+            }
+        }
+    }
+
+    fn keyring_lookup(&self, keyring: Keyring, name: &str) -> Result<Option<String>, InstallerError> {
+        let mut command = keyring.lookup(name);
+        self.trace_command(&command);
+        let output = command.stdin(Stdio::null()).stderr(Stdio::null()).output()?;
+        // Every front end fails the same way for "not found" as for a locked keyring
+        let secret = String::from_utf8_lossy(&output.stdout).trim_end_matches(['\r', '\n']).to_string();
+        if !output.status.success() || secret.is_empty() {
+            return Ok(None);
+        }
+        redact::register_secret(&secret);
+        Ok(Some(secret))
+    }
+
+    fn keyring(&self) -> Result<Keyring, InstallerError> {
+        Keyring::detect().ok_or_else(|| {
+            let front_end = if cfg!(windows) {
+                "PowerShell"
+            } else if cfg!(target_os = "macos") {
+                "the security tool"
+            } else {
+                "secret-tool (libsecret-tools or libsecret, with GNOME Keyring or KWallet running)"
+            };
+            InstallerError::PathError(format!(
+                "No keyring to store credentials in: kipper needs {}. Set the credential's environment variable instead",
+                front_end
+            ))
+        })
+    }
+
+    /// `kipper auth login`: store `secret` as credential `name`.
+    pub fn login(&self, name: &str, secret: &str) -> Result<(), InstallerError> {
+        let credential = credential(name)?;
+        if secret.is_empty() {
+            return Err(InstallerError::PathError(format!("No {} was given", credential.description)));
+        }
+        if name == "proxy" && !secret.contains(':') {
+            return Err(InstallerError::PathError("The proxy credential is USER:PASSWORD".to_string()));
+        }
+        redact::register_secret(secret);
+        let keyring = self.keyring()?;
+        let mut command = keyring.store(name);
+        self.trace_command(&command);
+        let mut child = command.stdin(Stdio::piped()).stdout(Stdio::null()).stderr(Stdio::piped()).spawn()?;
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(keyring.store_input(name, secret)?.as_bytes())?;
+        }
+        let output = child.wait_with_output()?;
+        if !output.status.success() {
+            let error = String::from_utf8_lossy(&output.stderr);
+            return Err(InstallerError::PathError(format!(
+                "Could not store {} in {}: {}",
+                name,
+                keyring.name(),
+                error.trim_end()
+            )));
+        }
+        self.log_success(&format!("Stored the {} in {}", credential.description, keyring.name()));
+        if let Some(var) = credential.env.iter().find(|var| env::var_os(var).is_some()) {
+            self.log_warning(&format!("{} is set, and is used instead while it is", var));
+        }
+        Ok(())
+    }
+
+    /// `kipper auth logout`: remove credential `name` from the keyring.
+    /// Returns whether it was there.
+    pub fn logout(&self, name: &str) -> Result<bool, InstallerError> {
+        credential(name)?;
+        let keyring = self.keyring()?;
+        if self.keyring_lookup(keyring, name)?.is_none() {
+            self.log_info(&format!("No {} credential is stored in {}", name, keyring.name()));
+            return Ok(false);
+        }
+        let mut command = keyring.remove(name);
+        self.trace_command(&command);
+        let output = command.stdin(Stdio::null()).output()?;
+        if !output.status.success() {
+            let error = String::from_utf8_lossy(&output.stderr);
+            return Err(InstallerError::PathError(format!(
+                "Could not remove {} from {}: {}",
+                name,
+                keyring.name(),
+                error.trim_end()
+            )));
+        }
+        self.log_success(&format!("Removed the {} credential from {}", name, keyring.name()));
+        Ok(true)
+    }
+
+    /// `kipper auth status`: every credential and where it is set.
+    pub fn auth_status(&self) -> Vec<CredentialStatus> {
+        let keyring = Keyring::detect();
+        CREDENTIALS
+            .iter()
+            .map(|credential| {
+                let from_env = credential.env.iter().find(|var| env::var(var).is_ok_and(|v| !v.is_empty()));
+                let source = match (from_env, keyring) {
+                    (Some(var), _) => Some(format!("${}", var)),
+                    (None, Some(keyring)) => {
+                        matches!(self.keyring_lookup(keyring, credential.name), Ok(Some(_))).then(|| keyring.name().to_string())
+                    }
+                    (None, None) => None,
+                };
+                CredentialStatus {
+                    name: credential.name.to_string(),
+                    description: credential.description.to_string(),
+                    source,
+                }
+            })
+            .collect()
+    }
+
+    /// An HTTP client for kipper's own requests, going through the proxy
+    /// with the proxy credential when both are set.
+    #[cfg(feature = "http")]
+    pub(crate) fn http_client(&self, timeout: std::time::Duration) -> reqwest::Result<reqwest::blocking::Client> {
+        let mut builder = reqwest::blocking::Client::builder().timeout(timeout);
+        // A proxy URL with its own credentials keeps them
+        let proxy_url = |var: &str| {
+            env::var(var)
+                .or_else(|_| env::var(var.to_uppercase()))
+                .ok()
+                .filter(|url| !url.is_empty() && !url.contains('@'))
+        };
+        let (https, http) = (proxy_url("https_proxy"), proxy_url("http_proxy"));
+        if (https.is_some() || http.is_some())
+            && let Some(auth) = self.credential("proxy")
+            && let Some((user, password)) = auth.split_once(':')
+        {
+            let proxies = [https.map(reqwest::Proxy::https), http.map(reqwest::Proxy::http)];
+            for proxy in proxies.into_iter().flatten() {
+                builder = builder.proxy(proxy?.basic_auth(user, password).no_proxy(reqwest::NoProxy::from_env()));
+            }
+        }
+        builder.build()
+    }
+}
+
+/// `read_secret` for credential `name`'s prompt.
+pub fn prompt_for(name: &str) -> Result<String, InstallerError> {
+    let credential = credential(name)?;
+    let mut secret = read_secret(&format!("{} ({})", credential.description, name))?;
+    // Pasted tokens often come with a trailing space
+    secret.truncate(secret.trim_end().len());
+    Ok(secret)
+}
@@ -0,0 +1,252 @@
+// Built binaries cache: every build's binaries are kept under
+// `<cache dir>/bin/<commit>-<rustc>-<target>/`, so switching back to a version
+// built before, or reinstalling one after an uninstall, copies the binaries
+// back instead of compiling again. `build.json` in each entry records the
+// profile and build options it was made with and the size and FNV-1a hash of
+// each binary; an entry is only used when it was built for the same options
+// and at least the current profile, and every binary still hashes the same.
+// Entries missing here can come from a remote cache (see `remote_cache`);
+// those have a `prebuilt.json` saying whether their attestations were
+// checked (see `provenance`), which the versions installed from them record.
+
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Component, Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::manifest::Manifest;
+use crate::provenance::Attestation;
+use crate::remote_cache::sibling;
+use crate::state::Profile;
+use crate::toolchain::{COMPONENTS, exe_name};
+use crate::{Installer, InstallerError};
+
+pub const BIN_CACHE_DIR: &str = "bin";
+const BUILD_FILE: &str = "build.json";
+/// Written into entries downloaded from the remote cache.
+const PREBUILT_FILE: &str = "prebuilt.json";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CachedBuild {
+    /// The profile built for; a build for a larger profile serves smaller ones.
+    profile: Profile,
+    /// Build options the binaries were built with.
+    options: BTreeMap<String, bool>,
+    /// Each binary's size and hash, by tool name.
+    binaries: BTreeMap<String, CachedBinary>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct CachedBinary {
+    size: u64,
+    fnv1a: String,
+}
+
+impl CachedBinary {
+    fn of(path: &Path) -> io::Result<CachedBinary> {
+        let mut file = File::open(path)?;
+        let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+        let mut size = 0;
+        let mut buf = [0; 64 * 1024];
+        loop {
+            let read = file.read(&mut buf)?;
+            if read == 0 {
+                break;
+            }
+            size += read as u64;
+            for byte in &buf[..read] {
+                hash = (hash ^ u64::from(*byte)).wrapping_mul(0x0000_0100_0000_01b3);
+            }
+        }
+        Ok(CachedBinary {
+            size,
+            fnv1a: format!("{:016x}", hash),
+        })
+    }
+}
+
+/// Whether a binary name from `build.json`, which may come from a remote
+/// cache, names a file right inside the entry and the release dir.
+fn is_plain_name(name: &str) -> bool {
+    let mut components = Path::new(name).components();
+    matches!((components.next(), components.next()), (Some(Component::Normal(_)), None)) && !name.contains(['/', '\\'])
+}
+
+/// `build.json` of the entry in `dir`, if it can be read and every binary
+/// name in it is a plain file name.
+#[cfg(feature = "http")]
+fn read_build(dir: &Path) -> Option<CachedBuild> {
+    let build: CachedBuild = serde_json::from_str(&fs::read_to_string(dir.join(BUILD_FILE)).ok()?).ok()?;
+    build.binaries.keys().all(|name| is_plain_name(name)).then_some(build)
+}
+
+/// The file names of the binaries the entry in `dir` lists.
+#[cfg(feature = "http")]
+pub(crate) fn entry_binaries(dir: &Path) -> Option<Vec<String>> {
+    Some(read_build(dir)?.binaries.keys().map(|name| exe_name(name)).collect())
+}
+
+/// Whether every binary the entry in `dir` lists is there and hashes as
+/// `build.json` says.
+#[cfg(feature = "http")]
+pub(crate) fn entry_verifies(dir: &Path) -> bool {
+    read_build(dir).is_some_and(|build| {
+        build
+            .binaries
+            .iter()
+            .all(|(name, cached)| CachedBinary::of(&dir.join(exe_name(name))).is_ok_and(|actual| actual == *cached))
+    })
+}
+
+/// Record in the downloaded entry `dir` how its attestations stood.
+#[cfg(feature = "http")]
+pub(crate) fn mark_prebuilt(dir: &Path, attestation: &Attestation) -> io::Result<()> {
+    let contents = serde_json::to_string_pretty(attestation).map_err(io::Error::other)?;
+    fs::write(dir.join(PREBUILT_FILE), contents + "\n")
+}
+
+/// How the attestations of the entry in `dir` stood when it was downloaded;
+/// `None` for builds made here.
+fn prebuilt_attestation(dir: &Path) -> Option<Attestation> {
+    serde_json::from_str(&fs::read_to_string(dir.join(PREBUILT_FILE)).ok()?).ok()
+}
+
+impl Installer {
+    /// The newest other entry built by the same rustc for the same target
+    /// as `entry`, which a patch to `entry` can start from.
+    #[cfg(feature = "http")]
+    pub(crate) fn delta_base(&self, entry: &Path) -> Option<PathBuf> {
+        let name = entry.file_name()?.to_str()?;
+        let (_, toolchain) = name.split_once('-')?;
+        fs::read_dir(entry.parent()?)
+            .ok()?
+            .flatten()
+            .filter(|other| {
+                let other = other.file_name();
+                let other = other.to_string_lossy();
+                other != name && other.split_once('-').is_some_and(|(_, rest)| rest == toolchain)
+            })
+            .filter_map(|other| Some((other.path().join(BUILD_FILE).metadata().ok()?.modified().ok()?, other.path())))
+            .max_by_key(|(modified, _)| *modified)
+            .map(|(_, path)| path)
+    }
+
+    /// The cache entry for the build in `checkout`: its commit, plus the
+    /// release and host of the rustc that builds it there. `None` when rustc
+    /// can't be asked.
+    fn bin_cache_entry(&self, checkout: &Path) -> Option<PathBuf> {
+        let commit = self.source_record().ok()?.commit;
+        let info = self.rustc_info(checkout)?;
+        let field = |name: &str| info.lines().find_map(|line| line.strip_prefix(name)).map(str::trim);
+        let release = field("release:")?;
+        let host = field("host:")?;
+        // Pre-release toolchains share a release number across days
+        let rustc = match field("commit-hash:") {
+            Some(hash) if release.contains('-') => format!("{}-{}", release, hash.get(..9).unwrap_or(hash)),
+            _ => release.to_string(),
+        };
+        let target = self.build_target().unwrap_or(host);
+        Some(self.cache_dir().join(BIN_CACHE_DIR).join(format!("{}-{}-{}", commit, rustc, target)))
+    }
+
+    /// Put a cached build of `checkout` where cargo would have left it, if
+    /// there is one that verifies. Returns whether the build can be skipped.
+    pub(crate) fn restore_cached_build(&self, version: &str, checkout: &Path) -> Result<bool, InstallerError> {
+        let Some(entry) = self.bin_cache_entry(checkout) else {
+            return Ok(false);
+        };
+        if !entry.join(BUILD_FILE).exists() && !self.download_cached_build(&entry) {
+            return Ok(false);
+        }
+        let Some(build) = fs::read_to_string(entry.join(BUILD_FILE))
+            .ok()
+            .and_then(|contents| serde_json::from_str::<CachedBuild>(&contents).ok())
+        else {
+            return Ok(false);
+        };
+        let manifest = Manifest::load(checkout)?;
+        let profile = self.effective_profile();
+        let covers_profile = profile.components().iter().all(|c| build.profile.components().contains(c));
+        if !covers_profile || build.options != self.chosen_options(version, manifest.as_ref()) {
+            return Ok(false);
+        }
+        if let Some(name) = build.binaries.keys().find(|name| !is_plain_name(name)) {
+            self.log_warning(&format!("Not using the cached build in {}: '{}' is not a binary name", entry.display(), name));
+            return Ok(false);
+        }
+        for (name, cached) in &build.binaries {
+            if !CachedBinary::of(&entry.join(exe_name(name))).is_ok_and(|actual| actual == *cached) {
+                self.log_info(&format!("Not using the cached build in {}: {} is missing or changed", entry.display(), name));
+                return Ok(false);
+            }
+        }
+        if !build.binaries.contains_key("kopi") {
+            return Ok(false);
+        }
+
+        let release_dir = self.release_dir(checkout);
+        fs::create_dir_all(&release_dir)?;
+        for name in build.binaries.keys() {
+            let cached = entry.join(exe_name(name));
+            if cached.exists() {
+                fs::copy(&cached, release_dir.join(exe_name(name)))?;
+            }
+        }
+        self.touch_cache_entry(&entry);
+        self.set_prebuilt_attestation(prebuilt_attestation(&entry));
+        self.log_success(&format!("Reusing the build cached in {}; nothing to compile", entry.display()));
+        Ok(true)
+    }
+
+    /// Keep the binaries cargo just built in `checkout` for later installs of
+    /// the same commit. A failure only costs a rebuild later, so it's a warning.
+    pub(crate) fn store_cached_build(&self, version: &str, checkout: &Path) {
+        if let Err(e) = self.try_store_cached_build(version, checkout) {
+            self.log_warning(&format!("Could not cache the build: {:?}", e));
+        }
+    }
+
+    fn try_store_cached_build(&self, version: &str, checkout: &Path) -> Result<(), InstallerError> {
+        let Some(entry) = self.bin_cache_entry(checkout) else {
+            return Ok(());
+        };
+        let manifest = Manifest::load(checkout)?;
+        let release_dir = self.release_dir(checkout);
+        let mut names: Vec<String> = std::iter::once("kopi").chain(COMPONENTS.iter().copied()).map(str::to_string).collect();
+        if let Some(manifest) = &manifest {
+            names.extend(manifest.binary.iter().map(|binary| binary.name.clone()));
+        }
+
+        // Built in a sibling dir and renamed, so a half-written entry is never used
+        let partial = sibling(&entry, ".partial");
+        if partial.exists() {
+            fs::remove_dir_all(&partial)?;
+        }
+        fs::create_dir_all(&partial)?;
+        let mut build = CachedBuild {
+            profile: self.effective_profile(),
+            options: self.chosen_options(version, manifest.as_ref()),
+            binaries: BTreeMap::new(),
+        };
+        for name in names {
+            let built = release_dir.join(exe_name(&name));
+            if built.exists() && !build.binaries.contains_key(&name) {
+                let dest = partial.join(exe_name(&name));
+                fs::copy(&built, &dest)?;
+                build.binaries.insert(name, CachedBinary::of(&dest)?);
+            }
+        }
+        let contents = serde_json::to_string_pretty(&build).map_err(io::Error::other)?;
+        fs::write(partial.join(BUILD_FILE), contents + "\n")?;
+
+        if entry.exists() {
+            fs::remove_dir_all(&entry)?;
+        }
+        fs::rename(&partial, &entry)?;
+        self.touch_cache_entry(&entry);
+        self.upload_cached_build(&entry);
+        Ok(())
+    }
+}
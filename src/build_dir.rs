@@ -0,0 +1,185 @@
+// The cargo target dir builds share: `<cache dir>/target`, kept between
+// runs instead of living in the per-run scratch checkout. Cargo's own
+// fingerprints decide what needs compiling again, so a build that was
+// interrupted (Ctrl-C, a dropped SSH session, a timeout) picks up where it
+// stopped when it is retried, and a version built before recompiles only
+// what changed. A marker file records that a build is under way; when a
+// retry finds it, the artifacts are checked first and any files left empty
+// or unreadable are removed together with their fingerprints, so only
+// those crates are rebuilt. `--fresh` throws the whole dir away instead,
+// for when the cache itself is suspect.
+//
+// A target dir the user chose (CARGO_TARGET_DIR passed through with
+// `--inherit-env` or `keep-env`, or kopi-lang's own cargo config) wins.
+
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+use crate::cache::{dir_size, format_size};
+use crate::platform::extended_length_path;
+use crate::workspace;
+use crate::{Installer, InstallerError};
+
+/// Present in the target dir while a build is running or after one was cut short.
+const BUILDING_FILE: &str = ".kipper-building";
+
+/// Whether the build artifact at `path` can't be what its name says: it
+/// can't be read, is empty, or is a library archive without the archive
+/// header. Cargo leaves some files empty on purpose (the `.rmeta` of a
+/// crate only linked in full, `output-*` when rustc had nothing to say), so
+/// those are never counted.
+fn is_damaged(path: &Path) -> bool {
+    let name = path.file_name().and_then(|name| name.to_str()).unwrap_or_default();
+    if name.ends_with(".rmeta") || name.starts_with("output-") {
+        return false;
+    }
+    let mut head = Vec::new();
+    let readable = fs::File::open(path).and_then(|file| file.take(8).read_to_end(&mut head));
+    match readable {
+        Err(_) => true,
+        Ok(0) => true,
+        Ok(_) => (name.ends_with(".rlib") || name.ends_with(".a")) && head != b"!<arch>\n",
+    }
+}
+
+/// Files under `dir`, not following links, that look damaged.
+fn damaged_files(dir: &Path, found: &mut Vec<PathBuf>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            damaged_files(&path, found)?;
+        } else if file_type.is_file() && is_damaged(&path) {
+            found.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// The fingerprint dir names a file in `deps/` may belong to: cargo names
+/// both after the crate and its hash (`libserde-1a2b.rlib`, `serde-1a2b`).
+fn units_of(file: &Path) -> Vec<String> {
+    let Some(name) = file.file_name().and_then(|name| name.to_str()) else {
+        return Vec::new();
+    };
+    let stem = name.split('.').next().unwrap_or(name);
+    let mut units = vec![stem.to_string()];
+    if let Some(unprefixed) = stem.strip_prefix("lib") {
+        units.push(unprefixed.to_string());
+    }
+    units
+}
+
+impl Installer {
+    /// `--fresh`: build from nothing, ignoring the shared target dir and the
+    /// built binaries cache.
+    pub fn with_fresh(mut self, fresh: bool) -> Self {
+        self.fresh = fresh;
+        self
+    }
+
+    /// The target dir kipper keeps between builds.
+    pub fn build_cache_dir(&self) -> PathBuf {
+        self.cache_dir().join("target")
+    }
+
+    /// Where cargo builds the checkout: the user's target dir if they set
+    /// one for it, else the shared one.
+    pub(crate) fn cargo_target_dir(&self, checkout: &Path) -> PathBuf {
+        workspace::chosen_target_dir(checkout, self.inherit_env || !self.sanitized("CARGO_TARGET_DIR"))
+            .unwrap_or_else(|| self.build_cache_dir())
+    }
+
+    /// Get the target dir ready for a build of `version`: emptied for
+    /// `--fresh`, checked when the last build in it didn't finish, and
+    /// marked as in use until `finish_build_dir`.
+    pub(crate) fn prepare_build_dir(&self, checkout: &Path, version: &str) -> Result<(), InstallerError> {
+        let dir = self.cargo_target_dir(checkout);
+        if self.fresh && dir == self.build_cache_dir() && dir.exists() {
+            let size = dir_size(&dir).unwrap_or(0);
+            fs::remove_dir_all(extended_length_path(&dir))?;
+            self.log_info(&format!("Starting a clean build (--fresh); removed {} of build artifacts", format_size(size)));
+        }
+        let marker = dir.join(BUILDING_FILE);
+        if let Ok(interrupted) = fs::read_to_string(&marker) {
+            let interrupted = interrupted.trim();
+            self.log_info(&format!(
+                "The last build{} in {} didn't finish; checking what it left before reusing it",
+                if interrupted.is_empty() { String::new() } else { format!(" (Kopi {})", interrupted) },
+                dir.display()
+            ));
+            let removed = self.remove_damaged_artifacts(&dir)?;
+            if removed > 0 {
+                self.log_warning(&format!(
+                    "Removed {} damaged build artifact{}; cargo rebuilds the crates they belong to",
+                    removed,
+                    if removed == 1 { "" } else { "s" }
+                ));
+            } else {
+                self.log_info("Build artifacts are intact; continuing where it stopped (use --fresh to start over)");
+            }
+        }
+        fs::create_dir_all(&dir)?;
+        fs::write(&marker, format!("{}\n", version))?;
+        Ok(())
+    }
+
+    /// `kipper cache clean --build`: remove the shared target dir, returning
+    /// how many bytes that freed (`None` when there was none).
+    pub fn clean_build_dir(&self) -> Result<Option<u64>, InstallerError> {
+        let dir = self.build_cache_dir();
+        if !dir.exists() {
+            return Ok(None);
+        }
+        let size = dir_size(&dir).unwrap_or(0);
+        fs::remove_dir_all(extended_length_path(&dir))?;
+        Ok(Some(size))
+    }
+
+    /// Mark the build in the target dir as complete.
+    pub(crate) fn finish_build_dir(&self, checkout: &Path) {
+        let _ = fs::remove_file(self.cargo_target_dir(checkout).join(BUILDING_FILE));
+    }
+
+    /// Remove empty or unreadable files in each profile's `deps` and
+    /// `.fingerprint` dirs, with the fingerprints of the crates they belong
+    /// to so cargo doesn't take them as up to date. Returns how many files
+    /// were damaged.
+    fn remove_damaged_artifacts(&self, dir: &Path) -> Result<usize, InstallerError> {
+        let mut profile_dirs = vec![dir.join("release")];
+        // With --target, each triple has its own profile dirs
+        if let Ok(entries) = fs::read_dir(dir) {
+            profile_dirs.extend(entries.flatten().map(|entry| entry.path().join("release")));
+        }
+        let mut removed = 0;
+        for profile_dir in profile_dirs.into_iter().filter(|dir| dir.is_dir()) {
+            let fingerprints = profile_dir.join(".fingerprint");
+            let mut damaged = Vec::new();
+            for sub in ["deps", ".fingerprint"] {
+                if profile_dir.join(sub).is_dir() {
+                    damaged_files(&profile_dir.join(sub), &mut damaged)?;
+                }
+            }
+            for file in &damaged {
+                self.log_debug("build", &format!("Damaged build artifact: {}", file.display()));
+                let units = match file.strip_prefix(&fingerprints) {
+                    Ok(inside) => inside.iter().take(1).map(|unit| unit.to_string_lossy().into_owned()).collect(),
+                    Err(_) => units_of(file),
+                };
+                for unit in units {
+                    let fingerprint = fingerprints.join(unit);
+                    if fingerprint.is_dir() {
+                        fs::remove_dir_all(&fingerprint)?;
+                    }
+                }
+                if file.exists() {
+                    fs::remove_file(file)?;
+                }
+                removed += 1;
+            }
+        }
+        Ok(removed)
+    }
+}
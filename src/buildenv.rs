@@ -0,0 +1,97 @@
+// The environment cargo builds kopi-lang in. Flags and overrides meant for
+// the user's own projects (RUSTFLAGS, CARGO_PROFILE_*, a RUSTC_WRAPPER) can
+// break or quietly change the Kopi build, so they are removed unless
+// `[build] keep-env` in kipper.toml lists them or `--inherit-env` is given.
+
+use std::env;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::Installer;
+
+/// Compiler settings dropped from the build environment.
+const RUST_VARS: [&str; 7] = [
+    "RUSTFLAGS",
+    "RUSTDOCFLAGS",
+    "RUSTC",
+    "RUSTC_WRAPPER",
+    "RUSTC_WORKSPACE_WRAPPER",
+    "RUSTDOC",
+    "RUSTC_BOOTSTRAP",
+];
+/// CARGO_* settings that only concern where and how crates are downloaded
+/// (proxies, certificates, registry mirrors) or how output looks; the rest
+/// of CARGO_* is dropped.
+const KEPT_CARGO_PREFIXES: [&str; 6] = [
+    "CARGO_HOME",
+    "CARGO_NET_",
+    "CARGO_HTTP_",
+    "CARGO_REGISTRIES_",
+    "CARGO_REGISTRY_",
+    "CARGO_TERM_",
+];
+
+/// `name` matches a `keep-env` entry: exactly, or by prefix for entries ending in `*`.
+fn kept_by(entry: &str, name: &str) -> bool {
+    match entry.strip_suffix('*') {
+        Some(prefix) => name.starts_with(prefix),
+        None => name == entry,
+    }
+}
+
+impl Installer {
+    /// Build with the user's environment as it is.
+    pub fn with_inherited_env(mut self, inherit: bool) -> Self {
+        self.inherit_env = inherit;
+        self
+    }
+
+    /// Whether `name` is left out of the build environment.
+    pub(crate) fn sanitized(&self, name: &str) -> bool {
+        let relevant = RUST_VARS.contains(&name)
+            || (name.starts_with("CARGO_") && !KEPT_CARGO_PREFIXES.iter().any(|prefix| name.starts_with(prefix)));
+        relevant && !self.config.build.keep_env.iter().any(|entry| kept_by(entry, name))
+    }
+
+    /// The CARGO_HOME builds use when `[build] isolated-cargo-home` is on,
+    /// so the user's ~/.cargo/config.toml doesn't apply.
+    pub fn isolated_cargo_home(&self) -> Option<PathBuf> {
+        self.config.build.isolated_cargo_home.then(|| self.install_dir.join("cargo"))
+    }
+
+    /// Remove the user's build settings from `command`'s environment.
+    pub(crate) fn sanitize_build_env(&self, command: &mut Command) {
+        if self.inherit_env {
+            return;
+        }
+        let mut removed = Vec::new();
+        for (name, _) in env::vars_os() {
+            if let Some(name) = name.to_str()
+                && self.sanitized(name)
+            {
+                command.env_remove(name);
+                removed.push(name.to_string());
+            }
+        }
+        if let Some(home) = self.isolated_cargo_home() {
+            command.env("CARGO_HOME", home);
+        }
+        if !removed.is_empty() {
+            removed.sort();
+            self.log_info(&format!(
+                "Building without {} from your environment (--inherit-env keeps them)",
+                removed.join(", ")
+            ));
+        }
+    }
+
+    /// Where the build of `checkout` puts release binaries: under a dir
+    /// named for the target when `--target` is given.
+    pub(crate) fn release_dir(&self, checkout: &Path) -> PathBuf {
+        let target_dir = self.cargo_target_dir(checkout);
+        match self.build_target() {
+            Some(target) => target_dir.join(target).join("release"),
+            None => target_dir.join("release"),
+        }
+    }
+}
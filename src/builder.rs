@@ -0,0 +1,293 @@
+// What turns the checked out source into binaries. Each way is a `Builder`,
+// picked with `--builder`:
+//
+//     cargo              `cargo build --release` with the host toolchain (the
+//                        default)
+//     cargo-install      `cargo install` of each package with binaries,
+//                        resolving dependencies as it does
+//     prebuilt           only the built binaries cache and the remote cache;
+//                        fails when neither has a build that fits
+//     container[:IMAGE]  `cargo build --release` inside a throwaway container
+//                        (podman, else docker) of IMAGE, by default the
+//                        official Rust image, for builds that mustn't touch
+//                        the host or need a toolchain it lacks
+//
+// Whatever a builder does, it leaves kopi and its tools in the staging dir,
+// the release dir of the shared cargo target dir (`release_dir`), which is
+// where installing, `update --prepare` and the built binaries cache take
+// them from. Builders that compile with the host toolchain reuse and fill
+// that cache; the prebuilt builder is the cache alone, and container builds
+// are kept out of it, since their binaries aren't made by the rustc its
+// entries are named after.
+
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+use crate::manifest::Manifest;
+use crate::options::describe_options;
+use crate::toolchain::{COMPONENTS, exe_name};
+use crate::{Installer, InstallerError};
+
+/// The image `--builder container` builds in.
+const DEFAULT_IMAGE: &str = "docker.io/library/rust:latest";
+/// Where the checkout and the build dir are mounted in the container.
+const CONTAINER_SOURCE: &str = "/src";
+const CONTAINER_BUILD: &str = "/build";
+
+/// A way of building the checkout.
+pub trait Builder: Send + Sync {
+    /// The name `--builder` knows it by.
+    fn name(&self) -> String;
+
+    /// Build `version` from `checkout`, leaving the binaries in `staging`.
+    /// Returns false when this builder has nothing for it.
+    fn build(&self, installer: &Installer, version: &str, checkout: &Path, staging: &Path) -> Result<bool, InstallerError>;
+
+    /// Whether it builds with the host's rustc, so its binaries belong in
+    /// the built binaries cache (and one from there can stand in).
+    fn caches_output(&self) -> bool {
+        true
+    }
+
+    /// Whether it needs cargo on the host.
+    fn needs_cargo(&self) -> bool {
+        true
+    }
+}
+
+/// The builder `--builder VALUE` names.
+pub fn select(value: &str) -> Result<Box<dyn Builder>, String> {
+    match value.split_once(':') {
+        None if value == "cargo" => Ok(Box::new(CargoBuild)),
+        None if value == "cargo-install" => Ok(Box::new(CargoInstall)),
+        None if value == "prebuilt" => Ok(Box::new(PrebuiltExtract)),
+        None if value == "container" => Ok(Box::new(ContainerBuild { image: DEFAULT_IMAGE.to_string() })),
+        Some(("container", image)) if !image.is_empty() => Ok(Box::new(ContainerBuild { image: image.to_string() })),
+        _ => Err(format!(
+            "unknown builder '{}' (use cargo, cargo-install, prebuilt or container[:IMAGE])",
+            value
+        )),
+    }
+}
+
+impl Installer {
+    /// Build with `builder` instead of `cargo build` on the host.
+    pub fn with_builder(mut self, builder: Box<dyn Builder>) -> Self {
+        self.builder = builder;
+        self
+    }
+}
+
+/// Names of the binaries a build may leave: kopi, its tools and those the
+/// checkout's manifest adds.
+fn binary_names(manifest: Option<&Manifest>) -> Vec<String> {
+    let mut names: Vec<String> = std::iter::once("kopi").chain(COMPONENTS.iter().copied()).map(str::to_string).collect();
+    if let Some(manifest) = manifest {
+        names.extend(manifest.binary.iter().map(|binary| binary.name.clone()));
+    }
+    names
+}
+
+/// Copy the binaries named in `names` from `dir` to `staging`.
+fn stage_binaries(dir: &Path, staging: &Path, names: &[String]) -> Result<(), InstallerError> {
+    fs::create_dir_all(staging)?;
+    for name in names {
+        let built = dir.join(exe_name(name));
+        if built.exists() {
+            fs::copy(&built, staging.join(exe_name(name)))?;
+        }
+    }
+    Ok(())
+}
+
+/// `cargo build --release` on the host.
+pub struct CargoBuild;
+
+impl Builder for CargoBuild {
+    fn name(&self) -> String {
+        "cargo".to_string()
+    }
+
+    fn build(&self, installer: &Installer, version: &str, checkout: &Path, _staging: &Path) -> Result<bool, InstallerError> {
+        installer.probe_build_dependencies(checkout)?;
+        installer.check_rust_version(checkout)?;
+        installer.prepare_build_dir(checkout, version)?;
+        installer.log_info("Building Kopi (this may take a few minutes)...");
+
+        let manifest = Manifest::load(checkout)?;
+        let chosen = installer.chosen_options(version, manifest.as_ref());
+        if !chosen.is_empty() {
+            installer.log_info(&format!("Build options: {}", describe_options(&chosen)));
+        }
+        // Cargo puts the binaries in the staging dir itself
+        let build_output = installer.run_command(&mut installer.release_build(checkout, version, manifest.as_ref())?)?;
+        // Only a build that was cut short leaves the marker behind
+        installer.finish_build_dir(checkout);
+
+        if !build_output.status.success() {
+            let error = String::from_utf8_lossy(&build_output.stderr);
+            return Err(InstallerError::Cargo(format!(
+                "Build failed: {}",
+                installer.bounded_output("cargo build", &error)
+            )));
+        }
+        Ok(true)
+    }
+}
+
+/// `cargo install --path` of each package in the checkout that has
+/// binaries, into a scratch root whose `bin` is staged.
+pub struct CargoInstall;
+
+impl Builder for CargoInstall {
+    fn name(&self) -> String {
+        "cargo-install".to_string()
+    }
+
+    fn build(&self, installer: &Installer, version: &str, checkout: &Path, staging: &Path) -> Result<bool, InstallerError> {
+        installer.probe_build_dependencies(checkout)?;
+        installer.check_rust_version(checkout)?;
+        installer.prepare_build_dir(checkout, version)?;
+        installer.log_info("Building Kopi with cargo install (this may take a few minutes)...");
+
+        let manifest = Manifest::load(checkout)?;
+        let chosen = installer.chosen_options(version, manifest.as_ref());
+        let option_args = installer.option_build_args(version, manifest.as_ref(), &chosen)?;
+        let root = installer.temp_dir.join("cargo-install");
+        let packages = installer.workspace_metadata(checkout)?.packages;
+        for package in packages.iter().filter(|package| package.has_binaries()) {
+            let dir = package.manifest_path.parent().unwrap_or(checkout);
+            let mut cargo = installer.cargo_command(checkout)?;
+            cargo.args(["install", "--no-track", "--force", "--path"]).arg(dir).arg("--root").arg(&root);
+            if installer.locked {
+                cargo.arg("--locked");
+            }
+            if let Some(target) = installer.build_target() {
+                cargo.args(["--target", target]);
+            }
+            // Build options are kopi's features; other packages are built as they come
+            if package.name == "kopi" {
+                cargo.args(&option_args);
+            }
+            if let Some(jobs) = installer.build_jobs() {
+                cargo.args(["--jobs", &jobs.to_string()]);
+            }
+            cargo.envs(installer.build_profile_env().iter().copied());
+            cargo.env("CARGO_TARGET_DIR", installer.cargo_target_dir(checkout));
+            let output = installer.run_command(&mut cargo)?;
+            if !output.status.success() {
+                installer.finish_build_dir(checkout);
+                let error = String::from_utf8_lossy(&output.stderr);
+                return Err(InstallerError::Cargo(format!(
+                    "cargo install of {} failed: {}",
+                    package.name,
+                    installer.bounded_output("cargo install", &error)
+                )));
+            }
+        }
+        installer.finish_build_dir(checkout);
+
+        stage_binaries(&root.join("bin"), staging, &binary_names(manifest.as_ref()))?;
+        Ok(true)
+    }
+}
+
+/// The built binaries cache, and the remote cache behind it.
+pub struct PrebuiltExtract;
+
+impl Builder for PrebuiltExtract {
+    fn name(&self) -> String {
+        "prebuilt".to_string()
+    }
+
+    fn build(&self, installer: &Installer, version: &str, checkout: &Path, _staging: &Path) -> Result<bool, InstallerError> {
+        // Restored entries land in the staging dir
+        installer.restore_cached_build(version, checkout)
+    }
+
+    fn caches_output(&self) -> bool {
+        false
+    }
+}
+
+/// `cargo build --release` in a container of `image`.
+pub struct ContainerBuild {
+    image: String,
+}
+
+impl Builder for ContainerBuild {
+    fn name(&self) -> String {
+        format!("container:{}", self.image)
+    }
+
+    fn build(&self, installer: &Installer, version: &str, checkout: &Path, staging: &Path) -> Result<bool, InstallerError> {
+        let Some(engine) = ["podman", "docker"].into_iter().find(|engine| installer.command_exists(engine)) else {
+            return Err(InstallerError::PathError(
+                "--builder container needs podman or docker, and neither is installed".to_string(),
+            ));
+        };
+        // Kept apart from the host's target dir: its artifacts are from another rustc
+        let build_dir = installer.cache_dir().join("container");
+        fs::create_dir_all(&build_dir)?;
+        installer.log_info(&format!("Building Kopi in {} with {} (this may take a few minutes)...", self.image, engine));
+
+        let manifest = Manifest::load(checkout)?;
+        let mut command = Command::new(engine);
+        command.args(["run", "--rm", "--workdir", CONTAINER_SOURCE]);
+        command.arg("--volume").arg(format!("{}:{}", checkout.display(), CONTAINER_SOURCE));
+        command.arg("--volume").arg(format!("{}:{}", build_dir.display(), CONTAINER_BUILD));
+        command.args(["--env", &format!("CARGO_TARGET_DIR={}/target", CONTAINER_BUILD)]);
+        command.args(["--env", &format!("CARGO_HOME={}/cargo-home", CONTAINER_BUILD)]);
+        for (name, value) in installer.build_profile_env() {
+            command.args(["--env", &format!("{}={}", name, value)]);
+        }
+        command.args(owner_args(engine, &build_dir));
+        command.arg(&self.image).arg("cargo");
+        command.args(installer.release_build_args(checkout, version, manifest.as_ref())?);
+        let output = installer.run_command(&mut command)?;
+        if !output.status.success() {
+            let error = String::from_utf8_lossy(&output.stderr);
+            return Err(InstallerError::Cargo(format!(
+                "Build in {} failed: {}",
+                self.image,
+                installer.bounded_output("cargo build", &error)
+            )));
+        }
+
+        let mut release = build_dir.join("target");
+        if let Some(target) = installer.build_target() {
+            release.push(target);
+        }
+        stage_binaries(&release.join("release"), staging, &binary_names(manifest.as_ref()))?;
+        Ok(true)
+    }
+
+    fn caches_output(&self) -> bool {
+        false
+    }
+
+    fn needs_cargo(&self) -> bool {
+        false
+    }
+}
+
+/// Arguments that make what the container writes to `dir` belong to the
+/// owner of `dir`: rootless podman maps that user already with keep-id,
+/// docker runs as root unless told the uid.
+#[cfg(unix)]
+fn owner_args(engine: &str, dir: &Path) -> Vec<String> {
+    use std::os::unix::fs::MetadataExt;
+    match fs::metadata(dir) {
+        Ok(metadata) if metadata.uid() != 0 => match engine {
+            "podman" => vec!["--userns=keep-id".to_string()],
+            _ => vec!["--user".to_string(), format!("{}:{}", metadata.uid(), metadata.gid())],
+        },
+        _ => Vec::new(),
+    }
+}
+
+#[cfg(not(unix))]
+fn owner_args(_engine: &str, _dir: &Path) -> Vec<String> {
+    Vec::new()
+}
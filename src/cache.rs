@@ -0,0 +1,199 @@
+// Housekeeping for kipper's scratch and cache directories
+
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use crate::platform::{extended_length_path, scratch_root};
+use crate::toolchain::NIGHTLY;
+use crate::{Installer, InstallerError};
+
+/// Prefix of per-process scratch directories in the system temp dir.
+pub const TEMP_PREFIX: &str = "kopi-install-";
+/// Overrides the cache location, e.g. to share one cache between users.
+pub const CACHE_DIR_ENV: &str = "KIPPER_CACHE_DIR";
+
+/// Total size in bytes of everything under `path`, not following symlinks.
+pub fn dir_size(path: &Path) -> io::Result<u64> {
+    let metadata = fs::symlink_metadata(path)?;
+    if !metadata.is_dir() {
+        return Ok(metadata.len());
+    }
+
+    let mut total = 0;
+    for entry in fs::read_dir(path)? {
+        total += dir_size(&entry?.path())?;
+    }
+    Ok(total)
+}
+
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} {}", bytes, UNITS[0])
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
+}
+
+fn process_alive(pid: u32) -> bool {
+    if pid == std::process::id() {
+        return true;
+    }
+
+    if cfg!(windows) {
+        Command::new("tasklist")
+            .args(["/FI", &format!("PID eq {}", pid), "/NH"])
+            .output()
+            .map(|out| String::from_utf8_lossy(&out.stdout).contains(&pid.to_string()))
+            .unwrap_or(true)
+    } else if Path::new("/proc/self").exists() {
+        Path::new("/proc").join(pid.to_string()).exists()
+    } else {
+        Command::new("kill")
+            .args(["-0", &pid.to_string()])
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .map(|status| status.success())
+            .unwrap_or(true)
+    }
+}
+
+/// Scratch dirs of installs whose process is no longer running.
+pub fn stale_temp_dirs() -> io::Result<Vec<PathBuf>> {
+    let mut stale = Vec::new();
+    for entry in fs::read_dir(scratch_root())? {
+        let entry = entry?;
+        let name = entry.file_name();
+        let Some(pid) = name
+            .to_str()
+            .and_then(|name| name.strip_prefix(TEMP_PREFIX))
+            .and_then(|pid| pid.parse::<u32>().ok())
+        else {
+            continue;
+        };
+
+        if entry.file_type()?.is_dir() && !process_alive(pid) {
+            stale.push(entry.path());
+        }
+    }
+    Ok(stale)
+}
+
+impl Installer {
+    pub fn cache_dir(&self) -> PathBuf {
+        env::var_os(CACHE_DIR_ENV)
+            .map(PathBuf::from)
+            .unwrap_or_else(|| self.install_dir.join("cache"))
+    }
+
+    /// Bare mirror of kopi-lang that installs clone from. Git stores objects
+    /// by their hash, so every version fetched once is available offline and
+    /// shared by all installs using the same cache dir.
+    pub fn source_cache(&self) -> PathBuf {
+        self.cache_dir().join("git").join("kopi-lang.git")
+    }
+
+    pub(crate) fn git_in_cache(&self, args: &[&str]) -> Result<std::process::Output, InstallerError> {
+        self.run_command(Command::new("git").args(args).current_dir(self.source_cache()))
+    }
+
+    /// Make sure the source cache holds `version`, fetching only what's missing.
+    /// Tags already in the cache are used as they are; nightly always fetches
+    /// but falls back to the cached branch when the fetch fails.
+    pub(crate) fn refresh_source_cache(&self, version: Option<&str>) -> Result<(), InstallerError> {
+        let cache = self.source_cache();
+        let url = self.source_url();
+
+        if !cache.exists() {
+            fs::create_dir_all(cache.parent().unwrap_or(&cache))?;
+            let output = self.run_command(Command::new("git").args(["clone", "--quiet", "--mirror", &url]).arg(&cache))?;
+            if !output.status.success() {
+                let _ = fs::remove_dir_all(&cache);
+                let error = String::from_utf8_lossy(&output.stderr);
+                return Err(InstallerError::Git(format!("Failed to clone repository: {}{}", error.trim_end(), self.offline_note())));
+            }
+            return Ok(());
+        }
+
+        let pinned = version.filter(|v| *v != NIGHTLY);
+        if let Some(version) = pinned {
+            let wanted = format!("{}^{{commit}}", version);
+            if self.git_in_cache(&["rev-parse", "--verify", "--quiet", &wanted])?.status.success() {
+                self.log_info(&format!("Using cached source for Kopi {}", version));
+                return Ok(());
+            }
+        }
+
+        let output = self.git_in_cache(&["fetch", "--quiet", "--prune", "--tags", &url, "+refs/heads/*:refs/heads/*"])?;
+        if !output.status.success() {
+            let error = String::from_utf8_lossy(&output.stderr);
+            if pinned.is_none() {
+                self.log_warning(&format!("Could not fetch the latest source, using the cached copy: {}", error.trim()));
+                return Ok(());
+            }
+            return Err(InstallerError::Git(format!("Failed to fetch repository: {}{}", error.trim_end(), self.offline_note())));
+        }
+        Ok(())
+    }
+
+    /// `kipper cache verify`: check every object in the source cache against its hash.
+    pub fn verify_cache(&self) -> Result<(), InstallerError> {
+        let cache = self.source_cache();
+        if !cache.exists() {
+            self.log_info(&format!("No source cache at {}", cache.display()));
+            return Ok(());
+        }
+
+        self.log_info(&format!("Verifying {}...", cache.display()));
+        let output = self.git_in_cache(&["fsck", "--full", "--no-dangling", "--no-progress"])?;
+        if !output.status.success() {
+            let error = String::from_utf8_lossy(&output.stderr);
+            self.log_error(error.trim());
+            self.log_info(&format!("Delete {} to download the source again", cache.display()));
+            return Err(InstallerError::Git("Source cache is corrupt".to_string()));
+        }
+
+        let size = dir_size(&cache).unwrap_or(0);
+        self.log_success(&format!("Source cache is intact ({})", format_size(size)));
+        Ok(())
+    }
+
+    /// Remove `kopi-install-<pid>` directories left behind by runs that crashed
+    /// or were killed. Directories whose process is still alive are skipped.
+    /// Returns how many were removed and how many bytes that freed.
+    pub fn clean_stale_temp_dirs(&self) -> Result<(usize, u64), InstallerError> {
+        let mut removed = 0;
+        let mut freed = 0;
+
+        for dir in stale_temp_dirs()? {
+            let size = dir_size(&dir).unwrap_or(0);
+            match fs::remove_dir_all(extended_length_path(&dir)) {
+                Ok(()) => {
+                    removed += 1;
+                    freed += size;
+                }
+                Err(e) => self.log_warning(&format!("Could not remove {}: {}", dir.display(), e)),
+            }
+        }
+
+        if removed > 0 {
+            self.log_info(&format!(
+                "Removed {} leftover temporary director{} from interrupted installs ({})",
+                removed,
+                if removed == 1 { "y" } else { "ies" },
+                format_size(freed)
+            ));
+        }
+        Ok((removed, freed))
+    }
+}
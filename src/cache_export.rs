@@ -0,0 +1,151 @@
+// `kipper cache export DIR` and `kipper cache restore DIR`: move the caches
+// that make an install fast between CI runs, through a directory that
+// actions/cache or GitLab's `cache:` saves and brings back. An export holds
+//
+//     git/      the kopi-lang mirror, so nothing is cloned again
+//     bin/      the built binaries of installed versions' commits
+//     target/   cargo's target dir, so a new commit rebuilds only what changed
+//
+// and `kipper-cache.json` saying what it is. Restoring fills in only what the
+// local cache lacks, and a missing DIR (the first run, or a cache miss) is
+// not an error, so the restore step can run unconditionally before install.
+
+use std::fs;
+use std::path::Path;
+use std::time::Instant;
+
+use serde::{Deserialize, Serialize};
+
+use crate::bincache::BIN_CACHE_DIR;
+use crate::cache::{dir_size, format_size};
+use crate::platform::extended_length_path;
+use crate::state::State;
+use crate::timestamp;
+use crate::timing::format_duration;
+use crate::upstream::KIPPER_VERSION;
+use crate::{Installer, InstallerError, copy_dir};
+
+/// Written into an export, so restores and re-exports know the dir is one.
+const EXPORT_FILE: &str = "kipper-cache.json";
+/// The parts of the cache an export holds, by their dir name in both.
+const PARTS: [&str; 3] = ["git", BIN_CACHE_DIR, "target"];
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Export {
+    exported_by: String,
+    exported_at: String,
+    /// Bytes in the export, for the restore message.
+    size: u64,
+}
+
+impl Installer {
+    /// Copy the source mirror, the built binaries of installed versions and
+    /// the build dir to `dir`, replacing an earlier export there.
+    pub fn export_cache(&self, dir: &Path) -> Result<(), InstallerError> {
+        let started = Instant::now();
+        if dir.exists() && !dir.join(EXPORT_FILE).exists() && fs::read_dir(dir)?.next().is_some() {
+            return Err(InstallerError::PathError(format!(
+                "{} has other files in it; export to an empty or new directory",
+                dir.display()
+            )));
+        }
+        if dir.exists() {
+            fs::remove_dir_all(extended_length_path(dir))?;
+        }
+        fs::create_dir_all(dir)?;
+
+        let cache = self.cache_dir();
+        // Binaries of versions no longer installed would only grow the export
+        let commits: Vec<String> = State::load(&self.install_dir).versions.into_values().map(|record| record.commit).collect();
+        for part in PARTS {
+            let source = cache.join(part);
+            if !source.is_dir() {
+                continue;
+            }
+            if part == BIN_CACHE_DIR && !commits.is_empty() {
+                for entry in fs::read_dir(&source)? {
+                    let entry = entry?;
+                    let name = entry.file_name().to_string_lossy().into_owned();
+                    if commits.iter().any(|commit| name.starts_with(&format!("{}-", commit))) {
+                        copy_dir(&entry.path(), &extended_length_path(&dir.join(part).join(&name)))?;
+                    }
+                }
+            } else {
+                copy_dir(&extended_length_path(&source), &extended_length_path(&dir.join(part)))?;
+            }
+        }
+
+        let size = dir_size(dir).unwrap_or(0);
+        let export = Export {
+            exported_by: format!("kipper {}", KIPPER_VERSION),
+            exported_at: timestamp::now_utc(),
+            size,
+        };
+        let contents = serde_json::to_string_pretty(&export).map_err(|e| InstallerError::PathError(e.to_string()))?;
+        fs::write(dir.join(EXPORT_FILE), contents + "\n")?;
+        self.log_success(&format!(
+            "Exported the cache to {} ({}, {})",
+            dir.display(),
+            format_size(size),
+            format_duration(started.elapsed())
+        ));
+        Ok(())
+    }
+
+    /// Fill in the local cache from an export in `dir`. Parts the local
+    /// cache already has are kept; nothing to restore is not an error.
+    pub fn restore_cache(&self, dir: &Path) -> Result<(), InstallerError> {
+        let started = Instant::now();
+        let Ok(contents) = fs::read_to_string(dir.join(EXPORT_FILE)) else {
+            self.log_info(&format!("No exported cache in {}; installs start from an empty cache this time", dir.display()));
+            return Ok(());
+        };
+        let export: Export = serde_json::from_str(&contents)
+            .map_err(|e| InstallerError::PathError(format!("Invalid {} in {}: {}", EXPORT_FILE, dir.display(), e)))?;
+        self.log_debug(
+            "install",
+            &format!("Restoring a cache exported by {} at {}", export.exported_by, export.exported_at),
+        );
+
+        let cache = self.cache_dir();
+        let mut restored = Vec::new();
+        for part in PARTS {
+            let source = dir.join(part);
+            if !source.is_dir() {
+                continue;
+            }
+            let dest = cache.join(part);
+            if part == BIN_CACHE_DIR {
+                // Entries are named after what they were built from, so any missing one can be added
+                let mut added = 0;
+                for entry in fs::read_dir(&source)? {
+                    let entry = entry?;
+                    let target = dest.join(entry.file_name());
+                    if !target.exists() {
+                        copy_dir(&entry.path(), &extended_length_path(&target))?;
+                        added += 1;
+                    }
+                }
+                if added > 0 {
+                    restored.push(format!("{} built version{}", added, if added == 1 { "" } else { "s" }));
+                }
+            } else if !dest.exists() {
+                copy_dir(&extended_length_path(&source), &extended_length_path(&dest))?;
+                restored.push(if part == "git" { "source mirror".to_string() } else { "build dir".to_string() });
+            }
+        }
+
+        if restored.is_empty() {
+            self.log_info(&format!("The cache in {} already has everything {} holds", cache.display(), dir.display()));
+        } else {
+            self.log_success(&format!(
+                "Restored from {}: {} ({} exported, {})",
+                dir.display(),
+                restored.join(", "),
+                format_size(export.size),
+                format_duration(started.elapsed())
+            ));
+        }
+        Ok(())
+    }
+}
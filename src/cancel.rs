@@ -0,0 +1,28 @@
+// Cooperative cancellation for long-running installer operations
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// A cheaply clonable flag shared between the installer and whoever drives it.
+///
+/// Frontends keep a clone and call [`CancellationToken::cancel`] from their
+/// Cancel button; the installer checks it between phases and while waiting
+/// on child processes, killing them and rolling back when it trips.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+}
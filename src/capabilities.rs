@@ -0,0 +1,249 @@
+// What this machine and terminal can do, probed once and used to pick how
+// kipper works rather than assumed per platform:
+//
+//     symlinks  whether a symlink can be made where tools are exposed; when
+//               not (FAT and exFAT drives, some network shares, Windows
+//               without Developer Mode) tools are copies of the shim host
+//     colors    whether stderr is a terminal that takes color escapes: not
+//               with NO_COLOR, TERM=dumb or when it is redirected, unless
+//               CLICOLOR_FORCE says otherwise. A dumb terminal also gets
+//               announced progress (see `announce`)
+//     git       needed for every install: the source, its cache and the
+//               trees of other sources are git repositories
+//     cargo     needed by the builders that compile on this machine; the
+//               container builder does without it
+//     network   whether kopi-lang's source answers `git ls-remote`, with the
+//               proxy and URL rewrites git is set up with. Probed only when
+//               asked for, as it can take seconds: by `kipper doctor
+//               --capabilities`, and when a fetch fails, to tell an offline
+//               machine from a failing source
+//
+// `kipper doctor --capabilities` shows what was found and what kipper does
+// because of it; `[bin] strategy` and `--builder` override the choices.
+
+use std::env;
+use std::fs;
+use std::io::{self, IsTerminal};
+use std::path::Path;
+use std::process::Command;
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use serde::Serialize;
+
+use crate::announce::ProgressMode;
+use crate::config::BinStrategy;
+use crate::{Installer, InstallerError};
+
+/// Longest the network probe waits for the source to answer.
+const NETWORK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// What has been probed so far; each capability is probed the first time
+/// something depends on it, and only once.
+#[derive(Debug, Default)]
+pub(crate) struct Probed {
+    symlinks: OnceLock<bool>,
+    git: OnceLock<bool>,
+    cargo: OnceLock<bool>,
+    network: OnceLock<bool>,
+}
+
+/// The capabilities of this machine, as `Installer::capabilities` found them.
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct Capabilities {
+    pub symlinks: bool,
+    pub colors: bool,
+    pub git: bool,
+    pub cargo: bool,
+}
+
+/// One row of `kipper doctor --capabilities`.
+#[derive(Debug, Clone, Serialize)]
+pub struct Capability {
+    pub name: &'static str,
+    pub available: bool,
+    /// What the probe found.
+    pub detail: String,
+    /// What kipper does because of it.
+    pub effect: String,
+}
+
+/// Whether the terminal can't move the cursor or show colors.
+pub fn dumb_terminal() -> bool {
+    env::var("TERM").is_ok_and(|term| term == "dumb")
+}
+
+fn env_set(name: &str) -> bool {
+    env::var_os(name).is_some_and(|value| !value.is_empty() && value != "0")
+}
+
+/// Whether messages on stderr can be colored.
+pub fn colors_supported() -> bool {
+    if env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty()) {
+        return false;
+    }
+    env_set("CLICOLOR_FORCE") || (!dumb_terminal() && io::stderr().is_terminal())
+}
+
+/// Why `colors_supported` says what it does.
+fn colors_detail(colors: bool) -> &'static str {
+    if env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty()) {
+        "NO_COLOR is set"
+    } else if colors && !io::stderr().is_terminal() {
+        "CLICOLOR_FORCE is set"
+    } else if dumb_terminal() {
+        "TERM is dumb"
+    } else if colors {
+        "stderr is a terminal"
+    } else {
+        "stderr isn't a terminal"
+    }
+}
+
+/// Whether a symlink can be made in `dir`, checked by making one.
+pub fn symlinks_allowed(dir: &Path) -> bool {
+    let link = dir.join(format!(".kipper-symlink-{}", std::process::id()));
+    let _ = fs::remove_file(&link);
+    #[cfg(unix)]
+    let made = std::os::unix::fs::symlink(".", &link);
+    #[cfg(windows)]
+    let made = std::os::windows::fs::symlink_file(".", &link);
+    let allowed = made.is_ok();
+    let _ = fs::remove_file(&link);
+    allowed
+}
+
+/// `path` or the closest of its parents that exists.
+fn existing_ancestor(path: &Path) -> Option<&Path> {
+    path.ancestors().find(|dir| dir.is_dir())
+}
+
+impl Installer {
+    /// Whether a symlink can be made where tools are exposed.
+    pub fn can_symlink(&self) -> bool {
+        *self.probed.symlinks.get_or_init(|| existing_ancestor(&self.bin_dir).is_some_and(symlinks_allowed))
+    }
+
+    pub fn has_git(&self) -> bool {
+        *self.probed.git.get_or_init(|| self.command_exists("git"))
+    }
+
+    pub fn has_cargo(&self) -> bool {
+        // With rustup the toolchain's cargo is located via `rustup which cargo`
+        *self.probed.cargo.get_or_init(|| self.command_exists("cargo") || self.has_rustup())
+    }
+
+    /// Whether kopi-lang's source can be reached. This one can take seconds.
+    pub fn network_reachable(&self) -> bool {
+        *self.probed.network.get_or_init(|| self.probe_network().is_ok())
+    }
+
+    /// What to add to a failed fetch's error when the source can't be
+    /// reached at all.
+    pub(crate) fn offline_note(&self) -> &'static str {
+        if self.network_reachable() {
+            ""
+        } else {
+            "\nThe source can't be reached from here; check the network and proxy, or install a version that is already cached"
+        }
+    }
+
+    /// Every capability but the network, probing those not probed yet.
+    pub fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            symlinks: self.can_symlink(),
+            colors: colors_supported(),
+            git: self.has_git(),
+            cargo: self.has_cargo(),
+        }
+    }
+
+    /// `git ls-remote` the source, as a fetch would reach it.
+    fn probe_network(&self) -> Result<(), String> {
+        let url = self.mirror_candidates().into_iter().next().unwrap_or_default();
+        let output = self
+            .run_command_with_timeout(
+                Command::new("git").args(["ls-remote", "--heads", &url]).env("GIT_TERMINAL_PROMPT", "0"),
+                Some(NETWORK_TIMEOUT),
+            )
+            .map_err(|e| match e {
+                InstallerError::Io(e) => e.to_string(),
+                other => format!("{:?}", other),
+            })?;
+        if !output.status.success() {
+            return Err(String::from_utf8_lossy(&output.stderr).lines().last().unwrap_or_default().trim().to_string());
+        }
+        Ok(())
+    }
+
+    /// `kipper doctor --capabilities`: every capability, network included,
+    /// with what was found and what kipper does about it.
+    pub fn capability_matrix(&self) -> Vec<Capability> {
+        let found = self.capabilities();
+        let strategy = self.bin_strategy();
+        let chosen = if self.config.bin.strategy.is_some() { " ([bin] strategy)" } else { "" };
+        let link_dir = existing_ancestor(&self.bin_dir).unwrap_or(&self.bin_dir);
+
+        let url = self.mirror_candidates().into_iter().next().unwrap_or_default();
+        let network = if found.git { self.probe_network() } else { Err("git is needed to probe it".to_string()) };
+        let _ = self.probed.network.set(network.is_ok());
+
+        vec![
+            Capability {
+                name: "symlinks",
+                available: found.symlinks,
+                detail: format!("{} in {}", if found.symlinks { "can be made" } else { "can't be made" }, link_dir.display()),
+                effect: match strategy {
+                    BinStrategy::Symlink => format!("tools are symlinks to the shim host{}", chosen),
+                    BinStrategy::Copy => format!("tools are copies of the shim host{}", chosen),
+                    BinStrategy::Shim => format!("tools are shim scripts{}", chosen),
+                    BinStrategy::Direct => format!("{} goes on PATH{}", self.bin_dir.display(), chosen),
+                },
+            },
+            Capability {
+                name: "colors",
+                available: found.colors,
+                detail: colors_detail(found.colors).to_string(),
+                effect: format!(
+                    "{} messages{}",
+                    // --unattended and JSON logs turn them off too
+                    if self.theme.has_colors() { "colored" } else { "plain" },
+                    if self.effective_progress_mode() == ProgressMode::Announce { ", progress announced line by line" } else { "" }
+                ),
+            },
+            Capability {
+                name: "git",
+                available: found.git,
+                detail: format!("git {}", if found.git { "is installed" } else { "isn't on PATH" }),
+                effect: if found.git { "installs can fetch and cache the source" } else { "nothing can be installed" }.to_string(),
+            },
+            Capability {
+                name: "cargo",
+                available: found.cargo,
+                detail: format!("cargo {}", if found.cargo { "or rustup is installed" } else { "and rustup aren't on PATH" }),
+                effect: if found.cargo {
+                    "kopi is built on this machine"
+                } else {
+                    "only the container builder can build kopi"
+                }
+                .to_string(),
+            },
+            Capability {
+                name: "network",
+                available: network.is_ok(),
+                detail: match &network {
+                    Ok(()) => format!("{} answers", url),
+                    Err(e) if e.is_empty() => format!("{} doesn't answer", url),
+                    Err(e) => format!("{} doesn't answer: {}", url, e),
+                },
+                effect: if network.is_ok() {
+                    "sources are fetched as needed"
+                } else {
+                    "only cached sources and builds can be installed"
+                }
+                .to_string(),
+            },
+        ]
+    }
+}
+
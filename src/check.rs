@@ -0,0 +1,215 @@
+// Preflight checks (`kipper check`): everything an install needs, verified up
+// front so provisioning scripts can gate on it before a long build starts.
+
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+use std::time::Duration;
+
+use crate::cache::format_size;
+use crate::platform::{bsd, rust_install_hint};
+use crate::policy::policy_path;
+use crate::rust_source::RustProvider;
+use crate::script::ps_quote;
+use crate::{Installer, InstallerError};
+
+/// Free space wanted in the temp dir for the checkout and cargo's target dir.
+const MIN_BUILD_SPACE: u64 = 2 * 1024 * 1024 * 1024;
+/// Free space wanted where the built binaries are installed.
+const MIN_INSTALL_SPACE: u64 = 100 * 1024 * 1024;
+const NETWORK_TIMEOUT: Duration = Duration::from_secs(15);
+
+#[derive(Debug, Clone)]
+pub struct CheckResult {
+    pub name: &'static str,
+    pub ok: bool,
+    pub detail: String,
+    /// What to do about a failure.
+    pub hint: Option<String>,
+}
+
+impl CheckResult {
+    pub(crate) fn pass(name: &'static str, detail: impl Into<String>) -> Self {
+        CheckResult {
+            name,
+            ok: true,
+            detail: detail.into(),
+            hint: None,
+        }
+    }
+
+    pub(crate) fn fail(name: &'static str, detail: impl Into<String>, hint: impl Into<String>) -> Self {
+        CheckResult {
+            name,
+            ok: false,
+            detail: detail.into(),
+            hint: Some(hint.into()),
+        }
+    }
+
+    /// A passing check that still deserves an explanation.
+    pub(crate) fn note(name: &'static str, detail: impl Into<String>, hint: impl Into<String>) -> Self {
+        CheckResult {
+            name,
+            ok: true,
+            detail: detail.into(),
+            hint: Some(hint.into()),
+        }
+    }
+}
+
+/// Nearest directory at or above `path` that already exists.
+fn existing_ancestor(path: &Path) -> Option<&Path> {
+    path.ancestors().find(|p| p.is_dir())
+}
+
+/// Free bytes on the filesystem holding `path`, if the platform tool reports it.
+fn free_space(path: &Path) -> Option<u64> {
+    if cfg!(windows) {
+        let script = format!("(Get-Item -LiteralPath {}).PSDrive.Free", ps_quote(&path.display().to_string()));
+        let output = Command::new("powershell")
+            .args(["-NoProfile", "-Command", &script])
+            .output()
+            .ok()?;
+        String::from_utf8_lossy(&output.stdout).trim().parse().ok()
+    } else {
+        // POSIX output: Filesystem 1024-blocks Used Available Capacity Mounted-on
+        let output = Command::new("df").arg("-Pk").arg(path).output().ok()?;
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let available: u64 = stdout.lines().nth(1)?.split_whitespace().nth(3)?.parse().ok()?;
+        Some(available * 1024)
+    }
+}
+
+fn writable(dir: &Path) -> bool {
+    let probe = dir.join(format!(".kipper-check-{}", std::process::id()));
+    let ok = fs::write(&probe, b"").is_ok();
+    let _ = fs::remove_file(&probe);
+    ok
+}
+
+impl Installer {
+    /// Run every preflight check without changing anything on disk.
+    pub fn preflight_checks(&self) -> Vec<CheckResult> {
+        let mut results = Vec::new();
+
+        results.push(if self.has_git() {
+            CheckResult::pass("git", "found")
+        } else {
+            CheckResult::fail("git", "not found on PATH", "Install git and try again")
+        });
+
+        results.push(if self.command_exists("cargo") {
+            match self.rust_provider() {
+                Some(provider) if provider != RustProvider::Other => {
+                    CheckResult::pass("cargo", format!("found, from {}", provider))
+                }
+                _ => CheckResult::pass("cargo", "found"),
+            }
+        } else if self.has_rustup() {
+            CheckResult::pass("cargo", "provided by rustup")
+        } else {
+            CheckResult::fail("cargo", "not found on PATH", format!("{} and try again", rust_install_hint()))
+        });
+
+        results.push(match self.probe_c_compiler() {
+            Ok(compiler) => CheckResult::pass("c compiler", format!("{} can build and link", compiler)),
+            Err(detail) => CheckResult::fail(
+                "c compiler",
+                detail,
+                match bsd() {
+                    Some(os) => format!("Install a compiler with `{}`", os.install_command("llvm")),
+                    None => "Install your platform's build tools (build-essential, gcc or Xcode command line tools)".to_string(),
+                },
+            ),
+        });
+
+        let source = self.source_url();
+        let network = self.run_command_with_timeout(
+            Command::new("git")
+                .args(["ls-remote", "--heads", &source])
+                .env("GIT_TERMINAL_PROMPT", "0"),
+            Some(NETWORK_TIMEOUT),
+        );
+        results.push(match network {
+            Ok(output) if output.status.success() => CheckResult::pass("network", format!("{} is reachable", source)),
+            Ok(output) => CheckResult::fail(
+                "network",
+                String::from_utf8_lossy(&output.stderr).trim().to_string(),
+                "Check your connection and proxy settings (HTTPS_PROXY)",
+            ),
+            Err(e) => CheckResult::fail(
+                "network",
+                format!("could not reach {}: {:?}", source, e),
+                "Check your connection and proxy settings (HTTPS_PROXY)",
+            ),
+        });
+
+        if self.policy().is_some() {
+            results.push(CheckResult::pass("policy", format!("{} applies", policy_path().display())));
+        }
+
+        let locations = [
+            ("temp dir", self.temp_dir.as_path(), MIN_BUILD_SPACE),
+            ("install dir", self.install_dir.as_path(), MIN_INSTALL_SPACE),
+            ("bin dir", self.bin_dir.as_path(), 0),
+        ];
+        for (name, dir, min_space) in locations {
+            let Some(existing) = existing_ancestor(dir) else {
+                results.push(CheckResult::fail(name, format!("{} has no existing parent", dir.display()), "Check the path"));
+                continue;
+            };
+
+            if !writable(existing) {
+                results.push(CheckResult::fail(
+                    name,
+                    format!("{} is not writable", existing.display()),
+                    format!("Fix the permissions of {} or choose another location", existing.display()),
+                ));
+                continue;
+            }
+
+            match free_space(existing) {
+                Some(free) if free < min_space => results.push(CheckResult::fail(
+                    name,
+                    format!("only {} free, {} needed", format_size(free), format_size(min_space)),
+                    format!("Free up space on the filesystem holding {}", existing.display()),
+                )),
+                Some(free) => results.push(CheckResult::pass(name, format!("writable, {} free", format_size(free)))),
+                None => results.push(CheckResult::pass(name, "writable (free space unknown)")),
+            }
+        }
+
+        results
+    }
+
+    /// `kipper check`: report every preflight check and fail if any of them failed.
+    pub fn check(&self) -> Result<(), InstallerError> {
+        self.report_checks(&self.preflight_checks())
+    }
+
+    pub(crate) fn report_checks(&self, results: &[CheckResult]) -> Result<(), InstallerError> {
+        for result in results {
+            if result.ok {
+                self.log_success(&format!("{}: {}", result.name, result.detail));
+            } else {
+                self.log_error(&format!("{}: {}", result.name, result.detail));
+            }
+            if let Some(hint) = &result.hint {
+                let mut lines = hint.lines();
+                if let Some(first) = lines.next() {
+                    self.log_info(first);
+                }
+                for line in lines {
+                    self.print_line(line);
+                }
+            }
+        }
+
+        let failed = results.iter().filter(|r| !r.ok).count();
+        if failed > 0 {
+            return Err(InstallerError::PathError(format!("{} check(s) failed", failed)));
+        }
+        Ok(())
+    }
+}
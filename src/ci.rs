@@ -0,0 +1,120 @@
+// CI runners and containers, recognised from what they set up: GitHub
+// Actions, GitLab CI, any CI that sets CI, Kubernetes pods and Docker
+// containers. Nobody is there to answer a prompt or read colors, so kipper
+// runs as with `--unattended` (and without offering to change PATH), and
+// installs the minimal profile unless `--profile` or the settings pick one.
+// A container only counts without a terminal: `docker run -it` is someone
+// at a shell. `--no-detect` keeps the interactive defaults.
+//
+// On GitHub Actions, environment changes don't outlive the step that made
+// them, so after an install the bin dir is appended to $GITHUB_PATH and
+// KOPI_HOME, the default version's dir, to $GITHUB_ENV: later steps run
+// `kopi` without exporting anything themselves.
+
+use std::env;
+use std::fmt;
+use std::fs::{self, OpenOptions};
+use std::io::{self, IsTerminal, Write};
+use std::path::Path;
+
+use crate::toolchain::HOME_ENV;
+use crate::{Installer, InstallerError};
+
+/// Files GitHub Actions reads PATH entries and variables for later steps from.
+const GITHUB_PATH_ENV: &str = "GITHUB_PATH";
+const GITHUB_ENV_ENV: &str = "GITHUB_ENV";
+
+/// Where kipper found itself running.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CiEnvironment {
+    GithubActions,
+    GitlabCi,
+    /// A CI service that sets only `CI`.
+    OtherCi,
+    Kubernetes,
+    Docker,
+}
+
+impl fmt::Display for CiEnvironment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            CiEnvironment::GithubActions => "GitHub Actions",
+            CiEnvironment::GitlabCi => "GitLab CI",
+            CiEnvironment::OtherCi => "CI",
+            CiEnvironment::Kubernetes => "a Kubernetes pod",
+            CiEnvironment::Docker => "a Docker container",
+        })
+    }
+}
+
+/// Whether `name` is set to something other than empty, `0` or `false`.
+fn set(name: &str) -> bool {
+    env::var(name).is_ok_and(|value| !matches!(value.trim(), "" | "0" | "false"))
+}
+
+/// The CI service or container this process runs in, if any.
+pub fn detect() -> Option<CiEnvironment> {
+    if set("GITHUB_ACTIONS") {
+        return Some(CiEnvironment::GithubActions);
+    }
+    if set("GITLAB_CI") {
+        return Some(CiEnvironment::GitlabCi);
+    }
+    if set("CI") {
+        return Some(CiEnvironment::OtherCi);
+    }
+    if io::stdin().is_terminal() {
+        return None;
+    }
+    if env::var_os("KUBERNETES_SERVICE_HOST").is_some() {
+        return Some(CiEnvironment::Kubernetes);
+    }
+    Path::new("/.dockerenv").exists().then_some(CiEnvironment::Docker)
+}
+
+impl Installer {
+    /// Take on the defaults for `environment`: unattended, and the minimal
+    /// profile when none is chosen.
+    pub fn with_ci_environment(mut self, environment: CiEnvironment) -> Self {
+        self.ci_environment = Some(environment);
+        self.with_unattended(true)
+    }
+
+    /// The CI service or container detected at startup.
+    pub fn ci_environment(&self) -> Option<CiEnvironment> {
+        self.ci_environment
+    }
+
+    /// On GitHub Actions, hand the bin dir and KOPI_HOME to later steps of
+    /// the job through $GITHUB_PATH and $GITHUB_ENV.
+    pub(crate) fn export_to_github_actions(&self) -> Result<(), InstallerError> {
+        if self.ci_environment != Some(CiEnvironment::GithubActions) {
+            return Ok(());
+        }
+        if let Some(file) = env::var_os(GITHUB_PATH_ENV).filter(|file| !file.is_empty())
+            && append_line(Path::new(&file), &self.bin_dir.display().to_string())?
+        {
+            self.log_info(&format!("Added {} to PATH for the following steps", self.bin_dir.display()));
+        }
+        let default = self.default_version().filter(|version| self.version_dir(version).exists());
+        if let Some(file) = env::var_os(GITHUB_ENV_ENV).filter(|file| !file.is_empty())
+            && let Some(version) = default
+        {
+            let version_dir = self.version_dir(&version);
+            if append_line(Path::new(&file), &format!("{}={}", HOME_ENV, version_dir.display()))? {
+                self.log_info(&format!("Set {} to {} for the following steps", HOME_ENV, version_dir.display()));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Append `line` to `file` unless it already has it; returns whether it was added.
+fn append_line(file: &Path, line: &str) -> Result<bool, InstallerError> {
+    if fs::read_to_string(file).is_ok_and(|contents| contents.lines().any(|existing| existing == line)) {
+        return Ok(false);
+    }
+    let mut file = OpenOptions::new().create(true).append(true).open(file)?;
+    writeln!(file, "{}", line)?;
+    Ok(true)
+}
@@ -0,0 +1,283 @@
+// Shell completions for kipper: `kipper completions SHELL` prints the script
+// for bash, zsh or fish, and `kipper completions --install` writes it where
+// each shell the user has loads completions from by itself:
+//
+//     bash  ~/.local/share/bash-completion/completions/kipper
+//     zsh   ~/.zfunc/_kipper (added to fpath in ~/.zshrc if it isn't yet)
+//     fish  ~/.config/fish/completions/kipper.fish
+//
+// A shell counts as the user's when it is the login shell or its startup
+// file exists. Installed scripts are recorded in the state file, so
+// uninstalling kipper removes them with everything else.
+
+use std::env;
+use std::fmt;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use crate::state::State;
+use crate::{Installer, InstallerError};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+impl Shell {
+    pub const ALL: [Shell; 3] = [Shell::Bash, Shell::Zsh, Shell::Fish];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Shell::Bash => "bash",
+            Shell::Zsh => "zsh",
+            Shell::Fish => "fish",
+        }
+    }
+}
+
+impl FromStr for Shell {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Shell::ALL
+            .into_iter()
+            .find(|shell| shell.name() == s)
+            .ok_or_else(|| format!("unknown shell '{}' (use bash, zsh or fish)", s))
+    }
+}
+
+impl fmt::Display for Shell {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Commands, each with the words that may follow it.
+pub const COMMANDS: [(&str, &[&str]); 29] = [
+    ("alias", &["list", "add", "remove"]),
+    ("apply", &[]),
+    ("auth", &["status", "login", "logout", "--json", "remote-cache", "github", "proxy"]),
+    ("cache", &["clean", "verify", "export", "restore", "--temp", "--logs", "--build"]),
+    ("check", &[]),
+    ("completions", &["bash", "zsh", "fish", "--install"]),
+    ("config", &["set", "--global", "--local"]),
+    ("doctor", &["--fix", "--capabilities", "--json"]),
+    ("exec", &[]),
+    ("explain", &[]),
+    ("info", &["--provenance"]),
+    ("install", &["latest", "stable", "nightly", "--json", "--from-file"]),
+    ("list", &["--json"]),
+    ("ls-remote", &["--json"]),
+    ("mirror", &["list", "add", "remove", "test", "prefer", "serve"]),
+    ("optimize", &[]),
+    ("release-check", &["--json"]),
+    ("sbom", &["--format"]),
+    ("serve", &["--port"]),
+    ("size", &[]),
+    ("state", &["export", "--json"]),
+    ("test-matrix", &[]),
+    ("toolchain-path", &["--json", "--ensure"]),
+    ("uninstall", &["--force", "--purge", "--all", "--remove-self"]),
+    ("update", &["--yes", "--log", "--json", "--prepare", "--commit", "--auto"]),
+    ("use", &[]),
+    ("verify", &["--json", "--enable", "--disable"]),
+    ("warm", &["latest", "stable", "nightly"]),
+    ("which", &["--verbose"]),
+];
+
+/// Options accepted anywhere on the command line.
+pub const OPTIONS: [&str; 25] = [
+    "--help",
+    "--profile",
+    "--audit",
+    "--deny-advisories",
+    "--require-signed",
+    "--force",
+    "--builder",
+    "--fresh",
+    "--quick",
+    "--locked-installer",
+    "--inherit-env",
+    "--include-prereleases",
+    "--log-format",
+    "--no-detect",
+    "--progress-fd",
+    "--announce",
+    "--unattended",
+    "--source",
+    "--target",
+    "--timeout",
+    "--trace-commands",
+    "--uninstall",
+    "--version",
+    "--system",
+    "--allow-root",
+];
+
+/// The completion script for `shell`.
+pub fn completion_script(shell: Shell) -> String {
+    let commands: Vec<&str> = COMMANDS.iter().map(|(command, _)| *command).collect();
+    let commands = commands.join(" ");
+    let options = OPTIONS.join(" ");
+    let mut script = String::new();
+    match shell {
+        Shell::Bash => {
+            script.push_str("# bash completion for kipper\n_kipper() {\n");
+            script.push_str("    local cur=\"${COMP_WORDS[COMP_CWORD]}\" words\n");
+            script.push_str("    if [ \"$COMP_CWORD\" -eq 1 ]; then\n");
+            script.push_str(&format!("        words=\"{} {}\"\n", commands, options));
+            script.push_str("    else\n        case \"${COMP_WORDS[1]}\" in\n");
+            for (command, following) in COMMANDS.iter().filter(|(_, following)| !following.is_empty()) {
+                script.push_str(&format!("            {}) words=\"{} {}\" ;;\n", command, following.join(" "), options));
+            }
+            script.push_str(&format!("            *) words=\"{}\" ;;\n", options));
+            script.push_str("        esac\n    fi\n");
+            script.push_str("    COMPREPLY=($(compgen -W \"$words\" -- \"$cur\"))\n}\n");
+            script.push_str("complete -o default -F _kipper kipper\n");
+        }
+        Shell::Zsh => {
+            script.push_str("#compdef kipper\n\n_kipper() {\n");
+            script.push_str("    if (( CURRENT == 2 )); then\n");
+            script.push_str(&format!("        compadd -- {} {}\n", commands, options));
+            script.push_str("        return\n    fi\n    case ${words[2]} in\n");
+            for (command, following) in COMMANDS.iter().filter(|(_, following)| !following.is_empty()) {
+                script.push_str(&format!("        {}) compadd -- {} {} ;;\n", command, following.join(" "), options));
+            }
+            script.push_str(&format!("        *) compadd -- {}; _files ;;\n", options));
+            script.push_str("    esac\n}\n\n_kipper \"$@\"\n");
+        }
+        Shell::Fish => {
+            script.push_str("# fish completion for kipper\n");
+            script.push_str(&format!("complete -c kipper -n __fish_use_subcommand -f -a '{}'\n", commands));
+            for (command, following) in COMMANDS.iter().filter(|(_, following)| !following.is_empty()) {
+                let words: Vec<&str> = following.iter().filter(|word| !word.starts_with("--")).copied().collect();
+                let flags: Vec<&str> = following.iter().filter_map(|word| word.strip_prefix("--")).collect();
+                if !words.is_empty() {
+                    script.push_str(&format!(
+                        "complete -c kipper -n '__fish_seen_subcommand_from {}' -f -a '{}'\n",
+                        command,
+                        words.join(" ")
+                    ));
+                }
+                for flag in flags {
+                    script.push_str(&format!("complete -c kipper -n '__fish_seen_subcommand_from {}' -l {}\n", command, flag));
+                }
+            }
+            for option in OPTIONS {
+                script.push_str(&format!("complete -c kipper -l {}\n", option.trim_start_matches("--")));
+            }
+        }
+    }
+    script
+}
+
+fn home() -> Option<PathBuf> {
+    env::var_os("HOME").filter(|home| !home.is_empty()).map(PathBuf::from)
+}
+
+/// The directory the XDG variable `name` sets, or `fallback` under the home dir.
+fn xdg_dir(name: &str, fallback: &str) -> Option<PathBuf> {
+    match env::var_os(name).filter(|dir| !dir.is_empty()) {
+        Some(dir) => Some(PathBuf::from(dir)),
+        None => Some(home()?.join(fallback)),
+    }
+}
+
+/// The startup file that shows `shell` is in use.
+fn startup_file(shell: Shell) -> Option<PathBuf> {
+    match shell {
+        Shell::Bash => Some(home()?.join(".bashrc")),
+        Shell::Zsh => Some(home()?.join(".zshrc")),
+        Shell::Fish => Some(xdg_dir("XDG_CONFIG_HOME", ".config")?.join("fish").join("config.fish")),
+    }
+}
+
+/// Where `shell` loads kipper's completions from without further setup.
+fn completion_path(shell: Shell) -> Option<PathBuf> {
+    match shell {
+        Shell::Bash => Some(xdg_dir("XDG_DATA_HOME", ".local/share")?.join("bash-completion/completions/kipper")),
+        Shell::Zsh => Some(home()?.join(".zfunc").join("_kipper")),
+        Shell::Fish => Some(xdg_dir("XDG_CONFIG_HOME", ".config")?.join("fish/completions/kipper.fish")),
+    }
+}
+
+/// The login shell and every other shell whose startup file exists.
+fn user_shells() -> Vec<Shell> {
+    let login = env::var("SHELL").unwrap_or_default();
+    let login = login.rsplit('/').next().unwrap_or_default();
+    Shell::ALL
+        .into_iter()
+        .filter(|shell| shell.name() == login || startup_file(*shell).is_some_and(|file| file.exists()))
+        .collect()
+}
+
+const ZSH_FPATH: &str = "fpath+=~/.zfunc";
+
+impl Installer {
+    /// Write completions for each of the user's shells, returning where.
+    pub fn install_completions(&self) -> Result<Vec<(Shell, PathBuf)>, InstallerError> {
+        let shells = user_shells();
+        if shells.is_empty() || cfg!(windows) {
+            return Err(InstallerError::PathError(
+                "No bash, zsh or fish found to install completions for; print one with `kipper completions SHELL`"
+                    .to_string(),
+            ));
+        }
+        let mut installed = Vec::new();
+        for shell in shells {
+            let Some(path) = completion_path(shell) else {
+                continue;
+            };
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::write(&path, completion_script(shell))?;
+            self.log_success(&format!("Installed {} completions to {}", shell, path.display()));
+            if shell == Shell::Zsh {
+                self.ensure_zsh_fpath()?;
+            }
+            installed.push((shell, path));
+        }
+
+        fs::create_dir_all(&self.install_dir)?;
+        let mut state = State::load(&self.install_dir);
+        for (_, path) in &installed {
+            if !state.completions.contains(path) {
+                state.completions.push(path.clone());
+            }
+        }
+        state.save(&self.install_dir)?;
+        // Keep the uninstaller removing what was just added
+        if self.uninstaller_path().exists() {
+            self.create_uninstaller()?;
+        }
+        self.log_info("Start a new shell to use them");
+        Ok(installed)
+    }
+
+    /// Make zsh look in ~/.zfunc, which it doesn't by default.
+    fn ensure_zsh_fpath(&self) -> Result<(), InstallerError> {
+        let Some(zshrc) = startup_file(Shell::Zsh) else {
+            return Ok(());
+        };
+        if fs::read_to_string(&zshrc).is_ok_and(|contents| contents.contains(".zfunc")) {
+            return Ok(());
+        }
+        let lines = format!("{}\nautoload -Uz compinit && compinit", ZSH_FPATH);
+        if !self.may_edit_path() {
+            self.log_info(&format!("To load them, add these lines to {}:", zshrc.display()));
+            for line in lines.lines() {
+                self.print_line(&format!("  {}", self.theme.paint(self.theme.highlight.as_deref(), line)));
+            }
+            return Ok(());
+        }
+        let mut file = OpenOptions::new().create(true).append(true).open(&zshrc)?;
+        writeln!(file, "\n# Added by kipper: load shell completions from ~/.zfunc\n{}", lines)?;
+        self.log_success(&format!("Added ~/.zfunc to fpath in {}", zshrc.display()));
+        Ok(())
+    }
+}
@@ -0,0 +1,309 @@
+// User settings (`~/.kopi/kipper.toml`). Unlike the admin policy, everything
+// here is a preference: a missing file means the defaults.
+//
+// A project can commit its own settings as `.kipper.toml`, found like git
+// finds its repository: in the current directory or the nearest one above
+// it. Its settings are merged over the user's, section by section, so a team
+// can share the version `kipper install` picks, the profile and the build
+// options:
+//
+//     [install]
+//     version = "v0.3.1"
+//     profile = "minimal"
+//
+//     [build]
+//     options = { jit = true }
+
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::InstallerError;
+use crate::announce::ProgressMode;
+use crate::size::parse_size;
+use crate::state::Profile;
+use crate::window::MaintenanceWindow;
+
+const CONFIG_FILE: &str = "kipper.toml";
+/// Project settings, committed with the project (see `find_local_config`).
+pub const LOCAL_CONFIG_FILE: &str = ".kipper.toml";
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Config {
+    pub install: InstallConfig,
+    pub theme: ThemeConfig,
+    pub confirm: ConfirmConfig,
+    pub build: BuildConfig,
+    pub update: UpdateConfig,
+    pub metrics: MetricsConfig,
+    pub cache: CacheConfig,
+    pub logs: LogsConfig,
+    pub bin: BinConfig,
+    /// The project's `.kipper.toml` merged into these settings, if any.
+    #[serde(skip)]
+    pub project_file: Option<PathBuf>,
+}
+
+/// `[install]`: what `kipper install` installs when not told.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct InstallConfig {
+    /// The version `kipper install` installs without one given.
+    pub version: Option<String>,
+    /// The profile to install with, unless `--profile` says otherwise.
+    pub profile: Option<Profile>,
+    /// Always install as with `--locked-installer`.
+    pub locked: bool,
+}
+
+/// `[theme]`: a built-in theme, optionally with some of its parts replaced.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct ThemeConfig {
+    /// `default`, `boring` or `high-contrast`.
+    pub name: Option<String>,
+    pub debug: Option<StyleConfig>,
+    pub info: Option<StyleConfig>,
+    pub success: Option<StyleConfig>,
+    pub warning: Option<StyleConfig>,
+    pub error: Option<StyleConfig>,
+    /// Color of the banner's first line and the closing message.
+    pub banner: Option<String>,
+    /// Color of the banner's second line.
+    pub tagline: Option<String>,
+    /// Color of commands worth copying, such as the one to try the install.
+    pub highlight: Option<String>,
+    /// `standard`, or `announce` for one line per progress milestone.
+    pub progress: Option<ProgressMode>,
+}
+
+/// How one message level is marked, e.g. `[theme.warning]`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct StyleConfig {
+    pub prefix: Option<String>,
+    pub color: Option<String>,
+}
+
+/// `[confirm]`: which actions ask before going ahead. Without a terminal to
+/// ask at, an action that needs confirmation isn't done unless `--force` is given.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct ConfirmConfig {
+    /// Rebuilding when Kopi is already installed.
+    pub reinstall: bool,
+    /// Adding the bin dir to a shell startup file.
+    pub modify_path: bool,
+    /// Removing Kopi or one of its versions.
+    pub uninstall: bool,
+    /// Deleting settings, caches and logs with `--purge`.
+    pub purge: bool,
+}
+
+impl Default for ConfirmConfig {
+    fn default() -> Self {
+        ConfirmConfig {
+            reinstall: true,
+            modify_path: true,
+            uninstall: false,
+            purge: true,
+        }
+    }
+}
+
+/// `[build]`: the environment kopi-lang is built in.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct BuildConfig {
+    /// Variables to pass through although they'd normally be removed, e.g.
+    /// `RUSTC_WRAPPER`; a trailing `*` matches a prefix.
+    pub keep_env: Vec<String>,
+    /// Build with a CARGO_HOME of kipper's own, ignoring ~/.cargo/config.toml.
+    pub isolated_cargo_home: bool,
+    /// Build options kopi-lang offers, as with `--with-NAME`/`--without-NAME`;
+    /// the command line still has the last word.
+    pub options: BTreeMap<String, bool>,
+}
+
+/// `[update]`: how `kipper update --auto` behaves when a timer runs it.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct UpdateConfig {
+    /// Maintenance windows (see `window`) in which an update may switch
+    /// versions; none means any time.
+    pub windows: Vec<String>,
+}
+
+impl UpdateConfig {
+    pub fn maintenance_windows(&self) -> Vec<MaintenanceWindow> {
+        self.windows.iter().filter_map(|window| window.parse().ok()).collect()
+    }
+}
+
+/// `[metrics]`: where to write run metrics for Prometheus.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct MetricsConfig {
+    /// A `.prom` file in node_exporter's textfile collector directory.
+    pub textfile: Option<PathBuf>,
+}
+
+/// `[cache]`: a remote build cache shared between machines.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct CacheConfig {
+    /// Base URL builds are fetched from (see `remote_cache`).
+    pub remote: Option<String>,
+    /// Also upload builds made on this machine.
+    pub upload: bool,
+    /// GitHub repository (`OWNER/REPO`) whose workflows attest the builds in
+    /// the remote cache; downloads without a valid attestation aren't used.
+    pub attested_by: Option<String>,
+}
+
+/// `[logs]`: how much of ~/.kopi/logs is kept (see `logging`).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct LogsConfig {
+    /// Size at which kipper.log is rotated, e.g. `10M`.
+    pub max_size: String,
+    /// Rotated, compressed logs to keep.
+    pub keep: usize,
+    /// Days after which rotated logs are removed.
+    pub max_age_days: u64,
+}
+
+impl Default for LogsConfig {
+    fn default() -> Self {
+        LogsConfig {
+            max_size: "10M".to_string(),
+            keep: 5,
+            max_age_days: 30,
+        }
+    }
+}
+
+impl LogsConfig {
+    pub fn max_size_bytes(&self) -> u64 {
+        parse_size(&self.max_size).unwrap_or(10 * 1024 * 1024)
+    }
+}
+
+/// How kopi and its tools are put on PATH (see `expose`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BinStrategy {
+    /// A symlink in the bin dir to the shim host.
+    Symlink,
+    /// A copy of the shim host in the bin dir, for filesystems without symlinks.
+    Copy,
+    /// A small script in the bin dir that runs the shim host.
+    Shim,
+    /// Nothing in the bin dir: `~/.kopi/current`, a link to the default
+    /// version's dir, goes on PATH instead.
+    #[serde(rename = "none")]
+    Direct,
+}
+
+/// What to do when a file kipper didn't make is where a tool would go.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConflictPolicy {
+    /// Stop with an error and leave the file alone.
+    Fail,
+    /// Replace it.
+    #[default]
+    Overwrite,
+    /// Rename it to `NAME.kipper-backup` first.
+    Backup,
+}
+
+/// `[bin]`: how executables are exposed in the bin dir.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct BinConfig {
+    /// Symlinks on Unix and copies on Windows when unset.
+    pub strategy: Option<BinStrategy>,
+    pub conflict: ConflictPolicy,
+}
+
+/// A config file as a table, checked on its own so errors point at the
+/// right file; empty when there is no file.
+fn read_table(path: &Path) -> Result<toml::Table, InstallerError> {
+    if !path.exists() {
+        return Ok(toml::Table::new());
+    }
+    let contents = fs::read_to_string(path)?;
+    let invalid = |reason: String| InstallerError::PathError(format!("Invalid config file {}: {}", path.display(), reason));
+    Config::parse(&contents).map_err(invalid)?;
+    contents.parse().map_err(|e: toml::de::Error| invalid(e.to_string()))
+}
+
+/// `over` laid over `base`: sections are merged key by key, anything else
+/// in `over` replaces what `base` has.
+fn merge(base: &mut toml::Table, over: toml::Table) {
+    for (key, value) in over {
+        match (base.get_mut(&key), value) {
+            (Some(toml::Value::Table(base)), toml::Value::Table(over)) => merge(base, over),
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+/// Where the user's settings live.
+pub fn config_path(install_dir: &Path) -> PathBuf {
+    install_dir.join(CONFIG_FILE)
+}
+
+/// The project settings file for `dir`: the nearest `.kipper.toml` in it or
+/// a directory above, as git finds its repository.
+pub fn find_local_config(dir: &Path) -> Option<PathBuf> {
+    dir.ancestors().map(|dir| dir.join(LOCAL_CONFIG_FILE)).find(|path| path.is_file())
+}
+
+impl Config {
+    /// Read the settings file. A file that exists but can't be parsed is an
+    /// error rather than ignored, so a typo doesn't silently drop a setting.
+    ///
+    /// The project settings for the current directory are merged in.
+    pub fn load(install_dir: &Path) -> Result<Config, InstallerError> {
+        let mut settings = read_table(&config_path(install_dir))?;
+        let project_file = env::current_dir().ok().and_then(|cwd| find_local_config(&cwd));
+        if let Some(project_file) = &project_file {
+            merge(&mut settings, read_table(project_file)?);
+        }
+        let contents = toml::to_string(&settings).map_err(|e| InstallerError::PathError(e.to_string()))?;
+        let mut config = Config::parse(&contents).map_err(|reason| {
+            let files = match &project_file {
+                Some(project_file) => format!("{} with {}", config_path(install_dir).display(), project_file.display()),
+                None => config_path(install_dir).display().to_string(),
+            };
+            // toml quotes the merged settings, which aren't a file; the last line says what's wrong
+            let reason = reason.trim().lines().last().unwrap_or_default().to_string();
+            InstallerError::PathError(format!("Invalid settings in {}: {}", files, reason))
+        })?;
+        config.project_file = project_file;
+        Ok(config)
+    }
+
+    /// Settings from the contents of a config file, checked against what
+    /// each setting accepts.
+    pub fn parse(contents: &str) -> Result<Config, String> {
+        let config: Config = toml::from_str(contents).map_err(|e| e.to_string())?;
+        if parse_size(&config.logs.max_size).is_none() {
+            return Err(format!("[logs] max-size '{}' is not a size such as 10M", config.logs.max_size));
+        }
+        for window in &config.update.windows {
+            window
+                .parse::<MaintenanceWindow>()
+                .map_err(|e| format!("maintenance window '{}': {}", window, e))?;
+        }
+        Ok(config)
+    }
+}
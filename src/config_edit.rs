@@ -0,0 +1,212 @@
+// `kipper config set KEY VALUE`: change one setting without opening the
+// file. KEY is the setting's path, such as `logs.keep` or
+// `theme.warning.color`; VALUE is read as TOML (`10`, `true`, `["A", "B"]`)
+// and otherwise taken as a string, so `logs.max-size 20M` needs no quotes.
+//
+// `--global` (the default) changes ~/.kopi/kipper.toml; `--local` changes
+// the project's `.kipper.toml`, the nearest one above the current directory
+// or a new one in it. The file is checked against the settings schema with
+// the change applied before anything is written, and the old and new values
+// are shown as a diff. Only the setting's own line is rewritten, so comments
+// and layout elsewhere in the file stay as they are.
+
+use std::env;
+use std::fmt;
+use std::fs;
+use std::path::PathBuf;
+
+use crate::config::{Config, LOCAL_CONFIG_FILE, config_path, find_local_config};
+use crate::{Installer, InstallerError};
+
+/// Which config file a change goes to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Scope {
+    Global,
+    Local,
+}
+
+impl fmt::Display for Scope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Scope::Global => "global",
+            Scope::Local => "local",
+        })
+    }
+}
+
+/// VALUE as TOML, or as a string when it isn't valid TOML.
+fn parse_value(raw: &str) -> toml::Value {
+    format!("value = {}", raw)
+        .parse::<toml::Table>()
+        .ok()
+        .and_then(|mut table| table.remove("value"))
+        .unwrap_or_else(|| toml::Value::String(raw.to_string()))
+}
+
+fn lookup<'a>(table: &'a toml::Table, path: &[&str]) -> Option<&'a toml::Value> {
+    let (key, sections) = path.split_last()?;
+    let mut table = table;
+    for section in sections {
+        table = table.get(*section)?.as_table()?;
+    }
+    table.get(*key)
+}
+
+fn insert(table: &mut toml::Table, path: &[&str], value: toml::Value) -> Result<(), String> {
+    let Some((key, sections)) = path.split_last() else {
+        return Err("no setting given".to_string());
+    };
+    let mut table = table;
+    for section in sections {
+        table = table
+            .entry(section.to_string())
+            .or_insert_with(|| toml::Value::Table(toml::Table::new()))
+            .as_table_mut()
+            .ok_or_else(|| format!("'{}' is a value, not a section", section))?;
+    }
+    table.insert(key.to_string(), value);
+    Ok(())
+}
+
+/// The name of a `[section]` header line.
+fn header_name(line: &str) -> Option<String> {
+    let line = line.trim();
+    let name = line.strip_prefix('[')?.split(']').next()?;
+    (!line.starts_with("[[")).then(|| name.split('.').map(str::trim).collect::<Vec<_>>().join("."))
+}
+
+/// `contents` with `key = value` set in `[section]`: the key's line
+/// replaced where it has one, else added at the end of the section, else
+/// the section added.
+fn set_in_text(contents: &str, sections: &[&str], key: &str, value: &toml::Value) -> String {
+    let section = sections.join(".");
+    let assignment = format!("{} = {}", key, value);
+    let mut lines: Vec<String> = contents.lines().map(str::to_string).collect();
+    let mut current = String::new();
+    let mut section_end = None;
+    for (i, line) in lines.iter().enumerate() {
+        if let Some(name) = header_name(line) {
+            current = name;
+            continue;
+        }
+        if current != section {
+            continue;
+        }
+        if line.split_once('=').is_some_and(|(name, _)| name.trim() == key) {
+            lines[i] = assignment;
+            return lines.join("\n") + "\n";
+        }
+        // Comments just above the next header belong to it
+        if !line.trim().is_empty() && !line.trim_start().starts_with('#') {
+            section_end = Some(i + 1);
+        }
+    }
+
+    let has_section = section.is_empty() || lines.iter().any(|line| header_name(line).as_deref() == Some(&section));
+    match section_end {
+        Some(end) => lines.insert(end, assignment),
+        // Top-level keys go before the first section
+        None if section.is_empty() => {
+            let first = lines.iter().position(|line| header_name(line).is_some()).unwrap_or(lines.len());
+            lines.insert(first, assignment);
+        }
+        None if has_section => {
+            let header = lines.iter().position(|line| header_name(line).as_deref() == Some(&section)).unwrap_or(0);
+            lines.insert(header + 1, assignment);
+        }
+        None => {
+            if lines.last().is_some_and(|line| !line.trim().is_empty()) {
+                lines.push(String::new());
+            }
+            lines.push(format!("[{}]", section));
+            lines.push(assignment);
+        }
+    }
+    lines.join("\n") + "\n"
+}
+
+impl Installer {
+    /// The file `scope` refers to from the current directory.
+    pub fn config_file(&self, scope: Scope) -> Result<PathBuf, InstallerError> {
+        Ok(match scope {
+            Scope::Global => config_path(&self.install_dir),
+            Scope::Local => {
+                let cwd = env::current_dir()?;
+                find_local_config(&cwd).unwrap_or_else(|| cwd.join(LOCAL_CONFIG_FILE))
+            }
+        })
+    }
+
+    /// Set `key` to `raw` in the `scope` config file, after checking the
+    /// result is a valid config, and show what changed.
+    pub fn config_set(&self, key: &str, raw: &str, scope: Scope) -> Result<(), InstallerError> {
+        let path = self.config_file(scope)?;
+        let keys: Vec<&str> = key.split('.').collect();
+        if keys.iter().any(|part| part.is_empty() || part.contains(char::is_whitespace)) {
+            return Err(InstallerError::PathError(format!("'{}' is not a setting such as logs.keep", key)));
+        }
+        let contents = if path.exists() { fs::read_to_string(&path)? } else { String::new() };
+        let table: toml::Table = contents
+            .parse()
+            .map_err(|e| InstallerError::PathError(format!("Invalid config file {}: {}", path.display(), e)))?;
+        let old = lookup(&table, &keys).cloned();
+
+        // A value that reads as a number or boolean may be meant as a string
+        let typed = parse_value(raw);
+        let mut candidates = vec![typed.clone()];
+        if !typed.is_str() {
+            candidates.push(toml::Value::String(raw.to_string()));
+        }
+        let mut rejected = None;
+        let mut accepted = None;
+        for value in candidates {
+            let mut changed = table.clone();
+            insert(&mut changed, &keys, value.clone()).map_err(InstallerError::PathError)?;
+            match Config::parse(&toml::to_string(&changed).map_err(|e| InstallerError::PathError(e.to_string()))?) {
+                Ok(_) => {
+                    accepted = Some((value, changed));
+                    break;
+                }
+                // toml quotes the file it parsed, which isn't the user's; the last line says what's wrong
+                Err(reason) => {
+                    rejected.get_or_insert_with(|| reason.trim().lines().last().unwrap_or_default().to_string());
+                }
+            }
+        }
+        let Some((value, changed)) = accepted else {
+            return Err(InstallerError::PathError(format!(
+                "Can't set {} to {}: {}",
+                key,
+                raw,
+                rejected.unwrap_or_default()
+            )));
+        };
+
+        if old.as_ref() == Some(&value) {
+            self.log_info(&format!("{} is already {} in {}", key, value, path.display()));
+            return Ok(());
+        }
+
+        let (sections, name) = keys.split_at(keys.len() - 1);
+        let mut updated = set_in_text(&contents, sections, name[0], &value);
+        // Inline tables and dotted keys aren't edited in place; rewrite those files whole
+        if updated.parse::<toml::Table>().ok().as_ref() != Some(&changed) {
+            updated = toml::to_string(&changed).map_err(|e| InstallerError::PathError(e.to_string()))?;
+        }
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&path, updated)?;
+
+        self.log_success(&format!("Set {} in {} ({})", key, path.display(), scope));
+        let default = toml::Table::try_from(Config::default()).ok();
+        let before = match (&old, default.as_ref().and_then(|default| lookup(default, &keys))) {
+            (Some(old), _) => format!("{} = {}", key, old),
+            (None, Some(default)) => format!("{} = {} (default)", key, default),
+            (None, None) => format!("{} (unset)", key),
+        };
+        self.print_line(&self.theme.paint(self.theme.error.color.as_deref(), &format!("  - {}", before)));
+        self.print_line(&self.theme.paint(self.theme.success.color.as_deref(), &format!("  + {} = {}", key, value)));
+        Ok(())
+    }
+}
@@ -0,0 +1,87 @@
+// Asking before doing something hard to undo. `[confirm]` in kipper.toml
+// picks which actions ask; `--force` answers yes to every question but
+// those asked in person: running something as root, or opening a shell.
+
+use std::io::{self, IsTerminal, Write};
+
+use crate::{Installer, InstallerError};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Reinstall,
+    ModifyPath,
+    Uninstall,
+    Purge,
+}
+
+impl Installer {
+    /// Answer yes to every confirmation instead of asking.
+    pub fn with_force(mut self, force: bool) -> Self {
+        self.force = force;
+        self
+    }
+
+    pub fn is_forced(&self) -> bool {
+        self.force
+    }
+
+    fn needs_confirmation(&self, action: Action) -> bool {
+        let confirm = &self.config.confirm;
+        match action {
+            Action::Reinstall => confirm.reinstall,
+            Action::ModifyPath => confirm.modify_path,
+            Action::Uninstall => confirm.uninstall,
+            Action::Purge => confirm.purge,
+        }
+    }
+
+    /// Whether to go ahead with `action`: yes when the config doesn't ask for
+    /// confirmation, otherwise the answer to `question`.
+    pub(crate) fn confirm(&self, action: Action, question: &str) -> Result<bool, InstallerError> {
+        if !self.needs_confirmation(action) {
+            return Ok(true);
+        }
+        self.ask(question, false)
+    }
+
+    /// The error for an action the user (or a missing terminal) said no to.
+    pub(crate) fn declined(&self, action: &str) -> InstallerError {
+        if !io::stdin().is_terminal() {
+            self.log_info(&format!("Not asking without a terminal; pass --force to {} anyway", action));
+        }
+        InstallerError::Cancelled
+    }
+
+    /// Ask a yes/no `question`. `default` is the answer to an empty reply;
+    /// without a terminal the answer is no, unless `--force` or
+    /// `--unattended` was given.
+    pub(crate) fn ask(&self, question: &str, default: bool) -> Result<bool, InstallerError> {
+        if self.force || self.unattended {
+            return Ok(true);
+        }
+        if !io::stdin().is_terminal() {
+            return Ok(false);
+        }
+
+        self.prompt(question, default)
+    }
+
+    /// Ask a yes/no `question` that only the user at a terminal may answer:
+    /// `--force`, `--yes` and `--unattended` don't, and without a terminal
+    /// the answer is no.
+    pub(crate) fn ask_in_person(&self, question: &str, default: bool) -> Result<bool, InstallerError> {
+        if self.unattended || !io::stdin().is_terminal() {
+            return Ok(false);
+        }
+        self.prompt(question, default)
+    }
+
+    fn prompt(&self, question: &str, default: bool) -> Result<bool, InstallerError> {
+        eprint!("{} {} ", question, if default { "(Y/n):" } else { "(y/N):" });
+        io::stderr().flush()?;
+        let mut input = String::new();
+        io::stdin().read_line(&mut input)?;
+        let answer = input.trim().to_lowercase();
+        Ok(if answer.is_empty() { default } else { answer.starts_with('y') })
+    }
+}
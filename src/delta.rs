@@ -0,0 +1,177 @@
+// Delta downloads from the remote cache: next to an entry, the store may
+// hold a patch to it from an earlier entry of the same rustc and target
+// (usually the nightly before), so a machine that has the earlier build
+// downloads what changed rather than every binary whole. kipper publishes no
+// release binaries of its own; the remote cache is where prebuilt binaries
+// come from, so "patches published alongside releases" are these patches
+// between its entries, uploaded next to them. A delta is
+//
+//     <remote>/<entry>.from-<base>.tar.gz
+//
+// holding the entry's `build.json` and, for each binary, `<binary>.zst-patch`
+// made with `zstd --patch-from=<base's binary>`, or the binary itself when the
+// base has none to patch. The base is the newest entry of the same rustc and
+// target in the local cache. Patched binaries have to hash as `build.json`
+// says; a delta that isn't there, doesn't apply or doesn't check out is
+// dropped for the whole entry, as before.
+//
+// Machines that upload their builds (`[cache] upload`) also upload the delta
+// from their own newest earlier entry, which for a machine building every
+// nightly is the one others have. Both ends need the `zstd` tool; without
+// it, entries go whole.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::process::Command;
+
+use crate::bincache::{entry_binaries, entry_verifies};
+use crate::cache::format_size;
+use crate::remote_cache::{sibling, unpack_archive};
+use crate::{Installer, InstallerError};
+
+/// Ending of a binary's patch in a delta.
+const PATCH_SUFFIX: &str = ".zst-patch";
+
+/// `zstd` needs a window as large as the binary it patches; this covers any.
+const WINDOW_LOG: &str = "--long=31";
+
+/// The name a delta from `base` to `entry` goes by in the remote cache.
+fn delta_name(entry: &Path, base: &Path) -> Option<String> {
+    Some(format!("{}.from-{}", entry.file_name()?.to_str()?, base.file_name()?.to_str()?))
+}
+
+impl Installer {
+    /// Fetch `entry` as a delta from the local entry it is patched from, when
+    /// the remote cache has one. Returns whether `entry` is now there; when
+    /// not, the entry is downloaded whole.
+    pub(crate) fn download_delta(&self, entry: &Path) -> bool {
+        let Some(base) = self.delta_base(entry) else {
+            return false;
+        };
+        let Some(url) = delta_name(entry, &base).and_then(|name| self.remote_cache_file(&name)) else {
+            return false;
+        };
+        if !self.command_exists("zstd") {
+            return false;
+        }
+        match self.try_download_delta(&url, &base, entry) {
+            Ok(Some(size)) => {
+                self.log_success(&format!("Downloaded a prebuilt Kopi from the remote cache as a {} patch", format_size(size)));
+                true
+            }
+            Ok(None) => false,
+            Err(e) => {
+                self.log_info(&format!("Not using the patch from {}: {:?}; downloading the whole build", url, e));
+                false
+            }
+        }
+    }
+
+    /// Download and apply the delta at `url` to `base`, giving `entry`.
+    /// Returns the delta's size, or `None` when there is none.
+    fn try_download_delta(&self, url: &str, base: &Path, entry: &Path) -> Result<Option<u64>, InstallerError> {
+        let Some(archive) = self.fetch_archive(url)? else {
+            return Ok(None);
+        };
+        let partial = sibling(entry, ".delta");
+        let applied = unpack_archive(&archive, &partial)
+            .map_err(InstallerError::from)
+            .and_then(|()| self.apply_delta(base, &partial));
+        if let Err(e) = applied {
+            let _ = fs::remove_dir_all(&partial);
+            return Err(e);
+        }
+        if entry.exists() {
+            fs::remove_dir_all(entry)?;
+        }
+        fs::rename(&partial, entry)?;
+        Ok(Some(archive.len() as u64))
+    }
+
+    /// Patch each binary in `dir` from the same one in `base`, and check the
+    /// results against the entry's hashes.
+    fn apply_delta(&self, base: &Path, dir: &Path) -> Result<(), InstallerError> {
+        let binaries = entry_binaries(dir).ok_or_else(|| InstallerError::PathError("it has no usable build.json".to_string()))?;
+        for binary in &binaries {
+            let patch = dir.join(format!("{}{}", binary, PATCH_SUFFIX));
+            if !patch.exists() {
+                continue;
+            }
+            let from = base.join(binary);
+            let output = self.run_command(
+                Command::new("zstd")
+                    .args(["-q", "-d", "-f", WINDOW_LOG])
+                    .arg(format!("--patch-from={}", from.display()))
+                    .arg(&patch)
+                    .arg("-o")
+                    .arg(dir.join(binary)),
+            )?;
+            if !output.status.success() {
+                let error = String::from_utf8_lossy(&output.stderr);
+                return Err(InstallerError::PathError(format!("zstd couldn't patch {}: {}", binary, error.trim())));
+            }
+            fs::set_permissions(dir.join(binary), fs::metadata(&from)?.permissions())?;
+            fs::remove_file(&patch)?;
+        }
+        if !entry_verifies(dir) {
+            return Err(InstallerError::PathError("the patched binaries don't match their hashes".to_string()));
+        }
+        Ok(())
+    }
+
+    /// After uploading `entry`, upload the delta to it from the newest
+    /// earlier entry here. A failure only costs others a whole download.
+    pub(crate) fn upload_delta(&self, entry: &Path) {
+        let Some(base) = self.delta_base(entry) else {
+            return;
+        };
+        let Some(url) = delta_name(entry, &base).and_then(|name| self.remote_cache_file(&name)) else {
+            return;
+        };
+        if !self.command_exists("zstd") {
+            return;
+        }
+        let staging = sibling(entry, ".delta-upload");
+        let uploaded = self.make_delta(&base, entry, &staging).and_then(|()| Ok(self.put_archive(&url, &staging)?));
+        let _ = fs::remove_dir_all(&staging);
+        match uploaded {
+            Ok(()) => self.log_success(&format!("Uploaded the patch from the previous build to {}", url)),
+            Err(e) => self.log_warning(&format!("Could not upload a patch to the remote cache: {:?}", e)),
+        }
+    }
+
+    /// Write the delta from `base` to `entry` into `dest`.
+    fn make_delta(&self, base: &Path, entry: &Path, dest: &Path) -> Result<(), InstallerError> {
+        let binaries = entry_binaries(entry).ok_or_else(|| InstallerError::PathError("the build has no build.json".to_string()))?;
+        if dest.exists() {
+            fs::remove_dir_all(dest)?;
+        }
+        fs::create_dir_all(dest)?;
+        fs::copy(entry.join("build.json"), dest.join("build.json"))?;
+        for binary in &binaries {
+            let from = base.join(binary);
+            if !from.is_file() {
+                fs::copy(entry.join(binary), dest.join(binary))?;
+                continue;
+            }
+            let output = self.run_command(
+                Command::new("zstd")
+                    .args(["-q", "-19", "-f", WINDOW_LOG])
+                    .arg(format!("--patch-from={}", from.display()))
+                    .arg(entry.join(binary))
+                    .arg("-o")
+                    .arg(dest.join(format!("{}{}", binary, PATCH_SUFFIX))),
+            )?;
+            if !output.status.success() {
+                let error = String::from_utf8_lossy(&output.stderr);
+                return Err(InstallerError::Io(io::Error::other(format!(
+                    "zstd couldn't make a patch of {}: {}",
+                    binary,
+                    error.trim()
+                ))));
+            }
+        }
+        Ok(())
+    }
+}
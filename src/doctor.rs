@@ -0,0 +1,321 @@
+// `kipper doctor`: diagnose an existing installation and the environment it
+// runs in, explaining constraints rather than just reporting failures.
+//
+// Every PATH entry that provides kopi (or another shimmed tool) is listed
+// when there is more than one, and links kipper made that now point at
+// nothing, typically after a version dir was deleted by hand, are flagged;
+// `kipper doctor --fix` removes them.
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::check::CheckResult;
+use crate::features;
+use crate::known_releases::pin_mismatch;
+use crate::path::{PathStatus, same_dir};
+use crate::platform::{ImmutableKind, bsd, immutable_os, in_dev_container, is_wsl, on_windows_drive};
+use crate::shim::{SHIM_BUDGET, SHIM_HOST};
+use crate::signing::Verdict;
+use crate::state::State;
+use crate::target::WindowsAbi;
+use crate::toolchain::exe_name;
+use crate::{Installer, InstallerError};
+
+/// A tool found in a directory on PATH (or in the bin dir).
+struct Found {
+    path: PathBuf,
+    /// Where it links to, when it is a symlink.
+    target: Option<PathBuf>,
+}
+
+impl Found {
+    /// A link whose target no longer exists.
+    fn is_dangling(&self) -> bool {
+        self.target.is_some() && fs::metadata(&self.path).is_err()
+    }
+}
+
+fn link_target(path: &Path) -> Option<PathBuf> {
+    let target = fs::read_link(path).ok()?;
+    Some(match path.parent() {
+        Some(parent) if target.is_relative() => parent.join(target),
+        _ => target,
+    })
+}
+
+impl Installer {
+    /// Each place `name` is found, in PATH order, with the bin dir checked
+    /// too when it isn't on PATH. A directory listed twice counts once.
+    fn find_everywhere(&self, name: &str) -> Vec<Found> {
+        let mut dirs: Vec<PathBuf> = Vec::new();
+        let path = env::var_os("PATH").unwrap_or_default();
+        for dir in env::split_paths(&path).chain([self.bin_dir.clone()]) {
+            if !dirs.iter().any(|seen| same_dir(seen, &dir)) {
+                dirs.push(dir);
+            }
+        }
+        dirs.into_iter()
+            .map(|dir| dir.join(exe_name(name)))
+            .filter(|path| path.symlink_metadata().is_ok())
+            .map(|path| Found {
+                target: link_target(&path),
+                path,
+            })
+            .collect()
+    }
+
+    /// Whether kipper made the link at `found`: it points into the install dir.
+    fn made_by_kipper(&self, found: &Found) -> bool {
+        found.target.as_ref().is_some_and(|target| target.starts_with(&self.install_dir))
+    }
+
+    /// Links to the install dir whose targets are gone, for every tool and alias.
+    pub fn stale_links(&self) -> Vec<PathBuf> {
+        let mut names = self.tools();
+        names.extend(self.aliases().into_iter().map(|(alias, _)| alias));
+        names
+            .iter()
+            .flat_map(|name| self.find_everywhere(name))
+            .filter(|found| found.is_dangling() && self.made_by_kipper(found))
+            .map(|found| found.path)
+            .collect()
+    }
+
+    /// `kipper doctor --fix`: remove the stale links, returning how many.
+    pub fn remove_stale_links(&self) -> Result<usize, InstallerError> {
+        let stale = self.stale_links();
+        for link in &stale {
+            fs::remove_file(link)?;
+            self.log_success(&format!("Removed {}", link.display()));
+        }
+        if stale.is_empty() {
+            self.log_success("No stale links to remove");
+        }
+        Ok(stale.len())
+    }
+
+    fn path_entry_checks(&self) -> Vec<CheckResult> {
+        let mut results = Vec::new();
+        for name in self.tools() {
+            let live: Vec<Found> = self
+                .find_everywhere(&name)
+                .into_iter()
+                .filter(|found| !found.is_dangling())
+                .collect();
+            if live.len() > 1 {
+                let listed: Vec<String> = live
+                    .iter()
+                    .enumerate()
+                    .map(|(i, found)| {
+                        let whose = if self.made_by_kipper(found) { "kipper's" } else { "another install" };
+                        let runs = if i == 0 { ", runs" } else { "" };
+                        format!("  {} ({}{})", found.path.display(), whose, runs)
+                    })
+                    .collect();
+                results.push(CheckResult::note(
+                    "PATH",
+                    format!("{} is found {} times:\n{}", name, live.len(), listed.join("\n")),
+                    "Remove the copies you don't use, so it is clear which one runs",
+                ));
+            }
+        }
+
+        let stale = self.stale_links();
+        if !stale.is_empty() {
+            let listed: Vec<String> = stale.iter().map(|link| format!("  {}", link.display())).collect();
+            results.push(CheckResult::fail(
+                "stale links",
+                format!("{} link(s) point at removed versions:\n{}", stale.len(), listed.join("\n")),
+                "Run `kipper doctor --fix` to remove them",
+            ));
+        }
+        results
+    }
+
+    pub fn doctor_checks(&self) -> Vec<CheckResult> {
+        let mut results = Vec::new();
+
+        if let Some(os) = immutable_os() {
+            let layout = match os.kind {
+                ImmutableKind::Ostree => "system packages are layered with rpm-ostree and need a reboot",
+                ImmutableKind::Nix => "system packages come from your Nix configuration",
+            };
+            results.push(CheckResult::note(
+                "system",
+                format!(
+                    "{} has a read-only root; kipper only writes to {} and {} ({})",
+                    os.name,
+                    self.install_dir.display(),
+                    self.bin_dir.display(),
+                    layout
+                ),
+                os.build_tools_hint(),
+            ));
+        } else if in_dev_container() {
+            results.push(CheckResult::pass("system", "running inside a toolbox/distrobox container"));
+        } else if let Some(os) = bsd() {
+            results.push(if self.command_exists("gmake") {
+                CheckResult::pass("system", format!("{} with GNU make available as gmake", os.name()))
+            } else {
+                CheckResult::note(
+                    "system",
+                    format!("{} without GNU make", os.name()),
+                    format!(
+                        "Build scripts that run make may fail with BSD make; install gmake with `{}`",
+                        os.install_command("gmake")
+                    ),
+                )
+            });
+        }
+
+        if let Some(environment) = self.ci_environment() {
+            results.push(CheckResult::pass(
+                "environment",
+                format!("running in {}: unattended, minimal profile unless chosen (--no-detect to turn off)", environment),
+            ));
+        }
+
+        if is_wsl() {
+            results.push(match self.windows_kopi_on_path() {
+                Some(windows_kopi) => CheckResult::note(
+                    "WSL",
+                    format!("a Windows Kopi is reachable through interop: {}", windows_kopi.display()),
+                    "Run `kopi` inside WSL, not `kopi.exe`; set appendWindowsPath = false under [interop]\n  \
+                     in /etc/wsl.conf to keep Windows programs off WSL's PATH",
+                ),
+                None => CheckResult::pass("WSL", "no Windows Kopi visible through interop"),
+            });
+            if on_windows_drive(&self.install_dir) {
+                results.push(CheckResult::fail(
+                    "WSL",
+                    format!("{} is on a Windows drive", self.install_dir.display()),
+                    "Keep HOME on the Linux filesystem; builds on /mnt drives are slow and lose file permissions",
+                ));
+            }
+        }
+
+        if cfg!(windows) {
+            results.push(self.windows_toolchain_check());
+            if self.windows_toolchains().contains(&WindowsAbi::Msvc) {
+                results.push(self.build_tools_check());
+            }
+        }
+
+        let missing = features::missing();
+        results.push(if self.config.cache.remote.is_some() && missing.contains(&"http") {
+            CheckResult::note(
+                "features",
+                "a remote cache is configured, but this kipper is built without `http`",
+                "Builds happen locally; use a kipper built with `--features http` to share them",
+            )
+        } else if missing.is_empty() {
+            CheckResult::pass("features", format!("built with {}", features::enabled().join(", ")))
+        } else {
+            CheckResult::pass("features", format!("built without {}", missing.join(", ")))
+        });
+
+        match self.default_version() {
+            Some(version) if self.version_dir(&version).join(exe_name("kopi")).exists() => {
+                results.push(CheckResult::pass("default version", format!("{} is installed", version)));
+            }
+            Some(version) => results.push(CheckResult::fail(
+                "default version",
+                format!("{} is the default but isn't installed", version),
+                format!("Run `kipper install {}`", version),
+            )),
+            None => results.push(CheckResult::fail(
+                "default version",
+                "no Kopi version is installed",
+                "Run `kipper install`",
+            )),
+        }
+
+        let yanked = self.yanked_versions();
+        for toolchain in self.list().unwrap_or_default() {
+            if let Some(yanked) = yanked.iter().find(|y| y.version == toolchain.name) {
+                results.push(CheckResult::fail(
+                    "yanked",
+                    yanked.describe(),
+                    format!("Install a newer version, then `kipper uninstall {}`", toolchain.name),
+                ));
+            }
+        }
+
+        let state = State::load(&self.install_dir);
+        for (version, record) in &state.versions {
+            if record.source.is_none()
+                && let Some(mismatch) = pin_mismatch(version, &record.commit)
+            {
+                results.push(CheckResult::fail(
+                    "known release",
+                    mismatch,
+                    format!("Reinstall {} from a source you trust, or uninstall it", version),
+                ));
+            }
+        }
+
+        let host = self.install_dir.join(exe_name(SHIM_HOST));
+        results.push(if host.exists() {
+            CheckResult::pass("shims", format!("{} is present", host.display()))
+        } else {
+            CheckResult::fail(
+                "shims",
+                format!("{} is missing, so the kopi shims can't run", host.display()),
+                "Reinstall any version to restore it",
+            )
+        });
+
+        if let Some(overhead) = self.shim_overhead(20) {
+            let took = format!("{:.2} ms before kopi starts", overhead.as_secs_f64() * 1000.0);
+            results.push(if overhead <= SHIM_BUDGET {
+                CheckResult::pass("shim overhead", took)
+            } else {
+                CheckResult::note(
+                    "shim overhead",
+                    format!("{} (budget {} ms)", took, SHIM_BUDGET.as_millis()),
+                    format!("Every kopi run reads {}; a network or slow disk there slows them all", self.install_dir.display()),
+                )
+            });
+        }
+
+        results.push(match self.path_status("kopi") {
+            PathStatus::Ok => CheckResult::pass("PATH", format!("kopi resolves to {}", self.bin_dir.display())),
+            PathStatus::Missing => CheckResult::fail(
+                "PATH",
+                format!("{} is not on PATH", self.bin_dir.display()),
+                format!("Add {} to PATH in your shell startup file", self.bin_dir.display()),
+            ),
+            PathStatus::Shadowed(other) => CheckResult::fail(
+                "PATH",
+                format!("{} runs instead of kipper's kopi", other.display()),
+                format!("Put {} before {} in PATH", self.bin_dir.display(), other.parent().unwrap_or(&other).display()),
+            ),
+        });
+        results.extend(self.path_entry_checks());
+
+        if let Ok(verdicts) = self.verify_metadata() {
+            let bad: Vec<String> = verdicts
+                .iter()
+                .filter(|v| v.verdict != Verdict::Valid)
+                .map(|v| format!("{} {}", v.file, v.verdict.describe()))
+                .collect();
+            results.push(if bad.is_empty() {
+                CheckResult::pass("signatures", format!("{} metadata files match their signatures", verdicts.len()))
+            } else {
+                CheckResult::fail(
+                    "signatures",
+                    bad.join("; "),
+                    "Something other than kipper changed its metadata; see `kipper verify`, and reinstall what it names",
+                )
+            });
+        }
+
+        results
+    }
+
+    /// Report every doctor check and fail if any of them failed.
+    pub fn doctor(&self) -> Result<(), InstallerError> {
+        self.report_checks(&self.doctor_checks())
+    }
+}
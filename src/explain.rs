@@ -0,0 +1,264 @@
+// Error codes: failures kipper recognises get a stable code, printed with
+// the error, and `kipper explain <code>` describes the failure at length with
+// its usual causes and fixes, in the spirit of `rustc --explain`.
+
+use std::io;
+
+use crate::InstallerError;
+
+pub struct Explanation {
+    pub code: &'static str,
+    pub title: &'static str,
+    pub description: &'static str,
+    pub causes: &'static [&'static str],
+    pub fixes: &'static [&'static str],
+}
+
+pub const GIT_MISSING: &str = "E0001";
+pub const FETCH_FAILED: &str = "E0002";
+pub const GIT_AUTH: &str = "E0003";
+pub const VERSION_NOT_FOUND: &str = "E0004";
+pub const RUST_MISSING: &str = "E0005";
+pub const LINKER_MISSING: &str = "E0006";
+pub const BUILD_FAILED: &str = "E0007";
+pub const NATIVE_LIBS_MISSING: &str = "E0008";
+pub const PERMISSION_DENIED: &str = "E0009";
+pub const DISK_FULL: &str = "E0010";
+pub const PATH_NOT_APPLIED: &str = "E0011";
+pub const YANKED: &str = "E0012";
+pub const KIPPER_TOO_OLD: &str = "E0013";
+
+pub const EXPLANATIONS: &[Explanation] = &[
+    Explanation {
+        code: GIT_MISSING,
+        title: "git is not installed",
+        description: "kipper builds Kopi from the kopi-lang sources, which it fetches with git. \
+                      No `git` executable was found on PATH.",
+        causes: &[
+            "git was never installed on this machine",
+            "git is installed somewhere that isn't on PATH in this shell",
+        ],
+        fixes: &[
+            "Install git: `sudo apt install git`, `sudo dnf install git`, `xcode-select --install` or https://git-scm.com",
+            "Open a new shell after installing so PATH is reloaded, then run the install again",
+        ],
+    },
+    Explanation {
+        code: FETCH_FAILED,
+        title: "The kopi-lang sources could not be fetched",
+        description: "git could not clone or update the kopi-lang repository. The error shows \
+                      git's own message, which usually names the problem.",
+        causes: &[
+            "No network connection, or a firewall blocking github.com",
+            "A proxy that git isn't configured for",
+            "A configured mirror that is down or no longer exists",
+        ],
+        fixes: &[
+            "Check that `git ls-remote https://github.com/kinoite/kopi-lang.git` works in this shell",
+            "Behind a proxy, set https_proxy or git's http.proxy",
+            "Run `kipper mirror test` to see which sources respond, and remove dead ones with `kipper mirror remove`",
+        ],
+    },
+    Explanation {
+        code: GIT_AUTH,
+        title: "git could not authenticate",
+        description: "The kopi-lang repository or a mirror asked for credentials that git \
+                      couldn't provide. kipper runs git without a terminal prompt, so any \
+                      credentials have to come from git's own configuration.",
+        causes: &[
+            "A private mirror that needs a token or SSH key",
+            "A `url.<base>.insteadOf` rule rewriting the GitHub URL to SSH without a key loaded",
+            "Expired credentials in git's credential helper",
+        ],
+        fixes: &[
+            "Run `git ls-remote <url>` yourself to see what git asks for",
+            "Load your SSH key with `ssh-add`, or store a token with `git credential approve`",
+            "Check `git config --get-regexp insteadof` for rewrites you didn't mean to apply",
+        ],
+    },
+    Explanation {
+        code: VERSION_NOT_FOUND,
+        title: "The requested version doesn't exist",
+        description: "The kopi-lang repository has no tag or branch with the name given to \
+                      `kipper install`.",
+        causes: &[
+            "A typo in the version, e.g. `0.2.0` instead of `v0.2.0`",
+            "A release that hasn't been tagged yet, or a mirror that is behind",
+        ],
+        fixes: &[
+            "Install `latest` or `stable` for the newest release",
+            "List the tags with `git ls-remote --tags https://github.com/kinoite/kopi-lang.git`",
+        ],
+    },
+    Explanation {
+        code: RUST_MISSING,
+        title: "No usable Rust toolchain",
+        description: "Kopi is built with cargo, and kipper couldn't find or run one, either \
+                      on PATH or through rustup.",
+        causes: &[
+            "Rust isn't installed",
+            "rustup is installed but the toolchain kopi-lang pins couldn't be installed",
+            "~/.cargo/bin isn't on PATH in this shell",
+        ],
+        fixes: &[
+            "Install Rust from https://rustup.rs, or let kipper offer to do it on the next install",
+            "Run `rustup show` inside the kopi-lang checkout to see which toolchain it wants",
+            "Open a new shell, or add ~/.cargo/bin to PATH",
+        ],
+    },
+    Explanation {
+        code: LINKER_MISSING,
+        title: "No C compiler or linker",
+        description: "Rust needs the platform's C toolchain to link executables, even for a \
+                      pure Rust program like Kopi. kipper checks for one before building, and \
+                      cargo fails the same way when the check is skipped.",
+        causes: &[
+            "A fresh system or container without build tools",
+            "An immutable OS (Silverblue, NixOS) where packages can't be installed the usual way",
+            "On Windows, Visual Studio Build Tools without the C++ workload",
+        ],
+        fixes: &[
+            "Debian/Ubuntu: `sudo apt install build-essential`",
+            "Fedora: `sudo dnf install gcc`; macOS: `xcode-select --install`",
+            "Run `kipper check` to confirm it is found before installing again",
+        ],
+    },
+    Explanation {
+        code: BUILD_FAILED,
+        title: "cargo failed to build Kopi",
+        description: "The kopi-lang sources were fetched but didn't compile. The error \
+                      includes cargo's output; the first `error:` line in it is usually the \
+                      one that matters.",
+        causes: &[
+            "A Rust toolchain older than kopi-lang needs",
+            "A broken commit when installing `nightly` or a branch",
+            "Build options (`--with-NAME`) that don't work together on this version",
+            "Settings kept from your environment with `--inherit-env` or `[build] keep-env`",
+        ],
+        fixes: &[
+            "Update Rust (`rustup update`, `brew upgrade rust`, or via asdf or your distribution) and install again",
+            "Install a release (`kipper install stable`) instead of a moving branch",
+            "Install again without extra build options or environment",
+        ],
+    },
+    Explanation {
+        code: NATIVE_LIBS_MISSING,
+        title: "Native libraries are missing",
+        description: "kopi-lang declares C libraries it links against, and pkg-config \
+                      couldn't find some of them.",
+        causes: &[
+            "The libraries' development packages aren't installed",
+            "pkg-config isn't installed, or PKG_CONFIG_PATH doesn't include where they are",
+        ],
+        fixes: &[
+            "Install the `-dev` (Debian/Ubuntu) or `-devel` (Fedora) packages for the libraries named",
+            "Run `pkg-config --libs <name>` to check each one",
+        ],
+    },
+    Explanation {
+        code: PERMISSION_DENIED,
+        title: "No permission to write",
+        description: "kipper installs into your home directory and never needs root. A \
+                      location it writes to isn't writable by you.",
+        causes: &[
+            "An earlier `sudo kipper ...` left root-owned files in ~/.kopi or ~/.local/bin",
+            "A read-only or network home directory",
+        ],
+        fixes: &[
+            "Run `kipper check` to see which locations aren't writable",
+            "Take back ownership, e.g. `sudo chown -R $USER ~/.kopi ~/.local/bin`",
+        ],
+    },
+    Explanation {
+        code: DISK_FULL,
+        title: "Out of disk space",
+        description: "A build of Kopi's dependencies can take gigabytes in the temporary \
+                      build directory, on top of the install itself.",
+        causes: &[
+            "A small or nearly full temp or home filesystem",
+            "Build dirs left behind by interrupted installs",
+        ],
+        fixes: &[
+            "Run `kipper size` to see where the space goes",
+            "Remove leftovers with `kipper cache clean --temp`",
+            "Set KIPPER_CACHE_MAX to keep the cache in check",
+        ],
+    },
+    Explanation {
+        code: PATH_NOT_APPLIED,
+        title: "The bin dir is not on PATH",
+        description: "Kopi installed, but the shell doesn't find the `kopi` command yet. \
+                      Changes to shell startup files only take effect in shells started \
+                      afterwards.",
+        causes: &[
+            "The shell was started before PATH was changed",
+            "The line was added to a startup file this shell doesn't read, e.g. ~/.bashrc in a login shell that reads ~/.bash_profile",
+            "Another program's setup resets PATH later in the startup file",
+        ],
+        fixes: &[
+            "Open a new terminal, or `source` the startup file kipper edited",
+            "Run `kipper doctor` to see whether `kopi` is found and which copy runs",
+            "Add the bin dir to PATH by hand in the file your shell reads",
+        ],
+    },
+    Explanation {
+        code: YANKED,
+        title: "The version has been yanked",
+        description: "kopi-lang withdrew this release, usually because it is broken in a \
+                      way users should not run into. The error includes the reason given.",
+        causes: &["Installing a pinned version that was yanked after it was pinned"],
+        fixes: &[
+            "Install a newer release (`kipper install stable`)",
+            "If you really need it, install it anyway with `--force`",
+        ],
+    },
+    Explanation {
+        code: KIPPER_TOO_OLD,
+        title: "kipper is too old for this Kopi",
+        description: "kopi-lang declares a minimum kipper version for building it, and this \
+                      kipper is older.",
+        causes: &["kopi-lang changed its build in a way older kippers don't understand"],
+        fixes: &["Update kipper to its latest release, then run the install again"],
+    },
+];
+
+/// The explanation for `code`, given as `E0007`, `e0007` or `7`.
+pub fn explanation(code: &str) -> Option<&'static Explanation> {
+    let number = code.trim().trim_start_matches(['E', 'e']).parse::<u32>().ok()?;
+    let code = format!("E{:04}", number);
+    EXPLANATIONS.iter().find(|explanation| explanation.code == code)
+}
+
+/// The code of a failure kipper recognises.
+pub fn error_code(error: &InstallerError) -> Option<&'static str> {
+    let message = match error {
+        InstallerError::Io(e) if e.kind() == io::ErrorKind::PermissionDenied => return Some(PERMISSION_DENIED),
+        InstallerError::Io(e) if e.kind() == io::ErrorKind::StorageFull => return Some(DISK_FULL),
+        InstallerError::Io(_) | InstallerError::Cancelled => return None,
+        InstallerError::Git(message) | InstallerError::Cargo(message) | InstallerError::PathError(message) => message,
+    };
+    let has = |needle: &str| message.contains(needle);
+
+    Some(match error {
+        InstallerError::Git(_) if has("git not found") => GIT_MISSING,
+        InstallerError::Git(_)
+            if has("Authentication failed")
+                || has("could not read Username")
+                || has("Permission denied (publickey")
+                || has("terminal prompts disabled") =>
+        {
+            GIT_AUTH
+        }
+        InstallerError::Git(_) if has("Failed to check out") || has("not found in upstream") => VERSION_NOT_FOUND,
+        InstallerError::Git(_) if has("Failed to clone") || has("Failed to fetch") => FETCH_FAILED,
+        InstallerError::Git(_) if has("is yanked") => YANKED,
+        InstallerError::Cargo(_) if has("cargo not found") || has("rustup") => RUST_MISSING,
+        InstallerError::Cargo(_) if has("C compiler/linker") || (has("linker `") && has("not found")) => LINKER_MISSING,
+        InstallerError::Cargo(_) if has("Missing native libraries") => NATIVE_LIBS_MISSING,
+        InstallerError::Cargo(_) if has("No space left on device") => DISK_FULL,
+        InstallerError::Cargo(_) if has("Build failed") || has("Built binary not found") => BUILD_FAILED,
+        InstallerError::PathError(_) if has("No permission to write") => PERMISSION_DENIED,
+        InstallerError::PathError(_) if has("is too old for Kopi") => KIPPER_TOO_OLD,
+        _ => return None,
+    })
+}
@@ -0,0 +1,105 @@
+// State export (`kipper state export --json`): everything about this
+// machine's Kopi setup in one JSON document, for configuration management
+// facts (Ansible, Chef ohai) and drift detection.
+//
+// The schema is versioned by `schema`; fields are only ever added within a
+// version. Top-level fields: `kipper_version`, `install_dir`, `bin_dir`,
+// `profile`, `default`, `versions` (oldest first, as `kipper list` orders them, each with `name`,
+// `commit`, `kopi_version`, `installed_at`, `provenance`, `prebuilt` and
+// `options`), `tools`, `aliases`, `mirrors`, `preferred_mirror`, `runs` (the last run of
+// each install, update, apply and uninstall), `overrides` (the
+// environment variables that change what kipper or the shims do, only those
+// set), `policy` (`path` and `rules`, or null), `path` (`status` and
+// `shadowed_by`) and `config` (`path` and `settings`, the user settings with
+// defaults filled in).
+
+use std::env;
+
+use serde_json::{Value, json};
+
+use crate::cache::CACHE_DIR_ENV;
+use crate::config::config_path;
+use crate::metrics::METRICS_FILE_ENV;
+use crate::path::PathStatus;
+use crate::policy::policy_path;
+use crate::quota::CACHE_MAX_ENV;
+use crate::size::SIZE_WARN_ENV;
+use crate::state::State;
+use crate::toolchain::VERSION_ENV;
+use crate::upstream::KIPPER_VERSION;
+use crate::verbosity::LOG_FILTER_ENV;
+use crate::{Installer, InstallerError};
+
+pub const EXPORT_SCHEMA: u32 = 1;
+
+/// Environment variables reported under `overrides`.
+const OVERRIDE_VARS: [&str; 7] = [
+    VERSION_ENV,
+    CACHE_DIR_ENV,
+    CACHE_MAX_ENV,
+    SIZE_WARN_ENV,
+    METRICS_FILE_ENV,
+    LOG_FILTER_ENV,
+    "NO_COLOR",
+];
+
+impl Installer {
+    pub fn export_state(&self) -> Result<Value, InstallerError> {
+        let state = State::load(&self.install_dir);
+        let versions: Vec<Value> = self
+            .list()?
+            .into_iter()
+            .map(|toolchain| {
+                let record = state.versions.get(&toolchain.name);
+                json!({
+                    "name": toolchain.name,
+                    "commit": record.map(|r| &r.commit),
+                    "kopi_version": record.and_then(|r| r.kopi_version.as_ref()),
+                    "installed_at": record.and_then(|r| r.installed_at.as_ref()),
+                    "provenance": record.and_then(|r| r.provenance.as_ref()),
+                    "prebuilt": record.and_then(|r| r.prebuilt.as_ref()),
+                    "options": record.map(|r| &r.options),
+                })
+            })
+            .collect();
+
+        let overrides: serde_json::Map<String, Value> = OVERRIDE_VARS
+            .iter()
+            .filter_map(|name| Some((name.to_string(), Value::String(env::var(name).ok()?))))
+            .collect();
+
+        let (path_status, shadowed_by) = match self.path_status("kopi") {
+            PathStatus::Ok => ("ok", None),
+            PathStatus::Missing => ("missing", None),
+            PathStatus::Shadowed(other) => ("shadowed", Some(other.display().to_string())),
+        };
+
+        Ok(json!({
+            "schema": EXPORT_SCHEMA,
+            "kipper_version": KIPPER_VERSION,
+            "install_dir": self.install_dir.display().to_string(),
+            "bin_dir": self.bin_dir.display().to_string(),
+            "profile": self.effective_profile(),
+            "default": self.default_version(),
+            "versions": versions,
+            "tools": self.tools(),
+            "aliases": state.aliases,
+            "mirrors": state.mirrors,
+            "preferred_mirror": state.preferred_mirror,
+            "runs": state.runs,
+            "overrides": overrides,
+            "policy": self.policy().map(|policy| json!({
+                "path": policy_path().display().to_string(),
+                "rules": policy,
+            })),
+            "path": {
+                "status": path_status,
+                "shadowed_by": shadowed_by,
+            },
+            "config": {
+                "path": config_path(&self.install_dir).display().to_string(),
+                "settings": self.config,
+            },
+        }))
+    }
+}
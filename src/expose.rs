@@ -0,0 +1,194 @@
+// How kopi and its tools reach PATH, set with `[bin] strategy`:
+//
+//     symlink  a symlink in the bin dir to the shim host (the default where
+//              symlinks can be made, see `capabilities`)
+//     copy     a copy of the shim host (the default where they can't, as on
+//              Windows without Developer Mode and FAT drives)
+//     shim     a small script that runs the shim host, for tools that
+//              resolve symlinks before looking at the command name
+//     none     nothing in the bin dir; ~/.kopi/current, a link to the
+//              default version's dir, goes on PATH instead, so project
+//              version files are not consulted
+//
+// `[bin] conflict` says what happens when a file kipper didn't make is
+// already where a tool would go: `overwrite` (the default) replaces it,
+// `backup` renames it to NAME.kipper-backup first and `fail` stops.
+
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use crate::config::{BinStrategy, ConflictPolicy};
+use crate::shim::{SHIM_ENV, SHIM_HOST};
+use crate::toolchain::exe_name;
+use crate::{Installer, InstallerError};
+
+/// The link to the default version's dir used by the `none` strategy.
+pub const CURRENT_LINK: &str = "current";
+/// Written into shim scripts so kipper knows them as its own.
+const SCRIPT_MARKER: &str = "kipper shim";
+const BACKUP_SUFFIX: &str = ".kipper-backup";
+
+/// The script that runs `host` as `tool`.
+fn shim_script(tool: &str, host: &Path) -> String {
+    if cfg!(windows) {
+        format!(
+            "@echo off\r\nrem {}: runs {} from the Kopi version selected for the current directory\r\nsetlocal\r\nset {}={}\r\n\"{}\" %*\r\n",
+            SCRIPT_MARKER,
+            tool,
+            SHIM_ENV,
+            tool,
+            host.display()
+        )
+    } else {
+        format!(
+            "#!/bin/sh\n# {}: runs {} from the Kopi version selected for the current directory\n{}={} exec \"{}\" \"$@\"\n",
+            SCRIPT_MARKER,
+            tool,
+            SHIM_ENV,
+            tool,
+            host.display()
+        )
+    }
+}
+
+fn is_shim_script(path: &Path) -> bool {
+    let mut head = Vec::new();
+    fs::File::open(path).and_then(|file| file.take(256).read_to_end(&mut head)).is_ok()
+        && String::from_utf8_lossy(&head).contains(SCRIPT_MARKER)
+}
+
+fn same_contents(a: &Path, b: &Path) -> bool {
+    match (fs::metadata(a), fs::metadata(b)) {
+        (Ok(ma), Ok(mb)) if ma.len() == mb.len() => matches!((fs::read(a), fs::read(b)), (Ok(a), Ok(b)) if a == b),
+        _ => false,
+    }
+}
+
+impl Installer {
+    /// How tools are exposed: the configured strategy, else symlinks where
+    /// they can be made and copies where they can't.
+    pub fn bin_strategy(&self) -> BinStrategy {
+        self.config.bin.strategy.unwrap_or_else(|| {
+            if self.can_symlink() { BinStrategy::Symlink } else { BinStrategy::Copy }
+        })
+    }
+
+    /// Where `name` goes in the bin dir: scripts are `.cmd` files on Windows.
+    pub(crate) fn exposed_path(&self, name: &str) -> PathBuf {
+        if cfg!(windows) && self.bin_strategy() == BinStrategy::Shim {
+            self.bin_dir.join(format!("{}.cmd", name))
+        } else {
+            self.bin_dir.join(exe_name(name))
+        }
+    }
+
+    /// Whether kipper put the file at `path` there: a link into the
+    /// install dir, a shim script, a copy of the shim host, or anything in
+    /// the install dir itself (the bin dir on Windows).
+    fn kipper_made(&self, path: &Path) -> bool {
+        if path.parent().is_some_and(|dir| dir == self.install_dir) {
+            return true;
+        }
+        if let Ok(target) = fs::read_link(path) {
+            return target.starts_with(&self.install_dir);
+        }
+        is_shim_script(path) || same_contents(path, &self.install_dir.join(exe_name(SHIM_HOST)))
+    }
+
+    /// Make room at `path` for a tool, as `[bin] conflict` says to when
+    /// something kipper didn't make is there.
+    fn clear_for_tool(&self, path: &Path) -> Result<(), InstallerError> {
+        if path.symlink_metadata().is_err() {
+            return Ok(());
+        }
+        if !self.kipper_made(path) {
+            match self.config.bin.conflict {
+                ConflictPolicy::Fail => {
+                    return Err(InstallerError::PathError(format!(
+                        "{} is in the way and wasn't made by kipper; move it, or set [bin] conflict to \"backup\" or \"overwrite\"",
+                        path.display()
+                    )));
+                }
+                ConflictPolicy::Backup => {
+                    let mut backup = path.as_os_str().to_owned();
+                    backup.push(BACKUP_SUFFIX);
+                    let mut backup = PathBuf::from(backup);
+                    let mut n = 1;
+                    while backup.symlink_metadata().is_ok() {
+                        let mut numbered = path.as_os_str().to_owned();
+                        numbered.push(format!("{}.{}", BACKUP_SUFFIX, n));
+                        backup = PathBuf::from(numbered);
+                        n += 1;
+                    }
+                    fs::rename(path, &backup)?;
+                    self.log_warning(&format!("Moved {} to {} to make room for kipper's", path.display(), backup.display()));
+                    return Ok(());
+                }
+                ConflictPolicy::Overwrite => {}
+            }
+        }
+        fs::remove_file(path)?;
+        Ok(())
+    }
+
+    /// Expose `name` in the bin dir the configured way, replacing what
+    /// kipper put there before.
+    pub(crate) fn expose(&self, name: &str) -> Result<(), InstallerError> {
+        let strategy = self.bin_strategy();
+        if strategy == BinStrategy::Direct {
+            return Ok(());
+        }
+        let host = self.install_dir.join(exe_name(SHIM_HOST));
+        let path = self.exposed_path(name);
+        // Switching between copies and scripts on Windows changes the file name
+        let other = if path.extension().is_some_and(|ext| ext == "cmd") {
+            self.bin_dir.join(exe_name(name))
+        } else {
+            self.bin_dir.join(format!("{}.cmd", name))
+        };
+        if cfg!(windows) && other.exists() && self.kipper_made(&other) {
+            fs::remove_file(&other)?;
+        }
+        self.clear_for_tool(&path)?;
+
+        match strategy {
+            BinStrategy::Symlink => {
+                #[cfg(unix)]
+                std::os::unix::fs::symlink(&host, &path)?;
+                #[cfg(windows)]
+                std::os::windows::fs::symlink_file(&host, &path)?;
+            }
+            BinStrategy::Copy => {
+                fs::copy(&host, &path)?;
+            }
+            BinStrategy::Shim => {
+                fs::write(&path, shim_script(name, &host))?;
+                #[cfg(unix)]
+                {
+                    use std::os::unix::fs::PermissionsExt;
+                    fs::set_permissions(&path, fs::Permissions::from_mode(0o755))?;
+                }
+            }
+            BinStrategy::Direct => {}
+        }
+        Ok(())
+    }
+
+    /// Point `~/.kopi/current` at `version`'s dir, for the `none` strategy.
+    pub(crate) fn update_current_link(&self, version: &str) -> Result<(), InstallerError> {
+        if self.bin_strategy() != BinStrategy::Direct {
+            return Ok(());
+        }
+        let link = self.install_dir.join(CURRENT_LINK);
+        if link.symlink_metadata().is_ok() {
+            fs::remove_file(&link).or_else(|_| fs::remove_dir(&link))?;
+        }
+        let target = self.version_dir(version);
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(&target, &link)?;
+        #[cfg(windows)]
+        std::os::windows::fs::symlink_dir(&target, &link)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,44 @@
+// Optional parts of kipper, chosen with cargo features at build time so
+// distro packagers and the static bootstrap binary can leave out what they
+// don't ship:
+//
+//     async  the async API for embedding kipper in tokio programs
+//     http   kipper's own HTTP client: the remote build cache, HTTP mirror
+//            probes and `--source` archives from the web (everything else
+//            goes through git)
+//     serve  the local HTTP API (`kipper serve`) and `kipper mirror serve`
+//
+// All are on by default. Commands that need a feature this kipper was built
+// without fail with an error naming it, rather than as unknown commands.
+
+use crate::InstallerError;
+
+/// Each optional feature, whether this build has it, and what it provides.
+pub const FEATURES: [(&str, bool, &str); 3] = [
+    ("async", cfg!(feature = "async"), "the async API"),
+    ("http", cfg!(feature = "http"), "the remote build cache, HTTP mirror probes and source archives from the web"),
+    ("serve", cfg!(feature = "serve"), "`kipper serve` and `kipper mirror serve`"),
+];
+
+/// The features this build has.
+pub fn enabled() -> Vec<&'static str> {
+    FEATURES.iter().filter(|(_, on, _)| *on).map(|(name, _, _)| *name).collect()
+}
+
+/// The features this build was compiled without.
+pub fn missing() -> Vec<&'static str> {
+    FEATURES.iter().filter(|(_, on, _)| !*on).map(|(name, _, _)| *name).collect()
+}
+
+/// What to tell someone who needs `feature`.
+pub fn without_feature(what: &str, feature: &str) -> String {
+    format!(
+        "{} needs the `{}` feature, which this kipper was built without; rebuild it with `--features {}`",
+        what, feature, feature
+    )
+}
+
+/// The error for a command that needs `feature`.
+pub fn not_compiled_in(command: &str, feature: &str) -> InstallerError {
+    InstallerError::PathError(without_feature(&format!("`kipper {}`", command), feature))
+}
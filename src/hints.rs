@@ -0,0 +1,112 @@
+// Next steps: after a command, a few hints on what to do next, such as how
+// to pin a freshly installed version in a project, what still overrides the
+// default after `use`, or which command looks into a failure. Each hint is a
+// rule over what ran and how it ended; `next_steps` collects the ones that
+// apply, so commands don't print their own advice.
+
+use std::env;
+
+use crate::explain::error_code;
+use crate::logging::{LOG_FILE, log_dir};
+use crate::metrics::MEASURED_COMMANDS;
+use crate::permissions::is_permission_denied;
+use crate::toolchain::{VERSION_ENV, VERSION_FILE, VersionSource};
+use crate::{Installer, InstallerError};
+
+/// How a command ended, as the rules see it.
+pub struct Outcome<'a> {
+    /// The subcommand, `install` for plain `kipper`.
+    pub command: &'a str,
+    pub error: Option<&'a InstallerError>,
+}
+
+type Rule = fn(&Installer, &Outcome) -> Option<String>;
+
+const RULES: [Rule; 5] = [
+    pin_after_install,
+    overrides_after_use,
+    check_permissions,
+    explain_code,
+    doctor_after_failure,
+];
+
+fn pin_after_install(installer: &Installer, outcome: &Outcome) -> Option<String> {
+    if outcome.command != "install" || outcome.error.is_some() {
+        return None;
+    }
+    let version = installer.summaries().pop()?.version;
+    if env::current_dir().is_ok_and(|cwd| cwd.join(VERSION_FILE).exists()) {
+        return None;
+    }
+    Some(format!("To use Kopi {} for a project, pin it there: echo {} > {}", version, version, VERSION_FILE))
+}
+
+fn overrides_after_use(installer: &Installer, outcome: &Outcome) -> Option<String> {
+    if outcome.command != "use" || outcome.error.is_some() {
+        return None;
+    }
+    let default = installer.default_version()?;
+    let resolved = installer.resolve_version(&env::current_dir().ok()?)?;
+    if resolved.name == default {
+        return None;
+    }
+    match resolved.source {
+        VersionSource::Env => Some(format!(
+            "{}={} is set in this shell and still overrides the default",
+            VERSION_ENV, resolved.name
+        )),
+        VersionSource::Project(file) => Some(format!(
+            "{} pins Kopi {} here, which still overrides the default",
+            file.display(),
+            resolved.name
+        )),
+        VersionSource::Default => None,
+    }
+}
+
+fn check_permissions(_: &Installer, outcome: &Outcome) -> Option<String> {
+    outcome
+        .error
+        .filter(|error| is_permission_denied(error))
+        .map(|_| "Run 'kipper check' to see which locations aren't writable".to_string())
+}
+
+fn explain_code(_: &Installer, outcome: &Outcome) -> Option<String> {
+    let code = error_code(outcome.error?)?;
+    Some(format!("Run 'kipper explain {}' for common causes and fixes", code))
+}
+
+fn doctor_after_failure(installer: &Installer, outcome: &Outcome) -> Option<String> {
+    let error = outcome.error?;
+    // Recognised failures have better advice, and lookups have nothing to diagnose
+    if matches!(error, InstallerError::Cancelled)
+        || error_code(error).is_some()
+        || !MEASURED_COMMANDS.contains(&outcome.command)
+    {
+        return None;
+    }
+    let log = log_dir(&installer.install_dir).join(LOG_FILE);
+    if log.exists() {
+        Some(format!("Run 'kipper doctor' to diagnose the install; the full log is in {}", log.display()))
+    } else {
+        Some("Run 'kipper doctor' to diagnose the install".to_string())
+    }
+}
+
+impl Installer {
+    /// The hints that apply after `outcome`.
+    pub fn next_steps(&self, outcome: &Outcome) -> Vec<String> {
+        RULES.iter().filter_map(|rule| rule(self, outcome)).collect()
+    }
+
+    /// Show the hints that apply after `outcome`. Unattended runs only get
+    /// the ones about failures.
+    pub fn print_next_steps(&self, outcome: &Outcome) {
+        if self.unattended && outcome.error.is_none() {
+            return;
+        }
+        for hint in self.next_steps(outcome) {
+            self.log_info(&hint);
+        }
+    }
+}
@@ -0,0 +1,64 @@
+// Known releases: the commit each kopi-lang release tag pointed at when this
+// kipper was released, embedded in the binary. A git-based install can't tell
+// a moved tag from a legitimate one by itself; comparing against a table
+// that ships with kipper (and is refreshed by each kipper release) catches a
+// force-pushed tag or a tampered mirror.
+
+use serde::Deserialize;
+
+use crate::Installer;
+
+const KNOWN_RELEASES: &str = include_str!("known-releases.toml");
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct KnownRelease {
+    pub tag: String,
+    pub commit: String,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct KnownReleases {
+    #[serde(default)]
+    release: Vec<KnownRelease>,
+}
+
+/// The releases pinned in this kipper.
+pub fn known_releases() -> Vec<KnownRelease> {
+    // The table is part of kipper itself; a mistake in it shouldn't stop installs
+    toml::from_str::<KnownReleases>(KNOWN_RELEASES).map_or_else(|_| Vec::new(), |known| known.release)
+}
+
+/// The commit `tag` is pinned to, if it is a known release.
+pub fn pinned_commit(tag: &str) -> Option<String> {
+    known_releases().into_iter().find(|release| release.tag == tag).map(|release| release.commit)
+}
+
+/// A description of how `commit` differs from what `tag` is pinned to, if it does.
+pub fn pin_mismatch(tag: &str, commit: &str) -> Option<String> {
+    let pinned = pinned_commit(tag)?;
+    (!commit.eq_ignore_ascii_case(&pinned))
+        .then(|| format!("Kopi {} is commit {}, but this kipper knows it as {}", tag, commit, pinned))
+}
+
+impl Installer {
+    /// Warn loudly when the checked out `version` is a known release whose tag
+    /// now points at a different commit.
+    pub(crate) fn check_known_release(&self, version: &str) {
+        // Commits of trees without history are kipper's, not upstream's
+        if !self.source_has_history() {
+            return;
+        }
+        let Ok(output) = self.git_in_checkout(&["rev-parse", "HEAD"]) else {
+            return;
+        };
+        let commit = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        let Some(mismatch) = pin_mismatch(version, &commit) else {
+            return;
+        };
+        self.log_warning("!!! The release tag does not match the commit pinned in kipper !!!");
+        self.log_warning(&mismatch);
+        self.log_warning("The tag has been moved (force-pushed) or the source was tampered with.");
+        self.log_warning("Check the mirror you install from and kopi-lang's release announcements before trusting this build.");
+    }
+}
@@ -0,0 +1,1520 @@
+// Kipper - The Kopi Language Installer
+// A git-based installer for Kopi written in Rust
+
+pub mod advisory;
+pub mod announce;
+pub mod auth;
+pub mod bincache;
+pub mod build_dir;
+pub mod builder;
+pub mod buildenv;
+pub mod cache;
+pub mod cache_export;
+pub mod capabilities;
+mod cancel;
+pub mod check;
+pub mod ci;
+pub mod completions;
+pub mod config;
+pub mod config_edit;
+pub mod confirm;
+#[cfg(feature = "http")]
+mod delta;
+pub mod doctor;
+pub mod explain;
+pub mod expose;
+pub mod export;
+pub mod features;
+pub mod hints;
+pub mod known_releases;
+pub mod listing;
+pub mod lock;
+pub mod locked;
+pub mod logging;
+pub mod manifest;
+pub mod matrix;
+pub mod metrics;
+pub mod mirror;
+#[cfg(feature = "serve")]
+pub mod mirror_serve;
+pub mod optimize;
+pub mod options;
+pub mod path;
+pub mod permissions;
+mod picker;
+pub mod platform;
+pub mod policy;
+pub mod probe;
+pub mod progress;
+pub mod provision;
+pub mod provenance;
+pub mod quick;
+pub mod quota;
+pub mod redact;
+pub mod release;
+pub mod release_check;
+pub mod remote_cache;
+mod repair;
+pub mod root;
+pub mod rust_source;
+pub mod rustup;
+pub mod sbom;
+mod script;
+pub mod shim;
+pub mod signing;
+pub mod size;
+pub mod source;
+pub mod stamp;
+pub mod state;
+pub mod suggest;
+pub mod summary;
+pub mod target;
+pub mod theme;
+pub mod timestamp;
+pub mod timing;
+pub mod toolchain;
+mod trace;
+pub mod unattended;
+pub mod update;
+pub mod upstream;
+pub mod verbosity;
+pub mod vsbuild;
+pub mod warm;
+pub mod window;
+pub mod workspace;
+pub mod yanked;
+
+use std::collections::BTreeMap;
+use std::env;
+use std::ffi::OsStr;
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+use std::sync::atomic::AtomicBool;
+use std::sync::{Arc, Mutex, Once};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+use serde_json::Map;
+
+use advisory::AdvisoryCheck;
+use announce::ProgressMode;
+use builder::{Builder, CargoBuild, PrebuiltExtract};
+use capabilities::Probed;
+use ci::CiEnvironment;
+pub use cancel::CancellationToken;
+use config::{BinStrategy, Config};
+use confirm::Action;
+use logging::LogFormat;
+use manifest::{MANIFEST_FILE, Manifest};
+use metrics::PhaseRun;
+use path::PathStatus;
+use platform::{bsd, clear_download_mark, extended_length_path, rust_install_hint, scratch_root};
+use script::{batch_echo_text, batch_quote, sh_quote};
+use source::{GitClone, SourceProvider};
+use policy::Policy;
+use provenance::Attestation;
+use stamp::Stamp;
+use state::{Profile, State, VersionRecord};
+use summary::{InstallSummary, PhaseTime};
+use theme::Theme;
+use timing::{Phase, format_duration};
+use toolchain::{COMPONENTS, NIGHTLY, RejectedVersion, ResolvedVersion, VERSION_ENV, exe_name};
+use verbosity::LogFilter;
+
+#[cfg(feature = "async")]
+pub mod async_api;
+#[cfg(feature = "serve")]
+pub mod serve;
+
+pub(crate) const REPO_URL: &str = "https://github.com/kinoite/kopi-lang.git";
+/// File in the install dir naming the version used outside pinned projects.
+const DEFAULT_FILE: &str = "default";
+
+/// How often a running child process is polled for exit or cancellation.
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+#[derive(Debug)]
+pub enum InstallerError {
+    Io(io::Error),
+    Git(String),
+    Cargo(String),
+    PathError(String),
+    Cancelled,
+}
+
+impl From<io::Error> for InstallerError {
+    fn from(error: io::Error) -> Self {
+        InstallerError::Io(error)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogLevel {
+    /// Detail for debugging, shown only when `KIPPER_LOG` asks for it.
+    Debug,
+    Info,
+    Success,
+    Warning,
+    Error,
+}
+
+/// A single status message, as printed to the terminal, for frontends to display.
+#[derive(Debug, Clone, Serialize)]
+pub struct ProgressEvent {
+    pub level: LogLevel,
+    pub message: String,
+    /// The install phase running when the message was logged, if any.
+    pub phase: Option<Phase>,
+}
+
+pub type ProgressObserver = Arc<dyn Fn(&ProgressEvent) + Send + Sync>;
+
+/// An installed Kopi toolchain.
+#[derive(Debug, Clone)]
+pub struct Toolchain {
+    pub name: String,
+    pub binary: PathBuf,
+}
+
+/// Where the tools for a resolved version live, for editor integrations.
+#[derive(Debug, Clone)]
+pub struct ToolchainPaths {
+    pub version: ResolvedVersion,
+    pub interpreter: PathBuf,
+    pub language_server: Option<PathBuf>,
+    pub installed: bool,
+}
+
+pub struct Installer {
+    install_dir: PathBuf,
+    bin_dir: PathBuf,
+    temp_dir: PathBuf,
+    cancel: CancellationToken,
+    observer: Option<ProgressObserver>,
+    profile: Option<Profile>,
+    advisories: AdvisoryCheck,
+    require_signed: bool,
+    policy: Option<Policy>,
+    /// Phases timed since the last summary, for the end-of-install report.
+    phase_times: Mutex<Vec<PhaseTime>>,
+    /// Every phase run by this installer, failed ones included, for metrics.
+    phase_runs: Mutex<Vec<PhaseRun>>,
+    current_phase: Mutex<Option<Phase>>,
+    summaries: Mutex<Vec<InstallSummary>>,
+    theme: Theme,
+    progress_mode: Option<ProgressMode>,
+    config: Config,
+    /// `--force`: answer yes to every confirmation.
+    force: bool,
+    include_prereleases: bool,
+    /// `--with-NAME` / `--without-NAME` build options from the command line.
+    option_choices: BTreeMap<String, bool>,
+    /// `--inherit-env`: build with the user's RUSTFLAGS, CARGO_* and so on.
+    inherit_env: bool,
+    /// `--builder`: what turns the source into binaries.
+    builder: Box<dyn Builder>,
+    /// `--source`: where kopi-lang's source comes from.
+    source: Box<dyn SourceProvider>,
+    /// `--target`: the triple to build for, when not rustc's default host.
+    target: Option<String>,
+    /// `--fresh`: build from scratch instead of reusing earlier build output.
+    fresh: bool,
+    /// `--quick`: the fastest build there is, unoptimized if need be.
+    quick: bool,
+    /// Whether the last build made was a quick one.
+    built_unoptimized: AtomicBool,
+    /// Set when the last build was downloaded from the remote cache.
+    prebuilt: Mutex<Option<Attestation>>,
+    /// Set while `kipper optimize` builds.
+    optimizing: AtomicBool,
+    /// `--locked-installer`: install only versions pinned to a commit.
+    locked: bool,
+    /// The commit each version is pinned to in locked mode.
+    pins: Mutex<BTreeMap<String, String>>,
+    log_format: LogFormat,
+    log_filter: LogFilter,
+    trace_commands: bool,
+    /// `--unattended`: tuned for CI runners and build farms.
+    unattended: bool,
+    /// The CI service or container whose defaults are in use.
+    ci_environment: Option<CiEnvironment>,
+    /// Longest an external command may run before it is killed.
+    command_timeout: Option<Duration>,
+    /// Logs are rotated at most once per run, before the first message.
+    log_rotation: Once,
+    /// What this machine can do, as far as it has been probed.
+    probed: Probed,
+}
+
+impl Installer {
+    pub fn new() -> Result<Self, InstallerError> {
+        Installer::for_shim()?.with_settings()
+    }
+
+    /// `--system`: an installer for the machine-wide install in /opt/kopi,
+    /// with shims in /usr/local/bin.
+    pub fn new_system() -> Result<Self, InstallerError> {
+        Installer::at(PathBuf::from(root::SYSTEM_INSTALL_DIR), PathBuf::from(root::SYSTEM_BIN_DIR)).with_settings()
+    }
+
+    fn with_settings(self) -> Result<Self, InstallerError> {
+        let mut installer = self;
+        installer.config = Config::load(&installer.install_dir)?;
+        installer.theme = Theme::from_config(&installer.config.theme).map_err(|e| {
+            InstallerError::PathError(format!(
+                "Invalid [theme] in {}: {}",
+                config::config_path(&installer.install_dir).display(),
+                e
+            ))
+        })?;
+        installer.policy = Policy::load()?;
+        // Without anything in the bin dir, the link to the default version goes on PATH
+        if installer.bin_strategy() == BinStrategy::Direct {
+            installer.bin_dir = installer.install_dir.join(expose::CURRENT_LINK);
+        }
+        Ok(installer)
+    }
+
+    /// An installer for a throwaway install under `root`, with the default
+    /// settings, for `kipper release-check`.
+    pub fn scratch(root: &Path) -> Self {
+        Installer::at(root.join(".kopi"), root.join("bin"))
+    }
+
+    /// An installer that knows only where things are installed, for shims.
+    /// No settings, theme or policy file is read: shims sit in front of
+    /// every kopi run, so they stick to the version files and the install dir.
+    /// The machine-wide install is used when this binary is its shim host.
+    pub fn for_shim() -> Result<Self, InstallerError> {
+        if root::running_system_host() {
+            return Ok(Installer::at(PathBuf::from(root::SYSTEM_INSTALL_DIR), PathBuf::from(root::SYSTEM_BIN_DIR)));
+        }
+        let home = env::var("HOME")
+            .or_else(|_| env::var("USERPROFILE"))
+            .map_err(|_| InstallerError::PathError("Could not determine home directory".to_string()))?;
+        
+        let install_dir = Path::new(&home).join(".kopi");
+        let bin_dir = if cfg!(windows) {
+            install_dir.clone()
+        } else {
+            Path::new(&home).join(".local").join("bin")
+        };
+        Ok(Installer::at(install_dir, bin_dir))
+    }
+
+    fn at(install_dir: PathBuf, bin_dir: PathBuf) -> Self {
+        let temp_dir = scratch_root().join(format!("{}{}", cache::TEMP_PREFIX, std::process::id()));
+
+        Installer {
+            install_dir,
+            bin_dir,
+            temp_dir,
+            cancel: CancellationToken::new(),
+            observer: None,
+            profile: None,
+            advisories: AdvisoryCheck::Off,
+            require_signed: false,
+            policy: None,
+            phase_times: Mutex::new(Vec::new()),
+            phase_runs: Mutex::new(Vec::new()),
+            current_phase: Mutex::new(None),
+            summaries: Mutex::new(Vec::new()),
+            theme: Theme::default_theme(),
+            progress_mode: None,
+            config: Config::default(),
+            force: false,
+            include_prereleases: false,
+            option_choices: BTreeMap::new(),
+            inherit_env: false,
+            builder: Box::new(CargoBuild),
+            source: Box::new(GitClone),
+            target: None,
+            fresh: false,
+            quick: false,
+            built_unoptimized: AtomicBool::new(false),
+            prebuilt: Mutex::new(None),
+            optimizing: AtomicBool::new(false),
+            locked: false,
+            pins: Mutex::new(BTreeMap::new()),
+            log_format: LogFormat::Text,
+            log_filter: LogFilter::from_env(),
+            trace_commands: false,
+            unattended: false,
+            ci_environment: None,
+            command_timeout: None,
+            log_rotation: Once::new(),
+            probed: Probed::default(),
+        }
+    }
+
+    /// Install with `profile` instead of the recorded (or environment default) one.
+    pub fn with_profile(mut self, profile: Profile) -> Self {
+        self.profile = Some(profile);
+        self
+    }
+
+    /// The profile to install with: an explicit choice, else the one in the
+    /// settings, else the one recorded by the last install, else `minimal` on
+    /// CI and in containers and `default` everywhere else.
+    pub fn effective_profile(&self) -> Profile {
+        self.profile
+            .or(self.config.install.profile)
+            .or(State::load(&self.install_dir).profile)
+            .unwrap_or(if self.ci_environment.is_some() || env::var_os("CI").is_some() { Profile::Minimal } else { Profile::Default })
+    }
+
+    fn record_profile(&self) -> Result<(), InstallerError> {
+        let mut state = State::load(&self.install_dir);
+        state.profile = Some(self.effective_profile());
+        state.save(&self.install_dir)?;
+        Ok(())
+    }
+
+    /// Render messages as `format`. JSON is never colored.
+    pub fn with_log_format(mut self, format: LogFormat) -> Self {
+        self.log_format = format;
+        if format == LogFormat::Json {
+            self.theme = self.theme.without_colors();
+        }
+        self
+    }
+
+    /// Receive every status message the installer logs, in addition to the terminal output.
+    pub fn with_observer(mut self, observer: ProgressObserver) -> Self {
+        self.observer = Some(observer);
+        self
+    }
+
+    /// Use `token` to cancel clone/build steps from another thread.
+    pub fn with_cancellation(mut self, token: CancellationToken) -> Self {
+        self.cancel = token;
+        self
+    }
+
+    pub fn cancellation_token(&self) -> CancellationToken {
+        self.cancel.clone()
+    }
+
+    pub fn install_dir(&self) -> &Path {
+        &self.install_dir
+    }
+
+    pub fn theme(&self) -> &Theme {
+        &self.theme
+    }
+
+    fn check_cancelled(&self) -> Result<(), InstallerError> {
+        if self.cancel.is_cancelled() {
+            return Err(InstallerError::Cancelled);
+        }
+        Ok(())
+    }
+
+    /// Run a child process to completion, killing it if the installer is
+    /// cancelled or it outlives the command timeout.
+    fn run_command(&self, command: &mut Command) -> Result<Output, InstallerError> {
+        self.run_command_with_timeout(command, self.command_timeout)
+    }
+
+    /// Like `run_command`, but also kill the child once `timeout` has elapsed.
+    fn run_command_with_timeout(&self, command: &mut Command, timeout: Option<Duration>) -> Result<Output, InstallerError> {
+        self.check_cancelled()?;
+        self.trace_command(command);
+        let program = command.get_program().to_string_lossy().into_owned();
+        let subsystem = verbosity::program_subsystem(&program).unwrap_or_else(|| self.subsystem());
+        if self.debugging(subsystem) {
+            let mut line = program.clone();
+            for arg in command.get_args() {
+                line.push(' ');
+                line.push_str(&arg.to_string_lossy());
+            }
+            match command.get_current_dir() {
+                Some(dir) => self.log_debug(subsystem, &format!("Running `{}` in {}", line, dir.display())),
+                None => self.log_debug(subsystem, &format!("Running `{}`", line)),
+            }
+        }
+        let started = Instant::now();
+
+        let mut child = command
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+
+        // Drain the pipes on their own threads so a chatty child can't block on a full pipe
+        let mut stdout_pipe = child.stdout.take();
+        let mut stderr_pipe = child.stderr.take();
+        let stdout_reader = thread::spawn(move || {
+            let mut buf = Vec::new();
+            if let Some(pipe) = stdout_pipe.as_mut() {
+                let _ = pipe.read_to_end(&mut buf);
+            }
+            buf
+        });
+        let stderr_reader = thread::spawn(move || {
+            let mut buf = Vec::new();
+            if let Some(pipe) = stderr_pipe.as_mut() {
+                let _ = pipe.read_to_end(&mut buf);
+            }
+            buf
+        });
+
+        let mut heartbeat = started;
+        let status = loop {
+            if let Some(status) = child.try_wait()? {
+                break status;
+            }
+            if self.unattended && heartbeat.elapsed() >= unattended::HEARTBEAT_INTERVAL {
+                heartbeat = Instant::now();
+                self.log_info(&format!("Still running {} ({} so far)", program, format_duration(started.elapsed())));
+            }
+            if self.cancel.is_cancelled() {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(InstallerError::Cancelled);
+            }
+            if let Some(limit) = timeout.filter(|limit| started.elapsed() > *limit) {
+                let _ = child.kill();
+                let _ = child.wait();
+                let message = format!("{} timed out after {}", program, format_duration(limit));
+                return Err(InstallerError::Io(io::Error::new(io::ErrorKind::TimedOut, message)));
+            }
+            thread::sleep(POLL_INTERVAL);
+        };
+
+        let output = Output {
+            status,
+            stdout: stdout_reader.join().unwrap_or_default(),
+            stderr: stderr_reader.join().unwrap_or_default(),
+        };
+        if self.debugging(subsystem) {
+            let mut message = format!("{} finished ({}) in {:.2}s", program, status, started.elapsed().as_secs_f64());
+            for (name, bytes) in [("stdout", &output.stdout), ("stderr", &output.stderr)] {
+                let text = String::from_utf8_lossy(bytes);
+                if !text.trim().is_empty() {
+                    message.push_str(&format!("\n{}:\n{}", name, text.trim_end()));
+                }
+            }
+            self.log_debug(subsystem, &message);
+        }
+        Ok(output)
+    }
+
+    fn print_banner(&self) {
+        let theme = &self.theme;
+        self.print_line(&theme.paint(theme.banner.as_deref(), "Kipper - The Kopi Language Installer"));
+        self.print_line(&theme.paint(theme.tagline.as_deref(), "Fast, modern, and lightweight scripting language"));
+        self.print_line("");
+    }
+
+    fn notify(&self, level: LogLevel, msg: &str) {
+        if let Some(observer) = &self.observer {
+            observer(&ProgressEvent {
+                level,
+                message: msg.to_string(),
+                phase: self.current_phase.lock().ok().and_then(|phase| *phase),
+            });
+        }
+    }
+
+    /// Human-facing output goes to stderr; stdout is reserved for command data
+    /// such as paths and JSON, so `$(kipper which)` captures just the answer.
+    fn print_line(&self, line: &str) {
+        self.output_line(line);
+    }
+
+    pub fn log_info(&self, msg: &str) {
+        self.log_event(LogLevel::Info, msg, Map::new());
+    }
+
+    fn log_success(&self, msg: &str) {
+        self.log_event(LogLevel::Success, msg, Map::new());
+    }
+
+    fn log_warning(&self, msg: &str) {
+        self.log_event(LogLevel::Warning, msg, Map::new());
+    }
+
+    pub fn log_error(&self, msg: &str) {
+        self.log_event(LogLevel::Error, msg, Map::new());
+    }
+
+    fn check_dependencies(&self) -> Result<(), InstallerError> {
+        self.log_info("Checking dependencies...");
+
+        if !self.has_git() {
+            self.log_error("git is required but not installed");
+            match bsd() {
+                Some(os) => self.log_info(&format!("Please install git (`{}`) and try again", os.install_command("git"))),
+                None => self.log_info("Please install git and try again"),
+            }
+            return Err(InstallerError::Git("git not found".to_string()));
+        }
+        
+        if self.builder.needs_cargo() && !self.has_cargo() {
+            self.log_error("Rust/Cargo is required but not installed");
+            self.log_info(&format!("{} and try again", rust_install_hint()));
+            return Err(InstallerError::Cargo("cargo not found".to_string()));
+        }
+
+        self.log_success("All dependencies found");
+        Ok(())
+    }
+
+    fn command_exists(&self, cmd: &str) -> bool {
+        let mut command = Command::new(cmd);
+        command.arg("--version");
+        self.trace_command(&command);
+        command
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .map(|status| status.success())
+            .unwrap_or(false)
+    }
+
+    fn create_directories(&self) -> Result<(), InstallerError> {
+        self.log_info("Creating installation directories...");
+        self.ensure_writable(&self.install_dir)?;
+        self.ensure_writable(&self.bin_dir)?;
+        self.ensure_writable(&self.temp_dir)
+    }
+
+    fn download_and_build(&self, version: &str) -> Result<(), InstallerError> {
+        self.log_info(&format!("Downloading Kopi source code ({})...", version));
+        self.timed(Phase::Fetch, || self.clone_source(Some(version)))?;
+        self.refuse_yanked(version)?;
+        self.check_known_release(version);
+        self.timed(Phase::Build, || self.build_source(version))
+    }
+
+    /// Put kopi-lang's source in the temp dir: just `version` when given,
+    /// otherwise the full history so several versions can be checked out
+    /// from one clone. Trees without history are committed there (see `source`).
+    fn clone_source(&self, version: Option<&str>) -> Result<(), InstallerError> {
+        let clone_dir = self.temp_dir.join("kopi-lang");
+        if clone_dir.exists() {
+            fs::remove_dir_all(extended_length_path(&clone_dir))?;
+        }
+        self.enforce_source_policy()?;
+        self.source.fetch(self, version, &clone_dir)?;
+        if !self.source.has_history() {
+            source::commit_tree(self, &clone_dir)?;
+        }
+        Ok(())
+    }
+
+    /// Switch the full clone to `rev`, keeping `target/` so later builds reuse compiled dependencies.
+    fn checkout_source(&self, rev: &str) -> Result<(), InstallerError> {
+        let output = self.run_command(
+            Command::new("git")
+                .args(["checkout", "--force", "--quiet", rev])
+                .current_dir(self.temp_dir.join("kopi-lang")),
+        )?;
+
+        if !output.status.success() {
+            let error = String::from_utf8_lossy(&output.stderr);
+            return Err(InstallerError::Git(format!("Failed to check out {}: {}", rev, error)));
+        }
+        Ok(())
+    }
+
+    pub(crate) fn git_in_checkout(&self, args: &[&str]) -> Result<Output, InstallerError> {
+        self.run_command(Command::new("git").args(args).current_dir(self.temp_dir.join("kopi-lang")))
+    }
+
+    /// The commit checked out in the scratch clone and the kopi version it declares.
+    pub(crate) fn source_record(&self) -> Result<VersionRecord, InstallerError> {
+        let clone_dir = self.temp_dir.join("kopi-lang");
+        let output = self.run_command(Command::new("git").args(["rev-parse", "HEAD"]).current_dir(&clone_dir))?;
+        if !output.status.success() {
+            let error = String::from_utf8_lossy(&output.stderr);
+            return Err(InstallerError::Git(format!("Failed to read the checked out commit: {}", error)));
+        }
+
+        let kopi_version = fs::read_to_string(clone_dir.join("Cargo.toml"))
+            .ok()
+            .and_then(|contents| contents.parse::<toml::Table>().ok())
+            .and_then(|manifest| {
+                let version = manifest.get("package")?.get("version")?;
+                version.as_str().map(str::to_string)
+            });
+
+        let lockfile = self
+            .run_command(Command::new("git").args(["rev-parse", "--verify", "--quiet", "HEAD:Cargo.lock"]).current_dir(&clone_dir))?;
+
+        Ok(VersionRecord {
+            commit: String::from_utf8_lossy(&output.stdout).trim().to_string(),
+            kopi_version,
+            provenance: None,
+            options: BTreeMap::new(),
+            installed_at: None,
+            phase_seconds: BTreeMap::new(),
+            lockfile: lockfile.status.success().then(|| String::from_utf8_lossy(&lockfile.stdout).trim().to_string()),
+            rustc: None,
+            source: (!self.source.has_history()).then(|| self.source.describe()),
+            prebuilt: None,
+            unoptimized: false,
+        })
+    }
+
+    fn build_source(&self, version: &str) -> Result<(), InstallerError> {
+        let clone_dir = self.temp_dir.join("kopi-lang");
+
+        self.check_cancelled()?;
+        self.enforce_policy(version)?;
+        self.enforce_min_kipper(version, &clone_dir)?;
+        self.enforce_provenance(version)?;
+        self.verify_pin(version)?;
+        self.set_built_unoptimized(false);
+        self.set_prebuilt_attestation(None);
+        let staging = self.release_dir(&clone_dir);
+        // Builders whose output goes into the built binaries cache also take from it
+        if !self.fresh && self.builder.caches_output() && PrebuiltExtract.build(self, version, &clone_dir, &staging)? {
+            return self.check_advisories();
+        }
+        self.log_debug("build", &format!("Building with the {} builder", self.builder.name()));
+        if !self.builder.build(self, version, &clone_dir, &staging)? {
+            return Err(InstallerError::Cargo(format!(
+                "No prebuilt Kopi {} matches this rustc, target and build options; use another --builder to build it",
+                version
+            )));
+        }
+
+        let binary_path = staging.join(exe_name("kopi"));
+        if !binary_path.exists() {
+            return Err(InstallerError::Cargo("Built binary not found".to_string()));
+        }
+
+        // A quick build would stand in for optimized ones
+        if self.builds_quick() {
+            self.set_built_unoptimized(true);
+        } else if self.builder.caches_output() {
+            self.store_cached_build(version, &clone_dir);
+        }
+        self.check_advisories()?;
+
+        self.log_success("Build completed successfully");
+        Ok(())
+    }
+
+    /// The `cargo build --release` that builds `version` in `clone_dir`.
+    fn release_build(&self, clone_dir: &Path, version: &str, manifest: Option<&Manifest>) -> Result<Command, InstallerError> {
+        let mut cargo = self.cargo_command(clone_dir)?;
+        cargo.args(self.release_build_args(clone_dir, version, manifest)?);
+        cargo.envs(self.build_profile_env().iter().copied());
+        cargo.env("CARGO_TARGET_DIR", self.cargo_target_dir(clone_dir));
+        Ok(cargo)
+    }
+
+    /// The arguments of that `cargo build`, wherever cargo runs.
+    fn release_build_args(&self, clone_dir: &Path, version: &str, manifest: Option<&Manifest>) -> Result<Vec<String>, InstallerError> {
+        let mut args: Vec<String> = vec!["build".to_string(), "--release".to_string()];
+        if self.locked {
+            args.push("--locked".to_string());
+        }
+        if let Some(target) = self.build_target() {
+            args.extend(["--target".to_string(), target.to_string()]);
+        }
+        args.extend(workspace::build_selection(clone_dir, self.effective_profile(), manifest));
+        let chosen = self.chosen_options(version, manifest);
+        args.extend(self.option_build_args(version, manifest, &chosen)?);
+        if let Some(jobs) = self.build_jobs() {
+            args.extend(["--jobs".to_string(), jobs.to_string()]);
+        }
+        Ok(args)
+    }
+
+    fn install_binary(&self, version: &str) -> Result<(), InstallerError> {
+        self.log_info("Installing Kopi binary...");
+
+        let version_dir = self.version_dir(version);
+        let manifest = self.copy_build(&version_dir)?;
+        self.install_shims(&version_dir)?;
+
+        let record = self.build_record(version, manifest.as_ref())?;
+        Stamp::new(version, &record).save(&version_dir)?;
+        self.sign(&version_dir.join(stamp::STAMP_FILE));
+        let mut state = State::load(&self.install_dir);
+        state.versions.insert(version.to_string(), record);
+        state.save(&self.install_dir)?;
+
+        // The first version installed becomes the default
+        let default = self.default_version();
+        if default.is_none() || default.as_deref() == Some(version) {
+            self.set_default(version)?;
+        }
+
+        Ok(())
+    }
+
+    /// Copy what the build in the scratch checkout produced for the current
+    /// profile into `version_dir`, returning kopi-lang's install manifest.
+    fn copy_build(&self, version_dir: &Path) -> Result<Option<Manifest>, InstallerError> {
+        let checkout = self.temp_dir.join("kopi-lang");
+        let release_dir = self.release_dir(&checkout);
+        let manifest = Manifest::load(&checkout)?;
+        fs::create_dir_all(version_dir)?;
+
+        let profile = self.effective_profile();
+        if let Some(manifest) = &manifest {
+            self.install_from_manifest(manifest, version_dir)?;
+        } else {
+            let dest_path = version_dir.join(exe_name("kopi"));
+            fs::copy(release_dir.join(exe_name("kopi")), &dest_path)?;
+
+            for component in COMPONENTS {
+                let source_path = release_dir.join(exe_name(component));
+                let component_path = version_dir.join(exe_name(component));
+                if profile.components().contains(component) && source_path.exists() {
+                    fs::copy(&source_path, &component_path)?;
+                } else if component_path.exists() {
+                    // Left over from an install with a larger profile
+                    fs::remove_file(&component_path)?;
+                }
+            }
+
+            if profile.includes_extras() {
+                for extra in ["docs", "completions"] {
+                    let source_dir = checkout.join(extra);
+                    if source_dir.is_dir() {
+                        copy_dir(&extended_length_path(&source_dir), &extended_length_path(&version_dir.join(extra)))?;
+                    }
+                }
+            }
+
+            // From an earlier build of this version that had one
+            let stale_manifest = version_dir.join(MANIFEST_FILE);
+            if stale_manifest.exists() {
+                fs::remove_file(&stale_manifest)?;
+                let _ = fs::remove_file(version_dir.join(format!("{}{}", MANIFEST_FILE, signing::SIGNATURE_SUFFIX)));
+            }
+        }
+
+        // Kept for `kipper sbom`
+        let lockfile = checkout.join(sbom::LOCKFILE);
+        if lockfile.exists() {
+            fs::copy(&lockfile, version_dir.join(sbom::LOCKFILE))?;
+        }
+
+        Ok(manifest)
+    }
+
+    /// The state record for `version` as built in the scratch checkout.
+    fn build_record(&self, version: &str, manifest: Option<&Manifest>) -> Result<VersionRecord, InstallerError> {
+        let mut record = self.source_record()?;
+        record.provenance = Some(self.source_provenance(version)?);
+        record.options = self.chosen_options(version, manifest);
+        record.installed_at = Some(timestamp::now_utc());
+        record.unoptimized = self.built_unoptimized();
+        record.prebuilt = self.prebuilt_attestation();
+        let checkout = self.temp_dir.join("kopi-lang");
+        record.rustc = self.rustc_info(&checkout).and_then(|info| {
+            let field = |name: &str| info.lines().find_map(|line| line.strip_prefix(name)).map(str::trim);
+            Some(format!("{} {}", field("release:")?, self.build_target().or(field("host:"))?))
+        });
+        Ok(record)
+    }
+
+    /// Install the binaries and files `manifest` lists for the current
+    /// profile, removing binaries a larger profile left behind.
+    fn install_from_manifest(&self, manifest: &Manifest, version_dir: &Path) -> Result<(), InstallerError> {
+        let checkout = self.temp_dir.join("kopi-lang");
+        let release_dir = self.release_dir(&checkout);
+        let profile = self.effective_profile();
+        let wanted: Vec<&str> = manifest.binaries(profile).collect();
+
+        for binary in &manifest.binary {
+            let dest = version_dir.join(exe_name(&binary.name));
+            if wanted.contains(&binary.name.as_str()) {
+                let source = release_dir.join(exe_name(&binary.name));
+                if !source.exists() {
+                    return Err(InstallerError::Cargo(format!(
+                        "{} lists {}, but the build didn't produce it",
+                        MANIFEST_FILE, binary.name
+                    )));
+                }
+                fs::copy(&source, &dest)?;
+            } else if dest.exists() {
+                fs::remove_file(&dest)?;
+            }
+        }
+
+        for file in manifest.files(profile) {
+            let source = checkout.join(&file.source);
+            let dest = version_dir.join(file.dest());
+            if source.is_dir() {
+                copy_dir(&extended_length_path(&source), &extended_length_path(&dest))?;
+            } else if source.is_file() {
+                if let Some(parent) = dest.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                fs::copy(&source, &dest)?;
+            } else {
+                return Err(InstallerError::PathError(format!(
+                    "{} lists {}, which isn't in the source",
+                    MANIFEST_FILE, file.source
+                )));
+            }
+        }
+
+        // Kept so later commands can show the post-install messages
+        fs::copy(checkout.join(MANIFEST_FILE), version_dir.join(MANIFEST_FILE))?;
+        self.sign(&version_dir.join(MANIFEST_FILE));
+
+        let mut state = State::load(&self.install_dir);
+        let builtin: Vec<&str> = shim::shimmed_tools().collect();
+        let before = state.tools.len();
+        state.tools.extend(wanted.iter().filter(|name| !builtin.contains(name)).map(|name| name.to_string()));
+        if state.tools.len() != before {
+            state.save(&self.install_dir)?;
+        }
+        Ok(())
+    }
+
+    /// Make `version` the default and point the `kopi` on PATH at it.
+    pub fn set_default(&self, version: &str) -> Result<(), InstallerError> {
+        let dest_path = self.version_dir(version).join(exe_name("kopi"));
+        if !dest_path.exists() {
+            return Err(self.not_installed(version));
+        }
+        fs::write(self.install_dir.join(DEFAULT_FILE), format!("{}\n", self.installed_name(version)))?;
+        self.sign(&self.install_dir.join(DEFAULT_FILE));
+        self.update_current_link(&self.installed_name(version))?;
+        Ok(())
+    }
+
+    /// Create shims in the bin dir for `kopi` and every component `version_dir` provides.
+    ///
+    /// Shims are kipper itself under the tool's name, so they pick the version
+    /// per invocation and all tools switch together.
+    fn install_shims(&self, version_dir: &Path) -> Result<(), InstallerError> {
+        let host = self.install_dir.join(exe_name(shim::SHIM_HOST));
+        let current = env::current_exe()?;
+        if fs::canonicalize(&current).ok() != fs::canonicalize(&host).ok() {
+            if host.exists() {
+                fs::remove_file(&host)?;
+            }
+            fs::copy(&current, &host)?;
+            // kipper itself was likely downloaded; shims copied from the host
+            // shouldn't inherit that
+            clear_download_mark(&host);
+        }
+
+        // Installs from before versioned layouts kept the binary directly in the install dir
+        #[cfg(unix)]
+        {
+            let legacy_path = self.install_dir.join("kopi");
+            if legacy_path.is_file() {
+                fs::remove_file(&legacy_path)?;
+            }
+        }
+
+        if self.bin_strategy() == BinStrategy::Direct {
+            let default = self.default_version().filter(|default| self.version_dir(default).exists());
+            let installed = version_dir.file_name().map(|name| name.to_string_lossy().into_owned());
+            if let Some(version) = default.or(installed) {
+                self.update_current_link(&version)?;
+            }
+        }
+        for tool in self.tools() {
+            if version_dir.join(exe_name(&tool)).exists() {
+                self.expose(&tool)?;
+            }
+        }
+        for (alias, tool) in &State::load(&self.install_dir).aliases {
+            if version_dir.join(exe_name(tool)).exists() {
+                self.expose(alias)?;
+            }
+        }
+
+        // Try to add to PATH or inform user
+        #[cfg(windows)]
+        self.update_windows_path()?;
+
+        Ok(())
+    }
+
+    pub fn default_version(&self) -> Option<String> {
+        let path = self.install_dir.join(DEFAULT_FILE);
+        let name = fs::read_to_string(&path).ok()?.trim().to_string();
+        if name.is_empty() {
+            return None;
+        }
+        if !toolchain::is_version_name(&name) {
+            let from = path.display().to_string();
+            self.log_warning(&RejectedVersion { from, name }.to_string());
+            return None;
+        }
+        Some(name)
+    }
+
+    /// The version selected for `cwd`: `KOPI_VERSION`, then the project pin,
+    /// then the default. Settings that can't name a version are skipped with
+    /// a warning.
+    pub fn resolve_version(&self, cwd: &Path) -> Option<ResolvedVersion> {
+        self.select_version(env::var(VERSION_ENV).ok().as_deref(), cwd)
+    }
+
+    /// Like `resolve_version`, with `requested` standing in for `KOPI_VERSION`.
+    pub(crate) fn select_version(&self, requested: Option<&str>, cwd: &Path) -> Option<ResolvedVersion> {
+        toolchain::resolve_version(requested, cwd, self.default_version(), |rejected| {
+            self.log_warning(&rejected.to_string())
+        })
+    }
+
+    pub fn version_dir(&self, version: &str) -> PathBuf {
+        self.install_dir.join("versions").join(version)
+    }
+
+    /// The name `version` is installed under. On a case-insensitive
+    /// filesystem `V1.0` reaches the `v1.0` directory, so comparisons with
+    /// the default and state records need the name as it is on disk.
+    pub fn installed_name(&self, version: &str) -> String {
+        let Ok(toolchains) = self.list() else {
+            return version.to_string();
+        };
+        if toolchains.iter().any(|t| t.name == version) || !self.version_dir(version).exists() {
+            return version.to_string();
+        }
+        toolchains
+            .into_iter()
+            .find(|t| t.name.eq_ignore_ascii_case(version))
+            .map_or_else(|| version.to_string(), |t| t.name)
+    }
+
+    /// Resolve the version for `cwd` and report where its tools live.
+    pub fn toolchain_paths(&self, cwd: &Path) -> Option<ToolchainPaths> {
+        let version = self.resolve_version(cwd)?;
+        let version_dir = self.version_dir(&version.name);
+        let interpreter = version_dir.join(exe_name("kopi"));
+        let language_server = Some(version_dir.join(exe_name("kopi-lsp"))).filter(|p| p.exists());
+
+        Some(ToolchainPaths {
+            installed: interpreter.exists(),
+            version,
+            interpreter,
+            language_server,
+        })
+    }
+
+    /// Path of `tool` in the version selected for `cwd`.
+    pub fn which(&self, tool: &str, cwd: &Path) -> Result<PathBuf, InstallerError> {
+        let resolved = self.resolve_version(cwd)
+            .ok_or_else(|| InstallerError::PathError("No Kopi version selected, run `kipper install`".to_string()))?;
+
+        let path = self.version_dir(&resolved.name).join(exe_name(tool));
+        if !path.exists() {
+            return Err(InstallerError::PathError(format!(
+                "{} is not installed for Kopi {}",
+                tool, resolved.name
+            )));
+        }
+        Ok(path)
+    }
+
+    /// Build a `Command` for `program` with the environment of a toolchain.
+    ///
+    /// Uses `version` if given, otherwise whatever `cwd` resolves to. The
+    /// version's directory is put first on PATH, `KOPI_HOME` points at it and
+    /// `KOPI_VERSION` is set so nested kipper invocations agree on the version.
+    pub fn toolchain_command(&self, version: Option<&str>, cwd: &Path, program: &OsStr) -> Result<Command, InstallerError> {
+        let version = match version {
+            Some(version) => toolchain::check_version_name(version).map(|()| version.to_string())?,
+            None => self.resolve_version(cwd)
+                .map(|resolved| resolved.name)
+                .ok_or_else(|| InstallerError::PathError("No Kopi version selected".to_string()))?,
+        };
+
+        let version_dir = self.version_dir(&version);
+        if !version_dir.join(exe_name("kopi")).exists() {
+            return Err(self.not_installed(&version));
+        }
+
+        let mut paths = vec![version_dir.clone()];
+        if let Some(existing) = env::var_os("PATH") {
+            paths.extend(env::split_paths(&existing));
+        }
+        let path = env::join_paths(paths).map_err(|e| InstallerError::PathError(e.to_string()))?;
+
+        let mut command = Command::new(program);
+        command
+            .env("PATH", path)
+            .env(toolchain::HOME_ENV, &version_dir)
+            .env(toolchain::VERSION_ENV, &version)
+            .current_dir(cwd);
+        Ok(command)
+    }
+
+    #[cfg(windows)]
+    fn update_windows_path(&self) -> Result<(), InstallerError> {
+        self.log_info("Note: You may need to add the installation directory to your PATH");
+        self.log_info(&format!("Installation directory: {}", self.install_dir.display()));
+        Ok(())
+    }
+
+    /// Everything an install puts on disk apart from user settings, caches and logs.
+    ///
+    /// A plain uninstall removes exactly these; `--purge` removes the whole
+    /// install dir on top. Entries are `(path, is_dir)`.
+    fn installed_paths(&self) -> Vec<(PathBuf, bool)> {
+        let mut paths = vec![
+            (self.install_dir.join("versions"), true),
+            (self.install_dir.join(DEFAULT_FILE), false),
+            (self.install_dir.join(format!("{}{}", DEFAULT_FILE, signing::SIGNATURE_SUFFIX)), false),
+            (self.install_dir.join(exe_name(shim::SHIM_HOST)), false),
+            // Installs from before versioned layouts kept the binary directly in the install dir
+            (self.install_dir.join(exe_name("kopi")), false),
+            (self.install_dir.join("serve-token"), false),
+        ];
+        let state = State::load(&self.install_dir);
+        if self.bin_strategy() == BinStrategy::Direct {
+            paths.push((self.install_dir.join(expose::CURRENT_LINK), false));
+        } else {
+            for name in self.tools().iter().chain(state.aliases.keys()) {
+                paths.push((self.exposed_path(name), false));
+            }
+        }
+        for script in state.completions {
+            paths.push((script, false));
+        }
+        paths.push((self.uninstaller_path(), false));
+        paths
+    }
+
+    fn uninstaller_path(&self) -> PathBuf {
+        if cfg!(windows) {
+            self.install_dir.join("uninstall.bat")
+        } else {
+            self.install_dir.join("uninstall.sh")
+        }
+    }
+
+    fn create_uninstaller(&self) -> Result<(), InstallerError> {
+        self.log_info("Creating uninstaller...");
+        
+        let install_dir = self.install_dir.display().to_string();
+        // Labels rather than a parenthesised block, so a `)` in a path can't
+        // end the block early; UTF-8 code page so non-ASCII paths match
+        let uninstall_script = if cfg!(windows) {
+            let removals: String = self
+                .installed_paths()
+                .iter()
+                .map(|(path, is_dir)| if *is_dir {
+                    format!("rmdir /s /q {} 2>nul\r\n", batch_quote(path))
+                } else {
+                    format!("del /f /q {} 2>nul\r\n", batch_quote(path))
+                })
+                .collect();
+            format!("@echo off\r\nchcp 65001 >nul\r\necho Uninstalling Kopi Language...\r\nif \"%~1\"==\"--purge\" goto purge\r\n{}echo Settings, caches and logs kept in {} - run with --purge to remove them\r\ngoto done\r\n:purge\r\nrmdir /s /q {} 2>nul\r\n:done\r\necho Kopi has been uninstalled successfully\r\npause\r\n",
+                removals, batch_echo_text(&install_dir), batch_quote(&self.install_dir))
+        } else {
+            let removals: String = self
+                .installed_paths()
+                .iter()
+                .map(|(path, _)| format!("rm -rf {}\n", sh_quote(&path.display().to_string())))
+                .collect();
+            format!("#!/bin/sh\ninstall_dir={}\necho \"Uninstalling Kopi Language...\"\n{}if [ \"$1\" = \"--purge\" ]; then\n  rm -rf \"$install_dir\"\nelse\n  rmdir \"$install_dir\" 2>/dev/null || echo \"Settings, caches and logs kept in $install_dir (run with --purge to remove them)\"\nfi\necho \"Kopi has been uninstalled successfully\"\n",
+                sh_quote(&install_dir), removals)
+        };
+
+        let uninstall_path = self.uninstaller_path();
+
+        fs::write(&uninstall_path, uninstall_script)?;
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mut perms = fs::metadata(&uninstall_path)?.permissions();
+            perms.set_mode(0o755);
+            fs::set_permissions(&uninstall_path, perms)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn cleanup(&self) -> Result<(), InstallerError> {
+        if self.temp_dir.exists() {
+            self.log_info("Cleaning up temporary files...");
+            fs::remove_dir_all(extended_length_path(&self.temp_dir))?;
+        }
+        Ok(())
+    }
+
+    fn verify_installation(&self, version: &str) -> Result<(), InstallerError> {
+        self.log_info("Verifying installation...");
+        
+        let binary_path = self.version_dir(version).join(exe_name("kopi"));
+        if !binary_path.exists() {
+            return Err(InstallerError::PathError("Installation verification failed".to_string()));
+        }
+
+        let summary = self.install_summary(version)?;
+        self.print_line("");
+        self.print_summary(&summary);
+        if let Some(manifest) = Manifest::load(&self.version_dir(version))? {
+            for message in &manifest.post_install {
+                self.log_info(message);
+            }
+        }
+        match self.path_status("kopi") {
+            PathStatus::Ok => {}
+            PathStatus::Missing => {
+                self.print_line("");
+                self.log_warning("Kopi installed but may not be in PATH yet; until it is, run it as:");
+                let command = format!("{} --help", binary_path.display());
+                self.print_line(&format!("  {}", self.theme.paint(self.theme.highlight.as_deref(), &command)));
+                self.explain_path_setup();
+                self.log_info(&format!(
+                    "If kopi still isn't found in a new shell, see `kipper explain {}`",
+                    explain::PATH_NOT_APPLIED
+                ));
+            }
+            PathStatus::Shadowed(shadow) => {
+                self.print_line("");
+                self.report_shadowed(&shadow)?;
+            }
+        }
+        self.report_wsl_interop();
+
+        if let Ok(mut summaries) = self.summaries.lock() {
+            summaries.push(summary);
+        }
+        Ok(())
+    }
+
+    /// Summaries of every version installed or updated by this installer so far.
+    pub fn summaries(&self) -> Vec<InstallSummary> {
+        self.summaries.lock().map(|s| s.clone()).unwrap_or_default()
+    }
+
+    /// Remove binaries and shims; with `purge`, also remove settings, caches and logs.
+    pub fn uninstall(&self, purge: bool) -> Result<(), InstallerError> {
+        if !self.confirm(Action::Uninstall, "Remove Kopi and every installed version?")? {
+            return Err(self.declined("uninstall"));
+        }
+        if purge
+            && self.install_dir.exists()
+            && !self.confirm(
+                Action::Purge,
+                &format!("Also delete settings, caches and logs in {}?", self.install_dir.display()),
+            )?
+        {
+            return Err(self.declined("purge"));
+        }
+        self.log_info("Uninstalling Kopi...");
+
+        for (path, is_dir) in self.installed_paths() {
+            if is_dir && path.is_dir() {
+                fs::remove_dir_all(extended_length_path(&path))?;
+            } else if path.symlink_metadata().is_ok() {
+                // A link to a directory is removed as one on Windows
+                fs::remove_file(&path).or_else(|_| fs::remove_dir(&path))?;
+            }
+        }
+
+        if self.install_dir.exists() {
+            if purge {
+                fs::remove_dir_all(extended_length_path(&self.install_dir))?;
+            } else if fs::read_dir(&self.install_dir)?.all(|entry| entry.is_ok_and(|e| e.file_name() == lock::LOCK_DIR)) {
+                // Nothing left but the lock files, which go with it
+                fs::remove_dir_all(&self.install_dir)?;
+            } else {
+                self.log_info(&format!(
+                    "Settings, caches and logs kept in {} (use --purge to remove them)",
+                    self.install_dir.display()
+                ));
+            }
+        }
+
+        self.log_success("Kopi has been uninstalled successfully");
+        Ok(())
+    }
+
+    /// Delete the running kipper executable, for `uninstall --all --remove-self`.
+    pub fn remove_self(&self) -> Result<(), InstallerError> {
+        let exe = env::current_exe()?;
+        if !exe.exists() {
+            return Ok(());
+        }
+        self.log_info(&format!("Removing {}...", exe.display()));
+
+        // Unix lets a running binary unlink itself
+        #[cfg(not(windows))]
+        fs::remove_file(&exe)?;
+
+        // Windows keeps the image locked until exit, so a detached cmd waits a
+        // moment for us to finish and then deletes it
+        #[cfg(windows)]
+        {
+            use std::os::windows::process::CommandExt;
+            const DETACHED_PROCESS: u32 = 0x0000_0008;
+            const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+            Command::new("cmd")
+                .arg("/C")
+                .arg(format!("ping 127.0.0.1 -n 3 > nul & del /f /q \"{}\"", exe.display()))
+                .creation_flags(DETACHED_PROCESS | CREATE_NO_WINDOW)
+                .stdin(Stdio::null())
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .spawn()?;
+        }
+
+        self.log_success("kipper has been removed");
+        Ok(())
+    }
+
+    /// Remove a single installed version, leaving the rest of the installation alone.
+    ///
+    /// Removing the default version is refused unless `force` is set, in which
+    /// case no default remains until another version is installed.
+    pub fn uninstall_version(&self, version: &str, force: bool) -> Result<(), InstallerError> {
+        toolchain::check_version_name(version)?;
+        let version = &self.installed_name(version);
+        let version_dir = self.version_dir(version);
+        if !version_dir.exists() {
+            return Err(self.not_installed(version));
+        }
+
+        let is_default = self.default_version().as_deref() == Some(version);
+        if is_default && !force {
+            self.log_info("Re-run with --force to remove the default version anyway");
+            return Err(InstallerError::PathError(format!("Refusing to remove default version {}", version)));
+        }
+
+        if !self.confirm(Action::Uninstall, &format!("Remove Kopi {}?", version))? {
+            return Err(self.declined("uninstall"));
+        }
+        self.log_info(&format!("Uninstalling Kopi {}...", version));
+        fs::remove_dir_all(extended_length_path(&version_dir))?;
+
+        let mut state = State::load(&self.install_dir);
+        if state.versions.remove(version).is_some() {
+            state.save(&self.install_dir)?;
+        }
+        self.discard_prepared(version)?;
+
+        if is_default {
+            fs::remove_file(self.install_dir.join(DEFAULT_FILE))?;
+            let _ = fs::remove_file(self.install_dir.join(format!("{}{}", DEFAULT_FILE, signing::SIGNATURE_SUFFIX)));
+            self.log_warning("No default version is set any more");
+        }
+
+        self.log_success(&format!("Kopi {} has been uninstalled", version));
+        Ok(())
+    }
+
+    pub fn install(&self) -> Result<(), InstallerError> {
+        self.print_banner();
+
+        if let Some(repaired) = self.current_install(NIGHTLY)? {
+            self.report_current(NIGHTLY, &repaired);
+            return Ok(());
+        }
+        if self.is_installed() {
+            self.log_warning("Kopi appears to already be installed");
+            if !self.confirm(Action::Reinstall, "Do you want to reinstall?")? {
+                self.log_info("Installation cancelled");
+                return Ok(());
+            }
+        }
+
+        self.log_info("Starting Kopi installation...");
+        self.build_and_install(NIGHTLY)?;
+
+        self.print_line("");
+        self.log_success("🎉 Kopi installation completed successfully!");
+        self.print_line("");
+        self.print_line(&self.theme.paint(self.theme.banner.as_deref(), "Happy coding with Kopi! ☕"));
+
+        Ok(())
+    }
+
+    /// What `kipper install` installs without a version: `[install] version`
+    /// from the settings, or nightly.
+    pub fn preferred_version(&self) -> String {
+        self.config.install.version.clone().unwrap_or_else(|| NIGHTLY.to_string())
+    }
+
+    /// Build and install a specific version (a kopi-lang tag, or `nightly`) without prompting.
+    pub fn install_version(&self, version: &str) -> Result<(), InstallerError> {
+        let version = self.resolve_release(version)?;
+        self.log_info(&format!("Installing Kopi {}...", version));
+        self.build_and_install(&version)
+    }
+
+    /// Install several versions from a single clone, reporting each outcome.
+    ///
+    /// The repository is fetched once and every version is built in the same
+    /// checkout, so dependencies compiled for one version are reused by the
+    /// next when they match. A failed version doesn't stop the others.
+    pub fn install_versions(&self, versions: &[String]) -> Result<(), InstallerError> {
+        let mut unique: Vec<String> = Vec::new();
+        for version in versions {
+            let version = self.resolve_release(version)?;
+            if !unique.contains(&version) {
+                unique.push(version);
+            }
+        }
+        if let [version] = unique.as_slice() {
+            return self.install_version(version);
+        }
+
+        let _ = self.clean_stale_temp_dirs();
+        self.check_dependencies()?;
+        self.create_directories()?;
+
+        // Other sources are fetched for each version in turn
+        let history = self.source.has_history();
+        let mut nightly_rev = String::new();
+        if history {
+            self.log_info(&format!("Downloading Kopi source code for {} versions...", unique.len()));
+            self.clone_source(None)?;
+
+            // A fresh clone sits on the default branch, which is what nightly means
+            let head = self.run_command(
+                Command::new("git")
+                    .args(["rev-parse", "HEAD"])
+                    .current_dir(self.temp_dir.join("kopi-lang")),
+            )?;
+            nightly_rev = String::from_utf8_lossy(&head.stdout).trim().to_string();
+        }
+
+        let mut results = Vec::new();
+        for version in &unique {
+            self.log_info(&format!("Installing Kopi {}...", version));
+            let rev = if *version == NIGHTLY { nightly_rev.as_str() } else { version };
+            let result = self
+                .refuse_yanked(version)
+                .and_then(|_| if history { self.checkout_source(rev) } else { self.clone_source(Some(version)) })
+                .inspect(|_| self.check_known_release(version))
+                .and_then(|_| self.build_source(version))
+                .and_then(|_| self.install_binary(version))
+                .and_then(|_| self.verify_installation(version));
+
+            if matches!(result, Err(InstallerError::Cancelled)) {
+                self.log_warning("Installation cancelled, rolling back...");
+                self.cleanup()?;
+                return Err(InstallerError::Cancelled);
+            }
+            if let Err(e) = &result {
+                self.log_error(&format!("Kopi {} failed: {:?}", version, e));
+            }
+            results.push((version.as_str(), result));
+        }
+
+        self.create_uninstaller()?;
+        self.record_profile()?;
+
+        self.print_line("");
+        self.log_info("Summary:");
+        for (version, result) in &results {
+            match result {
+                Ok(()) => self.print_line(&format!("  {} {}", self.theme.paint(self.theme.success.color.as_deref(), "✔"), version)),
+                Err(e) => self.print_line(&format!(
+                    "  {} {} ({:?})",
+                    self.theme.paint(self.theme.error.color.as_deref(), "✘"),
+                    version,
+                    e
+                )),
+            }
+        }
+
+        let failed = results.iter().filter(|(_, r)| r.is_err()).count();
+        if failed > 0 {
+            return Err(InstallerError::Cargo(format!("{} of {} versions failed to install", failed, results.len())));
+        }
+        Ok(())
+    }
+
+    /// Installed versions, in the order of `release::compare_versions`.
+    pub fn list(&self) -> Result<Vec<Toolchain>, InstallerError> {
+        let versions_dir = self.install_dir.join("versions");
+        if !versions_dir.exists() {
+            return Ok(Vec::new());
+        }
+
+        let mut toolchains = Vec::new();
+        for entry in fs::read_dir(&versions_dir)? {
+            let entry = entry?;
+            let binary = entry.path().join(exe_name("kopi"));
+            if binary.exists() {
+                toolchains.push(Toolchain {
+                    name: entry.file_name().to_string_lossy().into_owned(),
+                    binary,
+                });
+            }
+        }
+        toolchains.sort_by(|a, b| release::compare_versions(&a.name, &b.name));
+        Ok(toolchains)
+    }
+
+    pub fn is_installed(&self) -> bool {
+        // Installs from before versioned layouts kept the binary directly in the install dir
+        self.default_version().is_some() || self.install_dir.join(exe_name("kopi")).exists()
+    }
+
+    fn build_and_install(&self, version: &str) -> Result<(), InstallerError> {
+        self.prepare_install()?;
+        self.rollback_on_cancel(self.download_and_build(version))?;
+        self.finish_install(version)
+    }
+
+    fn prepare_install(&self) -> Result<(), InstallerError> {
+        if let Some(project_file) = &self.config.project_file {
+            self.log_info(&format!("Using project settings from {}", project_file.display()));
+        }
+        // Leftovers from crashed runs can be gigabytes of cargo output; never fatal
+        let _ = self.clean_stale_temp_dirs();
+        if let Ok(mut times) = self.phase_times.lock() {
+            times.clear();
+        }
+        self.check_dependencies()?;
+        self.create_directories()
+    }
+
+    /// Nothing outside the temp dir has been touched until install_binary, so a
+    /// cancelled clone or build only has to discard the scratch checkout.
+    fn rollback_on_cancel(&self, result: Result<(), InstallerError>) -> Result<(), InstallerError> {
+        if matches!(result, Err(InstallerError::Cancelled)) {
+            self.log_warning("Installation cancelled, rolling back...");
+            self.cleanup()?;
+        }
+        result
+    }
+
+    /// Install what build_source produced for `version`.
+    fn finish_install(&self, version: &str) -> Result<(), InstallerError> {
+        self.timed(Phase::Install, || {
+            self.install_binary(version)?;
+            self.create_uninstaller()?;
+            self.record_profile()
+        })?;
+        self.record_phase_seconds(version)?;
+        self.verify_installation(version)?;
+        self.suggest_optimizing(version);
+        // Space is reclaimed after the fact; a failed eviction never fails the install
+        if let Err(e) = self.enforce_cache_limit() {
+            self.log_warning(&format!("Could not trim the cache: {:?}", e));
+        }
+        Ok(())
+    }
+}
+
+pub(crate) fn copy_dir(source: &Path, dest: &Path) -> io::Result<()> {
+    fs::create_dir_all(dest)?;
+    for entry in fs::read_dir(source)? {
+        let entry = entry?;
+        let target = dest.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            copy_dir(&entry.path(), &target)?;
+        } else {
+            fs::copy(entry.path(), target)?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,73 @@
+// `kipper list` and `kipper ls-remote`: installed versions and the release
+// tags kopi-lang has. Both are in the order of `release::compare_versions`
+// (oldest release first, then other names by name) on every run, whatever
+// order the filesystem or git return them in, so their output can be diffed
+// and parsed. `--json` prints the same entries as one JSON array; fields are
+// only ever added.
+
+use serde::Serialize;
+
+use crate::release::{Release, compare_versions};
+use crate::state::State;
+use crate::{Installer, InstallerError};
+
+/// An installed version, as `kipper list` shows it.
+#[derive(Debug, Clone, Serialize)]
+pub struct InstalledEntry {
+    pub name: String,
+    pub default: bool,
+    pub commit: Option<String>,
+    pub kopi_version: Option<String>,
+    /// A quick build, not optimized yet.
+    pub unoptimized: bool,
+    pub path: String,
+}
+
+/// A release tag, as `kipper ls-remote` shows it.
+#[derive(Debug, Clone, Serialize)]
+pub struct RemoteEntry {
+    pub name: String,
+    pub prerelease: bool,
+    pub yanked: bool,
+    pub installed: bool,
+}
+
+impl Installer {
+    pub fn installed_entries(&self) -> Result<Vec<InstalledEntry>, InstallerError> {
+        let state = State::load(&self.install_dir);
+        let default = self.default_version();
+        Ok(self
+            .list()?
+            .into_iter()
+            .map(|toolchain| {
+                let record = state.versions.get(&toolchain.name);
+                InstalledEntry {
+                    default: default.as_deref() == Some(toolchain.name.as_str()),
+                    commit: record.map(|record| record.commit.clone()),
+                    kopi_version: record.and_then(|record| record.kopi_version.clone()),
+                    unoptimized: record.is_some_and(|record| record.unoptimized),
+                    path: toolchain.binary.display().to_string(),
+                    name: toolchain.name,
+                }
+            })
+            .collect())
+    }
+
+    /// Every tag in kopi-lang that is a release, fetching new ones first.
+    pub fn remote_entries(&self) -> Result<Vec<RemoteEntry>, InstallerError> {
+        let mut tags: Vec<String> = self.release_tags()?.into_iter().filter(|tag| Release::parse(tag).is_some()).collect();
+        tags.sort_by(|a, b| compare_versions(a, b));
+        tags.dedup();
+        let yanked = self.yanked_versions();
+        let installed: Vec<String> = self.list()?.into_iter().map(|toolchain| toolchain.name).collect();
+        Ok(tags
+            .into_iter()
+            .map(|tag| RemoteEntry {
+                prerelease: Release::parse(&tag).is_some_and(|release| release.is_prerelease()),
+                yanked: yanked.iter().any(|y| y.version == tag),
+                installed: installed.contains(&tag),
+                name: tag,
+            })
+            .collect())
+    }
+}
@@ -0,0 +1,165 @@
+// Locks that keep concurrent kippers, and programs embedding this library,
+// from changing one install at the same time. They are advisory file locks
+// (flock on Unix, LockFileEx on Windows) in `locks/` under the install dir,
+// so they are per user like the install itself, and the OS drops them when
+// the process holding them exits, however it exits.
+//
+//     install.lock  held for as long as a command changes what is installed:
+//                   versions, shims, the bin dir and the default. The CLI
+//                   takes it for every command that writes, and waits when
+//                   another one has it.
+//     state.lock    held while state.json is written. A read-modify-write of
+//                   the state done from outside should hold it around the
+//                   whole sequence; `State::save` takes it too.
+//
+// A guard is a `FileLock`, released when dropped. Within one process a lock
+// is shared rather than waited for, so code holding `Installer::lock_state`
+// can still call into kipper functions that save the state.
+
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions, TryLockError};
+use std::io::{self, Read, Seek, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock, Weak};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::{Installer, InstallerError};
+
+/// The dir under the install dir the lock files are in.
+pub const LOCK_DIR: &str = "locks";
+pub const INSTALL_LOCK: &str = "install.lock";
+pub const STATE_LOCK: &str = "state.lock";
+/// How often a held lock is tried again.
+const RETRY_INTERVAL: Duration = Duration::from_millis(100);
+
+/// A lock file this process holds, unlocked when the last guard sharing it
+/// is dropped.
+#[derive(Debug)]
+struct Held {
+    path: PathBuf,
+    file: File,
+}
+
+impl Drop for Held {
+    fn drop(&mut self) {
+        let _ = self.file.unlock();
+    }
+}
+
+/// The locks this process holds, by path.
+fn held() -> &'static Mutex<HashMap<PathBuf, Weak<Held>>> {
+    static HELD: OnceLock<Mutex<HashMap<PathBuf, Weak<Held>>>> = OnceLock::new();
+    HELD.get_or_init(Default::default)
+}
+
+/// A held lock; dropping it releases the lock.
+#[derive(Debug, Clone)]
+pub struct FileLock {
+    held: Arc<Held>,
+}
+
+impl FileLock {
+    /// Take the lock at `path` if nobody else holds it. Returns None when
+    /// another process does.
+    pub fn try_acquire(path: &Path) -> io::Result<Option<FileLock>> {
+        let mut locks = held().lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(held) = locks.get(path).and_then(Weak::upgrade) {
+            return Ok(Some(FileLock { held }));
+        }
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut file = OpenOptions::new().read(true).write(true).create(true).truncate(false).open(path)?;
+        match file.try_lock() {
+            Ok(()) => {}
+            Err(TryLockError::WouldBlock) => return Ok(None),
+            Err(TryLockError::Error(e)) => return Err(e),
+        }
+        // For whoever waits on it to say who they are waiting for
+        file.set_len(0)?;
+        file.rewind()?;
+        write!(file, "{}", std::process::id())?;
+        let held = Arc::new(Held {
+            path: path.to_path_buf(),
+            file,
+        });
+        locks.retain(|_, held| held.strong_count() > 0);
+        locks.insert(path.to_path_buf(), Arc::downgrade(&held));
+        Ok(Some(FileLock { held }))
+    }
+
+    /// Take the lock at `path`, waiting up to `timeout` for it (for ever
+    /// with None).
+    pub fn acquire(path: &Path, timeout: Option<Duration>) -> io::Result<FileLock> {
+        wait(path, timeout, |_| Ok(()))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.held.path
+    }
+}
+
+/// The pid of the process holding the lock at `path`, as far as it can be
+/// told (Windows doesn't let a lock file be read while it is held).
+pub fn holder(path: &Path) -> Option<u32> {
+    let mut contents = String::new();
+    File::open(path).ok()?.read_to_string(&mut contents).ok()?;
+    contents.trim().parse().ok()
+}
+
+/// Take the lock at `path`, calling `waiting` with its holder each time it
+/// is found held; an error from `waiting` stops the wait.
+fn wait<E: From<io::Error>>(
+    path: &Path,
+    timeout: Option<Duration>,
+    mut waiting: impl FnMut(Option<u32>) -> Result<(), E>,
+) -> Result<FileLock, E> {
+    let started = Instant::now();
+    loop {
+        if let Some(lock) = FileLock::try_acquire(path)? {
+            return Ok(lock);
+        }
+        let holder = holder(path);
+        if timeout.is_some_and(|timeout| started.elapsed() >= timeout) {
+            let by = holder.map_or(String::new(), |pid| format!(" by process {}", pid));
+            return Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                format!("{} is still held{} after {:.1}s", path.display(), by, started.elapsed().as_secs_f64()),
+            )
+            .into());
+        }
+        waiting(holder)?;
+        thread::sleep(RETRY_INTERVAL);
+    }
+}
+
+impl Installer {
+    /// Take the install lock, held while anything installed changes; waits
+    /// up to `timeout` (for ever with None) while another process has it.
+    pub fn lock_install(&self, timeout: Option<Duration>) -> Result<FileLock, InstallerError> {
+        self.wait_for_lock(INSTALL_LOCK, "changing this install", timeout)
+    }
+
+    /// Take the state lock, held while state.json is read and written back.
+    pub fn lock_state(&self, timeout: Option<Duration>) -> Result<FileLock, InstallerError> {
+        self.wait_for_lock(STATE_LOCK, "writing kipper's state", timeout)
+    }
+
+    pub fn lock_path(&self, name: &str) -> PathBuf {
+        self.install_dir.join(LOCK_DIR).join(name)
+    }
+
+    fn wait_for_lock(&self, name: &str, what: &str, timeout: Option<Duration>) -> Result<FileLock, InstallerError> {
+        let mut told = false;
+        wait(&self.lock_path(name), timeout, |holder| {
+            self.check_cancelled()?;
+            if !told {
+                let by = holder.map_or("Another process".to_string(), |pid| format!("Another kipper (pid {})", pid));
+                self.log_info(&format!("{} is {}; waiting for it to finish...", by, what));
+                told = true;
+            }
+            Ok::<(), InstallerError>(())
+        })
+    }
+}
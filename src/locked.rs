@@ -0,0 +1,158 @@
+// `--locked-installer` (or `[install] locked = true`): install only what is
+// pinned exactly, for environments that must record precisely what they
+// run. Every version is given as `REF@COMMIT` with the full commit id, such
+// as `v0.2.0@3f9c...`, and the install fails unless the ref checks out to
+// exactly that commit, so a moved tag or a mirror serving something else is
+// caught. Names that follow upstream (`latest`, `stable`, `nightly`, a bare
+// branch or tag) are refused, cargo builds with `--locked` so dependencies
+// are the ones the commit's Cargo.lock names with their checksums,
+// prebuilt binaries are never downloaded from a remote cache, and `update`
+// is refused.
+//
+// `kipper info --provenance` prints the pins of what is installed in the
+// same form, with the Cargo.lock and rustc each build used, so
+//
+//     kipper info --provenance > pins.txt
+//     kipper --locked-installer install --from-file pins.txt
+//
+// reproduces the set elsewhere.
+
+use crate::options::describe_options;
+use crate::release::{LATEST, STABLE};
+use crate::state::{State, VersionRecord};
+use crate::toolchain::{self, NIGHTLY};
+use crate::{Installer, InstallerError};
+
+/// Whether `commit` is a full git object id (SHA-1 or SHA-256).
+fn is_full_commit(commit: &str) -> bool {
+    matches!(commit.len(), 40 | 64) && commit.chars().all(|c| c.is_ascii_hexdigit() && !c.is_ascii_uppercase())
+}
+
+/// `REF@COMMIT` split into its parts, when COMMIT is a full commit id and
+/// REF something that names a fixed point.
+fn parse_pin(spec: &str) -> Result<(&str, &str), String> {
+    let Some((name, commit)) = spec.rsplit_once('@') else {
+        return Err(format!(
+            "'{}' isn't pinned; with --locked-installer give versions as REF@COMMIT, e.g. v0.2.0@<full commit id>",
+            spec
+        ));
+    };
+    if [LATEST, STABLE, NIGHTLY].contains(&name) {
+        return Err(format!("'{}' follows upstream, so it can't be pinned; name the tag instead", name));
+    }
+    if name.is_empty() || !is_full_commit(commit) {
+        return Err(format!("'{}' needs a ref and the full commit id after @", spec));
+    }
+    Ok((name, commit))
+}
+
+/// The line `kipper info --provenance` prints for an installed version.
+pub fn pin_line(version: &str, record: &VersionRecord) -> String {
+    let mut details = Vec::new();
+    if let Some(lockfile) = &record.lockfile {
+        details.push(format!("Cargo.lock {}", lockfile));
+    }
+    if let Some(rustc) = &record.rustc {
+        details.push(format!("rustc {}", rustc));
+    }
+    if !record.options.is_empty() {
+        details.push(describe_options(&record.options));
+    }
+    if let Some(source) = &record.source {
+        details.push(format!("from {}", source));
+    }
+    if let Some(provenance) = &record.provenance {
+        details.push(provenance.to_string());
+    }
+    format!("{}@{}  # {}", version, record.commit, details.join(", "))
+}
+
+impl Installer {
+    pub fn with_locked(mut self, locked: bool) -> Self {
+        self.locked = locked || self.config.install.locked;
+        self
+    }
+
+    pub fn is_locked(&self) -> bool {
+        self.locked
+    }
+
+    /// The version `spec` pins, remembering its commit for `verify_pin`. A
+    /// name pinned earlier in this run may be given without its commit.
+    pub(crate) fn pin_version(&self, spec: &str) -> Result<String, InstallerError> {
+        let mut pins = self.pins.lock().unwrap_or_else(|e| e.into_inner());
+        if pins.contains_key(spec) {
+            return Ok(spec.to_string());
+        }
+        let (name, commit) = parse_pin(spec).map_err(InstallerError::PathError)?;
+        match pins.get(name) {
+            Some(pinned) if pinned != commit => Err(InstallerError::PathError(format!(
+                "{} is pinned to both {} and {}",
+                name, pinned, commit
+            ))),
+            _ => {
+                pins.insert(name.to_string(), commit.to_string());
+                Ok(name.to_string())
+            }
+        }
+    }
+
+    /// In locked mode, make sure the checkout of `version` is the pinned
+    /// commit and has a Cargo.lock to build from.
+    pub(crate) fn verify_pin(&self, version: &str) -> Result<(), InstallerError> {
+        if !self.locked {
+            return Ok(());
+        }
+        let pinned = self.pins.lock().unwrap_or_else(|e| e.into_inner()).get(version).cloned();
+        let Some(pinned) = pinned else {
+            return Err(InstallerError::PathError(format!("{} isn't pinned to a commit", version)));
+        };
+        let record = self.source_record()?;
+        if record.commit != pinned {
+            return Err(InstallerError::Git(format!(
+                "{} checks out to {}, not the pinned {}; the ref moved or the source serves something else",
+                version, record.commit, pinned
+            )));
+        }
+        if record.lockfile.is_none() {
+            return Err(InstallerError::Cargo(format!(
+                "kopi-lang has no Cargo.lock at {}, so its dependencies can't be pinned",
+                pinned
+            )));
+        }
+        self.log_success(&format!("{} is the pinned commit {}", version, pinned));
+        Ok(())
+    }
+
+    /// Refuse what can't be pinned in locked mode, such as `update`.
+    pub(crate) fn refuse_when_locked(&self, what: &str) -> Result<(), InstallerError> {
+        if self.locked {
+            return Err(InstallerError::PathError(format!(
+                "{} follows upstream, which --locked-installer doesn't allow; install a new pin instead",
+                what
+            )));
+        }
+        Ok(())
+    }
+
+    /// `kipper info --provenance`: a pin line for `version`, or for every
+    /// installed version in `kipper list` order.
+    pub fn pin_lines(&self, version: Option<&str>) -> Result<Vec<String>, InstallerError> {
+        let state = State::load(&self.install_dir);
+        let versions: Vec<String> = match version {
+            Some(version) => toolchain::check_version_name(version).map(|()| vec![version.to_string()])?,
+            None => self.list()?.into_iter().map(|toolchain| toolchain.name).collect(),
+        };
+        let mut lines = Vec::new();
+        for version in &versions {
+            match state.versions.get(version) {
+                Some(record) => lines.push(pin_line(version, record)),
+                None if self.version_dir(version).exists() => {
+                    lines.push(format!("# {}: no record of its commit (installed by an older kipper)", version))
+                }
+                None => return Err(self.not_installed(version)),
+            }
+        }
+        Ok(lines)
+    }
+}
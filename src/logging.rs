@@ -0,0 +1,299 @@
+// Logging: every message kipper shows is also appended to
+// `~/.kopi/logs/kipper.log`, so a failed unattended run can be looked into
+// afterwards. `--log-format json` renders each message as one JSON object per
+// line instead, on stderr and in the log file, for ELK or Loki to ingest:
+//
+//     {"timestamp":"2024-05-01T12:00:00Z","level":"info","phase":"build",
+//      "subsystem":"build","message":"Building Kopi...","fields":{"pid":4242}}
+//
+// `phase` is the install phase running at the time, or null, and `subsystem`
+// the part of kipper the message is from (see verbosity.rs for how
+// `KIPPER_LOG` filters by it). `fields` holds
+// structured details: always `pid`, plus e.g. `code` on errors kipper
+// recognises. Plain output lines (summaries, lists) are events of level
+// `output`, and the commands `--trace-commands` shows of level `trace`.
+//
+// The first time a run logs, kipper.log is rotated once it is larger than
+// `[logs] max-size`: it is compressed to kipper.log.1.gz, older rotations
+// move up a number, and those beyond `keep` or older than `max-age-days` are
+// removed. `kipper cache clean --logs` removes them all.
+
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::{Duration, SystemTime};
+
+use flate2::Compression;
+use flate2::write::GzEncoder;
+
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+use crate::cache::dir_size;
+use crate::redact::redact;
+use crate::timestamp::now_utc;
+use crate::timing::Phase;
+use crate::verbosity::phase_subsystem;
+use crate::{Installer, InstallerError, LogLevel};
+
+pub const LOG_DIR: &str = "logs";
+pub const LOG_FILE: &str = "kipper.log";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LogFormat {
+    /// The themed messages people read in a terminal.
+    #[default]
+    Text,
+    /// One JSON object per message.
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(LogFormat::Text),
+            "json" => Ok(LogFormat::Json),
+            _ => Err(format!("unknown log format '{}' (expected text or json)", s)),
+        }
+    }
+}
+
+impl fmt::Display for LogFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            LogFormat::Text => "text",
+            LogFormat::Json => "json",
+        })
+    }
+}
+
+/// Where kipper's logs live.
+pub fn log_dir(install_dir: &Path) -> PathBuf {
+    install_dir.join(LOG_DIR)
+}
+
+/// The `n`th rotation of the log in `dir`, 1 being the most recent.
+fn rotated_log(dir: &Path, n: usize) -> PathBuf {
+    dir.join(format!("{}.{}.gz", LOG_FILE, n))
+}
+
+/// The rotation number of a file in the log dir, if it is one.
+fn rotation_number(name: &str) -> Option<usize> {
+    name.strip_prefix(LOG_FILE)?.strip_prefix('.')?.strip_suffix(".gz")?.parse().ok()
+}
+
+/// `text` without the terminal color escapes the theme adds.
+fn strip_colors(text: &str) -> String {
+    let mut plain = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            // An SGR sequence: ESC [ parameters m
+            chars.by_ref().find(|c| *c == 'm');
+        } else {
+            plain.push(c);
+        }
+    }
+    plain
+}
+
+#[derive(Serialize)]
+struct LogRecord<'a> {
+    timestamp: String,
+    level: &'static str,
+    phase: Option<Phase>,
+    subsystem: &'static str,
+    message: &'a str,
+    fields: &'a Map<String, Value>,
+}
+
+/// The `level` of plain output lines.
+pub(crate) const OUTPUT: &str = "output";
+
+fn level_name(level: LogLevel) -> &'static str {
+    match level {
+        LogLevel::Debug => "debug",
+        LogLevel::Info => "info",
+        LogLevel::Success => "success",
+        LogLevel::Warning => "warning",
+        LogLevel::Error => "error",
+    }
+}
+
+impl Installer {
+    /// Log `msg` with structured `fields`, which only the JSON format and the
+    /// log file show.
+    pub fn log_event(&self, level: LogLevel, msg: &str, fields: Map<String, Value>) {
+        self.log_from(self.subsystem(), level, msg, fields);
+    }
+
+    /// Log a debug message from `subsystem`, shown only when `KIPPER_LOG`
+    /// asks for it.
+    pub(crate) fn log_debug(&self, subsystem: &'static str, msg: &str) {
+        self.log_from(subsystem, LogLevel::Debug, msg, Map::new());
+    }
+
+    /// Whether `KIPPER_LOG` shows debug messages from `subsystem`, for
+    /// skipping the work of putting them together.
+    pub(crate) fn debugging(&self, subsystem: &str) -> bool {
+        self.log_filter.allows(subsystem, LogLevel::Debug)
+    }
+
+    fn log_from(&self, subsystem: &'static str, level: LogLevel, msg: &str, mut fields: Map<String, Value>) {
+        if !self.log_filter.allows(subsystem, level) {
+            return;
+        }
+        let msg = &redact(msg);
+        fields.insert("pid".to_string(), std::process::id().into());
+        match self.log_format {
+            LogFormat::Text => eprintln!("{}", self.theme.message(level, msg)),
+            LogFormat::Json => eprintln!("{}", self.json_record(subsystem, level_name(level), msg, &fields)),
+        }
+        self.append_to_log(subsystem, level_name(level), msg, &fields);
+        // Frontends only hear about what the user would see
+        if level != LogLevel::Debug {
+            self.notify(level, msg);
+        }
+    }
+
+    /// The subsystem of the phase running now.
+    pub(crate) fn subsystem(&self) -> &'static str {
+        phase_subsystem(self.current_phase.lock().ok().and_then(|phase| *phase))
+    }
+
+    /// Show a line of plain output, such as part of a summary.
+    pub(crate) fn output_line(&self, line: &str) {
+        let line = &redact(line);
+        match self.log_format {
+            LogFormat::Text => eprintln!("{}", line),
+            // Blank lines only space out the terminal output
+            LogFormat::Json if line.trim().is_empty() => return,
+            LogFormat::Json => {
+                let mut fields = Map::new();
+                fields.insert("pid".to_string(), std::process::id().into());
+                eprintln!("{}", self.json_record(self.subsystem(), OUTPUT, line, &fields));
+            }
+        }
+        self.append_to_log(self.subsystem(), OUTPUT, line, &Map::new());
+    }
+
+    pub(crate) fn json_record(&self, subsystem: &'static str, level: &'static str, msg: &str, fields: &Map<String, Value>) -> String {
+        let record = LogRecord {
+            timestamp: now_utc(),
+            level,
+            phase: self.current_phase.lock().ok().and_then(|phase| *phase),
+            subsystem,
+            message: &strip_colors(msg),
+            fields,
+        };
+        // Fields come from all over, so the whole record is redacted
+        redact(&serde_json::to_string(&record).unwrap_or_default())
+    }
+
+    /// Append a message to the log file, in the chosen format. Nothing is
+    /// logged while the install dir doesn't exist, so an uninstall doesn't
+    /// leave a log behind; failing to log never fails a command.
+    pub(crate) fn append_to_log(
+        &self,
+        subsystem: &'static str,
+        level: &'static str,
+        msg: &str,
+        fields: &Map<String, Value>,
+    ) {
+        if !self.install_dir.exists() || (level == OUTPUT && msg.trim().is_empty()) {
+            return;
+        }
+        let line = match self.log_format {
+            LogFormat::Json => {
+                let mut fields = fields.clone();
+                fields.insert("pid".to_string(), std::process::id().into());
+                self.json_record(subsystem, level, msg, &fields)
+            }
+            LogFormat::Text => {
+                let mut line = format!("{} {:<7} ", now_utc(), level.to_uppercase());
+                if let Some(phase) = self.current_phase.lock().ok().and_then(|phase| *phase) {
+                    line.push_str(&format!("[{}] ", phase.name()));
+                }
+                // Continuation lines are indented so each event still starts a line
+                line.push_str(&strip_colors(msg.trim_end()).replace('\n', "\n    "));
+                for (key, value) in fields.iter().filter(|(key, _)| *key != "pid") {
+                    line.push_str(&format!(" {}={}", key, value));
+                }
+                format!("{} pid={}", line, std::process::id())
+            }
+        };
+        let line = redact(&line);
+        let dir = log_dir(&self.install_dir);
+        self.log_rotation.call_once(|| {
+            // Logging must never fail a command, rotation included
+            let _ = self.rotate_logs(&dir);
+        });
+        let _ = fs::create_dir_all(&dir).and_then(|()| {
+            let mut file = OpenOptions::new().create(true).append(true).open(dir.join(LOG_FILE))?;
+            writeln!(file, "{}", line)
+        });
+    }
+
+    /// Rotate kipper.log if it has outgrown `[logs] max-size`, then drop
+    /// rotations past `keep` or `max-age-days`.
+    fn rotate_logs(&self, dir: &Path) -> io::Result<()> {
+        let settings = &self.config.logs;
+        let current = dir.join(LOG_FILE);
+        if fs::metadata(&current).is_ok_and(|metadata| metadata.len() > settings.max_size_bytes()) {
+            let mut rotations: Vec<usize> = fs::read_dir(dir)?
+                .filter_map(|entry| rotation_number(&entry.ok()?.file_name().to_string_lossy()))
+                .collect();
+            rotations.sort_unstable_by(|a, b| b.cmp(a));
+            for n in rotations {
+                fs::rename(rotated_log(dir, n), rotated_log(dir, n + 1))?;
+            }
+            let mut encoder = GzEncoder::new(File::create(rotated_log(dir, 1))?, Compression::default());
+            io::copy(&mut File::open(&current)?, &mut encoder)?;
+            encoder.finish()?;
+            fs::remove_file(&current)?;
+        }
+
+        let max_age = Duration::from_secs(settings.max_age_days * 24 * 60 * 60);
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            let Some(n) = rotation_number(&entry.file_name().to_string_lossy()) else {
+                continue;
+            };
+            let expired = entry
+                .metadata()?
+                .modified()
+                .ok()
+                .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+                .is_some_and(|age| age > max_age);
+            if n > settings.keep || expired {
+                fs::remove_file(entry.path())?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Remove every log, returning how many files went and the bytes freed.
+    pub fn clean_logs(&self) -> Result<(usize, u64), InstallerError> {
+        let dir = log_dir(&self.install_dir);
+        if !dir.exists() {
+            return Ok((0, 0));
+        }
+        let mut removed = 0;
+        let mut freed = 0;
+        for entry in fs::read_dir(&dir)? {
+            let path = entry?.path();
+            freed += dir_size(&path)?;
+            if path.is_dir() {
+                fs::remove_dir_all(&path)?;
+            } else {
+                fs::remove_file(&path)?;
+            }
+            removed += 1;
+        }
+        Ok((removed, freed))
+    }
+}
@@ -1,381 +1,1275 @@
 // Kipper - The Kopi Language Installer
-// A git-based installer for Kopi written in Rust
+// Command-line entry point
 
+use std::collections::BTreeMap;
 use std::env;
 use std::fs;
-use std::io::{self, Write};
-use std::path::{Path, PathBuf};
-use std::process::{Command, Stdio};
+use std::io;
+use std::path::Path;
+use std::time::Duration;
+
+use kipper::advisory::AdvisoryCheck;
+use kipper::announce::ProgressMode;
+use kipper::cache::format_size;
+use kipper::capabilities::Capability;
+use kipper::completions::{Shell, completion_script};
+use kipper::config_edit::Scope;
+use kipper::hints::Outcome;
+use kipper::listing::{InstalledEntry, RemoteEntry};
+use kipper::logging::LogFormat;
+use kipper::sbom::SbomFormat;
+use kipper::provision::Provision;
+use kipper::redact::{Redacted, redact};
+use kipper::release_check::ReleaseReport;
+use kipper::signing::Verdict;
+use kipper::state::{Profile, State};
+use kipper::verbosity::{LOG_FILTER_ENV, LogFilter};
+use kipper::{Installer, LogLevel, auth, completions, explain, features, metrics, options, shim, suggest, timestamp};
+use kipper::toolchain::{NIGHTLY, VersionSource, exe_name};
+use kipper::update::UpdateOptions;
+use serde_json::json;
 
-const REPO_URL: &str = "https://github.com/kinoite/kopi-lang.git";
 const INSTALLER_NAME: &str = "kipper";
 
-#[derive(Debug)]
-enum InstallerError {
-    Io(io::Error),
-    Git(String),
-    Cargo(String),
-    PathError(String),
+fn show_help() {
+    println!("Kipper - The Kopi Language Installer");
+    println!();
+    println!("USAGE:");
+    println!("    {} [OPTIONS]", INSTALLER_NAME);
+    println!("    {} <COMMAND>", INSTALLER_NAME);
+    println!();
+    println!("COMMANDS:");
+    println!("    alias [list]               List command aliases");
+    println!("    alias add NAME [TOOL] [--force]");
+    println!("                               Add NAME as another command for TOOL (default kopi)");
+    println!("    alias remove NAME          Remove a command alias");
+    println!("    apply FILE                 Converge this machine to a provision file: versions,");
+    println!("                               default, profile and PATH (see PROVISIONING)");
+    println!("    auth [status] [--json]     Show which credentials are set, and where from");
+    println!("    auth login NAME            Store credential NAME in the OS keyring, read without");
+    println!("                               echo (remote-cache, github or proxy; see CREDENTIALS)");
+    println!("    auth logout NAME           Remove credential NAME from the keyring");
+    println!("    check                      Verify an install can succeed, without installing");
+    println!("    completions SHELL          Print the completion script for bash, zsh or fish");
+    println!("    completions --install      Install completions for each shell you use, where it");
+    println!("                               loads them by itself (uninstall removes them)");
+    println!("    doctor                     Diagnose the installation and its environment");
+    println!("    doctor --fix               Remove links kipper made that point at removed versions");
+    println!("    doctor --capabilities [--json]");
+    println!("                               Show what this machine can do (symlinks, colors, git,");
+    println!("                               cargo, network) and what kipper does because of it");
+    println!("    explain [CODE]             Describe an error code (e.g. E0006): causes and fixes;");
+    println!("                               without CODE, list the codes");
+    println!("    install [VERSION...] [--from-file FILE] [--json]");
+    println!("                               Install Kopi versions: tags, 'nightly', or 'latest'");
+    println!("                               and 'stable' for the newest release; - reads versions");
+    println!("                               from stdin and --from-file from FILE (one or more per");
+    println!("                               line, # for comments); --json prints each install's");
+    println!("                               summary as JSON");
+    println!("    list [--json]              List installed versions, oldest first; * marks the");
+    println!("                               default");
+    println!("    ls-remote [--json]         List kopi-lang's release tags, oldest first, marking");
+    println!("                               pre-releases, yanked and installed versions");
+    println!("    update [--yes] [--log] [--json]");
+    println!("                               Rebuild the default version from the latest source,");
+    println!("                               after showing what changed (--log lists commits)");
+    println!("    update --prepare [--yes] [--log]");
+    println!("                               Build the update now, but keep running the installed");
+    println!("                               build until 'update --commit' switches to it");
+    println!("    update --auto              For timers: update, but outside the [update] windows");
+    println!("                               in kipper.toml only prepare, and switch in a window");
+    println!("    optimize [VERSION]         Rebuild the version in use (or VERSION), e.g. a --quick");
+    println!("                               one, fully optimized from the same commit, or take a");
+    println!("                               cached prebuilt build, and swap it in once it runs");
+    println!("    warm [VERSION]             Fetch the default version's (or VERSION's) latest");
+    println!("                               source and build its dependencies, so the next update");
+    println!("                               only compiles kopi-lang; run it from cron, a systemd");
+    println!("                               timer or launchd ahead of updates");
+    println!("    release-check TAG [--json] For kopi-lang's maintainers: install TAG in a throwaway");
+    println!("                               dir, run it and uninstall it, reporting each step;");
+    println!("                               exits 1 if any failed");
+    println!("    size                       Show disk usage of versions, caches and logs");
+    println!("    config set KEY VALUE [--global | --local]");
+    println!("                               Change a setting, e.g. logs.keep, in ~/.kopi/kipper.toml");
+    println!("                               or (--local) the project's .kipper.toml");
+    println!("    state export [--json]      Print versions, default, PATH status, overrides and");
+    println!("                               settings as JSON, for configuration management");
+    println!("    info [VERSION]             Show a version's commit, signature status and components");
+    println!("    info --provenance [VERSION]");
+    println!("                               Print installed versions as REF@COMMIT pins with the");
+    println!("                               Cargo.lock and rustc they were built with, for");
+    println!("                               `--locked-installer install --from-file`");
+    println!("    use [VERSION]              Make an installed version the default; without VERSION,");
+    println!("                               pick one from a list");
+    println!("    verify [--json]            Check kipper's metadata (state, default, stamps and");
+    println!("                               manifests) against its signatures; exits 1 if anything");
+    println!("                               else changed it");
+    println!("    verify --enable            Sign that metadata from now on, with a new local key");
+    println!("    verify --disable           Stop signing, removing the key and signatures");
+    println!("    which [TOOL] [--verbose]   Print the path of kopi (or TOOL) for this directory,");
+    println!("                               with --verbose also its version, commit and signature");
+    println!("    toolchain-path [--json] [--ensure]");
+    println!("                               Print the interpreter path for the current project");
+    println!("    uninstall [VERSION] [--force] [--purge]");
+    println!("                               Remove one version, or everything without VERSION;");
+    println!("                               --purge also removes settings, caches and logs");
+    println!("    uninstall --all --remove-self");
+    println!("                               Remove everything, including kipper itself");
+    println!("    sbom [VERSION] [--format cyclonedx|spdx]");
+    println!("                               Print the crates built into a version as an SBOM");
+    println!("    exec [VERSION] -- CMD...   Run a command with a toolchain's environment");
+    println!("    test-matrix [VERSION...] -- <script.kopi | CMD...>");
+    println!("                               Run a script against installed versions, tabulated");
+    println!("    mirror [list]              List the sources kopi-lang can be fetched from");
+    println!("    mirror add|remove URL      Configure a kopi-lang mirror");
+    println!("    mirror test                Measure every source and pick the fastest");
+    println!("    mirror prefer URL          Use URL whenever it responds (e.g. a classroom mirror)");
+    println!("    mirror serve [--port N] [--bind ADDR]");
+    println!("                               Serve this machine's cache to the LAN as a mirror");
+    println!("                               (default port 8787)");
+    println!("    cache clean --temp         Remove temp dirs left by interrupted installs");
+    println!("    cache clean --logs         Remove kipper's logs, rotated ones included");
+    println!("    cache clean --build        Remove the build artifacts kept to speed up rebuilds");
+    println!("    cache verify               Check the cached kopi-lang source for corruption");
+    println!("    cache export DIR           Copy the source mirror, built binaries and build dir to");
+    println!("                               DIR, for CI caches such as actions/cache");
+    println!("    cache restore DIR          Fill in the cache from an export before installing");
+    println!("    serve [--port N]           Serve the local HTTP API (default port 7878)");
+    println!();
+    println!("OPTIONS:");
+    println!("    -h, --help        Show this help message");
+    println!("    --profile NAME    Components to install: minimal, default or full");
+    println!("                      (remembered for updates; minimal by default on CI)");
+    println!("    --audit           Check built dependencies against the RustSec advisory");
+    println!("                      database (needs cargo-audit) and warn about findings");
+    println!("    --deny-advisories Like --audit, but refuse to install on any finding");
+    println!("    --require-signed  Only build tags/commits with a valid signature from a key");
+    println!("                      your git trusts");
+    println!("    --force           Answer yes to every confirmation, and override refusals");
+    println!("                      such as removing the default version");
+    println!("    --with-NAME, --without-NAME");
+    println!("                      Turn a build option kopi-lang offers on or off, e.g.");
+    println!("                      --with-jit (remembered for updates)");
+    println!("    --inherit-env     Build with your RUSTFLAGS, CARGO_* and compiler wrappers;");
+    println!("                      by default they are removed so they can't alter the build");
+    println!("    --target TARGET   Build for a target triple; on Windows, msvc or gnu picks");
+    println!("                      the MSVC or MinGW toolchain");
+    println!("    --source SOURCE   Where to get kopi-lang's source: git (the default), github");
+    println!("                      (or github:OWNER/REPO) for a release tag's archive, the URL");
+    println!("                      of a .tar.gz (or .tgz, .tar.xz, .tar.bz2, .tar), or such an");
+    println!("                      archive or a directory on disk");
+    println!("    --builder NAME    How to build: cargo (the default), cargo-install, prebuilt");
+    println!("                      (only reuse a cached build) or container[:IMAGE] (build");
+    println!("                      in podman or docker, by default in the official Rust image)");
+    println!("    --fresh           Build from scratch, without reusing earlier build output");
+    println!("                      or cached binaries (when you suspect they are corrupt)");
+    println!("    --quick           Install the fastest build there is: a cached prebuilt one,");
+    println!("                      else an unoptimized build that compiles in far less time;");
+    println!("                      `kipper optimize` replaces it with an optimized one later");
+    println!("    --locked-installer");
+    println!("                      Install only versions pinned as REF@COMMIT (full commit id),");
+    println!("                      build with cargo --locked, skip the remote cache and");
+    println!("                      refuse latest, stable, nightly and update");
+    println!("    --include-prereleases");
+    println!("                      Let 'latest' pick release candidates and other");
+    println!("                      pre-releases ('stable' never does)");
+    println!("    --log-format FMT  Print messages as text (default) or json, one object per");
+    println!("                      line; ~/.kopi/logs/kipper.log is written the same way");
+    println!("    --progress-fd N   Also write progress events as JSON lines to file");
+    println!("                      descriptor N, for graphical frontends (Unix)");
+    println!("    --announce        Show progress as single lines at each phase and at 25, 50");
+    println!("                      and 75%, never redrawn, for screen readers (the default on");
+    println!("                      TERM=dumb and with ACCESSIBILITY_ENABLED=1)");
+    println!("    --unattended      For CI runners and build farms: answer yes, no colors,");
+    println!("                      long build errors cut short (the rest is in the log),");
+    println!("                      a progress line every minute, external commands killed");
+    println!("                      after an hour, and builds on half the CPUs");
+    println!("    --no-detect       Keep the interactive defaults on CI (GitHub Actions,");
+    println!("                      GitLab CI, CI set) and in Docker or Kubernetes without a");
+    println!("                      terminal, where kipper otherwise runs as with --unattended");
+    println!("                      and the minimal profile");
+    println!("    --timeout MIN     Kill any external command still running after MIN minutes");
+    println!("    --trace-commands  Show every command kipper runs, with its directory and");
+    println!("                      environment (secrets redacted), ready to paste into a");
+    println!("                      shell; traced commands are also logged");
+    println!("    --system          Install for all users (as root): versions in /opt/kopi,");
+    println!("                      shims in /usr/local/bin");
+    println!("    --allow-root      Run as root for root itself, as in containers; without it");
+    println!("                      or --system, kipper refuses to change anything as root");
+    println!("    -u, --uninstall   Uninstall Kopi (add --purge to remove settings too)");
+    println!("    -v, --version     Show version information");
+    println!();
+    println!("EXAMPLES:");
+    println!("    {}              Install Kopi", INSTALLER_NAME);
+    println!("    {} --uninstall  Uninstall Kopi", INSTALLER_NAME);
+    println!();
+    println!("ENVIRONMENT:");
+    println!("    CLICOLOR_FORCE    Set to 1 for colors even when stderr isn't a terminal");
+    println!("    GITHUB_TOKEN      Token for downloads from GitHub (see CREDENTIALS)");
+    println!("    KIPPER_ALLOW_ROOT Set to 1 to run as root like --allow-root");
+    println!("    KIPPER_CACHE_DIR  Where the source cache lives (default ~/.kopi/cache)");
+    println!("    KIPPER_CACHE_MAX  Evict least recently used cache entries after installs");
+    println!("                      once the cache is larger than this, e.g. 10G");
+    println!("    KIPPER_LOG        Which messages to show and log: a level (debug, info,");
+    println!("                      warning, error or off) and/or SUBSYSTEM=LEVEL pairs for");
+    println!("                      kipper, git, toolchain, build and install, e.g.");
+    println!("                      git=debug,build=warning (debug shows commands run)");
+    println!("    KIPPER_METRICS_FILE");
+    println!("                      Write Prometheus metrics here after installs, updates");
+    println!("                      and uninstalls (overrides [metrics] textfile)");
+    println!("    KIPPER_PROXY_AUTH USER:PASSWORD for https_proxy and http_proxy (see CREDENTIALS)");
+    println!("    KIPPER_REMOTE_CACHE_TOKEN");
+    println!("                      Bearer token for the [cache] remote (see CREDENTIALS)");
+    println!("    KIPPER_SIZE_WARN  Total size above which `size` suggests cleaning up (5G)");
+    println!("    KIPPER_STAMP      Set to 1 to have kopi and its tools print the version,");
+    println!("                      commit and signature they run from on stderr");
+    println!("    NO_COLOR          Print messages without colors");
+    println!();
+    println!("CONFIGURATION:");
+    println!("    ~/.kopi/kipper.toml holds your settings. [theme] sets how messages look:");
+    println!("        [theme]");
+    println!("        name = \"high-contrast\"   # default, boring or high-contrast");
+    println!("        banner = \"bold-blue\"     # also tagline and highlight");
+    println!("        [theme.warning]");
+    println!("        prefix = \"!!\"");
+    println!("        color = \"magenta\"        # a terminal color, bold, bold-<color> or none");
+    println!("    [theme] progress = \"announce\" shows progress as with --announce every time,");
+    println!("    and \"standard\" keeps the usual output even on dumb terminals");
+    println!("    [confirm] sets which actions ask first (reinstall, modify-path and purge do,");
+    println!("    uninstall doesn't). Without a terminal they are skipped unless --force is given:");
+    println!("        [confirm]");
+    println!("        uninstall = true");
+    println!("    [bin] sets how kopi and its tools are put on PATH, and what happens to a file");
+    println!("    kipper didn't make that is already where one goes:");
+    println!("        [bin]");
+    println!("        strategy = \"symlink\"     # symlink, copy, shim (a script) or none");
+    println!("        conflict = \"overwrite\"   # fail, overwrite or backup");
+    println!("    [build] keeps variables in the build environment and can isolate cargo from");
+    println!("    ~/.cargo/config.toml:");
+    println!("        [build]");
+    println!("        keep-env = [\"RUSTC_WRAPPER\", \"CARGO_PROFILE_RELEASE_*\"]");
+    println!("        isolated-cargo-home = true       # use ~/.kopi/cargo as CARGO_HOME");
+    println!("    [install] locked = true installs as with --locked-installer every time:");
+    println!("        [install]");
+    println!("        locked = true");
+    println!("    [update] sets the local times `update --auto` may switch versions in; outside");
+    println!("    them it only prepares the update:");
+    println!("        [update]");
+    println!("        windows = [\"03:00-05:00\", \"Sat,Sun 10:00-18:00\", \"Mon-Fri 22:00-02:00\"]");
+    println!("    [metrics] writes how installs, updates and uninstalls went, phase by phase,");
+    println!("    for node_exporter's textfile collector:");
+    println!("        [metrics]");
+    println!("        textfile = \"/var/lib/node_exporter/textfile_collector/kipper.prom\"");
+    println!("    [cache] shares built binaries through an HTTP store (GET and PUT), so each");
+    println!("    commit is compiled once per target instead of once per machine:");
+    println!("        [cache]");
+    println!("        remote = \"https://artifacts.example.com/kipper\"");
+    println!("        upload = true                    # also upload builds made here");
+    println!("        attested-by = \"example/kopi-ci\"  # only builds attested by its workflows (via gh)");
+    println!("    [logs] limits ~/.kopi/logs; kipper.log is compressed and rotated when too big:");
+    println!("        [logs]");
+    println!("        max-size = \"10M\"                 # the defaults");
+    println!("        keep = 5");
+    println!("        max-age-days = 30");
+    println!();
+    println!("CREDENTIALS:");
+    println!("    `auth login NAME` stores a credential in the OS keyring (secret-tool on Linux");
+    println!("    and the BSDs, the keychain on macOS, the Credential Manager on Windows); none");
+    println!("    is ever read from kipper.toml. Each is sent only with the requests it is for,");
+    println!("    and its environment variable, when set, is used instead:");
+    println!("        remote-cache    KIPPER_REMOTE_CACHE_TOKEN   the [cache] remote");
+    println!("        github          GITHUB_TOKEN                --source github:...");
+    println!("        proxy           KIPPER_PROXY_AUTH           kipper's own downloads");
+    println!("        printf %s \"$TOKEN\" | kipper auth login remote-cache");
+    println!();
+    println!("PROVISIONING:");
+    println!("    `apply` installs what is missing and changes only what differs, so it can run");
+    println!("    on every MDM or Ansible check-in:");
+    println!("        versions = [\"v0.2.0\", \"stable\"]");
+    println!("        default = \"v0.2.0\"         # defaults to the first version");
+    println!("        profile = \"minimal\"        # rebuilds installed versions if it changes");
+    println!("        remove-unlisted = true     # uninstall versions not listed");
+    println!("        path = \"add\"               # add the bin dir to the shell startup file,");
+    println!("                                   # or \"leave\" PATH alone (the default)");
+    println!();
+    println!("Status messages are written to stderr; stdout only carries command output.");
 }
 
-impl From<io::Error> for InstallerError {
-    fn from(error: io::Error) -> Self {
-        InstallerError::Io(error)
+/// `kipper toolchain-path`: tell editor plugins where the tools for the current project live.
+///
+/// Stdout carries only the answer: the interpreter path, or with `--json` a
+/// single-line object (`schema` 1) with `version`, `source`, `pin_file`,
+/// `interpreter`, `language_server` and `installed`. Status messages, e.g.
+/// from `--ensure` installing the missing version, go to stderr. Returns
+/// whether the resolved toolchain is installed; the caller exits 1 if not.
+fn print_toolchain_path(installer: &Installer, json: bool, ensure: bool) -> Result<bool, kipper::InstallerError> {
+    let cwd = env::current_dir()?;
+    let mut paths = installer.toolchain_paths(&cwd);
+
+    if ensure {
+        let missing = match &paths {
+            Some(paths) if !paths.installed => Some(paths.version.name.clone()),
+            Some(_) => None,
+            None => Some(NIGHTLY.to_string()),
+        };
+        if let Some(version) = missing {
+            installer.install_version(&version)?;
+            paths = installer.toolchain_paths(&cwd);
+        }
     }
-}
 
-struct Installer {
-    install_dir: PathBuf,
-    bin_dir: PathBuf,
-    temp_dir: PathBuf,
-}
+    let installed = paths.as_ref().is_some_and(|p| p.installed);
 
-impl Installer {
-    fn new() -> Result<Self, InstallerError> {
-        let home = env::var("HOME")
-            .or_else(|_| env::var("USERPROFILE"))
-            .map_err(|_| InstallerError::PathError("Could not determine home directory".to_string()))?;
-        
-        let install_dir = Path::new(&home).join(".kopi");
-        let bin_dir = if cfg!(windows) {
-            install_dir.clone()
-        } else {
-            Path::new(&home).join(".local").join("bin")
+    if json {
+        let body = match &paths {
+            Some(p) => json!({
+                "schema": 1,
+                "version": p.version.name,
+                "source": p.version.source.describe(),
+                "pin_file": match &p.version.source {
+                    VersionSource::Project(file) => Some(file.display().to_string()),
+                    _ => None,
+                },
+                "interpreter": p.interpreter.display().to_string(),
+                "language_server": p.language_server.as_ref().map(|l| l.display().to_string()),
+                "installed": p.installed,
+            }),
+            None => json!({
+                "schema": 1,
+                "version": null,
+                "source": null,
+                "pin_file": null,
+                "interpreter": null,
+                "language_server": null,
+                "installed": false,
+            }),
         };
-        
-        let temp_dir = env::temp_dir().join(format!("kopi-install-{}", std::process::id()));
-
-        Ok(Installer {
-            install_dir,
-            bin_dir,
-            temp_dir,
-        })
+        println!("{}", body);
+    } else {
+        match &paths {
+            Some(p) if p.installed => println!("{}", p.interpreter.display()),
+            Some(p) => eprintln!("Kopi {} is not installed (run `{} install {}`)", p.version.name, INSTALLER_NAME, p.version.name),
+            None => eprintln!("No Kopi version selected (run `{}` to install one)", INSTALLER_NAME),
+        }
     }
 
-    fn print_banner(&self) {
-        println!("\x1b[34mKipper - The Kopi Language Installer\x1b[0m");
-        println!("\x1b[33mFast, modern, and lightweight scripting language\x1b[0m");
-        println!();
+    Ok(installed)
+}
+
+/// `kipper info`: what an installed version is and where it came from.
+fn print_info(installer: &Installer, version: Option<&str>) -> Result<(), kipper::InstallerError> {
+    let version = match version {
+        Some(version) => kipper::toolchain::check_version_name(version).map(|()| version.to_string())?,
+        None => match installer.toolchain_paths(&env::current_dir()?) {
+            Some(paths) => paths.version.name,
+            None => return Err(kipper::InstallerError::PathError("No Kopi version selected".to_string())),
+        },
+    };
+    let version_dir = installer.version_dir(&version);
+    if !version_dir.exists() {
+        return Err(installer.not_installed(&version));
     }
 
-    fn log_info(&self, msg: &str) {
-        println!("\x1b[34m[INFO]\x1b[0m {}", msg);
+    let state = State::load(installer.install_dir());
+    let record = state.versions.get(&version);
+    let is_default = installer.default_version().as_deref() == Some(version.as_str());
+    let components: Vec<String> = installer.tools().into_iter().filter(|tool| version_dir.join(exe_name(tool)).exists()).collect();
+
+    println!("Version:     {}{}", version, if is_default { " (default)" } else { "" });
+    println!("Kopi:        {}", record.and_then(|r| r.kopi_version.as_deref()).unwrap_or("unknown"));
+    println!("Commit:      {}", record.map_or("unknown", |r| r.commit.as_str()));
+    match record.and_then(|r| r.provenance.as_ref()) {
+        Some(provenance) => println!("Provenance:  {}", provenance),
+        None => println!("Provenance:  not recorded (installed by an older kipper)"),
+    }
+    if let Some(prebuilt) = record.and_then(|r| r.prebuilt.as_ref()) {
+        println!("Binaries:    {}", prebuilt);
     }
+    println!("Components:  {}", components.join(", "));
+    if let Some(chosen) = record.map(|r| &r.options).filter(|o| !o.is_empty()) {
+        println!("Options:     {}", options::describe_options(chosen));
+    }
+    if let Some(installed_at) = record.and_then(|r| r.installed_at.as_deref()) {
+        println!("Installed:   {}", timestamp::display_local(installed_at));
+    }
+    println!("Path:        {}", version_dir.display());
+    Ok(())
+}
 
-    fn log_success(&self, msg: &str) {
-        println!("\x1b[32m[YAY!]\x1b[0m {}", msg);
+/// `kipper --version`, naming any optional features left out of the build.
+fn print_version() {
+    let missing = features::missing();
+    let flavor = if missing.is_empty() { String::new() } else { format!(" (without {})", missing.join(", ")) };
+    println!("Kipper v{}{} - The Kopi Language Installer", kipper::upstream::KIPPER_VERSION, flavor);
+}
+
+/// `kipper which`: the binary `tool` runs here and, with `--verbose`, the
+/// stamp of the version it belongs to.
+fn print_which(installer: &Installer, tool: &str, verbose: bool) -> Result<(), kipper::InstallerError> {
+    let path = installer.which(tool, &env::current_dir()?)?;
+    println!("{}", path.display());
+    if !verbose {
+        return Ok(());
+    }
+    let version = path.parent().and_then(Path::file_name).map(|name| name.to_string_lossy().into_owned());
+    match version.and_then(|version| installer.stamp(&version)) {
+        Some(stamp) => stamp.lines().iter().for_each(|line| println!("{}", line)),
+        None => println!("No record of where this version came from (installed by an older kipper)"),
     }
+    Ok(())
+}
 
-    fn log_warning(&self, msg: &str) {
-        println!("\x1b[33m[WARN]\x1b[0m {}", msg);
+/// `kipper list`: installed versions, the default marked with `*`.
+fn print_installed(entries: &[InstalledEntry], json: bool) {
+    if json {
+        println!("{}", serde_json::to_string_pretty(entries).unwrap_or_default());
+        return;
+    }
+    for entry in entries {
+        let notes: Vec<&str> = [(entry.default, "default"), (entry.unoptimized, "unoptimized")]
+            .into_iter()
+            .filter_map(|(set, note)| set.then_some(note))
+            .collect();
+        let marker = if entry.default { '*' } else { ' ' };
+        if notes.is_empty() {
+            println!("{} {}", marker, entry.name);
+        } else {
+            println!("{} {} ({})", marker, entry.name, notes.join(", "));
+        }
     }
+}
 
-    fn log_error(&self, msg: &str) {
-        println!("\x1b[31m[ERR]\x1b[0m {}", msg);
+/// `kipper doctor --capabilities`: one row per capability.
+fn print_capabilities(matrix: &[Capability], json: bool) {
+    if json {
+        println!("{}", serde_json::to_string_pretty(matrix).unwrap_or_default());
+        return;
+    }
+    let width = matrix.iter().map(|row| row.detail.len()).max().unwrap_or(0);
+    println!("{:<10} {:<9} {:<width$}  SO", "CAPABILITY", "AVAILABLE", "FOUND", width = width);
+    for row in matrix {
+        let available = if row.available { "yes" } else { "no" };
+        println!("{:<10} {:<9} {:<width$}  {}", row.name, available, row.detail, row.effect, width = width);
     }
+}
 
-    fn check_dependencies(&self) -> Result<(), InstallerError> {
-        self.log_info("Checking dependencies...");
+/// `kipper release-check`: each step with how long it took and what it found.
+fn print_release_report(report: &ReleaseReport, json: bool) {
+    if json {
+        println!("{}", serde_json::to_string_pretty(report).unwrap_or_default());
+        return;
+    }
+    println!(
+        "Release check of {} with kipper {} on {} {}{}",
+        report.tag,
+        report.kipper,
+        report.os,
+        report.arch,
+        report.target.as_ref().map_or(String::new(), |target| format!(" ({})", target))
+    );
+    let width = report.steps.iter().map(|step| step.name.len()).max().unwrap_or(0);
+    for step in &report.steps {
+        let mark = if step.passed { "✔" } else { "✘" };
+        println!("  {} {:<width$}  {:>7.2}s  {}", mark, step.name, step.seconds, step.detail, width = width);
+    }
+    println!("{} in {:.1}s", if report.passed { "Passed" } else { "Failed" }, report.seconds);
+}
 
-        if !self.command_exists("git") {
-            self.log_error("git is required but not installed");
-            self.log_info("Please install git and try again");
-            return Err(InstallerError::Git("git not found".to_string()));
+/// `kipper ls-remote`: release tags, with what sets each apart.
+fn print_remote(entries: &[RemoteEntry], json: bool) {
+    if json {
+        println!("{}", serde_json::to_string_pretty(entries).unwrap_or_default());
+        return;
+    }
+    let width = entries.iter().map(|entry| entry.name.len()).max().unwrap_or(0);
+    for entry in entries {
+        let mut notes = Vec::new();
+        if entry.prerelease {
+            notes.push("pre-release");
         }
-        
-        if !self.command_exists("cargo") {
-            self.log_error("Rust/Cargo is required but not installed");
-            self.log_info("Please install Rust from https://rustup.rs/ and try again");
-            return Err(InstallerError::Cargo("cargo not found".to_string()));
+        if entry.yanked {
+            notes.push("yanked");
         }
-
-        self.log_success("All dependencies found");
-        Ok(())
+        if entry.installed {
+            notes.push("installed");
+        }
+        println!("{}", format!("{:width$}  {}", entry.name, notes.join(", "), width = width).trim_end());
     }
+}
 
-    fn command_exists(&self, cmd: &str) -> bool {
-        Command::new(cmd)
-            .arg("--version")
-            .stdout(Stdio::null())
-            .stderr(Stdio::null())
-            .status()
-            .map(|status| status.success())
-            .unwrap_or(false)
-    }
+/// `kipper explain`: the long description of an error code, or the list of codes.
+fn print_explanation(code: Option<&str>) -> Result<(), kipper::InstallerError> {
+    let Some(code) = code else {
+        for explanation in explain::EXPLANATIONS {
+            println!("{}  {}", explanation.code, explanation.title);
+        }
+        return Ok(());
+    };
+    let explanation = explain::explanation(code).ok_or_else(|| {
+        kipper::InstallerError::PathError(format!("Unknown error code '{}' (run `{} explain` for the list)", code, INSTALLER_NAME))
+    })?;
 
-    fn create_directories(&self) -> Result<(), InstallerError> {
-        self.log_info("Creating installation directories...");
-        fs::create_dir_all(&self.install_dir)?;
-        fs::create_dir_all(&self.bin_dir)?;
-        fs::create_dir_all(&self.temp_dir)?;
-        Ok(())
+    println!("{}: {}", explanation.code, explanation.title);
+    println!();
+    println!("{}", explanation.description);
+    println!();
+    println!("Common causes:");
+    for cause in explanation.causes {
+        println!("  - {}", cause);
     }
+    println!();
+    println!("How to fix it:");
+    for fix in explanation.fixes {
+        println!("  - {}", fix);
+    }
+    Ok(())
+}
 
-    fn download_and_build(&self) -> Result<(), InstallerError> {
-        self.log_info("Downloading Kopi source code...");
-        
-        let clone_dir = self.temp_dir.join("kopi-lang");
-        
-        let output = Command::new("git")
-            .args(&["clone", REPO_URL])
-            .arg(&clone_dir)
-            .output()?;
-
-        if !output.status.success() {
-            let error = String::from_utf8_lossy(&output.stderr);
-            return Err(InstallerError::Git(format!("Failed to clone repository: {}", error)));
-        }
+/// Remove the flag `name` from `args`, returning whether it was there.
+fn take_flag(args: &mut Vec<String>, name: &str) -> bool {
+    let before = args.len();
+    args.retain(|a| a != name);
+    args.len() != before
+}
 
-        self.log_info("Building Kopi (this may take a few minutes)...");
-        
-        let build_output = Command::new("cargo")
-            .args(&["build", "--release"])
-            .current_dir(&clone_dir)
-            .output()?;
+/// Whether the command may change the install, so that running it as root
+/// needs `--system` or `--allow-root`.
+fn changes_install(args: &[String]) -> bool {
+    let has = |flag: &str| args.iter().any(|a| a == flag);
+    match args.get(1).map(String::as_str) {
+        Some(
+            "-h" | "--help" | "-v" | "--version" | "auth" | "check" | "explain" | "info" | "list"
+            | "ls-remote" | "release-check" | "sbom" | "size" | "state" | "which",
+        ) => false,
+        Some("completions") => has("--install"),
+        Some("verify") => has("--enable") || has("--disable"),
+        Some("doctor") => has("--fix"),
+        Some("toolchain-path") => has("--ensure"),
+        Some("alias" | "mirror") => !matches!(args.get(2).map(String::as_str), None | Some("list")),
+        Some("config") => args.get(2).map(String::as_str) == Some("set"),
+        _ => true,
+    }
+}
 
-        if !build_output.status.success() {
-            let error = String::from_utf8_lossy(&build_output.stderr);
-            return Err(InstallerError::Cargo(format!("Build failed: {}", error)));
-        }
+/// Whether the command in `args` holds the install lock while it runs: any
+/// that changes the install, except those that run programs or serve for
+/// as long as they are left running (the daemon locks per install).
+fn holds_install_lock(args: &[String]) -> bool {
+    let command = args.get(1).map(String::as_str);
+    let serving = command == Some("mirror") && args.get(2).map(String::as_str) == Some("serve");
+    changes_install(args) && !serving && !matches!(command, Some("exec" | "serve" | "test-matrix"))
+}
 
-        let binary_name = if cfg!(windows) { "kopi.exe" } else { "kopi" };
-        let binary_path = clone_dir.join("target").join("release").join(binary_name);
-        
-        if !binary_path.exists() {
-            return Err(InstallerError::Cargo("Built binary not found".to_string()));
+/// The versions `install` was given: its arguments, with `-` standing for
+/// those read from stdin, and those in `--from-file`.
+fn listed_versions(operands: &[String], file: Option<&str>) -> Result<Vec<String>, kipper::InstallerError> {
+    let mut versions = Vec::new();
+    for operand in operands {
+        if operand == "-" {
+            versions.extend(parse_version_list(&io::read_to_string(io::stdin())?, "stdin")?);
+        } else {
+            versions.push(operand.clone());
         }
-
-        self.log_success("Build completed successfully");
-        Ok(())
     }
+    if let Some(file) = file {
+        let contents = fs::read_to_string(file)
+            .map_err(|e| kipper::InstallerError::PathError(format!("Can't read {}: {}", file, e)))?;
+        versions.extend(parse_version_list(&contents, file)?);
+    }
+    Ok(versions)
+}
 
-    fn install_binary(&self) -> Result<(), InstallerError> {
-        self.log_info("Installing Kopi binary...");
-        
-        let binary_name = if cfg!(windows) { "kopi.exe" } else { "kopi" };
-        let source_path = self.temp_dir.join("kopi-lang").join("target").join("release").join(binary_name);
-        let dest_path = self.install_dir.join(binary_name);
-        
-        fs::copy(&source_path, &dest_path)?;
-        
-        // On Unix-like systems, create a symlink in bin directory
-        #[cfg(unix)]
-        {
-            let bin_path = self.bin_dir.join("kopi");
-            if bin_path.exists() {
-                fs::remove_file(&bin_path)?;
+/// Versions separated by whitespace or newlines, `#` starting a comment.
+fn parse_version_list(text: &str, source: &str) -> Result<Vec<String>, kipper::InstallerError> {
+    let mut versions = Vec::new();
+    for line in text.lines() {
+        for version in line.split('#').next().unwrap_or_default().split_whitespace() {
+            // Names reach git as arguments; one that looks like an option is a mistake or worse
+            if version.starts_with('-') {
+                return Err(kipper::InstallerError::PathError(format!("'{}' in {} is not a version", version, source)));
             }
-            std::os::unix::fs::symlink(&dest_path, &bin_path)?;
+            versions.push(version.to_string());
         }
+    }
+    Ok(versions)
+}
 
-        // On Windows, copy to a directory that might be in PATH
-        #[cfg(windows)]
-        {
-            // Try to add to PATH or inform user
-            self.update_windows_path()?;
+/// With `--json`, print the end-of-install summary of each version installed,
+/// one JSON object per line.
+fn print_summaries(installer: &Installer, json: bool) {
+    if json {
+        for summary in installer.summaries() {
+            println!("{}", json!(summary));
         }
-
-        self.log_success(&format!("Kopi binary installed to {}", dest_path.display()));
-        Ok(())
     }
+}
 
-    #[cfg(windows)]
-    fn update_windows_path(&self) -> Result<(), InstallerError> {
-        self.log_info("Note: You may need to add the installation directory to your PATH");
-        self.log_info(&format!("Installation directory: {}", self.install_dir.display()));
-        Ok(())
+/// Remove `--name VALUE` from `args`, returning the value.
+fn take_option(args: &mut Vec<String>, name: &str) -> Option<String> {
+    let i = args.iter().position(|a| a == name)?;
+    if i + 1 >= args.len() {
+        eprintln!("{} requires a value", name);
+        std::process::exit(1);
     }
+    args.remove(i);
+    Some(args.remove(i))
+}
 
-    fn create_uninstaller(&self) -> Result<(), InstallerError> {
-        self.log_info("Creating uninstaller...");
-        
-        let home_dir = env::var("HOME").unwrap_or_else(|_| ".".to_string());
-        
-        let uninstall_script = if cfg!(windows) {
-            format!("@echo off\necho Uninstalling Kopi Language...\ndel /f /q \"{}\\kopi.exe\" 2>nul\nrmdir /s /q \"{}\" 2>nul\necho Kopi has been uninstalled successfully\npause", 
-                self.install_dir.display(), self.install_dir.display())
-        } else {
-            format!("#!/bin/bash\necho \"Uninstalling Kopi Language...\"\nrm -f \"{}/kopi\"\nrm -f \"{}/.local/bin/kopi\"\nrm -rf \"{}\"\necho \"Kopi has been uninstalled successfully\"", 
-                self.install_dir.display(), home_dir, self.install_dir.display())
+fn main() {
+    // Invoked through a shim: behave as that tool and nothing else
+    let argv0 = env::args_os().next().and_then(|a| a.into_string().ok()).unwrap_or_default();
+    if let Ok(installer) = Installer::for_shim()
+        && let Some(tool) = shim::invoked_as(&installer, &argv0)
+    {
+        let tool_args = env::args_os().skip(1).collect();
+        let code = match shim::dispatch(&installer, &tool, tool_args) {
+            Ok(code) => code,
+            Err(e) => {
+                eprintln!("{}: {:?}", tool, e);
+                1
+            }
         };
+        std::process::exit(code);
+    }
 
-        let uninstall_path = if cfg!(windows) {
-            self.install_dir.join("uninstall.bat")
-        } else {
-            self.install_dir.join("uninstall.sh")
-        };
+    let mut args: Vec<String> = env::args().collect();
+    // Answered before the settings are read, so scripts can call it cheaply
+    if args.len() == 2 && matches!(args[1].as_str(), "-v" | "--version") {
+        print_version();
+        return;
+    }
+    // What follows `--` is the command of `exec` and `test-matrix`: kipper's
+    // own options are taken from the arguments before it only
+    let passthrough = args.iter().position(|a| a == "--").map_or_else(Vec::new, |i| args.split_off(i));
 
-        fs::write(&uninstall_path, uninstall_script)?;
+    let system = take_flag(&mut args, "--system");
+    if system && cfg!(windows) {
+        eprintln!("--system is only for Unix; on Windows, run kipper as the user it is for");
+        std::process::exit(1);
+    }
+    let allow_root = take_flag(&mut args, "--allow-root");
+    // A release check installs into a throwaway dir, never over this machine's install
+    let release_check_root = (args.get(1).map(String::as_str) == Some("release-check"))
+        .then(|| kipper::platform::scratch_root().join(format!("kipper-release-check-{}", std::process::id())));
+    let installer = match &release_check_root {
+        Some(root) => Ok(Installer::scratch(root)),
+        None if system => Installer::new_system(),
+        None => Installer::new(),
+    };
+    let mut installer = match installer {
+        Ok(installer) => installer,
+        Err(e) => {
+            eprintln!("Failed to initialize installer: {:?}", Redacted(e));
+            std::process::exit(1);
+        }
+    };
 
-        #[cfg(unix)]
-        {
-            use std::os::unix::fs::PermissionsExt;
-            let mut perms = fs::metadata(&uninstall_path)?.permissions();
-            perms.set_mode(0o755);
-            fs::set_permissions(&uninstall_path, perms)?;
-        }
-
-        self.log_success(&format!("Uninstaller created at {}", uninstall_path.display()));
-        Ok(())
-    }
-
-    fn cleanup(&self) -> Result<(), InstallerError> {
-        if self.temp_dir.exists() {
-            self.log_info("Cleaning up temporary files...");
-            fs::remove_dir_all(&self.temp_dir)?;
-        }
-        Ok(())
-    }
-
-    fn verify_installation(&self) -> Result<(), InstallerError> {
-        self.log_info("Verifying installation...");
-        
-        let binary_name = if cfg!(windows) { "kopi.exe" } else { "kopi" };
-        let binary_path = self.install_dir.join(binary_name);
-        
-        if binary_path.exists() {
-            self.log_success("Kopi installed successfully!");
-            println!();
-            
-            if self.command_exists("kopi") {
-                self.log_info("Kopi is ready to use:");
-                println!("  \x1b[32mkopi --help\x1b[0m");
-                println!("  \x1b[32mkopi your_script.kopi\x1b[0m");
-            } else {
-                self.log_warning("Kopi installed but may not be in PATH yet");
-                println!("  \x1b[32m{} --help\x1b[0m", binary_path.display());
-                println!("  \x1b[32m{} your_script.kopi\x1b[0m", binary_path.display());
+    if let Some(profile) = take_option(&mut args, "--profile") {
+        match profile.parse::<Profile>() {
+            Ok(profile) => installer = installer.with_profile(profile),
+            Err(e) => {
+                eprintln!("--profile: {}", e);
+                std::process::exit(1);
             }
-            
-            println!();
-            self.log_info("To uninstall Kopi later, run the uninstaller:");
-            let uninstall_name = if cfg!(windows) { "uninstall.bat" } else { "uninstall.sh" };
-            println!("  \x1b[32m{}\x1b[0m", self.install_dir.join(uninstall_name).display());
-            
-            Ok(())
-        } else {
-            Err(InstallerError::PathError("Installation verification failed".to_string()))
         }
     }
 
-    fn uninstall(&self) -> Result<(), InstallerError> {
-        self.log_info("Uninstalling Kopi...");
-        
-        let binary_name = if cfg!(windows) { "kopi.exe" } else { "kopi" };
-        let binary_path = self.install_dir.join(binary_name);
-        
-        if binary_path.exists() {
-            fs::remove_file(&binary_path)?;
+    // A provision file's profile has to be set before the installer is borrowed below
+    let provision = match args.get(1).map(String::as_str) {
+        Some("apply") if args.len() == 3 => {
+            let provision = Provision::load(Path::new(&args[2]));
+            if let Ok(Provision { profile: Some(profile), .. }) = &provision {
+                installer = installer.with_profile(*profile);
+            }
+            Some(provision)
         }
+        _ => None,
+    };
 
-        #[cfg(unix)]
-        {
-            let bin_path = self.bin_dir.join("kopi");
-            if bin_path.exists() {
-                fs::remove_file(&bin_path)?;
+    if let Ok(spec) = env::var(LOG_FILTER_ENV)
+        && let Err(e) = spec.parse::<LogFilter>()
+    {
+        eprintln!("{}: {}", LOG_FILTER_ENV, e);
+        std::process::exit(1);
+    }
+    if let Some(format) = take_option(&mut args, "--log-format") {
+        match format.parse::<LogFormat>() {
+            Ok(format) => installer = installer.with_log_format(format),
+            Err(e) => {
+                eprintln!("--log-format: {}", e);
+                std::process::exit(1);
             }
         }
+    }
 
-        if self.install_dir.exists() {
-            fs::remove_dir_all(&self.install_dir)?;
+    if let Some(fd) = take_option(&mut args, "--progress-fd") {
+        match fd.parse().map_err(|_| format!("'{}' is not a file descriptor", fd)).and_then(|fd| {
+            kipper::progress::progress_fd_observer(fd).map_err(|e| e.to_string())
+        }) {
+            Ok(observer) => installer = installer.with_observer(observer),
+            Err(e) => {
+                eprintln!("--progress-fd: {}", e);
+                std::process::exit(1);
+            }
         }
-
-        self.log_success("Kopi has been uninstalled successfully");
-        Ok(())
     }
 
-    fn install(&self) -> Result<(), InstallerError> {
-        self.print_banner();
+    let force = take_flag(&mut args, "--force");
+    installer = installer.with_force(force);
 
-        // Check if already installed
-        let binary_name = if cfg!(windows) { "kopi.exe" } else { "kopi" };
-        let binary_path = self.install_dir.join(binary_name);
-        
-        if binary_path.exists() {
-            self.log_warning("Kopi appears to already be installed");
-            print!("Do you want to reinstall? (y/N): ");
-            io::stdout().flush()?;
-            
-            let mut input = String::new();
-            io::stdin().read_line(&mut input)?;
-            
-            if !input.trim().to_lowercase().starts_with('y') {
-                self.log_info("Installation cancelled");
-                return Ok(());
+    if let Some(minutes) = take_option(&mut args, "--timeout") {
+        match minutes.parse::<u64>() {
+            Ok(minutes) if minutes > 0 => installer = installer.with_command_timeout(Duration::from_secs(minutes * 60)),
+            _ => {
+                eprintln!("--timeout: '{}' is not a number of minutes", minutes);
+                std::process::exit(1);
             }
         }
+    }
+    installer = installer.with_unattended(take_flag(&mut args, "--unattended"));
+    if !take_flag(&mut args, "--no-detect")
+        && let Some(environment) = kipper::ci::detect()
+    {
+        installer = installer.with_ci_environment(environment);
+    }
+    installer = installer.with_trace_commands(take_flag(&mut args, "--trace-commands"));
+    installer = installer.with_fresh(take_flag(&mut args, "--fresh"));
+    installer = installer.with_quick(take_flag(&mut args, "--quick"));
+    installer = installer.with_locked(take_flag(&mut args, "--locked-installer"));
 
-        self.log_info("Starting Kopi installation...");
+    let mut option_choices = BTreeMap::new();
+    args.retain(|arg| match options::parse_option_flag(arg) {
+        Some((name, on)) => {
+            option_choices.insert(name, on);
+            false
+        }
+        None => true,
+    });
+    installer = installer.with_options(option_choices);
 
-        self.check_dependencies()?;
-        self.create_directories()?;
-        self.download_and_build()?;
-        self.install_binary()?;
-        self.create_uninstaller()?;
-        self.verify_installation()?;
+    if let Some(target) = take_option(&mut args, "--target") {
+        match kipper::target::resolve_target(&target) {
+            Ok(triple) => installer = installer.with_target(triple),
+            Err(e) => {
+                eprintln!("--target: {}", e);
+                std::process::exit(1);
+            }
+        }
+    }
+    if let Some(source) = take_option(&mut args, "--source") {
+        match kipper::source::select(&source) {
+            Ok(provider) => installer = installer.with_source(provider),
+            Err(e) => {
+                eprintln!("--source: {}", e);
+                std::process::exit(1);
+            }
+        }
+    }
+    if let Some(builder) = take_option(&mut args, "--builder") {
+        match kipper::builder::select(&builder) {
+            Ok(builder) => installer = installer.with_builder(builder),
+            Err(e) => {
+                eprintln!("--builder: {}", e);
+                std::process::exit(1);
+            }
+        }
+    }
+    if take_flag(&mut args, "--announce") {
+        installer = installer.with_progress_mode(ProgressMode::Announce);
+    }
+    if take_flag(&mut args, "--inherit-env") {
+        installer = installer.with_inherited_env(true);
+    }
+    if take_flag(&mut args, "--include-prereleases") {
+        installer = installer.with_prereleases(true);
+    }
+    if take_flag(&mut args, "--require-signed") {
+        installer = installer.with_required_signatures(true);
+    }
+    let audit = take_flag(&mut args, "--audit");
+    if take_flag(&mut args, "--deny-advisories") {
+        installer = installer.with_advisory_check(AdvisoryCheck::Deny);
+    } else if audit {
+        installer = installer.with_advisory_check(AdvisoryCheck::Warn);
+    }
 
-        println!();
-        self.log_success("🎉 Kopi installation completed successfully!");
-        println!();
-        println!("\x1b[34mHappy coding with Kopi! ☕\x1b[0m");
+    args.extend(passthrough);
 
-        Ok(())
+    if changes_install(&args)
+        && let Err(e) = installer.guard_root(allow_root)
+    {
+        installer.log_error(&format!("{:?}", e));
+        std::process::exit(1);
     }
-}
-
-fn show_help() {
-    println!("Kipper - The Kopi Language Installer");
-    println!();
-    println!("USAGE:");
-    println!("    {} [OPTIONS]", INSTALLER_NAME);
-    println!();
-    println!("OPTIONS:");
-    println!("    -h, --help        Show this help message");
-    println!("    -u, --uninstall   Uninstall Kopi");
-    println!("    -v, --version     Show version information");
-    println!();
-    println!("EXAMPLES:");
-    println!("    {}              Install Kopi", INSTALLER_NAME);
-    println!("    {} --uninstall  Uninstall Kopi", INSTALLER_NAME);
-}
 
-fn main() {
-    let args: Vec<String> = env::args().collect();
-    
-    let installer = match Installer::new() {
-        Ok(installer) => installer,
-        Err(e) => {
-            eprintln!("Failed to initialize installer: {:?}", e);
-            std::process::exit(1);
+    // Held to the end, so a concurrent kipper waits until this one is done
+    let _install_lock = if holds_install_lock(&args) {
+        match installer.lock_install(None) {
+            Ok(lock) => Some(lock),
+            Err(e) => {
+                installer.log_error(&format!("{:?}", e));
+                std::process::exit(1);
+            }
         }
+    } else {
+        None
     };
 
+    let measured = match args.get(1).map(String::as_str) {
+        None => Some("install"),
+        Some(command) => metrics::MEASURED_COMMANDS.into_iter().find(|c| *c == command),
+    };
+    // `toolchain-path` fails without an error when the version isn't installed
+    let mut not_installed = false;
     let result = match args.get(1).map(String::as_str) {
         Some("-h") | Some("--help") => {
             show_help();
             Ok(())
         }
         Some("-u") | Some("--uninstall") => {
-            installer.uninstall()
+            installer.uninstall(args.iter().any(|a| a == "--purge"))
+        }
+        Some("uninstall") => {
+            let remove_self = args.iter().any(|a| a == "--remove-self");
+            let all = args.iter().any(|a| a == "--all");
+            let version = args.get(2).filter(|a| !a.starts_with('-'));
+            if remove_self && (version.is_some() || !all) {
+                eprintln!("--remove-self can only be used with --all");
+                std::process::exit(1);
+            }
+            match version {
+                Some(version) => installer.uninstall_version(version, force),
+                // Removing kipper too leaves nothing to read kept settings, so it implies --purge
+                None if remove_self => installer.uninstall(true).and_then(|_| installer.remove_self()),
+                None => installer.uninstall(args.iter().any(|a| a == "--purge")),
+            }
+        }
+        Some("cache") => match (args.get(2).map(String::as_str), args.get(3).map(String::as_str)) {
+            (Some("clean"), Some("--temp")) => installer.clean_stale_temp_dirs().map(|(removed, _)| {
+                if removed == 0 {
+                    eprintln!("No leftover temporary directories found");
+                }
+            }),
+            (Some("clean"), Some("--logs")) => installer.clean_logs().map(|(removed, freed)| {
+                if removed == 0 {
+                    eprintln!("No logs found");
+                } else {
+                    eprintln!("Removed {} log file{} ({})", removed, if removed == 1 { "" } else { "s" }, format_size(freed));
+                }
+            }),
+            (Some("clean"), Some("--build")) => installer.clean_build_dir().map(|freed| match freed {
+                Some(freed) => eprintln!("Removed build artifacts ({})", format_size(freed)),
+                None => eprintln!("No build artifacts found"),
+            }),
+            (Some("verify"), None) => installer.verify_cache(),
+            (Some("export"), Some(dir)) if args.len() == 4 => installer.export_cache(Path::new(dir)),
+            (Some("restore"), Some(dir)) if args.len() == 4 => installer.restore_cache(Path::new(dir)),
+            _ => {
+                eprintln!(
+                    "Usage: {} cache [clean --temp | clean --logs | clean --build | verify | export DIR | restore DIR]",
+                    INSTALLER_NAME
+                );
+                std::process::exit(1);
+            }
+        },
+        Some("alias") => {
+            let operands: Vec<&str> = args[2..].iter().map(String::as_str).collect();
+            match operands.as_slice() {
+                [] | ["list"] => {
+                    for (alias, tool) in installer.aliases() {
+                        println!("{} -> {}", alias, tool);
+                    }
+                    Ok(())
+                }
+                ["add", name] => installer.add_alias(name, "kopi", force),
+                ["add", name, tool] => installer.add_alias(name, tool, force),
+                ["remove", name] => installer.remove_alias(name),
+                _ => {
+                    eprintln!("Usage: {} alias [list | add NAME [TOOL] [--force] | remove NAME]", INSTALLER_NAME);
+                    std::process::exit(1);
+                }
+            }
+        }
+        Some("verify") => {
+            let json = take_flag(&mut args, "--json");
+            match args.get(2).map(String::as_str) {
+                Some("--enable") if args.len() == 3 => installer.enable_signing().map(|_| ()),
+                Some("--disable") if args.len() == 3 => installer.disable_signing(),
+                None => installer.verify_metadata().and_then(|verdicts| {
+                    if json {
+                        println!("{}", serde_json::to_string_pretty(&verdicts).unwrap_or_default());
+                    } else {
+                        for verdict in &verdicts {
+                            let mark = if verdict.verdict == Verdict::Valid { "✔" } else { "✘" };
+                            println!("{} {}  {}", mark, verdict.file, verdict.verdict.describe());
+                        }
+                    }
+                    let changed = verdicts.iter().filter(|v| v.verdict != Verdict::Valid).count();
+                    if changed == 0 {
+                        Ok(())
+                    } else {
+                        Err(kipper::InstallerError::PathError(format!(
+                            "{} of {} metadata files don't match their signatures",
+                            changed,
+                            verdicts.len()
+                        )))
+                    }
+                }),
+                _ => {
+                    eprintln!("Usage: {} verify [--json | --enable | --disable]", INSTALLER_NAME);
+                    std::process::exit(1);
+                }
+            }
         }
+        Some("auth") => {
+            let json = take_flag(&mut args, "--json");
+            let operands: Vec<&str> = args[2..].iter().map(String::as_str).collect();
+            match operands.as_slice() {
+                [] | ["status"] => {
+                    let status = installer.auth_status();
+                    if json {
+                        println!("{}", serde_json::to_string_pretty(&status).unwrap_or_default());
+                    } else {
+                        for credential in &status {
+                            let source = credential.source.as_deref().unwrap_or("not set");
+                            println!("{:<14} {:<40} {}", credential.name, credential.description, source);
+                        }
+                    }
+                    Ok(())
+                }
+                ["login", name] => auth::prompt_for(name).and_then(|secret| installer.login(name, &secret)),
+                ["logout", name] => installer.logout(name).map(|_| ()),
+                _ => {
+                    eprintln!("Usage: {} auth [status [--json] | login NAME | logout NAME]", INSTALLER_NAME);
+                    std::process::exit(1);
+                }
+            }
+        }
+        Some("mirror") => match args.get(2).map(String::as_str) {
+            None | Some("list") => {
+                let preferred = installer.preferred_mirror();
+                for url in installer.mirror_candidates() {
+                    let marker = if preferred.as_deref() == Some(url.as_str()) { " (preferred)" } else { "" };
+                    println!("{}{}", url, marker);
+                }
+                Ok(())
+            }
+            Some("add") if args.len() == 4 => installer.add_mirror(&args[3]),
+            Some("prefer") if args.len() == 4 => installer.prefer_mirror(&args[3]),
+            #[cfg(feature = "serve")]
+            Some("serve") => {
+                let port = take_option(&mut args, "--port").map(|port| {
+                    port.parse().unwrap_or_else(|_| {
+                        eprintln!("--port requires a port number");
+                        std::process::exit(1);
+                    })
+                });
+                let bind = take_option(&mut args, "--bind").map(|bind| {
+                    bind.parse().unwrap_or_else(|_| {
+                        eprintln!("--bind requires an IPv4 address");
+                        std::process::exit(1);
+                    })
+                });
+                installer.serve_mirror(
+                    bind.unwrap_or(std::net::Ipv4Addr::UNSPECIFIED),
+                    port.unwrap_or(kipper::mirror_serve::DEFAULT_MIRROR_PORT),
+                )
+            }
+            #[cfg(not(feature = "serve"))]
+            Some("serve") => Err(features::not_compiled_in("mirror serve", "serve")),
+            Some("remove") if args.len() == 4 => installer.remove_mirror(&args[3]),
+            Some("test") => installer.test_mirrors().map(|probes| {
+                println!("{:<50} {:>10} {:>12}", "SOURCE", "LATENCY", "THROUGHPUT");
+                for probe in &probes {
+                    let latency = probe.latency.map_or("-".to_string(), |l| format!("{}ms", l.as_millis()));
+                    let throughput = probe.throughput.map_or("-".to_string(), kipper::mirror::format_rate);
+                    println!("{:<50} {:>10} {:>12}", probe.url, latency, throughput);
+                    if let Some(error) = &probe.error {
+                        println!("    {}", error);
+                    }
+                }
+            }),
+            _ => {
+                eprintln!("Usage: {} mirror [list | add URL | prefer URL | remove URL | test | serve]", INSTALLER_NAME);
+                std::process::exit(1);
+            }
+        },
+        Some("sbom") => {
+            let format = match take_option(&mut args, "--format").as_deref().unwrap_or("cyclonedx").parse::<SbomFormat>() {
+                Ok(format) => format,
+                Err(e) => {
+                    eprintln!("--format: {}", e);
+                    std::process::exit(1);
+                }
+            };
+            let version = args.get(2).map(String::as_str);
+            env::current_dir()
+                .map_err(kipper::InstallerError::from)
+                .and_then(|cwd| installer.sbom(version, &cwd, format))
+                .map(|document| println!("{:#}", document))
+        }
+        Some("size") => installer.disk_usage().map(|entries| {
+            let default = installer.default_version();
+            println!("{:<32} {:>12}", "ITEM", "SIZE");
+            for entry in &entries {
+                let marker = if default.as_deref() == Some(entry.name.as_str()) { " (default)" } else { "" };
+                println!("{:<32} {:>12}", format!("{}{}", entry.name, marker), format_size(entry.bytes));
+            }
+            println!("{:<32} {:>12}", "total", format_size(entries.iter().map(|e| e.bytes).sum()));
+            installer.suggest_cleanup(&entries);
+        }),
+        Some("info") if args.iter().any(|a| a == "--provenance") => {
+            let version = args[2..].iter().find(|a| *a != "--provenance");
+            installer.pin_lines(version.map(String::as_str)).map(|lines| lines.iter().for_each(|line| println!("{}", line)))
+        }
+        Some("info") => print_info(&installer, args.get(2).map(String::as_str)),
+        Some("check") => installer.check(),
+        Some("doctor") if args.get(2).map(String::as_str) == Some("--fix") => installer.remove_stale_links().map(|_| ()),
+        Some("doctor") if args.iter().any(|a| a == "--capabilities") => {
+            let json = take_flag(&mut args, "--json");
+            print_capabilities(&installer.capability_matrix(), json);
+            Ok(())
+        }
+        Some("doctor") => installer.doctor(),
+        // JSON is the only format; --json is accepted so scripts can say so
+        Some("state") if args.get(2).map(String::as_str) == Some("export")
+            && args[3..].iter().all(|a| a == "--json") =>
+        {
+            installer.export_state().map(|state| println!("{}", redact(&serde_json::to_string_pretty(&state).unwrap_or_default())))
+        }
+        Some("state") => {
+            eprintln!("Usage: {} state export [--json]", INSTALLER_NAME);
+            std::process::exit(1);
+        }
+        Some("config") if args.get(2).map(String::as_str) == Some("set") => {
+            let scope = if take_flag(&mut args, "--local") {
+                Scope::Local
+            } else {
+                take_flag(&mut args, "--global");
+                Scope::Global
+            };
+            match (args.get(3), args.get(4), args.len()) {
+                (Some(key), Some(value), 5) => installer.config_set(key, value, scope),
+                _ => {
+                    eprintln!("Usage: {} config set KEY VALUE [--global | --local]", INSTALLER_NAME);
+                    std::process::exit(1);
+                }
+            }
+        }
+        Some("completions") => match args.get(2).map(String::as_str) {
+            Some("--install") if args.len() == 3 => installer.install_completions().map(|_| ()),
+            Some(shell) if args.len() == 3 => match shell.parse::<Shell>() {
+                Ok(shell) => {
+                    print!("{}", completion_script(shell));
+                    Ok(())
+                }
+                Err(e) => {
+                    eprintln!("{}", e);
+                    std::process::exit(1);
+                }
+            },
+            _ => {
+                eprintln!("Usage: {} completions bash|zsh|fish|--install", INSTALLER_NAME);
+                std::process::exit(1);
+            }
+        },
+        Some("apply") => match provision {
+            Some(provision) => provision.and_then(|provision| installer.apply(&provision)),
+            None => {
+                eprintln!("Usage: {} apply <provision.toml>", INSTALLER_NAME);
+                std::process::exit(1);
+            }
+        },
+        Some("explain") | Some("--explain") => print_explanation(args.get(2).map(String::as_str)),
+        Some("update") => {
+            let json = take_flag(&mut args, "--json");
+            if take_flag(&mut args, "--commit") {
+                installer.commit_update()
+            } else if take_flag(&mut args, "--auto") {
+                installer.auto_update()
+            } else {
+                installer.update_with(UpdateOptions {
+                    confirm: !force && !args.iter().any(|a| a == "--yes" || a == "-y"),
+                    shortlog: args.iter().any(|a| a == "--log"),
+                    prepare: take_flag(&mut args, "--prepare"),
+                })
+            }
+            .map(|()| print_summaries(&installer, json))
+        }
+        Some("optimize") if args.len() <= 3 => env::current_dir()
+            .map_err(kipper::InstallerError::from)
+            .and_then(|cwd| installer.optimize(args.get(2).map(String::as_str), &cwd)),
+        Some("optimize") => {
+            eprintln!("Usage: {} optimize [VERSION]", INSTALLER_NAME);
+            std::process::exit(1);
+        }
+        Some("warm") => installer.warm(args.get(2).map(String::as_str)),
+        Some("test-matrix") => {
+            let Some(separator) = args.iter().position(|a| a == "--").filter(|i| *i + 1 < args.len()) else {
+                eprintln!("Usage: {} test-matrix [VERSION...] -- <script.kopi | command...>", INSTALLER_NAME);
+                std::process::exit(1);
+            };
+            env::current_dir()
+                .map_err(kipper::InstallerError::from)
+                .and_then(|cwd| installer.test_matrix(&args[2..separator], &args[separator + 1..], &cwd))
+                .and_then(|results| {
+                    println!("{:<20} {:<6} {:>6} {:>10}", "VERSION", "RESULT", "EXIT", "TIME");
+                    for result in &results {
+                        let code = result.code.map_or("-".to_string(), |c| c.to_string());
+                        let status = if result.passed() { "pass" } else { "FAIL" };
+                        println!("{:<20} {:<6} {:>6} {:>9.1}s", result.version, status, code, result.duration.as_secs_f64());
+                    }
+                    let failed = results.iter().filter(|r| !r.passed()).count();
+                    if failed > 0 {
+                        return Err(kipper::InstallerError::PathError(format!("{} of {} versions failed", failed, results.len())));
+                    }
+                    Ok(())
+                })
+        }
+        Some("install") => {
+            let json = take_flag(&mut args, "--json");
+            let from_file = take_option(&mut args, "--from-file");
+            match listed_versions(&args[2..], from_file.as_deref()) {
+                Ok(versions) if versions.is_empty() && (from_file.is_some() || args.len() > 2) => {
+                    Err(kipper::InstallerError::PathError("No versions given to install".to_string()))
+                }
+                Ok(versions) if versions.is_empty() => installer.install_version(&installer.preferred_version()),
+                Ok(versions) => installer.install_versions(&versions),
+                Err(e) => Err(e),
+            }
+            .map(|()| print_summaries(&installer, json))
+            .and_then(|()| if json { Ok(()) } else { installer.offer_path_setup() })
+        }
+        Some("list") => {
+            let json = take_flag(&mut args, "--json");
+            installer.installed_entries().map(|entries| print_installed(&entries, json))
+        }
+        Some("release-check") => {
+            let json = take_flag(&mut args, "--json");
+            let Some(tag) = args.get(2).filter(|_| args.len() == 3) else {
+                eprintln!("Usage: {} release-check TAG [--json]", INSTALLER_NAME);
+                std::process::exit(1);
+            };
+            let report = installer.release_check(tag);
+            print_release_report(&report, json);
+            if report.passed {
+                Ok(())
+            } else {
+                Err(kipper::InstallerError::PathError(format!("The release check of {} failed", tag)))
+            }
+        }
+        Some("ls-remote") => {
+            let json = take_flag(&mut args, "--json");
+            installer.remote_entries().map(|entries| print_remote(&entries, json))
+        }
+        Some("use") if args.len() <= 3 => installer.use_version(args.get(2).map(String::as_str)),
+        Some("use") => {
+            eprintln!("Usage: {} use [VERSION]", INSTALLER_NAME);
+            std::process::exit(1);
+        }
+        Some("which") => {
+            let verbose = args.iter().any(|a| a == "--verbose");
+            let tool = args.iter().skip(2).find(|a| *a != "--verbose").map(String::as_str).unwrap_or("kopi");
+            print_which(&installer, tool, verbose)
+        }
+        Some("toolchain-path") => {
+            let json = args.iter().any(|a| a == "--json");
+            let ensure = args.iter().any(|a| a == "--ensure");
+            match print_toolchain_path(&installer, json, ensure) {
+                Ok(true) => Ok(()),
+                Ok(false) => {
+                    not_installed = true;
+                    Ok(())
+                }
+                Err(e) => Err(e),
+            }
+        }
+        Some("exec") => {
+            let separator = args.iter().position(|a| a == "--");
+            let (version, command) = match separator {
+                Some(i) => (args[2..i].first(), &args[i + 1..]),
+                None => {
+                    eprintln!("Usage: {} exec [VERSION] -- <command> [args...]", INSTALLER_NAME);
+                    std::process::exit(1);
+                }
+            };
+            let Some((program, program_args)) = command.split_first() else {
+                eprintln!("Usage: {} exec [VERSION] -- <command> [args...]", INSTALLER_NAME);
+                std::process::exit(1);
+            };
+
+            env::current_dir()
+                .map_err(kipper::InstallerError::from)
+                .and_then(|cwd| installer.toolchain_command(version.map(String::as_str), &cwd, program.as_ref()))
+                .and_then(|mut command| Ok(command.args(program_args).status()?))
+                .map(|status| std::process::exit(status.code().unwrap_or(1)))
+        }
+        #[cfg(feature = "serve")]
+        Some("serve") => {
+            let port = match args.iter().position(|a| a == "--port") {
+                Some(i) => match args.get(i + 1).and_then(|p| p.parse().ok()) {
+                    Some(port) => port,
+                    None => {
+                        eprintln!("--port requires a port number");
+                        std::process::exit(1);
+                    }
+                },
+                None => kipper::serve::DEFAULT_PORT,
+            };
+            // The server takes the configured installer; the handle left here cleans up and reports
+            let handle = installer.reporting_handle();
+            kipper::serve::serve(std::mem::replace(&mut installer, handle), port)
+        }
+        #[cfg(not(feature = "serve"))]
+        Some("serve") => Err(features::not_compiled_in("serve", "serve")),
         Some("-v") | Some("--version") => {
-            println!("Kipper v0.1.0 - The Kopi Language Installer");
+            print_version();
             Ok(())
         }
         None => {
-            installer.install()
+            installer.install().and_then(|()| installer.offer_path_setup())
         }
         Some(arg) => {
-            eprintln!("Unknown option: {}", arg);
-            show_help();
+            let (kind, known): (&str, Vec<&str>) = if arg.starts_with('-') {
+                ("option", completions::OPTIONS.to_vec())
+            } else {
+                ("command", completions::COMMANDS.iter().map(|(command, _)| *command).collect())
+            };
+            eprintln!("Unknown {}: {}", kind, arg);
+            if let Some(suggestion) = suggest::closest(arg, known) {
+                eprintln!("Did you mean '{} {}'?", INSTALLER_NAME, suggestion);
+            }
+            eprintln!("Run '{} --help' for usage", INSTALLER_NAME);
             std::process::exit(1);
         }
     };
 
     let _ = installer.cleanup();
+    if let Some(root) = &release_check_root {
+        let _ = fs::remove_dir_all(root);
+    }
+    if not_installed {
+        std::process::exit(1);
+    }
 
-    if let Err(e) = result {
-        installer.log_error(&format!("{:?}", e));
+    if let Some(command) = measured
+        && let Err(e) = installer.record_run(command, result.as_ref().err())
+    {
+        installer.log_error(&format!("Could not record metrics: {:?}", e));
+    }
+
+    if let Err(e) = &result {
+        let mut fields = serde_json::Map::new();
+        if let Some(code) = explain::error_code(e) {
+            fields.insert("code".to_string(), code.into());
+        }
+        installer.log_event(LogLevel::Error, &format!("{:?}", e), fields);
+    }
+    installer.print_next_steps(&Outcome {
+        command: args.get(1).map_or("install", String::as_str),
+        error: result.as_ref().err(),
+    });
+    if result.is_err() {
         std::process::exit(1);
     }
 }
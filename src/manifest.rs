@@ -0,0 +1,141 @@
+// Install manifest: an optional `kipper-manifest.toml` at the root of
+// kopi-lang saying what an install consists of, so upstream can change its
+// layout without waiting for a kipper release:
+//
+//     post-install = ["Run `kopi --help` to get started"]
+//
+//     [[binary]]
+//     name = "kopi"
+//
+//     [[binary]]
+//     name = "kopi-lsp"
+//     package = "kopi-lsp"       # workspace member that builds it
+//     profiles = ["default", "full"]
+//
+//     [[file]]
+//     source = "stdlib"          # file or directory in the checkout
+//     dest = "lib/stdlib"        # where it goes in the version dir
+//
+// Entries without `profiles` are installed with every profile. Without a
+// manifest kipper falls back to its built-in layout.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Component, Path};
+
+use serde::Deserialize;
+
+use crate::state::Profile;
+use crate::InstallerError;
+
+pub const MANIFEST_FILE: &str = "kipper-manifest.toml";
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ManifestBinary {
+    pub name: String,
+    /// The workspace package the binary belongs to, built with `-p`.
+    pub package: Option<String>,
+    pub profiles: Option<Vec<Profile>>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ManifestFile {
+    pub source: String,
+    /// Defaults to `source`.
+    pub dest: Option<String>,
+    pub profiles: Option<Vec<Profile>>,
+}
+
+/// A build option users turn on with `--with-NAME` or off with `--without-NAME`.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct ManifestOption {
+    pub description: Option<String>,
+    /// Cargo features the option turns on.
+    pub features: Vec<String>,
+    /// Whether the option is on unless turned off.
+    pub default: bool,
+    /// Options that must be on too.
+    pub requires: Vec<String>,
+    /// Options that can't be on at the same time.
+    pub conflicts: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Manifest {
+    pub binary: Vec<ManifestBinary>,
+    pub file: Vec<ManifestFile>,
+    /// Shown after the install summary.
+    pub post_install: Vec<String>,
+    pub option: BTreeMap<String, ManifestOption>,
+    /// Cargo features that stay on when turning off a default option means
+    /// building with `--no-default-features`.
+    pub default_features: Vec<String>,
+}
+
+fn for_profile(profiles: &Option<Vec<Profile>>, profile: Profile) -> bool {
+    profiles.as_ref().is_none_or(|profiles| profiles.contains(&profile))
+}
+
+/// A path that stays inside the directory it is joined to.
+fn is_contained(path: &str) -> bool {
+    !path.is_empty() && Path::new(path).components().all(|c| matches!(c, Component::Normal(_)))
+}
+
+impl ManifestFile {
+    pub fn dest(&self) -> &str {
+        self.dest.as_deref().unwrap_or(&self.source)
+    }
+}
+
+impl Manifest {
+    /// Read the manifest from `dir` (a checkout or an installed version), if it has one.
+    pub fn load(dir: &Path) -> Result<Option<Manifest>, InstallerError> {
+        let path = dir.join(MANIFEST_FILE);
+        if !path.exists() {
+            return Ok(None);
+        }
+        let invalid = |reason: String| InstallerError::PathError(format!("Invalid {}: {}", path.display(), reason));
+        let manifest: Manifest = toml::from_str(&fs::read_to_string(&path)?).map_err(|e| invalid(e.to_string()))?;
+
+        if !manifest.binary.iter().any(|b| b.name == "kopi" && b.profiles.is_none()) {
+            return Err(invalid("`kopi` must be listed as a binary for every profile".to_string()));
+        }
+        if let Some(binary) = manifest.binary.iter().find(|b| b.name.contains(['/', '\\']) || !is_contained(&b.name)) {
+            return Err(invalid(format!("'{}' is not a binary name", binary.name)));
+        }
+        if let Some(file) = manifest.file.iter().find(|f| !is_contained(&f.source) || !is_contained(f.dest())) {
+            return Err(invalid(format!("'{}' -> '{}' leaves its directory", file.source, file.dest())));
+        }
+        for (name, option) in &manifest.option {
+            if let Some(other) = option.requires.iter().chain(&option.conflicts).find(|o| !manifest.option.contains_key(*o)) {
+                return Err(invalid(format!("option '{}' refers to unknown option '{}'", name, other)));
+            }
+        }
+        Ok(Some(manifest))
+    }
+
+    pub fn binaries(&self, profile: Profile) -> impl Iterator<Item = &str> {
+        self.binary.iter().filter(move |b| for_profile(&b.profiles, profile)).map(|b| b.name.as_str())
+    }
+
+    /// Packages to build for `profile`, when the manifest names them.
+    pub fn packages(&self, profile: Profile) -> Vec<String> {
+        let mut packages: Vec<String> = Vec::new();
+        for binary in self.binary.iter().filter(|b| for_profile(&b.profiles, profile)) {
+            if let Some(package) = &binary.package
+                && !packages.contains(package)
+            {
+                packages.push(package.clone());
+            }
+        }
+        packages
+    }
+
+    pub fn files(&self, profile: Profile) -> impl Iterator<Item = &ManifestFile> {
+        self.file.iter().filter(move |f| for_profile(&f.profiles, profile))
+    }
+}
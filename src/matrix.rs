@@ -0,0 +1,74 @@
+// Compatibility matrix (`kipper test-matrix`): run one script or command
+// against several installed versions and tabulate the results.
+
+use std::path::Path;
+use std::process::Stdio;
+use std::time::{Duration, Instant};
+
+use crate::toolchain::{self, exe_name};
+use crate::{Installer, InstallerError};
+
+#[derive(Debug)]
+pub struct MatrixResult {
+    pub version: String,
+    /// Exit code, or `None` if the process couldn't be run or was killed by a signal.
+    pub code: Option<i32>,
+    pub duration: Duration,
+}
+
+impl MatrixResult {
+    pub fn passed(&self) -> bool {
+        self.code == Some(0)
+    }
+}
+
+impl Installer {
+    /// Run `command` once per version, with that version's environment.
+    ///
+    /// `versions` defaults to every installed version. A lone argument naming
+    /// an existing file is treated as a Kopi script and run with `kopi`.
+    pub fn test_matrix(&self, versions: &[String], command: &[String], cwd: &Path) -> Result<Vec<MatrixResult>, InstallerError> {
+        let versions: Vec<String> = if versions.is_empty() {
+            self.list()?.into_iter().map(|t| t.name).collect()
+        } else {
+            versions.iter().try_for_each(|version| toolchain::check_version_name(version))?;
+            versions.to_vec()
+        };
+        if versions.is_empty() {
+            return Err(InstallerError::PathError("No Kopi versions are installed".to_string()));
+        }
+
+        let is_script = command.len() == 1 && cwd.join(&command[0]).is_file();
+
+        let mut results = Vec::new();
+        for version in versions {
+            self.log_info(&format!("Running against Kopi {}...", version));
+            let started = Instant::now();
+
+            let (program, args) = if is_script {
+                (self.version_dir(&version).join(exe_name("kopi")).into_os_string(), command)
+            } else {
+                (command[0].clone().into(), &command[1..])
+            };
+
+            let code = match self.toolchain_command(Some(&version), cwd, &program) {
+                Ok(mut child) => {
+                    child.args(args).stdin(Stdio::null());
+                    self.trace_command(&child);
+                    child.output().ok().and_then(|output| output.status.code())
+                }
+                Err(e) => {
+                    self.log_warning(&format!("Kopi {}: {:?}", version, e));
+                    None
+                }
+            };
+
+            results.push(MatrixResult {
+                version,
+                code,
+                duration: started.elapsed(),
+            });
+        }
+        Ok(results)
+    }
+}
@@ -0,0 +1,216 @@
+// Run metrics: how each install, update, apply and uninstall went, and how
+// long each of its phases took, kept in state. With `[metrics] textfile` in
+// kipper.toml (or KIPPER_METRICS_FILE) they are also written after every such
+// run as a Prometheus file for node_exporter's textfile collector, so fleet
+// operators can alert on machines whose updates keep failing.
+
+use std::collections::BTreeMap;
+use std::env;
+use std::fmt::Write;
+use std::fs;
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+use crate::explain::error_code;
+use crate::state::State;
+use crate::timestamp::{now_utc, parse_timestamp};
+use crate::timing::Phase;
+use crate::upstream::KIPPER_VERSION;
+use crate::{Installer, InstallerError};
+
+pub const METRICS_FILE_ENV: &str = "KIPPER_METRICS_FILE";
+
+/// The commands whose runs are recorded.
+pub const MEASURED_COMMANDS: [&str; 4] = ["install", "update", "apply", "uninstall"];
+
+/// One phase of a run, successful or not.
+#[derive(Debug, Clone, Copy)]
+pub struct PhaseRun {
+    pub phase: Phase,
+    pub seconds: f64,
+    pub succeeded: bool,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PhaseOutcome {
+    pub seconds: f64,
+    pub succeeded: bool,
+}
+
+/// The last run of a command, and how many runs it has had.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RunRecord {
+    /// When the run finished (RFC 3339, UTC).
+    pub finished_at: String,
+    pub succeeded: bool,
+    /// The error code of the failure, when kipper recognised it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error_code: Option<String>,
+    /// The phases the run went through, by phase name. A phase that ran more
+    /// than once (installing several versions) is summed.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub phases: BTreeMap<String, PhaseOutcome>,
+    pub successes: u64,
+    pub failures: u64,
+}
+
+/// `value` quoted as a Prometheus label value.
+fn label(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n"))
+}
+
+impl Installer {
+    pub(crate) fn record_phase_run(&self, run: PhaseRun) {
+        if let Ok(mut runs) = self.phase_runs.lock() {
+            runs.push(run);
+        }
+    }
+
+    /// The Prometheus textfile to write, if any.
+    pub fn metrics_file(&self) -> Option<PathBuf> {
+        env::var_os(METRICS_FILE_ENV)
+            .filter(|path| !path.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| self.config.metrics.textfile.clone())
+    }
+
+    /// Record how a run of `command` ended, then write the metrics file if
+    /// one is configured.
+    pub fn record_run(&self, command: &str, error: Option<&InstallerError>) -> Result<(), InstallerError> {
+        // Everything was uninstalled, or nothing ever got as far as creating it
+        if !self.install_dir.exists() {
+            return Ok(());
+        }
+        let mut state = State::load(&self.install_dir);
+        let previous = state.runs.remove(command).unwrap_or_default();
+        let mut phases: BTreeMap<String, PhaseOutcome> = BTreeMap::new();
+        if let Ok(runs) = self.phase_runs.lock() {
+            for run in runs.iter() {
+                let outcome = phases.entry(run.phase.name().to_string()).or_insert(PhaseOutcome {
+                    seconds: 0.0,
+                    succeeded: true,
+                });
+                outcome.seconds += run.seconds;
+                outcome.succeeded &= run.succeeded;
+            }
+        }
+        state.runs.insert(
+            command.to_string(),
+            RunRecord {
+                finished_at: now_utc(),
+                succeeded: error.is_none(),
+                error_code: error.and_then(error_code).map(str::to_string),
+                phases,
+                successes: previous.successes + u64::from(error.is_none()),
+                failures: previous.failures + u64::from(error.is_some()),
+            },
+        );
+        state.save(&self.install_dir)?;
+
+        if let Some(path) = self.metrics_file() {
+            if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+                fs::create_dir_all(parent)?;
+            }
+            // The collector may read at any moment, so never let it see a half-written file
+            let partial = path.with_extension("prom.tmp");
+            fs::write(&partial, self.prometheus_metrics(&state))?;
+            fs::rename(&partial, &path)?;
+        }
+        Ok(())
+    }
+
+    /// The metrics in Prometheus' text exposition format.
+    fn prometheus_metrics(&self, state: &State) -> String {
+        let mut out = String::new();
+        let mut metric = |name: &str, kind: &str, help: &str, samples: Vec<(String, String)>| {
+            let _ = writeln!(out, "# HELP {} {}", name, help);
+            let _ = writeln!(out, "# TYPE {} {}", name, kind);
+            for (labels, value) in samples {
+                let _ = writeln!(out, "{}{} {}", name, labels, value);
+            }
+        };
+
+        metric(
+            "kipper_info",
+            "gauge",
+            "The kipper version and default Kopi version.",
+            vec![(
+                format!(
+                    "{{kipper_version={},default_version={}}}",
+                    label(KIPPER_VERSION),
+                    label(self.default_version().as_deref().unwrap_or(""))
+                ),
+                "1".to_string(),
+            )],
+        );
+        metric(
+            "kipper_installed_versions",
+            "gauge",
+            "Kopi versions installed.",
+            vec![(String::new(), self.list().map_or(0, |list| list.len()).to_string())],
+        );
+
+        let runs = &state.runs;
+        let by_command = |value: &dyn Fn(&RunRecord) -> String| {
+            runs.iter()
+                .map(|(command, run)| (format!("{{command={}}}", label(command)), value(run)))
+                .collect::<Vec<_>>()
+        };
+        metric(
+            "kipper_last_run_success",
+            "gauge",
+            "Whether the last run of the command succeeded.",
+            by_command(&|run| u8::from(run.succeeded).to_string()),
+        );
+        metric(
+            "kipper_last_run_timestamp_seconds",
+            "gauge",
+            "When the last run of the command finished.",
+            by_command(&|run| parse_timestamp(&run.finished_at).unwrap_or(0).to_string()),
+        );
+        metric(
+            "kipper_runs_total",
+            "counter",
+            "Runs of the command, by outcome.",
+            runs.iter()
+                .flat_map(|(command, run)| {
+                    [("success", run.successes), ("failure", run.failures)]
+                        .map(|(outcome, count)| (format!("{{command={},outcome={}}}", label(command), label(outcome)), count.to_string()))
+                })
+                .collect(),
+        );
+        metric(
+            "kipper_last_run_error",
+            "gauge",
+            "The error code of the last run of the command, when it failed with a known error.",
+            runs.iter()
+                .filter_map(|(command, run)| {
+                    let code = run.error_code.as_deref().filter(|_| !run.succeeded)?;
+                    Some((format!("{{command={},code={}}}", label(command), label(code)), "1".to_string()))
+                })
+                .collect(),
+        );
+        metric(
+            "kipper_phase_duration_seconds",
+            "gauge",
+            "How long each phase of the last run of the command took, and whether it succeeded.",
+            runs.iter()
+                .flat_map(|(command, run)| {
+                    run.phases.iter().map(move |(phase, outcome)| {
+                        (
+                            format!(
+                                "{{command={},phase={},outcome={}}}",
+                                label(command),
+                                label(phase),
+                                label(if outcome.succeeded { "success" } else { "failure" })
+                            ),
+                            format!("{:.3}", outcome.seconds),
+                        )
+                    })
+                })
+                .collect(),
+        );
+        out
+    }
+}
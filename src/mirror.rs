@@ -0,0 +1,278 @@
+// Source mirrors: alternative kopi-lang git URLs. When any are configured,
+// each candidate is probed and the fastest is used, remembered for a day,
+// unless a preferred mirror is set and responds.
+
+use std::fs;
+#[cfg(feature = "http")]
+use std::io::Read;
+use std::path::PathBuf;
+use std::process::Command;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use crate::policy::policy_path;
+use crate::state::State;
+use crate::{Installer, InstallerError, REPO_URL, timestamp};
+
+/// How long a mirror choice is reused before probing again.
+const SELECTION_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+const PROBE_TIMEOUT: Duration = Duration::from_secs(10);
+/// Bytes requested from HTTP mirrors to measure throughput.
+#[cfg(feature = "http")]
+const PROBE_BYTES: u64 = 64 * 1024;
+/// Transfer size used to weigh latency against throughput when ranking.
+const RANKING_SIZE: f64 = 10.0 * 1024.0 * 1024.0;
+const SELECTION_FILE: &str = "mirror.json";
+
+#[derive(Debug, Clone)]
+pub struct MirrorProbe {
+    pub url: String,
+    /// Time to the first response, or `None` if the mirror couldn't be reached.
+    pub latency: Option<Duration>,
+    /// Bytes per second; only measured for HTTP(S) mirrors.
+    pub throughput: Option<f64>,
+    pub error: Option<String>,
+}
+
+impl MirrorProbe {
+    /// Estimated seconds to fetch a typical amount of data, for ranking.
+    fn cost(&self) -> Option<f64> {
+        let latency = self.latency?.as_secs_f64();
+        Some(match self.throughput {
+            Some(rate) if rate > 0.0 => latency + RANKING_SIZE / rate,
+            _ => latency,
+        })
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Selection {
+    url: String,
+    /// When the choice was made (RFC 3339, UTC).
+    selected_at: String,
+}
+
+#[cfg(feature = "http")]
+fn probe_http(url: &str) -> MirrorProbe {
+    let mut probe = MirrorProbe {
+        url: url.to_string(),
+        latency: None,
+        throughput: None,
+        error: None,
+    };
+    let refs = format!("{}/info/refs?service=git-upload-pack", url.trim_end_matches('/'));
+
+    let started = Instant::now();
+    let response = reqwest::blocking::Client::builder()
+        .timeout(PROBE_TIMEOUT)
+        .build()
+        .and_then(|client| {
+            client
+                .get(&refs)
+                .header(reqwest::header::RANGE, format!("bytes=0-{}", PROBE_BYTES - 1))
+                .send()
+        })
+        .and_then(|response| response.error_for_status());
+    let response = match response {
+        Ok(response) => response,
+        Err(e) => {
+            probe.error = Some(e.to_string());
+            return probe;
+        }
+    };
+    probe.latency = Some(started.elapsed());
+
+    let body_started = Instant::now();
+    let mut body = Vec::new();
+    match response.take(PROBE_BYTES).read_to_end(&mut body) {
+        Ok(read) if read > 0 => {
+            let elapsed = body_started.elapsed().as_secs_f64().max(0.001);
+            probe.throughput = Some(read as f64 / elapsed);
+        }
+        Ok(_) => {}
+        Err(e) => probe.error = Some(e.to_string()),
+    }
+    probe
+}
+
+pub fn format_rate(bytes_per_sec: f64) -> String {
+    format!("{}/s", crate::cache::format_size(bytes_per_sec as u64))
+}
+
+impl Installer {
+    fn selection_path(&self) -> PathBuf {
+        self.cache_dir().join(SELECTION_FILE)
+    }
+
+    /// The official repository followed by any configured mirrors, or exactly
+    /// the sources an admin policy allows.
+    pub fn mirror_candidates(&self) -> Vec<String> {
+        if let Some(allowed) = self.policy().and_then(|p| p.allowed_sources.clone()) {
+            return allowed;
+        }
+
+        let mut candidates = vec![REPO_URL.to_string()];
+        for mirror in State::load(&self.install_dir).mirrors {
+            if !candidates.contains(&mirror) {
+                candidates.push(mirror);
+            }
+        }
+        candidates
+    }
+
+    pub fn add_mirror(&self, url: &str) -> Result<(), InstallerError> {
+        let mut state = State::load(&self.install_dir);
+        if url == REPO_URL || state.mirrors.iter().any(|m| m == url) {
+            return Err(InstallerError::PathError(format!("{} is already configured", url)));
+        }
+        if self.policy().is_some_and(|p| p.allowed_sources.is_some()) {
+            return Err(InstallerError::PathError(format!(
+                "Sources are pinned by {}; mirrors can't be added",
+                policy_path().display()
+            )));
+        }
+        fs::create_dir_all(&self.install_dir)?;
+        state.mirrors.push(url.to_string());
+        state.save(&self.install_dir)?;
+        let _ = fs::remove_file(self.selection_path());
+        self.log_success(&format!("Added mirror {}", url));
+        Ok(())
+    }
+
+    pub fn remove_mirror(&self, url: &str) -> Result<(), InstallerError> {
+        let mut state = State::load(&self.install_dir);
+        let before = state.mirrors.len();
+        state.mirrors.retain(|m| m != url);
+        if state.mirrors.len() == before {
+            return Err(InstallerError::PathError(format!("{} is not a configured mirror", url)));
+        }
+        if state.preferred_mirror.as_deref() == Some(url) {
+            state.preferred_mirror = None;
+        }
+        state.save(&self.install_dir)?;
+        let _ = fs::remove_file(self.selection_path());
+        self.log_success(&format!("Removed mirror {}", url));
+        Ok(())
+    }
+
+    /// `kipper mirror prefer`: use `url` whenever it responds, adding it as a
+    /// mirror first if needed.
+    pub fn prefer_mirror(&self, url: &str) -> Result<(), InstallerError> {
+        if url != REPO_URL && !State::load(&self.install_dir).mirrors.iter().any(|m| m == url) {
+            self.add_mirror(url)?;
+        }
+        let mut state = State::load(&self.install_dir);
+        state.preferred_mirror = Some(url.to_string());
+        state.save(&self.install_dir)?;
+        self.log_success(&format!("Preferring {} while it responds", url));
+        Ok(())
+    }
+
+    /// The preferred mirror, if it is still a candidate.
+    pub fn preferred_mirror(&self) -> Option<String> {
+        State::load(&self.install_dir)
+            .preferred_mirror
+            .filter(|url| self.mirror_candidates().contains(url))
+    }
+
+    /// Measure one mirror: a small range request for HTTP(S) URLs, or a
+    /// timed `git ls-remote` for anything else (ssh, local paths) and for
+    /// every URL in builds without the `http` feature.
+    fn probe_mirror(&self, url: &str) -> MirrorProbe {
+        #[cfg(feature = "http")]
+        if url.starts_with("http://") || url.starts_with("https://") {
+            return probe_http(url);
+        }
+
+        let started = Instant::now();
+        let result = self.run_command_with_timeout(
+            Command::new("git")
+                .args(["ls-remote", "--heads", url])
+                .env("GIT_TERMINAL_PROMPT", "0"),
+            Some(PROBE_TIMEOUT),
+        );
+        let (latency, error) = match result {
+            Ok(output) if output.status.success() => (Some(started.elapsed()), None),
+            Ok(output) => (None, Some(String::from_utf8_lossy(&output.stderr).trim().to_string())),
+            Err(e) => (None, Some(format!("{:?}", e))),
+        };
+        MirrorProbe {
+            url: url.to_string(),
+            latency,
+            throughput: None,
+            error,
+        }
+    }
+
+    /// `kipper mirror test`: probe every candidate and remember the fastest.
+    /// Results are ordered fastest first; unreachable mirrors come last.
+    pub fn test_mirrors(&self) -> Result<Vec<MirrorProbe>, InstallerError> {
+        let mut probes = Vec::new();
+        for url in self.mirror_candidates() {
+            self.check_cancelled()?;
+            self.log_info(&format!("Probing {}...", url));
+            probes.push(self.probe_mirror(&url));
+        }
+        probes.sort_by(|a, b| match (a.cost(), b.cost()) {
+            (Some(a), Some(b)) => a.total_cmp(&b),
+            (Some(_), None) => std::cmp::Ordering::Less,
+            (None, Some(_)) => std::cmp::Ordering::Greater,
+            (None, None) => std::cmp::Ordering::Equal,
+        });
+
+        if let Some(fastest) = probes.first().filter(|p| p.latency.is_some()) {
+            let selection = Selection {
+                url: fastest.url.clone(),
+                selected_at: timestamp::now_utc(),
+            };
+            let path = self.selection_path();
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            let contents = serde_json::to_string_pretty(&selection).map_err(std::io::Error::other)?;
+            fs::write(path, contents + "\n")?;
+        }
+        Ok(probes)
+    }
+
+    /// The URL to fetch kopi-lang from: the only candidate, or the fastest of
+    /// several (re-probed daily).
+    pub fn source_url(&self) -> String {
+        let candidates = self.mirror_candidates();
+        if let Some(preferred) = self.preferred_mirror() {
+            if self.probe_mirror(&preferred).latency.is_some() {
+                return preferred;
+            }
+            self.log_warning(&format!("Preferred mirror {} is not responding, choosing another source", preferred));
+        }
+        if candidates.len() == 1 {
+            return candidates[0].clone();
+        }
+
+        let cached = fs::read_to_string(self.selection_path())
+            .ok()
+            .and_then(|contents| serde_json::from_str::<Selection>(&contents).ok())
+            .filter(|s| {
+                let age = timestamp::parse_timestamp(&s.selected_at).map(|at| timestamp::unix_now() as i64 - at);
+                age.is_some_and(|age| age < SELECTION_TTL.as_secs() as i64) && candidates.contains(&s.url)
+            });
+        if let Some(selection) = cached {
+            return selection.url;
+        }
+
+        match self.test_mirrors() {
+            Ok(probes) => match probes.into_iter().find(|p| p.latency.is_some()) {
+                Some(fastest) => {
+                    self.log_info(&format!("Using the fastest source: {}", fastest.url));
+                    fastest.url
+                }
+                None => {
+                    self.log_warning(&format!("No source responded to probing; trying {}", candidates[0]));
+                    candidates[0].clone()
+                }
+            },
+            Err(_) => candidates[0].clone(),
+        }
+    }
+}
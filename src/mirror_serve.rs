@@ -0,0 +1,118 @@
+// Classroom mirror (`kipper mirror serve`): serve this machine's cache over
+// plain HTTP on the LAN, so a room full of machines fetches kopi-lang from one
+// of them instead of each going out to GitHub. The source cache is served
+// with git's "dumb" HTTP protocol, which only needs static files once
+// `git update-server-info` has run, and the built binaries cache as it is;
+// nothing else in the cache dir is served. Students point kipper at it with
+// `kipper mirror prefer <url>`.
+
+use std::net::{Ipv4Addr, SocketAddr, UdpSocket};
+use std::path::{Component, Path, PathBuf};
+
+use tokio::fs::File;
+use tokio::io::{self, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+use crate::bincache::BIN_CACHE_DIR;
+use crate::serve::read_request;
+use crate::{Installer, InstallerError, LogLevel};
+
+pub const DEFAULT_MIRROR_PORT: u16 = 8787;
+
+/// The address other machines on the LAN reach this one at, as far as the
+/// routing table says. Nothing is sent.
+fn lan_address() -> Option<std::net::IpAddr> {
+    let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0)).ok()?;
+    socket.connect((Ipv4Addr::new(192, 0, 2, 1), 9)).ok()?;
+    Some(socket.local_addr().ok()?.ip()).filter(|ip| !ip.is_loopback() && !ip.is_unspecified())
+}
+
+/// What of the cache dir is served: the git mirror and the built binaries.
+const SERVED_DIRS: [&str; 2] = ["git", BIN_CACHE_DIR];
+
+/// The file under `root` a request path names. Only files in `SERVED_DIRS`
+/// are served, and a symlink only when what it points to is in there too.
+fn resolve(root: &Path, request_path: &str) -> Option<PathBuf> {
+    let relative = Path::new(request_path.trim_start_matches('/'));
+    if relative.components().count() < 2 || !relative.components().all(|c| matches!(c, Component::Normal(_))) {
+        return None;
+    }
+    let served = SERVED_DIRS.iter().find(|dir| relative.starts_with(dir))?;
+    let served = root.join(served).canonicalize().ok()?;
+    let path = root.join(relative).canonicalize().ok()?;
+    Some(path).filter(|path| path.starts_with(&served) && path.is_file())
+}
+
+impl Installer {
+    /// Fetch the latest kopi-lang into the cache, then serve the cache until
+    /// the process is interrupted.
+    pub fn serve_mirror(&self, bind: Ipv4Addr, port: u16) -> Result<(), InstallerError> {
+        if !self.has_git() {
+            return Err(InstallerError::Git("git not found".to_string()));
+        }
+        self.log_info("Updating the source cache before serving it...");
+        self.refresh_source_cache(None)?;
+        let output = self.git_in_cache(&["update-server-info"])?;
+        if !output.status.success() {
+            let error = String::from_utf8_lossy(&output.stderr);
+            return Err(InstallerError::Git(format!("Failed to prepare the cache for serving: {}", error.trim())));
+        }
+
+        let root = self.cache_dir();
+        let repo = self.source_cache();
+        let repo_path = repo.strip_prefix(&root).unwrap_or(&repo).to_string_lossy().replace('\\', "/");
+        let host = lan_address().map_or_else(|| "<this machine's address>".to_string(), |ip| ip.to_string());
+
+        let runtime = tokio::runtime::Runtime::new()?;
+        runtime.block_on(async {
+            let listener = TcpListener::bind(SocketAddr::from((bind, port))).await?;
+            self.log_success(&format!("Serving {} on port {}", root.display(), port));
+            self.log_info("On each machine that should use this mirror, run:");
+            let command = format!("kipper mirror prefer http://{}:{}/{}", host, port, repo_path);
+            self.print_line(&format!("  {}", self.theme.paint(self.theme.highlight.as_deref(), &command)));
+            self.log_info("Press Ctrl+C to stop serving");
+
+            loop {
+                let (stream, peer) = listener.accept().await?;
+                let root = root.clone();
+                let theme = self.theme.clone();
+                tokio::spawn(async move {
+                    if let Err(e) = handle(stream, &root).await {
+                        eprintln!("{}", theme.message(LogLevel::Warning, &format!("{}: {}", peer, e)));
+                    }
+                });
+            }
+        })
+    }
+}
+
+async fn handle(mut stream: TcpStream, root: &Path) -> io::Result<()> {
+    let Some(request) = read_request(&mut stream).await? else {
+        return respond_empty(&mut stream, "400 Bad Request").await;
+    };
+    let head_only = match request.method.as_str() {
+        "GET" => false,
+        "HEAD" => true,
+        _ => return respond_empty(&mut stream, "405 Method Not Allowed").await,
+    };
+    let Some(path) = resolve(root, &request.path) else {
+        return respond_empty(&mut stream, "404 Not Found").await;
+    };
+
+    let mut file = File::open(&path).await?;
+    let length = file.metadata().await?.len();
+    let header = format!(
+        "HTTP/1.1 200 OK\r\nContent-Type: application/octet-stream\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        length
+    );
+    stream.write_all(header.as_bytes()).await?;
+    if !head_only {
+        io::copy(&mut file, &mut stream).await?;
+    }
+    stream.shutdown().await
+}
+
+async fn respond_empty(stream: &mut TcpStream, status: &str) -> io::Result<()> {
+    let response = format!("HTTP/1.1 {}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n", status);
+    stream.write_all(response.as_bytes()).await
+}
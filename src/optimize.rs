@@ -0,0 +1,137 @@
+// `kipper optimize [VERSION]`: replace an installed build, typically a
+// `--quick` one, with the fastest build of the same commit. A prebuilt build
+// from the built binaries cache or the remote cache is taken when one fits
+// (their binaries are hashed, and checked before use); otherwise the commit
+// is compiled with the release profile at full strength: opt-level 3, fat
+// LTO and one codegen unit, whatever kopi-lang's own profile says. `--fresh`
+// skips the caches and always compiles.
+//
+// The version stays what it was: the build is of the commit it was built
+// from, and a source that no longer has that commit is an error, not an
+// update. The new build is staged next to the prepared updates, its `kopi`
+// has to run `--version` successfully, and only then is it renamed into the
+// place of the old one, as `update --commit` does: `kopi` is never missing
+// for more than an instant, and a build that fails leaves the install as it
+// was.
+
+use std::fs;
+use std::path::Path;
+use std::sync::atomic::Ordering;
+
+use crate::cache::format_size;
+use crate::platform::extended_length_path;
+use crate::stamp::{STAMP_FILE, Stamp};
+use crate::state::State;
+use crate::timing::Phase;
+use crate::toolchain::{self, exe_name};
+use crate::update::PREPARED_DIR;
+use crate::{Installer, InstallerError};
+
+fn short(commit: &str) -> &str {
+    &commit[..commit.len().min(7)]
+}
+
+impl Installer {
+    pub(crate) fn is_optimizing(&self) -> bool {
+        self.optimizing.load(Ordering::Relaxed)
+    }
+
+    /// Rebuild `version` (by default the one selected for `cwd`) as an
+    /// optimized build of the same commit, and swap it in once it runs.
+    pub fn optimize(&self, version: Option<&str>, cwd: &Path) -> Result<(), InstallerError> {
+        let version = match version {
+            Some(version) => toolchain::check_version_name(version).map(|()| self.installed_name(version))?,
+            None => self.resolve_version(cwd)
+                .map(|resolved| resolved.name)
+                .ok_or_else(|| InstallerError::PathError("No Kopi version selected, run `kipper install`".to_string()))?,
+        };
+        let installed = State::load(&self.install_dir).versions.remove(&version);
+        let Some(installed) = installed.filter(|_| self.version_dir(&version).join(exe_name("kopi")).exists()) else {
+            return Err(self.not_installed(&version));
+        };
+        if !installed.unoptimized {
+            self.log_info(&format!("Kopi {} isn't a quick build; rebuilding it with full optimization anyway", version));
+        }
+
+        self.log_info(&format!("Optimizing Kopi {} (built from {})...", version, short(&installed.commit)));
+        self.prepare_install()?;
+        self.optimizing.store(true, Ordering::Relaxed);
+        let built = self.rollback_on_cancel(self.build_commit(&version, &installed.commit));
+        self.optimizing.store(false, Ordering::Relaxed);
+        built?;
+
+        let staged = self.install_dir.join(PREPARED_DIR).join(format!(".{}.optimized", version));
+        if staged.exists() {
+            fs::remove_dir_all(extended_length_path(&staged))?;
+        }
+        let swapped = self.timed(Phase::Install, || {
+            let manifest = self.copy_build(&staged)?;
+            let tested = self.smoke_test(&staged.join(exe_name("kopi")))?;
+            self.log_success(&format!("The optimized build runs ({})", tested));
+            let mut record = self.build_record(&version, manifest.as_ref())?;
+            record.phase_seconds = installed.phase_seconds.clone();
+            Stamp::new(&version, &record).save(&staged)?;
+            self.swap_in(&version, &staged)?;
+            Ok(record)
+        });
+        let record = match swapped {
+            Ok(record) => record,
+            Err(e) => {
+                if staged.exists() {
+                    fs::remove_dir_all(extended_length_path(&staged))?;
+                }
+                return Err(e);
+            }
+        };
+
+        let version_dir = self.version_dir(&version);
+        self.sign(&version_dir.join(STAMP_FILE));
+        let mut state = State::load(&self.install_dir);
+        state.versions.insert(version.clone(), record);
+        state.save(&self.install_dir)?;
+
+        let size = version_dir.join(exe_name("kopi")).metadata()?.len();
+        self.log_success(&format!("Kopi {} is optimized ({} kopi binary)", version, format_size(size)));
+        Ok(())
+    }
+
+    /// Fetch `version` at `commit` and build it.
+    fn build_commit(&self, version: &str, commit: &str) -> Result<(), InstallerError> {
+        self.timed(Phase::Fetch, || {
+            self.clone_source(Some(version))?;
+            let fetched = self.source_record()?.commit;
+            if fetched == commit {
+                return Ok(());
+            }
+            // Nightly has moved on, or the tag was moved
+            if !self.source.has_history() || self.checkout_source(commit).is_err() {
+                return Err(InstallerError::Git(format!(
+                    "Kopi {} was built from {}, which the source doesn't have any more (it has {}); `kipper install {}` builds that instead",
+                    version,
+                    short(commit),
+                    short(&fetched),
+                    version
+                )));
+            }
+            Ok(())
+        })?;
+        self.timed(Phase::Build, || self.build_source(version))
+    }
+
+    /// Rename `staged` into the place of `version`'s dir, putting the old
+    /// dir back if that fails.
+    fn swap_in(&self, version: &str, staged: &Path) -> Result<(), InstallerError> {
+        let version_dir = self.version_dir(version);
+        let previous = self.install_dir.join(PREPARED_DIR).join(format!(".{}.previous", version));
+        if previous.exists() {
+            fs::remove_dir_all(extended_length_path(&previous))?;
+        }
+        fs::rename(&version_dir, &previous)?;
+        if let Err(e) = fs::rename(staged, &version_dir) {
+            fs::rename(&previous, &version_dir)?;
+            return Err(e.into());
+        }
+        fs::remove_dir_all(extended_length_path(&previous))?;
+        Ok(())
+    }
+}
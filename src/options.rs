@@ -0,0 +1,126 @@
+// Build options: upstream cargo features under names users understand
+// (`--with-jit`, `--without-repl`), declared as `[option.NAME]` in
+// kipper-manifest.toml. Choices are recorded per version so `kipper update`
+// rebuilds with the same set.
+
+use std::collections::BTreeMap;
+
+use crate::manifest::Manifest;
+use crate::state::State;
+use crate::{Installer, InstallerError};
+
+/// Parse `--with-NAME` and `--without-NAME` into `(NAME, on)`.
+pub fn parse_option_flag(arg: &str) -> Option<(String, bool)> {
+    if let Some(name) = arg.strip_prefix("--without-") {
+        return Some((name.to_string(), false));
+    }
+    arg.strip_prefix("--with-").map(|name| (name.to_string(), true))
+}
+
+/// `jit, no repl` for display.
+pub fn describe_options(choices: &BTreeMap<String, bool>) -> String {
+    choices
+        .iter()
+        .map(|(name, on)| if *on { name.clone() } else { format!("no {}", name) })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+impl Installer {
+    /// Turn options on or off, over whatever was chosen for a version before.
+    pub fn with_options(mut self, choices: BTreeMap<String, bool>) -> Self {
+        self.option_choices = choices;
+        self
+    }
+
+    /// The options chosen for `version`: those recorded at its last install
+    /// that `manifest` still declares, with the ones given on this command
+    /// line taking precedence.
+    pub(crate) fn chosen_options(&self, version: &str, manifest: Option<&Manifest>) -> BTreeMap<String, bool> {
+        let mut choices: BTreeMap<String, bool> = State::load(&self.install_dir)
+            .versions
+            .remove(version)
+            .map(|record| record.options)
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|(name, _)| manifest.is_some_and(|m| m.option.contains_key(name)))
+            .collect();
+        choices.extend(self.config.build.options.clone());
+        choices.extend(self.option_choices.clone());
+        choices
+    }
+
+    /// Whether the settings or command line change any option recorded for `version`.
+    pub(crate) fn options_changed(&self, version: &str) -> bool {
+        let recorded = State::load(&self.install_dir)
+            .versions
+            .remove(version)
+            .map(|record| record.options)
+            .unwrap_or_default();
+        let mut wanted = self.config.build.options.clone();
+        wanted.extend(self.option_choices.clone());
+        wanted.iter().any(|(name, on)| recorded.get(name) != Some(on))
+    }
+
+    /// Check `choices` against the options `manifest` declares and return the
+    /// extra `cargo build` arguments that select their features.
+    pub(crate) fn option_build_args(
+        &self,
+        version: &str,
+        manifest: Option<&Manifest>,
+        choices: &BTreeMap<String, bool>,
+    ) -> Result<Vec<String>, InstallerError> {
+        let declared = manifest.map(|m| &m.option);
+        let available = || declared.map_or_else(String::new, |d| d.keys().cloned().collect::<Vec<_>>().join(", "));
+        if let Some(unknown) = choices.keys().find(|name| !declared.is_some_and(|d| d.contains_key(*name))) {
+            let hint = match available() {
+                list if list.is_empty() => "it has no build options".to_string(),
+                list => format!("available: {}", list),
+            };
+            return Err(InstallerError::PathError(format!("Kopi {} has no option '{}' ({})", version, unknown, hint)));
+        }
+        let (Some(manifest), Some(declared)) = (manifest, declared) else {
+            return Ok(Vec::new());
+        };
+
+        let enabled = |name: &str| choices.get(name).copied().unwrap_or(declared[name].default);
+        for (name, option) in declared.iter().filter(|(name, _)| enabled(name)) {
+            if let Some(missing) = option.requires.iter().find(|other| !enabled(other)) {
+                return Err(InstallerError::PathError(format!(
+                    "Option '{}' needs '{}'; add --with-{}",
+                    name, missing, missing
+                )));
+            }
+            if let Some(conflict) = option.conflicts.iter().find(|other| enabled(other)) {
+                return Err(InstallerError::PathError(format!(
+                    "Options '{}' and '{}' can't be combined; add --without-{} or --without-{}",
+                    name, conflict, name, conflict
+                )));
+            }
+        }
+
+        // Turning off an option that is on by default means building without
+        // cargo's default features and listing everything wanted explicitly
+        let no_defaults = declared.iter().any(|(name, option)| option.default && !enabled(name));
+        let mut features: Vec<&str> = Vec::new();
+        if no_defaults {
+            features.extend(manifest.default_features.iter().map(String::as_str));
+        }
+        for (name, option) in declared {
+            if enabled(name) && (no_defaults || !option.default) {
+                features.extend(option.features.iter().map(String::as_str));
+            }
+        }
+        features.sort_unstable();
+        features.dedup();
+
+        let mut args = Vec::new();
+        if no_defaults {
+            args.push("--no-default-features".to_string());
+        }
+        if !features.is_empty() {
+            args.extend(["--features".to_string(), features.join(",")]);
+        }
+        Ok(args)
+    }
+}
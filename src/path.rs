@@ -0,0 +1,273 @@
+// PATH inspection: which `kopi` a shell will actually run, and fixing the
+// user's shell profile when that isn't the one kipper installed.
+
+use std::env;
+use std::fs::{self, OpenOptions};
+use std::io::{self, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::confirm::Action;
+use crate::platform::{in_nix_store, is_wsl, on_windows_drive};
+use crate::script::{fish_double_quoted, sh_double_quoted};
+use crate::toolchain::exe_name;
+use crate::{Installer, InstallerError};
+
+/// Where a command resolves on PATH relative to the bin dir.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PathStatus {
+    /// The first match on PATH is the one in the bin dir.
+    Ok,
+    /// Nothing on PATH provides the command.
+    Missing,
+    /// Another executable earlier on PATH wins.
+    Shadowed(PathBuf),
+}
+
+pub(crate) fn same_dir(a: &Path, b: &Path) -> bool {
+    match (fs::canonicalize(a), fs::canonicalize(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a == b,
+    }
+}
+
+/// First `name` executable on PATH, ignoring the directory `skip`.
+pub fn find_on_path(name: &str, skip: Option<&Path>) -> Option<PathBuf> {
+    let path = env::var_os("PATH")?;
+    env::split_paths(&path)
+        .filter(|dir| !skip.is_some_and(|skip| same_dir(dir, skip)))
+        .map(|dir| dir.join(exe_name(name)))
+        .find(|candidate| candidate.is_file())
+}
+
+/// The startup file of the user's shell and the line that puts `dir` first on PATH.
+fn shell_profile(dir: &Path) -> Option<(PathBuf, String)> {
+    let home = env::var_os("HOME").map(PathBuf::from)?;
+    let shell = env::var("SHELL").unwrap_or_default();
+    let shell = shell.rsplit('/').next().unwrap_or_default();
+    let dir = dir.display().to_string();
+    let export = format!("export PATH=\"{}:$PATH\"", sh_double_quoted(&dir));
+    Some(match shell {
+        "zsh" => (home.join(".zshrc"), export),
+        "bash" => (home.join(".bashrc"), export),
+        "fish" => (
+            home.join(".config").join("fish").join("config.fish"),
+            format!("fish_add_path --move --prepend \"{}\"", fish_double_quoted(&dir)),
+        ),
+        _ => (home.join(".profile"), export),
+    })
+}
+
+impl Installer {
+    /// Whether running `tool` from a shell reaches kipper's shim.
+    pub fn path_status(&self, tool: &str) -> PathStatus {
+        match find_on_path(tool, None) {
+            None => PathStatus::Missing,
+            Some(found) if found.parent().is_some_and(|dir| same_dir(dir, &self.bin_dir)) => PathStatus::Ok,
+            Some(found) => PathStatus::Shadowed(found),
+        }
+    }
+
+    /// Under WSL, a Windows-side Kopi install reachable through the Windows
+    /// PATH entries WSL appends: `kopi.exe`, or anything named `kopi` on a
+    /// Windows drive.
+    pub fn windows_kopi_on_path(&self) -> Option<PathBuf> {
+        if !is_wsl() {
+            return None;
+        }
+        let path = env::var_os("PATH")?;
+        env::split_paths(&path)
+            .filter(|dir| on_windows_drive(dir))
+            .flat_map(|dir| [dir.join("kopi.exe"), dir.join("kopi")])
+            .find(|candidate| candidate.is_file())
+    }
+
+    /// Warn about a Windows Kopi visible from WSL, which runs against Windows
+    /// paths and environment rather than the Linux toolchain.
+    pub(crate) fn report_wsl_interop(&self) {
+        let Some(windows_kopi) = self.windows_kopi_on_path() else {
+            return;
+        };
+        self.log_warning(&format!(
+            "A Windows Kopi is also on PATH through WSL interop: {}",
+            windows_kopi.display()
+        ));
+        self.log_info("Inside WSL, run `kopi` (the Linux toolchain kipper installed), not `kopi.exe`");
+        self.log_info("To hide Windows programs from WSL's PATH, set appendWindowsPath = false under [interop] in /etc/wsl.conf");
+    }
+
+    /// Explain that `shadow` runs instead of kipper's `kopi`, and offer to put
+    /// the bin dir first in the user's shell profile.
+    pub(crate) fn report_shadowed(&self, shadow: &Path) -> Result<(), InstallerError> {
+        self.log_warning(&format!(
+            "Another kopi comes first on PATH: {} runs instead of {}",
+            shadow.display(),
+            self.bin_dir.join(exe_name("kopi")).display()
+        ));
+        let bin_on_path = env::var_os("PATH")
+            .is_some_and(|path| env::split_paths(&path).any(|dir| same_dir(&dir, &self.bin_dir)));
+        if let Some(dir) = shadow.parent() {
+            if is_wsl() && on_windows_drive(dir) {
+                self.log_info(&format!(
+                    "{} is a Windows directory added to PATH by WSL interop; the Linux toolchain should come first",
+                    dir.display()
+                ));
+            } else if bin_on_path {
+                self.log_info(&format!("{} is listed before {} in PATH", dir.display(), self.bin_dir.display()));
+            } else {
+                self.log_info(&format!("{} is not on PATH at all", self.bin_dir.display()));
+            }
+        }
+
+        if cfg!(windows) {
+            self.log_info(&format!(
+                "Move {} above {} in your user PATH (System Properties > Environment Variables)",
+                self.bin_dir.display(),
+                shadow.parent().unwrap_or(shadow).display()
+            ));
+            return Ok(());
+        }
+
+        let Some((profile, line)) = shell_profile(&self.bin_dir) else {
+            return Ok(());
+        };
+        if in_nix_store(&profile) {
+            self.log_info(&format!(
+                "{} is managed by Nix; add {} to home.sessionPath in your home-manager configuration",
+                profile.display(),
+                self.bin_dir.display()
+            ));
+            return Ok(());
+        }
+        if !self.may_edit_path() || !self.confirm(Action::ModifyPath, &format!("Add '{}' to {}?", line, profile.display()))? {
+            self.log_info(&format!("To fix this, add to {}:", profile.display()));
+            self.print_line(&format!("  {}", line));
+            return Ok(());
+        }
+
+        self.add_to_profile(&profile, &line)?;
+        self.log_success(&format!("Updated {}; open a new shell for it to take effect", profile.display()));
+        Ok(())
+    }
+
+    pub(crate) fn may_edit_path(&self) -> bool {
+        self.policy().is_none_or(|p| p.allows_path_changes())
+    }
+
+    /// Append `line` to `profile`, unless it is already there. Returns
+    /// whether the file was changed.
+    fn add_to_profile(&self, profile: &Path, line: &str) -> Result<bool, InstallerError> {
+        if fs::read_to_string(profile).is_ok_and(|contents| contents.lines().any(|l| l.trim() == line)) {
+            return Ok(false);
+        }
+        if let Some(parent) = profile.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut file = OpenOptions::new().create(true).append(true).open(profile)?;
+        writeln!(file, "\n# Added by kipper: run Kopi's shims before other kopi installs\n{}", line)?;
+        Ok(true)
+    }
+
+    /// The shell startup file kipper can add the bin dir to, with the line to
+    /// add. `None` on Windows and when Nix manages the file.
+    fn editable_profile(&self) -> Option<(PathBuf, String)> {
+        shell_profile(&self.bin_dir).filter(|(profile, _)| !cfg!(windows) && !in_nix_store(profile))
+    }
+
+    /// Add the bin dir to the shell startup file without asking, unless the
+    /// line is already there. Returns whether the file was changed; where
+    /// kipper can't edit PATH, explains how to do it instead.
+    pub(crate) fn ensure_on_path(&self) -> Result<bool, InstallerError> {
+        let Some((profile, line)) = self.editable_profile().filter(|_| self.may_edit_path()) else {
+            self.explain_path_setup();
+            return Ok(false);
+        };
+        if !self.add_to_profile(&profile, &line)? {
+            return Ok(false);
+        }
+        self.log_success(&format!("Added {} to PATH in {}", self.bin_dir.display(), profile.display()));
+        Ok(true)
+    }
+
+    /// Tell the user how to put the bin dir on PATH themselves.
+    pub(crate) fn explain_path_setup(&self) {
+        match self.editable_profile() {
+            Some((profile, line)) => {
+                self.log_info(&format!("To put it on PATH, add this line to {}:", profile.display()));
+                self.print_line(&format!("  {}", self.theme().paint(self.theme().highlight.as_deref(), &line)));
+            }
+            None => self.log_info(&format!(
+                "To put it on PATH, add {} to your user PATH{}",
+                self.bin_dir.display(),
+                if cfg!(windows) { " (System Properties > Environment Variables)" } else { "" }
+            )),
+        }
+    }
+
+    /// After an install from a terminal whose shell can't run `kopi` yet,
+    /// offer to fix that now: add the PATH line to the shell's startup file,
+    /// open a shell that already has the bin dir on PATH so `kopi` can be
+    /// tried straight away, or both. On GitHub Actions, later steps get the
+    /// bin dir on PATH instead.
+    pub fn offer_path_setup(&self) -> Result<(), InstallerError> {
+        self.export_to_github_actions()?;
+        if self.path_status("kopi") != PathStatus::Missing
+            || self.unattended
+            || !io::stdin().is_terminal()
+            || !io::stderr().is_terminal()
+        {
+            return Ok(());
+        }
+        let profile = self.editable_profile().filter(|_| self.may_edit_path());
+
+        self.print_line("");
+        let (add, open_shell) = match &profile {
+            Some((profile, _)) => {
+                self.log_info("Kopi can be set up on PATH now:");
+                self.print_line(&format!("  1) Add the line above to {} for new shells", profile.display()));
+                self.print_line("  2) Open a shell with Kopi on PATH to try it right away");
+                self.print_line("  3) Both");
+                eprint!("Choose 1-3, or press Enter to leave PATH alone: ");
+                io::stderr().flush()?;
+                let mut input = String::new();
+                io::stdin().read_line(&mut input)?;
+                match input.trim() {
+                    "1" => (true, false),
+                    "2" => (false, true),
+                    "3" => (true, true),
+                    _ => (false, false),
+                }
+            }
+            None => (false, self.ask_in_person("Open a shell with Kopi on PATH to try it right away?", true)?),
+        };
+
+        if add && let Some((profile, line)) = &profile {
+            if self.add_to_profile(profile, line)? {
+                self.log_success(&format!("Updated {}; new shells will find kopi", profile.display()));
+            } else {
+                self.log_info(&format!("The PATH line is already in {}; new shells will find kopi", profile.display()));
+            }
+        }
+        if open_shell {
+            self.open_path_shell()?;
+        }
+        Ok(())
+    }
+
+    /// Run the user's shell with the bin dir first on PATH, until they exit it.
+    fn open_path_shell(&self) -> Result<(), InstallerError> {
+        let shell = if cfg!(windows) {
+            env::var_os("COMSPEC").unwrap_or_else(|| "cmd.exe".into())
+        } else {
+            env::var_os("SHELL").filter(|s| !s.is_empty()).unwrap_or_else(|| "/bin/sh".into())
+        };
+        let mut dirs = vec![self.bin_dir.clone()];
+        dirs.extend(env::split_paths(&env::var_os("PATH").unwrap_or_default()));
+        let path = env::join_paths(dirs).map_err(|e| InstallerError::PathError(e.to_string()))?;
+
+        self.log_info("Starting a shell with Kopi on PATH: try `kopi --version`, then `exit` to return");
+        Command::new(&shell).env("PATH", path).status()?;
+        self.log_info("Left the Kopi shell");
+        Ok(())
+    }
+}
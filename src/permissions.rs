@@ -0,0 +1,108 @@
+// Permission problems in the directories kipper writes to: explain who owns
+// what and how to fix it, and only ever elevate after the user agrees.
+
+use std::env;
+use std::fs;
+use std::io::{self, IsTerminal};
+use std::path::Path;
+use std::process::Command;
+
+use crate::script::sh_quote;
+use crate::{Installer, InstallerError};
+
+/// Whether an error means the OS refused access, as opposed to anything else
+/// going wrong with the file.
+pub fn is_permission_denied(error: &InstallerError) -> bool {
+    matches!(error, InstallerError::Io(e) if e.kind() == io::ErrorKind::PermissionDenied)
+}
+
+/// Create `dir` if needed and make sure a file can be written inside it.
+fn try_writable(dir: &Path) -> io::Result<()> {
+    fs::create_dir_all(dir)?;
+    let probe = dir.join(format!(".kipper-write-{}", std::process::id()));
+    fs::write(&probe, b"")?;
+    fs::remove_file(&probe)
+}
+
+/// Whether `path` belongs to root while kipper runs as someone else, the
+/// usual leftover of an earlier `sudo kipper ...`.
+#[cfg(unix)]
+fn owned_by_root(path: &Path) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    let home_uid = env::var_os("HOME").and_then(|home| fs::metadata(home).ok()).map(|m| m.uid());
+    fs::metadata(path).is_ok_and(|m| m.uid() == 0) && home_uid.is_some_and(|uid| uid != 0)
+}
+
+#[cfg(not(unix))]
+fn owned_by_root(_path: &Path) -> bool {
+    false
+}
+
+impl Installer {
+    /// Whether `path` is in one of the directories kipper keeps to itself,
+    /// so that taking it back changes nothing of anyone else's.
+    fn is_kipper_owned(&self, path: &Path) -> bool {
+        [&self.install_dir, &self.bin_dir, &self.cache_dir()].into_iter().any(|dir| path.starts_with(dir))
+    }
+
+    /// Make sure kipper can write to `dir`, creating it if needed. When it
+    /// can't, explain why and, if the user agrees at an interactive prompt,
+    /// take ownership back with `sudo chown`: only of kipper's own
+    /// directories left to root by an earlier `sudo kipper`, never of a
+    /// shared one like `/tmp` or `/usr/local`. Never elevates on its own.
+    pub(crate) fn ensure_writable(&self, dir: &Path) -> Result<(), InstallerError> {
+        match try_writable(dir) {
+            Ok(()) => return Ok(()),
+            Err(e) if e.kind() == io::ErrorKind::PermissionDenied => {}
+            Err(e) => return Err(e.into()),
+        }
+
+        // The directory itself, or the ancestor that stopped it being created
+        let blocked = dir.ancestors().find(|p| p.exists()).unwrap_or(dir);
+        let error = || InstallerError::PathError(format!("No permission to write to {}", blocked.display()));
+
+        if cfg!(windows) {
+            self.log_info(&format!(
+                "Choose a location you own, or run kipper from an elevated prompt (Run as administrator) to use {}",
+                blocked.display()
+            ));
+            return Err(error());
+        }
+
+        if !(self.is_kipper_owned(blocked) && owned_by_root(blocked)) {
+            self.log_info(&format!(
+                "Make {} writable for you, or have kipper use directories you own",
+                blocked.display()
+            ));
+            return Err(error());
+        }
+
+        let user = env::var("USER").or_else(|_| env::var("LOGNAME")).ok();
+        self.log_info(&format!(
+            "{} is owned by root, probably from running kipper with sudo; kipper installs per user and needs root only with --system",
+            blocked.display()
+        ));
+        let fix = format!(
+            "sudo chown -R {} {}",
+            user.as_deref().unwrap_or("$USER"),
+            sh_quote(&blocked.display().to_string())
+        );
+        let Some(user) = user.filter(|_| io::stdin().is_terminal() && self.command_exists("sudo")) else {
+            self.log_info("To fix this, run:");
+            self.print_line(&format!("  {}", fix));
+            return Err(error());
+        };
+
+        if !self.ask_in_person(&format!("Run `{}` now?", fix), false)? {
+            return Err(error());
+        }
+
+        let status = Command::new("sudo").args(["chown", "-R", &user]).arg(blocked).status()?;
+        if !status.success() {
+            return Err(InstallerError::PathError(format!("Could not take ownership of {}", blocked.display())));
+        }
+        try_writable(dir)?;
+        self.log_success(&format!("{} is writable again", blocked.display()));
+        Ok(())
+    }
+}
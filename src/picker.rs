@@ -0,0 +1,98 @@
+// Switching versions (`kipper use [VERSION]`): make an installed version the
+// default. Without a version, from a terminal, the installed versions are
+// listed with their size and install date, the default one marked, and the
+// version is picked by number or name.
+
+use std::io::{self, IsTerminal, Write};
+
+use crate::cache::{dir_size, format_size};
+use crate::state::State;
+use crate::timestamp::display_local;
+use crate::{Installer, InstallerError, toolchain};
+
+impl Installer {
+    /// Make `version` the default, or pick one interactively without it.
+    pub fn use_version(&self, version: Option<&str>) -> Result<(), InstallerError> {
+        let version = match version {
+            Some(version) => toolchain::check_version_name(version).map(|()| self.installed_name(version))?,
+            None => match self.pick_version()? {
+                Some(version) => version,
+                None => return Ok(()),
+            },
+        };
+        if self.default_version().as_deref() == Some(version.as_str()) {
+            self.log_success(&format!("Kopi {} is already the default", version));
+            return Ok(());
+        }
+        self.set_default(&version)?;
+        self.log_success(&format!("Kopi {} is now the default", version));
+        Ok(())
+    }
+
+    /// Ask which installed version to use; `None` keeps the current default.
+    fn pick_version(&self) -> Result<Option<String>, InstallerError> {
+        let versions: Vec<String> = self.list()?.into_iter().map(|toolchain| toolchain.name).collect();
+        if versions.is_empty() {
+            return Err(InstallerError::PathError("No versions of Kopi are installed".to_string()));
+        }
+        if self.unattended || !io::stdin().is_terminal() || !io::stderr().is_terminal() {
+            return Err(InstallerError::PathError(format!(
+                "No version given; use one of: {}",
+                versions.join(", ")
+            )));
+        }
+
+        let current = self.default_version();
+        let state = State::load(&self.install_dir);
+        let width = versions.iter().map(String::len).max().unwrap_or(0);
+        self.log_info("Installed versions:");
+        for (i, version) in versions.iter().enumerate() {
+            let size = dir_size(&self.version_dir(version)).map(format_size).unwrap_or_default();
+            let installed = state
+                .versions
+                .get(version)
+                .and_then(|record| record.installed_at.as_deref())
+                .map(|at| format!("  installed {}", display_local(at)))
+                .unwrap_or_default();
+            let active = current.as_deref() == Some(version.as_str());
+            let line = format!(
+                "{} {:>2}) {:<width$}  {:>10}{}",
+                if active { "*" } else { " " },
+                i + 1,
+                version,
+                size,
+                installed,
+                width = width
+            );
+            if active {
+                self.print_line(&self.theme.paint(self.theme.highlight.as_deref(), &line));
+            } else {
+                self.print_line(&line);
+            }
+        }
+
+        loop {
+            match &current {
+                Some(current) => eprint!("Choose 1-{}, or press Enter to keep {}: ", versions.len(), current),
+                None => eprint!("Choose 1-{}, or press Enter to leave the default unset: ", versions.len()),
+            }
+            io::stderr().flush()?;
+            let mut input = String::new();
+            if io::stdin().read_line(&mut input)? == 0 {
+                return Ok(None);
+            }
+            let input = input.trim();
+            if input.is_empty() {
+                return Ok(None);
+            }
+            let picked = match input.parse::<usize>() {
+                Ok(n) => n.checked_sub(1).and_then(|i| versions.get(i)),
+                Err(_) => versions.iter().find(|version| **version == self.installed_name(input)),
+            };
+            match picked {
+                Some(version) => return Ok(Some(version.clone())),
+                None => self.log_warning(&format!("'{}' is not one of the choices", input)),
+            }
+        }
+    }
+}
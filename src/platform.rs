@@ -0,0 +1,232 @@
+// Platform quirks that change what kipper can do or should suggest.
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The classic Windows path limit, still enforced by tools and APIs that
+/// haven't opted into long paths.
+pub const WINDOWS_MAX_PATH: usize = 260;
+/// Room cargo needs under a scratch dir for its deepest paths, e.g.
+/// `kipper-123\kopi-lang\target\release\build\<crate>-<hash>\out\...`.
+const BUILD_PATH_HEADROOM: usize = 180;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImmutableKind {
+    /// Fedora Silverblue/Kinoite, Fedora CoreOS, Endless OS and other
+    /// rpm-ostree/OSTree systems: packages are layered, not installed.
+    Ostree,
+    /// NixOS: system packages come from the Nix store and configuration.
+    Nix,
+}
+
+/// An operating system whose root filesystem is read-only, so build tools
+/// can't simply be installed into it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImmutableOs {
+    pub kind: ImmutableKind,
+    /// Human-readable name from os-release.
+    pub name: String,
+}
+
+/// A field from /etc/os-release, unquoted.
+fn os_release(key: &str) -> Option<String> {
+    let contents = fs::read_to_string("/etc/os-release")
+        .or_else(|_| fs::read_to_string("/usr/lib/os-release"))
+        .ok()?;
+    contents.lines().find_map(|line| {
+        let value = line.strip_prefix(key)?.strip_prefix('=')?;
+        Some(value.trim_matches('"').to_string())
+    })
+}
+
+/// Whether this process runs inside a toolbox, distrobox or similar
+/// container, where the image's package manager works normally.
+pub fn in_dev_container() -> bool {
+    Path::new("/run/.toolboxenv").exists() || Path::new("/run/.containerenv").exists()
+}
+
+/// Detect an immutable-root distribution. Returns `None` on mutable systems
+/// and inside dev containers running on an immutable host.
+pub fn immutable_os() -> Option<ImmutableOs> {
+    if !cfg!(target_os = "linux") || in_dev_container() {
+        return None;
+    }
+
+    let kind = if Path::new("/run/ostree-booted").exists() {
+        ImmutableKind::Ostree
+    } else if Path::new("/etc/NIXOS").exists() || os_release("ID").as_deref() == Some("nixos") {
+        ImmutableKind::Nix
+    } else {
+        return None;
+    };
+
+    let name = os_release("PRETTY_NAME")
+        .or_else(|| os_release("NAME"))
+        .unwrap_or_else(|| match kind {
+            ImmutableKind::Ostree => "an OSTree-based system".to_string(),
+            ImmutableKind::Nix => "NixOS".to_string(),
+        });
+    Some(ImmutableOs { kind, name })
+}
+
+impl ImmutableOs {
+    /// How to get compilers and -dev packages without touching the read-only root.
+    pub fn build_tools_hint(&self) -> &'static str {
+        match self.kind {
+            ImmutableKind::Ostree => {
+                "Build inside a toolbox or distrobox, where compilers can be installed normally:\n  \
+                 toolbox create && toolbox enter\n  \
+                 sudo dnf install gcc pkgconf-pkg-config && kipper install"
+            }
+            ImmutableKind::Nix => {
+                "Run kipper from a shell that provides the build tools:\n  \
+                 nix-shell -p gcc pkg-config --run 'kipper install'"
+            }
+        }
+    }
+}
+
+/// The BSDs, whose base systems ship BSD make rather than GNU make and whose
+/// Rust usually comes from packages rather than rustup.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Bsd {
+    FreeBsd,
+    OpenBsd,
+    NetBsd,
+    DragonFly,
+}
+
+/// The BSD kipper was built for, if any.
+pub fn bsd() -> Option<Bsd> {
+    match env::consts::OS {
+        "freebsd" => Some(Bsd::FreeBsd),
+        "openbsd" => Some(Bsd::OpenBsd),
+        "netbsd" => Some(Bsd::NetBsd),
+        "dragonfly" => Some(Bsd::DragonFly),
+        _ => None,
+    }
+}
+
+impl Bsd {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Bsd::FreeBsd => "FreeBSD",
+            Bsd::OpenBsd => "OpenBSD",
+            Bsd::NetBsd => "NetBSD",
+            Bsd::DragonFly => "DragonFly BSD",
+        }
+    }
+
+    /// The command that installs `packages` from the system's binary packages.
+    pub fn install_command(&self, packages: &str) -> String {
+        match self {
+            Bsd::FreeBsd | Bsd::DragonFly => format!("pkg install {}", packages),
+            Bsd::OpenBsd => format!("pkg_add {}", packages),
+            Bsd::NetBsd => format!("pkgin install {}", packages),
+        }
+    }
+}
+
+/// How to get Rust on this platform. rustup has no OpenBSD or NetBSD builds,
+/// so those get the system package instead.
+pub fn rust_install_hint() -> String {
+    match bsd() {
+        Some(os @ (Bsd::OpenBsd | Bsd::NetBsd)) => format!("Install Rust with `{}`", os.install_command("rust")),
+        Some(os) => format!("Install Rust with `{}` or from https://rustup.rs/", os.install_command("rust")),
+        None => "Install Rust from https://rustup.rs/".to_string(),
+    }
+}
+
+/// Whether this is Linux running under the Windows Subsystem for Linux.
+pub fn is_wsl() -> bool {
+    if !cfg!(target_os = "linux") {
+        return false;
+    }
+    env::var_os("WSL_DISTRO_NAME").is_some()
+        || Path::new("/proc/sys/fs/binfmt_misc/WSLInterop").exists()
+        || fs::read_to_string("/proc/sys/kernel/osrelease").is_ok_and(|release| release.to_lowercase().contains("microsoft"))
+}
+
+/// Whether `path` is on a Windows drive mounted into WSL (`/mnt/c/...`).
+pub fn on_windows_drive(path: &Path) -> bool {
+    let mut components = path.components().skip(1);
+    components.next().is_some_and(|c| c.as_os_str() == "mnt")
+        && components
+            .next()
+            .and_then(|c| c.as_os_str().to_str())
+            .is_some_and(|drive| drive.len() == 1 && drive.chars().all(|c| c.is_ascii_alphabetic()))
+}
+
+/// Where kipper's scratch build dirs go: the system temp dir, unless on
+/// Windows it is nested so deeply (a long user name is enough) that cargo's
+/// build paths would pass MAX_PATH. Then a short dir on the system drive is
+/// used instead, if it can be created.
+pub fn scratch_root() -> PathBuf {
+    let temp = env::temp_dir();
+    if !cfg!(windows) || temp.as_os_str().len() + BUILD_PATH_HEADROOM <= WINDOWS_MAX_PATH {
+        return temp;
+    }
+    let drive = env::var("SystemDrive").unwrap_or_else(|_| "C:".to_string());
+    let short = PathBuf::from(format!(r"{}\kipper-tmp", drive));
+    if fs::create_dir_all(&short).is_ok() { short } else { temp }
+}
+
+/// `path` with the `\\?\` prefix that lifts MAX_PATH for Windows file APIs
+/// (`\\?\UNC\` for network shares). Only absolute paths without `.` or
+/// `..` components can take the prefix; anything else, and every path on
+/// other platforms, is returned unchanged.
+pub fn extended_length_path(path: &Path) -> PathBuf {
+    if !cfg!(windows) {
+        return path.to_path_buf();
+    }
+    let Some(raw) = path.to_str() else {
+        return path.to_path_buf();
+    };
+    let normalized = raw.replace('/', r"\");
+    if normalized.starts_with(r"\\?\") || normalized.split('\\').any(|part| part == "." || part == "..") {
+        return path.to_path_buf();
+    }
+    if let Some(share) = normalized.strip_prefix(r"\\") {
+        return PathBuf::from(format!(r"\\?\UNC\{}", share));
+    }
+    let bytes = normalized.as_bytes();
+    if bytes.len() >= 3 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':' && bytes[2] == b'\\' {
+        return PathBuf::from(format!(r"\\?\{}", normalized));
+    }
+    path.to_path_buf()
+}
+
+/// Whether file names on this platform's usual filesystems ignore case
+/// (NTFS, APFS), so `Kopi` and `kopi` name the same command.
+pub fn names_fold_case() -> bool {
+    cfg!(windows) || cfg!(target_os = "macos")
+}
+
+/// Whether the filesystem holding the existing directory `dir` ignores case,
+/// checked by creating a file and looking it up under another case.
+pub fn case_insensitive(dir: &Path) -> bool {
+    let probe = dir.join(format!(".kipper-case-{}", std::process::id()));
+    if fs::write(&probe, b"").is_err() {
+        return names_fold_case();
+    }
+    let folded = dir.join(format!(".KIPPER-CASE-{}", std::process::id())).exists();
+    let _ = fs::remove_file(&probe);
+    folded
+}
+
+/// Drop the `Zone.Identifier` stream Windows attaches to downloaded files.
+/// Copies keep it, and on shims it makes SmartScreen question every tool.
+pub fn clear_download_mark(path: &Path) {
+    if cfg!(windows) {
+        let mut stream = path.as_os_str().to_owned();
+        stream.push(":Zone.Identifier");
+        let _ = fs::remove_file(stream);
+    }
+}
+
+/// Whether `path` resolves into the read-only Nix store, as shell startup
+/// files managed by home-manager do.
+pub fn in_nix_store(path: &Path) -> bool {
+    fs::canonicalize(path).is_ok_and(|resolved| resolved.starts_with("/nix/store"))
+}
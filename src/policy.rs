@@ -0,0 +1,111 @@
+// Admin policy (`/etc/kipper/policy.toml`): restrictions for managed machines
+// that per-user settings and command-line flags cannot loosen.
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+use crate::toolchain::NIGHTLY;
+use crate::{Installer, InstallerError};
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Policy {
+    /// The only kopi-lang URLs that may be fetched from. Replaces the official
+    /// repository and any user-configured mirrors when set.
+    pub allowed_sources: Option<Vec<String>>,
+    /// Whether nightly (the unreleased default branch) may be installed.
+    pub allow_nightly: Option<bool>,
+    /// Only build sources with a verified signature, as with `--require-signed`.
+    pub require_signatures: bool,
+    /// Whether kipper may edit shell startup files to change PATH.
+    pub modify_path: Option<bool>,
+}
+
+/// Where the machine-wide policy lives.
+pub fn policy_path() -> PathBuf {
+    if cfg!(windows) {
+        let program_data = env::var_os("ProgramData").unwrap_or_else(|| "C:\\ProgramData".into());
+        PathBuf::from(program_data).join("kipper").join("policy.toml")
+    } else {
+        PathBuf::from("/etc/kipper/policy.toml")
+    }
+}
+
+impl Policy {
+    /// Read the policy file. A missing file means no restrictions; a file that
+    /// exists but can't be read or parsed is an error, so a broken policy
+    /// never silently stops applying.
+    pub fn load() -> Result<Option<Policy>, InstallerError> {
+        let path = policy_path();
+        if !path.exists() {
+            return Ok(None);
+        }
+        let contents = fs::read_to_string(&path)?;
+        let policy: Policy = toml::from_str(&contents)
+            .map_err(|e| InstallerError::PathError(format!("Invalid policy file {}: {}", path.display(), e)))?;
+        if policy.allowed_sources.as_ref().is_some_and(|sources| sources.is_empty()) {
+            return Err(InstallerError::PathError(format!(
+                "Invalid policy file {}: allowed-sources is empty",
+                path.display()
+            )));
+        }
+        Ok(Some(policy))
+    }
+
+    pub fn allows_nightly(&self) -> bool {
+        self.allow_nightly.unwrap_or(true)
+    }
+
+    pub fn allows_path_changes(&self) -> bool {
+        self.modify_path.unwrap_or(true)
+    }
+
+    pub fn allows_source(&self, url: &str) -> bool {
+        self.allowed_sources
+            .as_ref()
+            .is_none_or(|allowed| allowed.iter().any(|a| a == url))
+    }
+}
+
+impl Installer {
+    pub fn policy(&self) -> Option<&Policy> {
+        self.policy.as_ref()
+    }
+
+    /// Stop before building anything the policy forbids.
+    pub(crate) fn enforce_policy(&self, version: &str) -> Result<(), InstallerError> {
+        if let Some(policy) = &self.policy
+            && version == NIGHTLY
+            && !policy.allows_nightly()
+        {
+            self.log_info(&format!("Nightly builds are disabled by {}", policy_path().display()));
+            return Err(InstallerError::PathError("Installing nightly is not allowed on this machine".to_string()));
+        }
+        Ok(())
+    }
+
+    /// Refuse a `--source` the policy doesn't list. Git sources aren't
+    /// checked here: with `allowed-sources` set, they are the allowed ones.
+    pub(crate) fn enforce_source_policy(&self) -> Result<(), InstallerError> {
+        let Some(policy) = &self.policy else {
+            return Ok(());
+        };
+        match self.source.origin() {
+            Some(origin) if !policy.allows_source(&origin) => {
+                self.log_info(&format!("Sources are pinned by {}", policy_path().display()));
+                Err(InstallerError::PathError(format!(
+                    "{} is not an allowed source on this machine",
+                    self.source.describe()
+                )))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    pub(crate) fn signatures_required(&self) -> bool {
+        self.require_signed || self.policy.as_ref().is_some_and(|p| p.require_signatures)
+    }
+}
@@ -0,0 +1,166 @@
+// Build-dependency probes: catch a missing C toolchain or native library in a
+// few seconds, before cargo spends minutes compiling up to the link step.
+
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+use std::time::Duration;
+
+use crate::platform::{bsd, immutable_os};
+use crate::{Installer, InstallerError};
+
+const PROBE_TIMEOUT: Duration = Duration::from_secs(60);
+const PROBE_SOURCE: &str = "int main(void) { return 0; }\n";
+
+/// Native libraries kopi-lang declares in its manifest, as pkg-config names:
+///
+/// ```toml
+/// [package.metadata.kipper]
+/// native-libs = ["openssl", "zlib"]
+/// ```
+///
+/// The same key under `[workspace.metadata.kipper]` is honoured too.
+pub fn declared_native_libs(checkout: &Path) -> Result<Vec<String>, InstallerError> {
+    let Ok(contents) = fs::read_to_string(checkout.join("Cargo.toml")) else {
+        return Ok(Vec::new());
+    };
+    let manifest: toml::Value = toml::from_str(&contents)
+        .map_err(|e| InstallerError::Cargo(format!("Invalid kopi-lang Cargo.toml: {}", e)))?;
+
+    let mut libs = Vec::new();
+    for section in ["package", "workspace"] {
+        let declared = manifest
+            .get(section)
+            .and_then(|s| s.get("metadata"))
+            .and_then(|m| m.get("kipper"))
+            .and_then(|k| k.get("native-libs"))
+            .and_then(|l| l.as_array());
+        for lib in declared.into_iter().flatten().filter_map(|l| l.as_str()) {
+            if !libs.iter().any(|known| known == lib) {
+                libs.push(lib.to_string());
+            }
+        }
+    }
+    Ok(libs)
+}
+
+impl Installer {
+    /// Compile and link a trivial C program with `$CC` (or `cc`), the same way
+    /// rustc will need to link kopi. On success, returns the compiler used.
+    ///
+    /// MSVC targets locate their linker through the Visual Studio install
+    /// rather than PATH, so this is a no-op on Windows.
+    pub fn probe_c_compiler(&self) -> Result<String, String> {
+        if cfg!(windows) {
+            return Ok("MSVC (not probed)".to_string());
+        }
+
+        let compiler = env::var("CC").ok().filter(|cc| !cc.trim().is_empty()).unwrap_or_else(|| "cc".to_string());
+        // CC may carry a wrapper or flags, e.g. `ccache gcc` or `gcc -m32`, as cc-rs allows
+        let mut words = compiler.split_whitespace();
+        let program = words.next().unwrap_or("cc");
+        let flags: Vec<&str> = words.collect();
+        let dir = env::temp_dir().join(format!("kipper-cc-probe-{}", std::process::id()));
+        let result = (|| {
+            fs::create_dir_all(&dir).map_err(|e| format!("could not create {}: {}", dir.display(), e))?;
+            fs::write(dir.join("probe.c"), PROBE_SOURCE).map_err(|e| e.to_string())?;
+
+            let output = self
+                .run_command_with_timeout(
+                    Command::new(program).args(&flags).args(["probe.c", "-o", "probe"]).current_dir(&dir),
+                    Some(PROBE_TIMEOUT),
+                )
+                .map_err(|e| match e {
+                    InstallerError::Io(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                        format!("'{}' not found", program)
+                    }
+                    e => format!("could not run '{}': {:?}", compiler, e),
+                })?;
+            if !output.status.success() {
+                let stderr = String::from_utf8_lossy(&output.stderr);
+                return Err(format!(
+                    "'{}' could not build a test program: {}",
+                    compiler,
+                    stderr.lines().next().unwrap_or("").trim()
+                ));
+            }
+            Ok(compiler.clone())
+        })();
+        let _ = fs::remove_dir_all(&dir);
+        result
+    }
+
+    /// Native libraries from `libs` that pkg-config can't find. `None` when
+    /// pkg-config itself is unavailable and nothing could be checked.
+    fn missing_native_libs(&self, libs: &[String]) -> Option<Vec<String>> {
+        if libs.is_empty() {
+            return Some(Vec::new());
+        }
+        if !self.command_exists("pkg-config") {
+            return None;
+        }
+        Some(
+            libs.iter()
+                .filter(|lib| {
+                    !self
+                        .run_command(Command::new("pkg-config").args(["--exists", lib]))
+                        .is_ok_and(|output| output.status.success())
+                })
+                .cloned()
+                .collect(),
+        )
+    }
+
+    /// Check everything the build needs from outside Rust before cargo runs.
+    pub(crate) fn probe_build_dependencies(&self, checkout: &Path) -> Result<(), InstallerError> {
+        self.log_info("Checking build dependencies...");
+
+        if cfg!(windows) {
+            self.probe_windows_linker()?;
+        }
+
+        if let Err(detail) = self.probe_c_compiler() {
+            self.log_error(&format!("No working C compiler/linker: {}", detail));
+            if let Some(os) = immutable_os() {
+                self.log_info(&format!("Rust needs one to link Kopi, and {} has a read-only root.", os.name));
+                self.print_line(&format!("  {}", os.build_tools_hint()));
+            } else if let Some(os) = bsd() {
+                self.log_info(&format!("Rust needs one to link Kopi. Install one with `{}`", os.install_command("llvm")));
+            } else {
+                self.log_info("Rust needs one to link Kopi. Install your platform's build tools, e.g.:");
+                self.print_line("  Debian/Ubuntu: sudo apt install build-essential");
+                self.print_line("  Fedora:        sudo dnf install gcc");
+                self.print_line("  macOS:         xcode-select --install");
+            }
+            return Err(InstallerError::Cargo("C compiler/linker not available".to_string()));
+        }
+
+        if let Some(os) = bsd()
+            && !self.command_exists("gmake")
+        {
+            self.log_warning("GNU make (gmake) not found; build scripts that run make may fail with BSD make");
+            self.log_info(&format!("Install it with `{}`", os.install_command("gmake")));
+        }
+
+        let libs = declared_native_libs(checkout)?;
+        match self.missing_native_libs(&libs) {
+            None => self.log_warning(&format!(
+                "pkg-config not found; can't check for native libraries: {}",
+                libs.join(", ")
+            )),
+            Some(missing) if !missing.is_empty() => {
+                self.log_error(&format!("Missing native libraries: {}", missing.join(", ")));
+                match immutable_os() {
+                    Some(os) => self.print_line(&format!("  {}", os.build_tools_hint())),
+                    None => self.log_info("Install their development packages (often named lib<name>-dev or <name>-devel) and try again"),
+                }
+                return Err(InstallerError::Cargo(format!("Missing native libraries: {}", missing.join(", "))));
+            }
+            Some(_) => {}
+        }
+
+        self.log_success("Build dependencies found");
+        Ok(())
+    }
+}
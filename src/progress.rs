@@ -0,0 +1,53 @@
+// `--progress-fd N`: progress events as newline-delimited JSON on a file
+// descriptor the caller opened for us, the way apt and pacman frontends read
+// progress without parsing the terminal output.
+
+use std::fs::File;
+use std::io::{self, Write};
+use std::sync::{Arc, Mutex};
+
+use crate::ProgressObserver;
+
+/// An observer writing each event as one JSON line to the inherited
+/// descriptor `fd`. 0, 1 and 2 are refused, since progress would then mix
+/// with normal input and output.
+#[cfg(unix)]
+pub fn progress_fd_observer(fd: i32) -> io::Result<ProgressObserver> {
+    use std::os::fd::FromRawFd;
+    use std::path::Path;
+
+    if fd <= 2 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "use a descriptor other than stdin, stdout or stderr",
+        ));
+    }
+    let open = Path::new("/dev/fd").join(fd.to_string()).exists()
+        || Path::new("/proc/self/fd").join(fd.to_string()).exists();
+    if !open {
+        return Err(io::Error::new(io::ErrorKind::NotFound, format!("descriptor {} is not open", fd)));
+    }
+
+    // SAFETY: the descriptor was checked to be open, and the caller handed
+    // it to kipper for writing progress; nothing else in kipper uses it.
+    let file = unsafe { File::from_raw_fd(fd) };
+    Ok(json_lines_observer(file))
+}
+
+#[cfg(not(unix))]
+pub fn progress_fd_observer(_fd: i32) -> io::Result<ProgressObserver> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "--progress-fd is only available on Unix"))
+}
+
+fn json_lines_observer(file: File) -> ProgressObserver {
+    let file = Mutex::new(file);
+    Arc::new(move |event| {
+        let Ok(line) = serde_json::to_string(event) else {
+            return;
+        };
+        if let Ok(mut file) = file.lock() {
+            // A reader that went away mustn't stop the install
+            let _ = writeln!(file, "{}", line).and_then(|_| file.flush());
+        }
+    })
+}
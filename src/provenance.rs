@@ -0,0 +1,204 @@
+// Source provenance: whether the tag or commit a version was built from
+// carries a valid git signature (GPG or SSH), checked with the keys and
+// allowed signers the user's git is configured to trust.
+//
+// Binaries downloaded prebuilt from the remote cache are checked too, when
+// `[cache] attested-by` names the GitHub repository whose workflows build
+// them: each needs a build provenance attestation from there, verified with
+// `gh attestation verify` against the sigstore bundle shipped next to it
+// (`<binary>.sigstore.json`) or the ones GitHub holds for its digest. A
+// download that fails is thrown away and the version is built locally, as
+// is any unchecked download when signatures are required.
+
+use std::fmt;
+#[cfg(feature = "http")]
+use std::{path::Path, process::Command};
+
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "http")]
+use crate::bincache::entry_binaries;
+use crate::toolchain::NIGHTLY;
+use crate::{Installer, InstallerError};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "lowercase")]
+pub enum Provenance {
+    /// A valid signature from a trusted key.
+    Verified { signer: String },
+    /// Neither the tag nor the commit is signed.
+    Unsigned,
+    /// Signed, but the signature didn't verify (bad, expired or unknown key).
+    Invalid { reason: String },
+}
+
+impl Provenance {
+    pub fn is_verified(&self) -> bool {
+        matches!(self, Provenance::Verified { .. })
+    }
+}
+
+impl fmt::Display for Provenance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Provenance::Verified { signer } => write!(f, "verified (signed by {})", signer),
+            Provenance::Unsigned => write!(f, "unsigned"),
+            Provenance::Invalid { reason } => write!(f, "signature not verified: {}", reason),
+        }
+    }
+}
+
+/// Where prebuilt binaries from the remote cache stand: recorded for the
+/// versions installed from them.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "lowercase")]
+pub enum Attestation {
+    /// Every binary has a verified build provenance attestation from a
+    /// workflow in `repo`.
+    Attested { repo: String },
+    /// Used without a check, as no `[cache] attested-by` was set.
+    Unchecked,
+}
+
+impl fmt::Display for Attestation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Attestation::Attested { repo } => write!(f, "prebuilt, attested by a workflow in {}", repo),
+            Attestation::Unchecked => write!(f, "prebuilt, attestation not checked"),
+        }
+    }
+}
+
+/// Ending of the sigstore bundle that may ship next to a prebuilt binary.
+#[cfg(feature = "http")]
+const BUNDLE_SUFFIX: &str = ".sigstore.json";
+
+/// The signer named in `git verify-* --raw` output, for GPG and SSH signatures.
+fn signer(output: &str) -> String {
+    for line in output.lines() {
+        if let Some(rest) = line.split("GOODSIG ").nth(1) {
+            // "[GNUPG:] GOODSIG <keyid> <user id>"
+            return rest.split_once(' ').map_or(rest, |(_, user)| user).to_string();
+        }
+        if let Some(rest) = line.split("signature for ").nth(1) {
+            // Good "git" signature for <principal> with ED25519 key SHA256:...
+            return rest.split(" with ").next().unwrap_or(rest).to_string();
+        }
+    }
+    "a trusted key".to_string()
+}
+
+impl Installer {
+    /// Refuse to build sources that aren't signed by a trusted key.
+    pub fn with_required_signatures(mut self, required: bool) -> Self {
+        self.require_signed = required;
+        self
+    }
+
+    /// Check the signature on `version`'s annotated tag, or on the checked out
+    /// commit for nightly and lightweight tags.
+    pub(crate) fn source_provenance(&self, version: &str) -> Result<Provenance, InstallerError> {
+        let is_annotated_tag = version != NIGHTLY
+            && self
+                .git_in_checkout(&["cat-file", "-t", &format!("refs/tags/{}", version)])
+                .is_ok_and(|out| String::from_utf8_lossy(&out.stdout).trim() == "tag");
+
+        let (object, verify) = if is_annotated_tag {
+            (format!("refs/tags/{}", version), "verify-tag")
+        } else {
+            ("HEAD".to_string(), "verify-commit")
+        };
+
+        let output = self.git_in_checkout(&[verify, "--raw", &object])?;
+        let report = String::from_utf8_lossy(&output.stderr).into_owned();
+        if output.status.success() {
+            return Ok(Provenance::Verified { signer: signer(&report) });
+        }
+
+        let kind = if is_annotated_tag { "tag" } else { "commit" };
+        let raw = self.git_in_checkout(&["cat-file", kind, &object])?;
+        let raw = String::from_utf8_lossy(&raw.stdout);
+        if !raw.contains("-----BEGIN") {
+            return Ok(Provenance::Unsigned);
+        }
+
+        let reason = report
+            .lines()
+            .find(|line| !line.starts_with("[GNUPG:]") && !line.trim().is_empty())
+            .unwrap_or("unknown key")
+            .trim()
+            .to_string();
+        Ok(Provenance::Invalid { reason })
+    }
+
+    /// With `--require-signed` (or a policy requiring it), stop before building unverified sources.
+    pub(crate) fn enforce_provenance(&self, version: &str) -> Result<(), InstallerError> {
+        if !self.signatures_required() {
+            return Ok(());
+        }
+
+        let provenance = self.source_provenance(version)?;
+        if provenance.is_verified() {
+            self.log_success(&format!("Kopi {} source is {}", version, provenance));
+            return Ok(());
+        }
+
+        self.log_error(&format!("Kopi {} source is {}", version, provenance));
+        self.log_info("Import the kopi-lang signing key into gpg, or list it in gpg.ssh.allowedSignersFile");
+        Err(InstallerError::Git(format!("Refusing to build unverified source for {}", version)))
+    }
+
+    /// Check the attestations of the binaries just downloaded into the cache
+    /// entry `entry`, as `[cache] attested-by` and the signature policy ask.
+    #[cfg(feature = "http")]
+    pub(crate) fn check_attestation(&self, entry: &Path) -> Result<Attestation, InstallerError> {
+        let Some(repo) = self.config.cache.attested_by.as_deref() else {
+            if self.signatures_required() {
+                return Err(InstallerError::PathError(
+                    "signatures are required, and without [cache] attested-by its attestations can't be checked".to_string(),
+                ));
+            }
+            return Ok(Attestation::Unchecked);
+        };
+        if !self.command_exists("gh") {
+            return Err(InstallerError::PathError(
+                "checking its attestations needs the GitHub CLI (gh), which isn't installed".to_string(),
+            ));
+        }
+        let binaries = entry_binaries(entry).ok_or_else(|| InstallerError::PathError("it has no usable build.json".to_string()))?;
+        for binary in &binaries {
+            let mut command = Command::new("gh");
+            command.args(["attestation", "verify"]).arg(entry.join(binary)).args(["--repo", repo]);
+            let bundle = entry.join(format!("{}{}", binary, BUNDLE_SUFFIX));
+            if bundle.is_file() {
+                command.arg("--bundle").arg(&bundle);
+            }
+            if let Some(token) = self.credential("github") {
+                command.env("GH_TOKEN", token);
+            }
+            let output = self.run_command(&mut command)?;
+            if !output.status.success() {
+                let error = String::from_utf8_lossy(&output.stderr);
+                return Err(InstallerError::PathError(format!(
+                    "{} has no attestation from {} that verifies: {}",
+                    binary,
+                    repo,
+                    error.trim()
+                )));
+            }
+        }
+        Ok(Attestation::Attested { repo: repo.to_string() })
+    }
+
+    /// Note where the binaries of the build just made came from: `None` when
+    /// compiled here.
+    pub(crate) fn set_prebuilt_attestation(&self, attestation: Option<Attestation>) {
+        if let Ok(mut prebuilt) = self.prebuilt.lock() {
+            *prebuilt = attestation;
+        }
+    }
+
+    pub(crate) fn prebuilt_attestation(&self) -> Option<Attestation> {
+        self.prebuilt.lock().ok().and_then(|prebuilt| prebuilt.clone())
+    }
+}
@@ -0,0 +1,127 @@
+// Provisioning files (`kipper apply provision.toml`): the state a machine
+// should be in, for fleets set up by MDM or Ansible. Applying a file only
+// changes what differs (a version is rebuilt only when upstream moved or its
+// binaries are gone), so it can run on every check-in:
+//
+//     versions = ["v0.2.0", "stable"]
+//     default = "v0.2.0"         # defaults to the first version
+//     profile = "minimal"        # components, as with --profile
+//     remove-unlisted = true     # uninstall versions not listed
+//     path = "add"               # add the bin dir to the shell startup file,
+//                                # or "leave" PATH alone (the default)
+
+use std::fs;
+use std::path::Path;
+
+use serde::Deserialize;
+
+use crate::path::PathStatus;
+use crate::state::{Profile, State};
+use crate::{Installer, InstallerError};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PathPolicy {
+    #[default]
+    Leave,
+    Add,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Provision {
+    pub versions: Vec<String>,
+    pub default: Option<String>,
+    pub profile: Option<Profile>,
+    pub remove_unlisted: bool,
+    pub path: PathPolicy,
+}
+
+impl Provision {
+    pub fn load(path: &Path) -> Result<Provision, InstallerError> {
+        let invalid = |reason: String| InstallerError::PathError(format!("Invalid provision file {}: {}", path.display(), reason));
+        let provision: Provision = toml::from_str(&fs::read_to_string(path)?).map_err(|e| invalid(e.to_string()))?;
+        if provision.versions.is_empty() {
+            return Err(invalid("`versions` lists no versions".to_string()));
+        }
+        if let Some(default) = &provision.default
+            && !provision.versions.contains(default)
+        {
+            return Err(invalid(format!("default '{}' is not one of `versions`", default)));
+        }
+        Ok(provision)
+    }
+}
+
+impl Installer {
+    /// Bring the machine to the state `provision` describes. Install with the
+    /// provision's profile (see `with_profile`) so versions built with another
+    /// one are rebuilt.
+    pub fn apply(&self, provision: &Provision) -> Result<(), InstallerError> {
+        let mut changes = 0;
+
+        let profile_changed = provision
+            .profile
+            .is_some_and(|profile| State::load(&self.install_dir).profile.is_some_and(|recorded| recorded != profile));
+        let mut wanted = Vec::new();
+        for version in &provision.versions {
+            let version = self.installed_name(&self.resolve_release(version)?);
+            if !wanted.contains(&version) {
+                wanted.push(version);
+            }
+        }
+        let mut missing = Vec::new();
+        for version in &wanted {
+            if profile_changed {
+                missing.push(version.clone());
+                continue;
+            }
+            match self.current_install(version)? {
+                Some(repaired) => {
+                    if !repaired.is_empty() {
+                        self.report_current(version, &repaired);
+                        changes += 1;
+                    }
+                }
+                None => missing.push(version.clone()),
+            }
+        }
+        if !missing.is_empty() {
+            if profile_changed {
+                self.log_info(&format!("Rebuilding with the {} profile", self.effective_profile()));
+            }
+            self.install_versions(&missing)?;
+            changes += missing.len();
+        }
+
+        let default = match &provision.default {
+            Some(default) => self.installed_name(&self.resolve_release(default)?),
+            None => wanted[0].clone(),
+        };
+        if self.default_version().as_deref() != Some(default.as_str()) {
+            self.set_default(&default)?;
+            self.log_success(&format!("Kopi {} is now the default", default));
+            changes += 1;
+        }
+
+        if provision.remove_unlisted {
+            for toolchain in self.list()? {
+                if !wanted.contains(&toolchain.name) {
+                    self.uninstall_version(&toolchain.name, false)?;
+                    changes += 1;
+                }
+            }
+        }
+
+        if provision.path == PathPolicy::Add && self.path_status("kopi") == PathStatus::Missing && self.ensure_on_path()? {
+            changes += 1;
+        }
+
+        match changes {
+            0 => self.log_success("Already in the provisioned state; nothing to do"),
+            1 => self.log_success("Applied 1 change"),
+            n => self.log_success(&format!("Applied {} changes", n)),
+        }
+        Ok(())
+    }
+}
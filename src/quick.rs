@@ -0,0 +1,89 @@
+// `--quick`: get a working kopi as soon as possible, and an optimized one
+// later. A prebuilt build from the built binaries cache or the remote cache
+// is taken when one fits, as always; without one, kopi is compiled with the
+// release profile turned down to what compiles fastest (no optimization, no
+// LTO, many codegen units, incremental), which is minutes faster and runs
+// fine for scripting, only slower.
+//
+// Such a build is marked `unoptimized` in the state and in `kipper list`, is
+// never put in the built binaries cache or uploaded to the remote cache, and
+// is timed apart from full builds so it doesn't skew their estimates. The
+// install ends by suggesting `kipper optimize` (see `optimize`), which
+// rebuilds it in place with the settings below the quick ones.
+
+use std::sync::atomic::Ordering;
+
+use crate::Installer;
+use crate::state::State;
+use crate::toolchain::NIGHTLY;
+
+/// Release profile settings of a quick build, as cargo's environment
+/// overrides, which win over kopi-lang's own `[profile.release]`.
+pub const QUICK_PROFILE_ENV: [(&str, &str); 4] = [
+    ("CARGO_PROFILE_RELEASE_OPT_LEVEL", "0"),
+    ("CARGO_PROFILE_RELEASE_LTO", "off"),
+    ("CARGO_PROFILE_RELEASE_CODEGEN_UNITS", "256"),
+    ("CARGO_PROFILE_RELEASE_INCREMENTAL", "true"),
+];
+
+/// Release profile settings of `kipper optimize`: everything for speed,
+/// whatever kopi-lang's own profile leaves out.
+pub const OPTIMIZED_PROFILE_ENV: [(&str, &str); 4] = [
+    ("CARGO_PROFILE_RELEASE_OPT_LEVEL", "3"),
+    ("CARGO_PROFILE_RELEASE_LTO", "fat"),
+    ("CARGO_PROFILE_RELEASE_CODEGEN_UNITS", "1"),
+    ("CARGO_PROFILE_RELEASE_INCREMENTAL", "false"),
+];
+
+impl Installer {
+    /// Install the fastest build there is, unoptimized if it has to be compiled.
+    pub fn with_quick(mut self, quick: bool) -> Self {
+        self.quick = quick;
+        self
+    }
+
+    /// Whether builds made now are quick ones: with `--quick`, except in
+    /// `kipper optimize`.
+    pub(crate) fn builds_quick(&self) -> bool {
+        self.quick && !self.is_optimizing()
+    }
+
+    /// The environment the build of a version is compiled with, beyond the
+    /// usual: the quick profile with `--quick`, the optimized one in
+    /// `kipper optimize`.
+    pub(crate) fn build_profile_env(&self) -> &'static [(&'static str, &'static str)] {
+        if self.is_optimizing() {
+            &OPTIMIZED_PROFILE_ENV
+        } else if self.quick {
+            &QUICK_PROFILE_ENV
+        } else {
+            &[]
+        }
+    }
+
+    /// Note whether the build just made is a quick one.
+    pub(crate) fn set_built_unoptimized(&self, unoptimized: bool) {
+        self.built_unoptimized.store(unoptimized, Ordering::Relaxed);
+    }
+
+    pub(crate) fn built_unoptimized(&self) -> bool {
+        self.built_unoptimized.load(Ordering::Relaxed)
+    }
+
+    /// Whether the installed `version` is an unoptimized quick build.
+    pub fn is_unoptimized(&self, version: &str) -> bool {
+        State::load(&self.install_dir).versions.get(version).is_some_and(|record| record.unoptimized)
+    }
+
+    /// After a quick install of `version`, say how to get the optimized build.
+    pub(crate) fn suggest_optimizing(&self, version: &str) {
+        if !self.is_unoptimized(version) {
+            return;
+        }
+        let optimize = if version == NIGHTLY { "kipper optimize".to_string() } else { format!("kipper optimize {}", version) };
+        self.log_info(&format!(
+            "Kopi {} is an unoptimized quick build: fine to start with, but slower. Run `{}` when you have a few minutes to replace it with an optimized build",
+            version, optimize
+        ));
+    }
+}
@@ -0,0 +1,142 @@
+// Cache size limit: after an install, evict the least recently used cache
+// entries until the cache fits under `KIPPER_CACHE_MAX`.
+
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use crate::bincache::BIN_CACHE_DIR;
+use crate::cache::{dir_size, format_size, stale_temp_dirs};
+use crate::platform::extended_length_path;
+use crate::size::parse_size;
+use crate::{Installer, InstallerError, timestamp};
+
+/// Maximum size of the cache dir plus leftover build dirs, e.g. `10G`.
+/// Unset means no limit.
+pub const CACHE_MAX_ENV: &str = "KIPPER_CACHE_MAX";
+/// When each cache entry was last used, keyed by its path relative to the cache dir.
+const USAGE_FILE: &str = "usage.json";
+
+fn modified_secs(path: &Path) -> u64 {
+    fs::metadata(path)
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |d| d.as_secs())
+}
+
+pub fn cache_limit() -> Option<u64> {
+    env::var(CACHE_MAX_ENV).ok().and_then(|value| parse_size(&value))
+}
+
+impl Installer {
+    /// Last use of each entry as an RFC 3339 UTC timestamp.
+    fn load_usage(&self) -> BTreeMap<String, String> {
+        fs::read_to_string(self.cache_dir().join(USAGE_FILE))
+            .ok()
+            .and_then(|contents| serde_json::from_str(&contents).ok())
+            .unwrap_or_default()
+    }
+
+    /// Record that the cache entry at `path` was just used.
+    pub(crate) fn touch_cache_entry(&self, path: &Path) {
+        let cache_dir = self.cache_dir();
+        let Ok(key) = path.strip_prefix(&cache_dir) else {
+            return;
+        };
+        let mut usage = self.load_usage();
+        usage.insert(key.to_string_lossy().replace('\\', "/"), timestamp::now_utc());
+        if let Ok(contents) = serde_json::to_string_pretty(&usage) {
+            let _ = fs::write(cache_dir.join(USAGE_FILE), contents + "\n");
+        }
+    }
+
+    /// Kipper's own cache entries: the source cache, each built binaries
+    /// entry, the shared build dir and the container builder's dir. Nothing
+    /// else in the cache dir is touched, as `KIPPER_CACHE_DIR` may point to
+    /// a dir other programs use too.
+    fn own_cache_paths(&self) -> Vec<PathBuf> {
+        let cache_dir = self.cache_dir();
+        let mut paths = vec![self.source_cache()];
+        paths.extend(fs::read_dir(cache_dir.join(BIN_CACHE_DIR)).into_iter().flatten().flatten().map(|entry| entry.path()));
+        paths.push(cache_dir.join("target"));
+        paths.push(cache_dir.join("container"));
+        paths.retain(|path| path.exists());
+        paths
+    }
+
+    /// Cache entries and leftover build dirs, least recently used first,
+    /// with their sizes.
+    fn cache_entries(&self) -> Vec<(PathBuf, u64, u64)> {
+        let cache_dir = self.cache_dir();
+        let usage = self.load_usage();
+        let mut entries = Vec::new();
+
+        for path in self.own_cache_paths() {
+            let key = path.strip_prefix(&cache_dir).map(|k| k.to_string_lossy().replace('\\', "/"));
+            let last_used = key
+                .ok()
+                .and_then(|k| usage.get(&k).and_then(|at| timestamp::parse_timestamp(at)))
+                .map(|at| at.max(0) as u64)
+                .unwrap_or_else(|| modified_secs(&path));
+            entries.push((path.clone(), last_used, dir_size(&path).unwrap_or(0)));
+        }
+        for dir in stale_temp_dirs().unwrap_or_default() {
+            let size = dir_size(&dir).unwrap_or(0);
+            entries.push((dir.clone(), modified_secs(&dir), size));
+        }
+
+        entries.sort_by_key(|(_, last_used, _)| *last_used);
+        entries
+    }
+
+    /// Evict least recently used entries until the cache is under the limit,
+    /// keeping the source cache the current install depends on. Returns the
+    /// bytes freed.
+    pub fn enforce_cache_limit(&self) -> Result<u64, InstallerError> {
+        let Some(limit) = cache_limit() else {
+            return Ok(0);
+        };
+        let entries = self.cache_entries();
+        let mut total: u64 = entries.iter().map(|(_, _, size)| size).sum();
+        if total <= limit {
+            return Ok(0);
+        }
+
+        let keep = self.source_cache();
+        let mut freed = 0;
+        for (path, _, size) in entries {
+            if total <= limit {
+                break;
+            }
+            if path == keep {
+                continue;
+            }
+            let removed = if path.is_dir() {
+                fs::remove_dir_all(extended_length_path(&path))
+            } else {
+                fs::remove_file(&path)
+            };
+            match removed {
+                Ok(()) => {
+                    self.log_info(&format!("Evicted {} from the cache ({})", path.display(), format_size(size)));
+                    total -= size;
+                    freed += size;
+                }
+                Err(e) => self.log_warning(&format!("Could not evict {}: {}", path.display(), e)),
+            }
+        }
+
+        if total > limit {
+            self.log_warning(&format!(
+                "The cache still uses {}, more than {}={}; the source cache is kept because installs need it",
+                format_size(total),
+                CACHE_MAX_ENV,
+                format_size(limit)
+            ));
+        }
+        Ok(freed)
+    }
+}
@@ -0,0 +1,179 @@
+// Release tags: picking the newest kopi-lang release for `latest` and
+// `stable`, with pre-releases (`v0.4.0-rc.1`) ordered before their release
+// and yanked versions passed over.
+
+use std::cmp::Ordering;
+
+use crate::{Installer, InstallerError, toolchain};
+
+/// The newest release tag, pre-releases included only with `--include-prereleases`.
+pub const LATEST: &str = "latest";
+/// The newest release tag that isn't a pre-release.
+pub const STABLE: &str = "stable";
+
+/// One dot-separated part of a pre-release, e.g. `rc` and `1` in `rc.1`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum PreId {
+    Numeric(u64),
+    Text(String),
+}
+
+impl Ord for PreId {
+    /// As in semver: numbers compare numerically and sort before text.
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (PreId::Numeric(a), PreId::Numeric(b)) => a.cmp(b),
+            (PreId::Numeric(_), PreId::Text(_)) => Ordering::Less,
+            (PreId::Text(_), PreId::Numeric(_)) => Ordering::Greater,
+            (PreId::Text(a), PreId::Text(b)) => a.cmp(b),
+        }
+    }
+}
+
+impl PartialOrd for PreId {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// A tag of the form `v1.2.3` or `v1.2.3-rc.1`; the `v` is optional and
+/// build metadata (`+...`) is ignored.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Release {
+    pub major: u64,
+    pub minor: u64,
+    pub patch: u64,
+    pre: Vec<PreId>,
+}
+
+impl Release {
+    pub fn parse(tag: &str) -> Option<Release> {
+        let version = tag.strip_prefix('v').unwrap_or(tag);
+        let version = version.split('+').next()?;
+        let (core, pre) = match version.split_once('-') {
+            Some((core, pre)) => (core, Some(pre)),
+            None => (version, None),
+        };
+
+        let mut numbers = core.split('.').map(|n| n.parse::<u64>().ok());
+        let (Some(Some(major)), Some(Some(minor)), Some(Some(patch)), None) =
+            (numbers.next(), numbers.next(), numbers.next(), numbers.next())
+        else {
+            return None;
+        };
+
+        let pre = match pre {
+            None => Vec::new(),
+            Some(pre) => pre
+                .split('.')
+                .map(|id| match id.parse::<u64>() {
+                    Ok(n) => Some(PreId::Numeric(n)),
+                    Err(_) if !id.is_empty() => Some(PreId::Text(id.to_string())),
+                    Err(_) => None,
+                })
+                .collect::<Option<Vec<_>>>()?,
+        };
+        Some(Release { major, minor, patch, pre })
+    }
+
+    pub fn is_prerelease(&self) -> bool {
+        !self.pre.is_empty()
+    }
+}
+
+impl Ord for Release {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.major, self.minor, self.patch)
+            .cmp(&(other.major, other.minor, other.patch))
+            .then_with(|| match (self.pre.is_empty(), other.pre.is_empty()) {
+                // A pre-release comes before the release it leads up to
+                (true, true) => Ordering::Equal,
+                (true, false) => Ordering::Greater,
+                (false, true) => Ordering::Less,
+                (false, false) => self.pre.cmp(&other.pre),
+            })
+    }
+}
+
+impl PartialOrd for Release {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// The newest release among `tags`; tags that aren't versions are skipped.
+pub fn newest_release<'a>(tags: impl IntoIterator<Item = &'a str>, include_prereleases: bool) -> Option<&'a str> {
+    tags.into_iter()
+        .filter_map(|tag| Release::parse(tag).map(|release| (release, tag)))
+        .filter(|(release, _)| include_prereleases || !release.is_prerelease())
+        .max_by(|(a, _), (b, _)| a.cmp(b))
+        .map(|(_, tag)| tag)
+}
+
+/// The order every list of versions kipper prints is in: releases oldest
+/// first as semver orders them (pre-releases before their release), then
+/// names that aren't releases, such as `nightly` or a branch, by name.
+/// Versions that compare equal (`v1.0.0` and `1.0.0`) fall back to their
+/// names, so the order never depends on where the list came from.
+pub fn compare_versions(a: &str, b: &str) -> Ordering {
+    match (Release::parse(a), Release::parse(b)) {
+        (Some(x), Some(y)) => x.cmp(&y),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
+    }
+    .then_with(|| a.cmp(b))
+}
+
+impl Installer {
+    /// Let `latest` pick pre-releases too.
+    pub fn with_prereleases(mut self, include: bool) -> Self {
+        self.include_prereleases = include;
+        self
+    }
+
+    /// Release tags in the source cache, fetching the newest first.
+    pub fn release_tags(&self) -> Result<Vec<String>, InstallerError> {
+        self.refresh_source_cache(None)?;
+        let output = self.git_in_cache(&["tag", "--list"])?;
+        if !output.status.success() {
+            let error = String::from_utf8_lossy(&output.stderr);
+            return Err(InstallerError::Git(format!("Failed to list tags: {}", error.trim())));
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).lines().map(str::to_string).collect())
+    }
+
+    /// The tag `version` stands for: `latest` and `stable` become the newest
+    /// matching release, anything else is returned as it is.
+    /// In locked mode, `version` has to be pinned instead (see `locked`).
+    pub fn resolve_release(&self, version: &str) -> Result<String, InstallerError> {
+        toolchain::check_version_name(version)?;
+        if self.is_locked() {
+            return self.pin_version(version);
+        }
+        let include_prereleases = match version {
+            LATEST => self.include_prereleases,
+            STABLE => false,
+            _ => return Ok(version.to_string()),
+        };
+
+        if !self.has_git() {
+            return Err(InstallerError::Git("git not found".to_string()));
+        }
+        let tags = self.release_tags()?;
+        let yanked = self.yanked_versions();
+        let candidates = tags
+            .iter()
+            .map(String::as_str)
+            .filter(|tag| !yanked.iter().any(|y| y.version == *tag));
+        let tag = newest_release(candidates, include_prereleases).ok_or_else(|| {
+            InstallerError::Git(format!(
+                "No {}release tags found for '{}'",
+                if include_prereleases { "" } else { "stable " },
+                version
+            ))
+        })?;
+        self.log_info(&format!("{} is {}", version, tag));
+        Ok(tag.to_string())
+    }
+}
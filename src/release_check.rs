@@ -0,0 +1,168 @@
+// `kipper release-check TAG`: for kopi-lang's maintainers, install a
+// candidate tag end to end on this machine the way users will: resolve it,
+// fetch it, build it (or take a prebuilt build, with `--builder prebuilt`),
+// install it, run it, and uninstall it again. Everything happens in a
+// throwaway install dir, so the machine's own install is untouched, and
+// `--source`, `--builder`, `--target` and `--profile` apply as for install.
+//
+// The result is a report with one entry per step, printed as a table or,
+// with `--json`, as one JSON object (`schema` 1; fields are only ever
+// added) for release pipelines to gate on. The command exits 1 when any
+// step failed; steps after a failure are skipped, except that something
+// installed is always uninstalled.
+
+use std::env;
+use std::path::Path;
+use std::process::Command;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+use crate::toolchain::exe_name;
+use crate::upstream::KIPPER_VERSION;
+use crate::{Installer, InstallerError};
+
+pub const REPORT_SCHEMA: u32 = 1;
+/// Longest the installed kopi may take to print its version.
+const SMOKE_TEST_TIMEOUT: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ReleaseReport {
+    pub schema: u32,
+    pub tag: String,
+    /// What the tag resolved to.
+    pub version: Option<String>,
+    pub commit: Option<String>,
+    pub kopi_version: Option<String>,
+    pub kipper: String,
+    pub os: String,
+    pub arch: String,
+    /// The target triple built for.
+    pub target: Option<String>,
+    pub passed: bool,
+    pub seconds: f64,
+    pub steps: Vec<ReleaseStep>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ReleaseStep {
+    pub name: String,
+    pub passed: bool,
+    pub seconds: f64,
+    /// What the step found, or why it failed.
+    pub detail: String,
+}
+
+fn seconds(elapsed: Duration) -> f64 {
+    (elapsed.as_secs_f64() * 100.0).round() / 100.0
+}
+
+/// What went wrong, without the error's variant around it.
+fn failure(error: &InstallerError) -> String {
+    match error {
+        InstallerError::Io(e) => e.to_string(),
+        InstallerError::Git(message) | InstallerError::Cargo(message) | InstallerError::PathError(message) => {
+            message.trim_end().to_string()
+        }
+        InstallerError::Cancelled => "cancelled".to_string(),
+    }
+}
+
+/// Run step `name`, recording how it went; returns its result when it passed.
+fn run_step<T>(
+    steps: &mut Vec<ReleaseStep>,
+    name: &str,
+    step: impl FnOnce() -> Result<(T, String), InstallerError>,
+) -> Option<T> {
+    let started = Instant::now();
+    let (passed, detail, value) = match step() {
+        Ok((value, detail)) => (true, detail, Some(value)),
+        Err(e) => (false, failure(&e), None),
+    };
+    steps.push(ReleaseStep {
+        name: name.to_string(),
+        passed,
+        seconds: seconds(started.elapsed()),
+        detail,
+    });
+    value
+}
+
+impl Installer {
+    /// Install `tag` and take it out again, step by step. Meant for an
+    /// installer made with `Installer::scratch`.
+    pub fn release_check(&self, tag: &str) -> ReleaseReport {
+        let started = Instant::now();
+        let mut steps = Vec::new();
+        let mut record = None;
+
+        let version = run_step(&mut steps, "resolve", || {
+            let version = self.resolve_release(tag)?;
+            let detail = if version == tag { version.clone() } else { format!("{} is {}", tag, version) };
+            Ok((version, detail))
+        });
+        let installed = version.as_deref().and_then(|version| {
+            run_step(&mut steps, "fetch", || {
+                self.prepare_install()?;
+                self.clone_source(Some(version))?;
+                let source = self.source_record()?;
+                let detail = format!("commit {}", source.commit);
+                record = Some(source);
+                Ok(((), detail))
+            })?;
+            run_step(&mut steps, "build", || {
+                self.build_source(version)?;
+                Ok(((), format!("with the {} builder", self.builder.name())))
+            })?;
+            run_step(&mut steps, "install", || {
+                self.install_binary(version)?;
+                Ok((version.to_string(), self.version_dir(version).display().to_string()))
+            })
+        });
+        if let Some(version) = &installed {
+            let kopi = self.version_dir(version).join(exe_name("kopi"));
+            run_step(&mut steps, "smoke-test", || self.smoke_test(&kopi).map(|detail| ((), detail)));
+            run_step(&mut steps, "uninstall", || {
+                self.uninstall_version(version, true)?;
+                if self.version_dir(version).exists() {
+                    return Err(InstallerError::PathError(format!("{} is still there", self.version_dir(version).display())));
+                }
+                Ok(((), "removed".to_string()))
+            });
+        }
+
+        let passed = installed.is_some() && steps.iter().all(|step| step.passed);
+        ReleaseReport {
+            schema: REPORT_SCHEMA,
+            tag: tag.to_string(),
+            version,
+            commit: record.as_ref().map(|record| record.commit.clone()),
+            kopi_version: record.and_then(|record| record.kopi_version),
+            kipper: KIPPER_VERSION.to_string(),
+            os: env::consts::OS.to_string(),
+            arch: env::consts::ARCH.to_string(),
+            target: self.build_target().map(str::to_string).or_else(|| self.rustc_host()),
+            passed,
+            seconds: seconds(started.elapsed()),
+            steps,
+        }
+    }
+
+    /// Run the `kopi` at `kopi` with `--version`; returns what it printed.
+    pub(crate) fn smoke_test(&self, kopi: &Path) -> Result<String, InstallerError> {
+        let output = self.run_command_with_timeout(Command::new(kopi).arg("--version"), Some(SMOKE_TEST_TIMEOUT))?;
+        let printed = String::from_utf8_lossy(&output.stdout).lines().next().unwrap_or_default().trim().to_string();
+        if !output.status.success() {
+            let error = String::from_utf8_lossy(&output.stderr);
+            return Err(InstallerError::PathError(format!(
+                "`kopi --version` exited with {}: {}",
+                output.status,
+                error.trim_end()
+            )));
+        }
+        if printed.is_empty() {
+            return Err(InstallerError::PathError("`kopi --version` printed nothing".to_string()));
+        }
+        Ok(format!("kopi --version: {}", printed))
+    }
+}
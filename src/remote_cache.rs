@@ -0,0 +1,216 @@
+// Remote build cache: an org-internal HTTP store (an S3 bucket behind a
+// gateway, Artifactory, a plain WebDAV share) that shares built binaries
+// between machines, so a private fork is compiled once per commit and target
+// rather than once per machine. Set in kipper.toml:
+//
+//     [cache]
+//     remote = "https://artifacts.example.com/kipper"
+//     upload = true        # also upload what this machine builds
+//
+// Entries of the local binary cache (see `bincache`) are fetched with GET and
+// uploaded with PUT as `<remote>/<entry>.tar.gz`, with the remote-cache
+// credential as a bearer token when there is one (KIPPER_REMOTE_CACHE_TOKEN or
+// `kipper auth login remote-cache`). What the store serves is installed as it
+// is, so it needs to be as trusted as the kopi-lang repository, unless
+// `[cache] attested-by` has each download's attestations checked (see
+// `provenance`). Next to the entries, the store may hold zstd patches from
+// one entry to the next, the delta updates for prebuilt binaries, which are
+// downloaded instead when this machine has the earlier one (see `delta`).
+//
+// Builds without the `http` feature, such as the static bootstrap binary,
+// have no HTTP client: they say the remote cache is skipped and build locally.
+
+use std::path::{Path, PathBuf};
+#[cfg(feature = "http")]
+use std::{
+    fs,
+    io::{self, Read},
+    time::Duration,
+};
+
+#[cfg(feature = "http")]
+use flate2::{Compression, read::GzDecoder, write::GzEncoder};
+#[cfg(feature = "http")]
+use reqwest::StatusCode;
+#[cfg(feature = "http")]
+use reqwest::blocking::RequestBuilder;
+
+use crate::Installer;
+#[cfg(feature = "http")]
+use crate::bincache::mark_prebuilt;
+#[cfg(not(feature = "http"))]
+use crate::features::without_feature;
+#[cfg(feature = "http")]
+use crate::provenance::Attestation;
+
+pub const REMOTE_CACHE_TOKEN_ENV: &str = "KIPPER_REMOTE_CACHE_TOKEN";
+
+/// Binaries are a few megabytes; this is for slow links, not hung servers.
+#[cfg(feature = "http")]
+const TRANSFER_TIMEOUT: Duration = Duration::from_secs(300);
+
+impl Installer {
+    #[cfg(feature = "http")]
+    fn authorized(&self, request: RequestBuilder) -> RequestBuilder {
+        match self.credential("remote-cache") {
+            Some(token) => request.bearer_auth(token),
+            None => request,
+        }
+    }
+
+    /// The URL of the local cache entry `entry` in the remote cache, if one is configured.
+    fn remote_cache_url(&self, entry: &Path) -> Option<String> {
+        self.remote_cache_file(&entry.file_name()?.to_string_lossy())
+    }
+
+    /// The URL of archive `name` (without `.tar.gz`) in the remote cache.
+    pub(crate) fn remote_cache_file(&self, name: &str) -> Option<String> {
+        let remote = self.config.cache.remote.as_deref()?;
+        Some(format!("{}/{}.tar.gz", remote.trim_end_matches('/'), name))
+    }
+}
+
+#[cfg(not(feature = "http"))]
+impl Installer {
+    pub(crate) fn download_cached_build(&self, entry: &Path) -> bool {
+        if self.remote_cache_url(entry).is_some() {
+            self.log_warning(&format!("{}; building locally", without_feature("The remote cache", "http")));
+        }
+        false
+    }
+
+    pub(crate) fn upload_cached_build(&self, _entry: &Path) {}
+}
+
+#[cfg(feature = "http")]
+impl Installer {
+    /// Fetch `entry` from the remote cache into the local one. Returns
+    /// whether it is now there; anything but "not in the remote cache" is
+    /// reported as a warning, and the build goes ahead as usual.
+    pub(crate) fn download_cached_build(&self, entry: &Path) -> bool {
+        let Some(url) = self.remote_cache_url(entry) else {
+            return false;
+        };
+        if self.is_locked() {
+            self.log_info("Not using the remote cache with --locked-installer; building from the pinned source");
+            return false;
+        }
+        self.log_info(&format!("Looking for a prebuilt Kopi in {}...", url));
+        let downloaded = self.download_delta(entry)
+            || match self.try_download_cached_build(&url, entry) {
+                Ok(true) => {
+                    self.log_success("Downloaded a prebuilt Kopi from the remote cache");
+                    true
+                }
+                Ok(false) => {
+                    self.log_info("Not in the remote cache yet");
+                    false
+                }
+                Err(e) => {
+                    self.log_warning(&format!("Could not use the remote cache: {}", e));
+                    false
+                }
+            };
+        downloaded && self.accept_download(entry)
+    }
+
+    /// Check the attestations of the entry just downloaded to `entry`, and
+    /// note the outcome there. An entry that fails is removed, so the
+    /// version is built locally instead.
+    fn accept_download(&self, entry: &Path) -> bool {
+        let checked = self
+            .check_attestation(entry)
+            .and_then(|attestation| Ok(mark_prebuilt(entry, &attestation).map(|()| attestation)?));
+        match checked {
+            Ok(attestation) => {
+                if let Attestation::Attested { repo } = &attestation {
+                    self.log_success(&format!("The downloaded binaries are attested by a workflow in {}", repo));
+                }
+                true
+            }
+            Err(e) => {
+                self.log_warning(&format!("Not using the prebuilt Kopi: {:?}; building locally", e));
+                let _ = fs::remove_dir_all(entry);
+                false
+            }
+        }
+    }
+
+    fn try_download_cached_build(&self, url: &str, entry: &Path) -> io::Result<bool> {
+        let Some(archive) = self.fetch_archive(url)? else {
+            return Ok(false);
+        };
+        // Unpacked next to the entry and renamed, like local entries
+        let partial = sibling(entry, ".download");
+        unpack_archive(&archive, &partial)?;
+        if entry.exists() {
+            fs::remove_dir_all(entry)?;
+        }
+        fs::rename(&partial, entry)?;
+        Ok(true)
+    }
+
+    /// GET `url` from the remote cache; `None` when it isn't there.
+    pub(crate) fn fetch_archive(&self, url: &str) -> io::Result<Option<Vec<u8>>> {
+        let client = self.http_client(TRANSFER_TIMEOUT).map_err(io::Error::other)?;
+        let response = self.authorized(client.get(url)).send().map_err(io::Error::other)?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        let mut archive = Vec::new();
+        response.error_for_status().map_err(io::Error::other)?.read_to_end(&mut archive)?;
+        Ok(Some(archive))
+    }
+
+    /// PUT the contents of `dir` to `url` in the remote cache, as a .tar.gz.
+    pub(crate) fn put_archive(&self, url: &str, dir: &Path) -> io::Result<()> {
+        let mut builder = tar::Builder::new(GzEncoder::new(Vec::new(), Compression::default()));
+        builder.append_dir_all(".", dir)?;
+        let archive = builder.into_inner()?.finish()?;
+
+        let client = self.http_client(TRANSFER_TIMEOUT).map_err(io::Error::other)?;
+        self.authorized(client.put(url))
+            .header(reqwest::header::CONTENT_TYPE, "application/gzip")
+            .body(archive)
+            .send()
+            .and_then(|response| response.error_for_status())
+            .map_err(io::Error::other)?;
+        Ok(())
+    }
+
+    /// Upload `entry` to the remote cache when `[cache] upload` is on.
+    pub(crate) fn upload_cached_build(&self, entry: &Path) {
+        if !self.config.cache.upload {
+            return;
+        }
+        let Some(url) = self.remote_cache_url(entry) else {
+            return;
+        };
+        match self.put_archive(&url, entry) {
+            Ok(()) => self.log_success(&format!("Uploaded the build to {}", url)),
+            Err(e) => {
+                self.log_warning(&format!("Could not upload the build to the remote cache: {}", e));
+                return;
+            }
+        }
+        self.upload_delta(entry);
+    }
+}
+
+/// Unpack the .tar.gz `archive` into `dest`, replacing whatever is there.
+#[cfg(feature = "http")]
+pub(crate) fn unpack_archive(archive: &[u8], dest: &Path) -> io::Result<()> {
+    if dest.exists() {
+        fs::remove_dir_all(dest)?;
+    }
+    fs::create_dir_all(dest)?;
+    // unpack() refuses paths that would land outside `dest`
+    tar::Archive::new(GzDecoder::new(archive)).unpack(dest)
+}
+
+/// `path` with `suffix` appended to its file name.
+pub(crate) fn sibling(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(suffix);
+    PathBuf::from(name)
+}
@@ -0,0 +1,99 @@
+// Re-runs: recognising an installed version that already matches upstream by
+// commit, and restoring what an install puts around it (shims, the default
+// version, the uninstaller) without rebuilding. This is what lets plain
+// `kipper` and `kipper apply` run on every boot.
+
+use std::fs;
+
+use crate::state::State;
+use crate::toolchain::{NIGHTLY, exe_name};
+use crate::{Installer, InstallerError, shim};
+
+impl Installer {
+    /// The commit `version` stands for upstream, fetching it into the source
+    /// cache if needed. Nightly is the default branch.
+    pub(crate) fn upstream_commit(&self, version: &str) -> Result<String, InstallerError> {
+        self.refresh_source_cache(Some(version))?;
+        let rev = if version == NIGHTLY { "HEAD".to_string() } else { format!("{}^{{commit}}", version) };
+        let output = self.git_in_cache(&["rev-parse", "--verify", "--quiet", &rev])?;
+        if !output.status.success() {
+            return Err(InstallerError::Git(format!("Failed to check out {}: no such tag or branch", version)));
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    }
+
+    /// Whether `version` is installed from the commit it stands for upstream.
+    /// If so, anything missing around it is repaired and the repairs are
+    /// returned; `None` means it has to be (re)built.
+    pub(crate) fn current_install(&self, version: &str) -> Result<Option<Vec<String>>, InstallerError> {
+        if !self.version_dir(version).join(exe_name("kopi")).exists() || !self.has_git() {
+            return Ok(None);
+        }
+        let Some(record) = State::load(&self.install_dir).versions.remove(version) else {
+            return Ok(None);
+        };
+        // A full install replaces a quick build of the same commit
+        if record.unoptimized && !self.quick {
+            return Ok(None);
+        }
+        let installed = record.commit;
+        let upstream = self.upstream_commit(version)?;
+        if installed != upstream {
+            self.log_info(&format!(
+                "Kopi {} was built from {}, upstream is at {}",
+                version,
+                short(&installed),
+                short(&upstream)
+            ));
+            return Ok(None);
+        }
+        self.repair_install(version).map(Some)
+    }
+
+    /// Say that `version` needed no rebuild, and what was repaired instead.
+    pub(crate) fn report_current(&self, version: &str, repaired: &[String]) {
+        if repaired.is_empty() {
+            self.log_success(&format!("Kopi {} is up to date; nothing to do", version));
+        } else {
+            self.log_success(&format!("Kopi {} is up to date; restored its {}", version, repaired.join(", ")));
+        }
+    }
+
+    /// Restore the shims, default version and uninstaller of an install,
+    /// returning what had to be restored.
+    pub(crate) fn repair_install(&self, version: &str) -> Result<Vec<String>, InstallerError> {
+        let mut repaired = Vec::new();
+        let version_dir = self.version_dir(version);
+
+        let mut shims: Vec<String> = self
+            .tools()
+            .into_iter()
+            .filter(|tool| version_dir.join(exe_name(tool)).exists())
+            .collect();
+        shims.extend(State::load(&self.install_dir).aliases.into_keys());
+        // fs::metadata follows the link, so a dangling shim counts as missing
+        let host = self.install_dir.join(exe_name(shim::SHIM_HOST));
+        if !host.exists() || shims.iter().any(|name| fs::metadata(self.exposed_path(name)).is_err()) {
+            fs::create_dir_all(&self.bin_dir)?;
+            self.install_shims(&version_dir)?;
+            for alias in State::load(&self.install_dir).aliases.keys() {
+                self.expose(alias)?;
+            }
+            repaired.push("shims".to_string());
+        }
+
+        if self.default_version().is_none() {
+            self.set_default(version)?;
+            repaired.push("default version".to_string());
+        }
+        if !self.uninstaller_path().exists() {
+            self.create_uninstaller()?;
+            repaired.push("uninstaller".to_string());
+        }
+        Ok(repaired)
+    }
+}
+
+fn short(commit: &str) -> &str {
+    commit.get(..10).unwrap_or(commit)
+}
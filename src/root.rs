@@ -0,0 +1,103 @@
+// Running as root on Unix. `sudo kipper` or a root shell installs into
+// /root/.kopi and /root/.local/bin, where the user's own shell never looks,
+// and with a sudo that keeps HOME it leaves root-owned files in the user's
+// home that later runs can't write to. So kipper stops when it finds itself
+// running as root, unless one of these says that is meant:
+//
+//     --system        install for every user: versions in /opt/kopi and
+//                     shims in /usr/local/bin
+//     --allow-root    install for root, as in containers and CI images
+//                     where root is the only user (or KIPPER_ALLOW_ROOT=1)
+//
+// At a terminal it asks instead of stopping. Commands that only read, such
+// as `info` or `which`, run as root without asking.
+
+use std::env;
+use std::io::{self, IsTerminal};
+use std::path::Path;
+
+use crate::{Installer, InstallerError};
+
+/// Where `--system` installs versions, the shim host and settings.
+pub const SYSTEM_INSTALL_DIR: &str = "/opt/kopi";
+/// Where `--system` puts shims, on every user's PATH already.
+pub const SYSTEM_BIN_DIR: &str = "/usr/local/bin";
+/// Set to allow running as root without `--allow-root`, for Dockerfiles
+/// piping the install script into sh.
+pub const ALLOW_ROOT_ENV: &str = "KIPPER_ALLOW_ROOT";
+
+/// Whether this process runs with root's user id.
+#[cfg(unix)]
+pub fn running_as_root() -> bool {
+    use std::os::unix::fs::MetadataExt;
+    // /proc/self belongs to the effective user; elsewhere ask id(1)
+    if let Ok(metadata) = std::fs::metadata("/proc/self") {
+        return metadata.uid() == 0;
+    }
+    std::process::Command::new("id")
+        .arg("-u")
+        .output()
+        .is_ok_and(|output| String::from_utf8_lossy(&output.stdout).trim() == "0")
+}
+
+#[cfg(not(unix))]
+pub fn running_as_root() -> bool {
+    false
+}
+
+/// Whether this binary is the machine-wide install's shim host, so shims
+/// run by any user find the versions in /opt/kopi.
+pub fn running_system_host() -> bool {
+    cfg!(unix)
+        && env::current_exe()
+            .ok()
+            .is_some_and(|exe| exe.parent() == Some(Path::new(SYSTEM_INSTALL_DIR)))
+}
+
+fn allowed_by_env() -> bool {
+    env::var(ALLOW_ROOT_ENV).is_ok_and(|value| !value.is_empty() && value != "0")
+}
+
+impl Installer {
+    /// Whether this installer manages the machine-wide install.
+    pub fn is_system_install(&self) -> bool {
+        self.install_dir == Path::new(SYSTEM_INSTALL_DIR)
+    }
+
+    /// Before changing anything: stop a run as root that isn't meant to be
+    /// one (asking first at a terminal), and a machine-wide change without
+    /// root.
+    pub fn guard_root(&self, allow_root: bool) -> Result<(), InstallerError> {
+        let root = running_as_root();
+        if self.is_system_install() {
+            if root {
+                return Ok(());
+            }
+            self.log_info(&format!("Changes to the install in {} for all users need root; run kipper with sudo", SYSTEM_INSTALL_DIR));
+            return Err(InstallerError::PathError(format!("No permission to change {}", SYSTEM_INSTALL_DIR)));
+        }
+        if !root || allow_root || allowed_by_env() {
+            return Ok(());
+        }
+
+        match env::var("SUDO_USER").ok().filter(|user| !user.is_empty() && user != "root") {
+            Some(user) => self.log_warning(&format!(
+                "kipper is running as root through sudo, so it would install into {} for root rather than for {}",
+                self.install_dir.display(),
+                user
+            )),
+            None => self.log_warning(&format!(
+                "kipper is running as root, so it would install into {} and {} for root only",
+                self.install_dir.display(),
+                self.bin_dir.display()
+            )),
+        }
+        if io::stdin().is_terminal() && !self.force && !self.unattended && self.ask("Install for root anyway?", false)? {
+            return Ok(());
+        }
+        self.log_info("kipper installs per user and needs no root: run it as yourself, without sudo");
+        self.log_info(&format!("To install for all users into {}, use --system", SYSTEM_INSTALL_DIR));
+        self.log_info(&format!("Where root is the only user, as in containers, use --allow-root or set {}=1", ALLOW_ROOT_ENV));
+        Err(InstallerError::PathError("Refusing to run as root without --system or --allow-root".to_string()))
+    }
+}
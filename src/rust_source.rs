@@ -0,0 +1,184 @@
+// Where Rust comes from. Without rustup (Rust from Homebrew, asdf or the
+// distribution) there is nothing to switch toolchains with, so kopi-lang's
+// rust-toolchain.toml can't be honoured, and `rustup ...` advice would fight
+// the version manager; Homebrew's rust formula even conflicts with rustup's
+// proxies. The provider is worked out from where the `cargo` on PATH lives,
+// the minimum Rust version kopi-lang declares (`rust-version`, or a version
+// pinned as its toolchain channel) is checked against the rustc that will
+// build it, and a version that is too old gets advice in the provider's
+// terms. asdf users get the pinned version picked for them when they have
+// it installed.
+
+use std::env;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::path::find_on_path;
+use crate::rustup::ToolchainRequest;
+use crate::{Installer, InstallerError};
+
+/// What installed the Rust that builds Kopi.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RustProvider {
+    Rustup,
+    Homebrew,
+    Asdf,
+    /// The distribution's package, in /usr.
+    System,
+    Other,
+}
+
+impl RustProvider {
+    pub fn name(self) -> &'static str {
+        match self {
+            RustProvider::Rustup => "rustup",
+            RustProvider::Homebrew => "Homebrew",
+            RustProvider::Asdf => "asdf",
+            RustProvider::System => "the system package",
+            RustProvider::Other => "an unknown source",
+        }
+    }
+
+    /// How to get Rust `version` or newer from this provider.
+    pub fn upgrade_advice(self, version: &str) -> String {
+        match self {
+            RustProvider::Rustup => "Run `rustup update` and try again".to_string(),
+            RustProvider::Homebrew => "Run `brew upgrade rust` and try again".to_string(),
+            RustProvider::Asdf => format!(
+                "Run `asdf install rust {}` and try again; kipper builds with it once it is installed",
+                version
+            ),
+            RustProvider::System => format!(
+                "Your distribution's Rust is older than {}; install a newer one from https://rustup.rs",
+                version
+            ),
+            RustProvider::Other => format!("Install Rust {} or newer and try again", version),
+        }
+    }
+}
+
+impl fmt::Display for RustProvider {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+fn asdf_dir() -> Option<PathBuf> {
+    env::var_os("ASDF_DATA_DIR")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".asdf")))
+}
+
+/// The provider of the Rust whose cargo is at `cargo`.
+fn provider_of(cargo: &Path) -> RustProvider {
+    let real = fs::canonicalize(cargo).unwrap_or_else(|_| cargo.to_path_buf());
+    let text = real.to_string_lossy();
+    if asdf_dir().is_some_and(|dir| cargo.starts_with(&dir) || real.starts_with(&dir)) {
+        RustProvider::Asdf
+    } else if text.contains("/Cellar/rust/")
+        || env::var_os("HOMEBREW_PREFIX").is_some_and(|prefix| cargo.starts_with(prefix))
+    {
+        RustProvider::Homebrew
+    } else if real.file_stem().is_some_and(|stem| stem == "rustup") {
+        // rustup's proxies are links to rustup itself
+        RustProvider::Rustup
+    } else if real.starts_with("/usr") {
+        RustProvider::System
+    } else {
+        RustProvider::Other
+    }
+}
+
+/// `1.80` or `1.80.1` as numbers, ignoring any pre-release suffix.
+fn parse_version(version: &str) -> Option<(u64, u64, u64)> {
+    let version = version.split(['-', '+']).next()?;
+    let mut parts = version.split('.').map(|part| part.parse::<u64>().ok());
+    let major = parts.next()??;
+    let minor = parts.next()??;
+    let patch = parts.next().unwrap_or(Some(0))?;
+    Some((major, minor, patch))
+}
+
+/// The lowest Rust version the checkout builds with: its `rust-version`,
+/// or its toolchain channel when that is a version number, whichever is
+/// higher.
+pub fn minimum_rust_version(checkout: &Path) -> Option<String> {
+    let manifest = fs::read_to_string(checkout.join("Cargo.toml")).ok()?.parse::<toml::Table>().ok();
+    let declared = manifest.as_ref().and_then(|manifest| {
+        let package = manifest.get("package").and_then(|package| package.get("rust-version"));
+        let workspace = manifest
+            .get("workspace")
+            .and_then(|workspace| workspace.get("package"))
+            .and_then(|package| package.get("rust-version"));
+        package.or(workspace)?.as_str().map(str::to_string)
+    });
+    let pinned = ToolchainRequest::load(checkout)
+        .ok()
+        .flatten()
+        .and_then(|request| request.channel)
+        .filter(|channel| parse_version(channel).is_some());
+    [declared, pinned].into_iter().flatten().max_by_key(|version| parse_version(version))
+}
+
+impl Installer {
+    /// Who provides the Rust kipper builds with.
+    pub fn rust_provider(&self) -> Option<RustProvider> {
+        if self.has_rustup() {
+            return Some(RustProvider::Rustup);
+        }
+        find_on_path("cargo", None).map(|cargo| provider_of(&cargo))
+    }
+
+    /// Point asdf's shims at the Rust version the checkout pins, when asdf
+    /// has it installed; its own `.tool-versions` choice applies otherwise.
+    pub(crate) fn select_asdf_rust(&self, checkout: &Path, command: &mut Command) {
+        if self.rust_provider() != Some(RustProvider::Asdf) || env::var_os("ASDF_RUST_VERSION").is_some() {
+            return;
+        }
+        let Some(version) = minimum_rust_version(checkout) else {
+            return;
+        };
+        let installed = self
+            .run_command(Command::new("asdf").args(["where", "rust", &version]))
+            .is_ok_and(|output| output.status.success());
+        if installed {
+            self.log_info(&format!("Building with Rust {} from asdf", version));
+            command.env("ASDF_RUST_VERSION", version);
+        }
+    }
+
+    /// Fail early, with advice for whoever provides Rust here, when the
+    /// rustc that builds the checkout is older than kopi-lang needs.
+    pub(crate) fn check_rust_version(&self, checkout: &Path) -> Result<(), InstallerError> {
+        let Some(required) = minimum_rust_version(checkout) else {
+            return Ok(());
+        };
+        let Some(found) = self
+            .rustc_info(checkout)
+            .and_then(|info| info.lines().find_map(|line| line.strip_prefix("release:")).map(|r| r.trim().to_string()))
+        else {
+            return Ok(());
+        };
+        let provider = self.rust_provider().unwrap_or(RustProvider::Other);
+        if provider != RustProvider::Rustup
+            && let Some(channel) = ToolchainRequest::load(checkout).ok().flatten().and_then(|request| request.channel)
+        {
+            self.log_info(&format!(
+                "kopi-lang pins the {} toolchain, but without rustup the Rust from {} ({}) is used",
+                channel, provider, found
+            ));
+        }
+        if parse_version(&found) >= parse_version(&required) {
+            return Ok(());
+        }
+        self.log_error(&format!(
+            "kopi-lang needs Rust {} or newer, and the Rust from {} is {}",
+            required, provider, found
+        ));
+        self.log_info(&provider.upgrade_advice(&required));
+        Err(InstallerError::Cargo(format!("Rust {} is older than the {} kopi-lang needs", found, required)))
+    }
+}
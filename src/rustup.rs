@@ -0,0 +1,252 @@
+// rustup integration: build kopi-lang with the toolchain its checkout asks for
+// (rust-toolchain.toml) and make sure that toolchain has what the build needs.
+
+use std::env;
+use std::fs;
+use std::io::{self, IsTerminal};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use serde::Deserialize;
+
+use crate::platform::bsd;
+use crate::toolchain::exe_name;
+use crate::{Installer, InstallerError};
+
+/// Toolchain requirements from a checkout's `rust-toolchain.toml` (or legacy
+/// `rust-toolchain`) file.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ToolchainRequest {
+    pub channel: Option<String>,
+    #[serde(default)]
+    pub components: Vec<String>,
+    #[serde(default)]
+    pub targets: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct ToolchainFile {
+    toolchain: ToolchainRequest,
+}
+
+impl ToolchainRequest {
+    /// Read the toolchain file in `dir`, if there is one.
+    pub fn load(dir: &Path) -> Result<Option<ToolchainRequest>, InstallerError> {
+        for name in ["rust-toolchain.toml", "rust-toolchain"] {
+            let path = dir.join(name);
+            let Ok(contents) = fs::read_to_string(&path) else {
+                continue;
+            };
+
+            // The legacy file may hold just a channel name
+            let trimmed = contents.trim();
+            if name == "rust-toolchain" && !trimmed.contains('[') {
+                return Ok(Some(ToolchainRequest {
+                    channel: Some(trimmed.to_string()),
+                    ..Default::default()
+                }));
+            }
+
+            let file: ToolchainFile = toml::from_str(&contents)
+                .map_err(|e| InstallerError::Cargo(format!("Invalid {}: {}", path.display(), e)))?;
+            return Ok(Some(file.toolchain));
+        }
+        Ok(None)
+    }
+}
+
+/// Whether `name` (as listed by rustup) is `wanted`, allowing for the host
+/// triple rustup appends to toolchain and component names.
+fn rustup_name_matches(name: &str, wanted: &str) -> bool {
+    name == wanted || name.strip_prefix(wanted).is_some_and(|rest| rest.starts_with('-'))
+}
+
+impl Installer {
+    pub(crate) fn has_rustup(&self) -> bool {
+        self.command_exists("rustup")
+    }
+
+    /// Names printed one per line by a `rustup ... list` command, minus rustup's
+    /// "(default)"/"(active)" annotations.
+    pub(crate) fn rustup_list(&self, dir: &Path, args: &[&str]) -> Result<Vec<String>, InstallerError> {
+        let output = self.run_command(Command::new("rustup").args(args).current_dir(dir))?;
+        if !output.status.success() {
+            return Err(InstallerError::Cargo(format!(
+                "rustup {} failed: {}",
+                args.join(" "),
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+        Ok(String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .filter_map(|line| line.split_whitespace().next())
+            .map(str::to_string)
+            .collect())
+    }
+
+    /// rustup commands still needed before `request` can be built with.
+    fn missing_rust_pieces(&self, dir: &Path, request: &ToolchainRequest) -> Result<Vec<Vec<String>>, InstallerError> {
+        let mut toolchain_args = Vec::new();
+        if let Some(channel) = &request.channel {
+            let installed = self.rustup_list(dir, &["toolchain", "list"])?;
+            if !installed.iter().any(|name| rustup_name_matches(name, channel)) {
+                // A fresh toolchain install can take its components and targets along
+                let mut install = vec!["toolchain".to_string(), "install".to_string(), channel.clone()];
+                for component in &request.components {
+                    install.extend(["--component".to_string(), component.clone()]);
+                }
+                for target in &request.targets {
+                    install.extend(["--target".to_string(), target.clone()]);
+                }
+                return Ok(vec![install]);
+            }
+            toolchain_args = vec!["--toolchain".to_string(), channel.clone()];
+        }
+
+        let mut missing = Vec::new();
+        if !request.targets.is_empty() {
+            let mut list = vec!["target", "list", "--installed"];
+            list.extend(toolchain_args.iter().map(String::as_str));
+            let installed = self.rustup_list(dir, &list)?;
+            let absent: Vec<String> =
+                request.targets.iter().filter(|t| !installed.contains(t)).cloned().collect();
+            if !absent.is_empty() {
+                missing.push([vec!["target".to_string(), "add".to_string()], toolchain_args.clone(), absent].concat());
+            }
+        }
+        if !request.components.is_empty() {
+            let mut list = vec!["component", "list", "--installed"];
+            list.extend(toolchain_args.iter().map(String::as_str));
+            let installed = self.rustup_list(dir, &list)?;
+            let absent: Vec<String> = request
+                .components
+                .iter()
+                .filter(|c| !installed.iter().any(|name| rustup_name_matches(name, c)))
+                .cloned()
+                .collect();
+            if !absent.is_empty() {
+                missing.push([vec!["component".to_string(), "add".to_string()], toolchain_args, absent].concat());
+            }
+        }
+        Ok(missing)
+    }
+
+    /// Make sure the toolchain requested by the checkout in `dir` is installed
+    /// with its components and targets, offering to add anything missing when
+    /// running interactively.
+    fn ensure_rust_toolchain(&self, dir: &Path) -> Result<Option<ToolchainRequest>, InstallerError> {
+        let mut request = ToolchainRequest::load(dir)?;
+        // `--target` needs the target's standard library too
+        if let Some(target) = self.build_target() {
+            let request = request.get_or_insert_with(ToolchainRequest::default);
+            if !request.targets.iter().any(|t| t == target) {
+                request.targets.push(target.to_string());
+            }
+        }
+        let Some(request) = request else {
+            return Ok(None);
+        };
+        if let Some(channel) = &request.channel {
+            self.log_info(&format!("kopi-lang requests the {} Rust toolchain", channel));
+        }
+
+        let missing = self.missing_rust_pieces(dir, &request)?;
+        if missing.is_empty() {
+            return Ok(Some(request));
+        }
+
+        self.log_warning("The Rust toolchain kopi-lang needs is incomplete. Missing pieces can be added with:");
+        for args in &missing {
+            self.print_line(&format!("  rustup {}", args.join(" ")));
+        }
+
+        if !io::stdin().is_terminal() && !self.is_forced() {
+            return Err(InstallerError::Cargo(
+                "Required Rust toolchain, components or targets are not installed".to_string(),
+            ));
+        }
+        if !self.ask("Run these commands now?", false)? {
+            return Err(InstallerError::Cancelled);
+        }
+
+        for args in &missing {
+            self.check_cancelled()?;
+            self.log_info(&format!("Running rustup {}...", args.join(" ")));
+            let output = self.run_command(Command::new("rustup").args(args).current_dir(dir))?;
+            if !output.status.success() {
+                return Err(InstallerError::Cargo(format!(
+                    "rustup {} failed: {}",
+                    args.join(" "),
+                    String::from_utf8_lossy(&output.stderr).trim()
+                )));
+            }
+        }
+        self.log_success("Rust toolchain is ready");
+        Ok(Some(request))
+    }
+
+    /// The cargo to build the checkout in `dir` with.
+    ///
+    /// With rustup, this is the toolchain's own cargo as reported by `rustup
+    /// which cargo` (which honours the checkout's rust-toolchain.toml) rather
+    /// than whatever `cargo` comes first on PATH.
+    pub(crate) fn cargo_command(&self, dir: &Path) -> Result<Command, InstallerError> {
+        let mut command = self.toolchain_cargo(dir)?;
+        self.sanitize_build_env(&mut command);
+        // On the BSDs `make` is BSD make; build scripts that honour $MAKE
+        // (cmake, autotools wrappers) need GNU make
+        if bsd().is_some() && env::var_os("MAKE").is_none() && self.command_exists("gmake") {
+            command.env("MAKE", "gmake");
+        }
+        Ok(command)
+    }
+
+    /// `rustc -vV` for the rustc that builds the checkout in `dir`.
+    pub(crate) fn rustc_info(&self, dir: &Path) -> Option<String> {
+        let cargo = self.cargo_command(dir).ok()?;
+        // rustup toolchains keep rustc next to cargo
+        let program = Path::new(cargo.get_program());
+        let mut rustc = if program.is_absolute() {
+            Command::new(program.with_file_name(exe_name("rustc")))
+        } else {
+            Command::new("rustc")
+        };
+        rustc.arg("-vV").current_dir(dir);
+        for (key, value) in cargo.get_envs() {
+            match value {
+                Some(value) => rustc.env(key, value),
+                None => rustc.env_remove(key),
+            };
+        }
+        let output = self.run_command(&mut rustc).ok().filter(|output| output.status.success())?;
+        Some(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+
+    fn toolchain_cargo(&self, dir: &Path) -> Result<Command, InstallerError> {
+        if !self.has_rustup() {
+            let mut command = Command::new("cargo");
+            command.current_dir(dir);
+            self.select_asdf_rust(dir, &mut command);
+            return Ok(command);
+        }
+
+        let request = self.ensure_rust_toolchain(dir)?;
+
+        let output = self.run_command(Command::new("rustup").args(["which", "cargo"]).current_dir(dir))?;
+        if !output.status.success() {
+            return Err(InstallerError::Cargo(format!(
+                "rustup could not find cargo: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+        let cargo = PathBuf::from(String::from_utf8_lossy(&output.stdout).trim());
+
+        let mut command = Command::new(cargo);
+        command.current_dir(dir);
+        // Keep rustc and friends, reached through rustup's proxies, on the same toolchain
+        if let Some(channel) = request.and_then(|r| r.channel) {
+            command.env("RUSTUP_TOOLCHAIN", channel);
+        }
+        Ok(command)
+    }
+}
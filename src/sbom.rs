@@ -0,0 +1,256 @@
+// Software bills of materials (`kipper sbom`): the crates inside an installed
+// toolchain, from the Cargo.lock it was built with, as CycloneDX or SPDX JSON.
+
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+use serde_json::{Value, json};
+
+use crate::state::State;
+use crate::{Installer, InstallerError, timestamp, toolchain};
+
+/// Copy of the build's lockfile kept in each version dir.
+pub const LOCKFILE: &str = "Cargo.lock";
+const CRATES_IO: &str = "registry+https://github.com/rust-lang/crates.io-index";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SbomFormat {
+    CycloneDx,
+    Spdx,
+}
+
+impl FromStr for SbomFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "cyclonedx" => Ok(SbomFormat::CycloneDx),
+            "spdx" => Ok(SbomFormat::Spdx),
+            _ => Err(format!("unknown SBOM format '{}' (expected cyclonedx or spdx)", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct LockedPackage {
+    name: String,
+    version: String,
+    /// `None` for the workspace's own packages.
+    source: Option<String>,
+    checksum: Option<String>,
+    dependencies: Vec<String>,
+}
+
+impl LockedPackage {
+    fn purl(&self) -> String {
+        format!("pkg:cargo/{}@{}", self.name, self.version)
+    }
+
+    fn spdx_id(&self) -> String {
+        let id: String = format!("{}-{}", self.name, self.version)
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() || c == '.' || c == '-' { c } else { '-' })
+            .collect();
+        format!("SPDXRef-Package-{}", id)
+    }
+
+    fn download_location(&self) -> String {
+        match self.source.as_deref() {
+            Some(CRATES_IO) => format!("https://crates.io/api/v1/crates/{}/{}/download", self.name, self.version),
+            Some(source) => source.split_once('+').map_or(source, |(_, url)| url).to_string(),
+            None => "NOASSERTION".to_string(),
+        }
+    }
+}
+
+fn parse_lockfile(contents: &str) -> Result<Vec<LockedPackage>, InstallerError> {
+    let lock: toml::Table = contents
+        .parse()
+        .map_err(|e| InstallerError::PathError(format!("Invalid Cargo.lock: {}", e)))?;
+    let packages = lock.get("package").and_then(|p| p.as_array()).cloned().unwrap_or_default();
+
+    Ok(packages
+        .iter()
+        .filter_map(|package| {
+            let field = |key: &str| package.get(key).and_then(|v| v.as_str()).map(str::to_string);
+            Some(LockedPackage {
+                name: field("name")?,
+                version: field("version")?,
+                source: field("source"),
+                checksum: field("checksum"),
+                dependencies: package
+                    .get("dependencies")
+                    .and_then(|d| d.as_array())
+                    .map(|deps| deps.iter().filter_map(|d| d.as_str().map(str::to_string)).collect())
+                    .unwrap_or_default(),
+            })
+        })
+        .collect())
+}
+
+/// Find the package a lockfile dependency entry ("name", "name version" or
+/// "name version (source)") refers to.
+fn resolve_dependency<'a>(packages: &'a [LockedPackage], entry: &str) -> Option<&'a LockedPackage> {
+    let mut parts = entry.split_whitespace();
+    let name = parts.next()?;
+    let version = parts.next();
+    packages
+        .iter()
+        .find(|p| p.name == name && version.is_none_or(|v| p.version == v))
+}
+
+fn cyclonedx(version: &str, commit: Option<&str>, root: &LockedPackage, packages: &[LockedPackage], created: &str) -> Value {
+    let components: Vec<Value> = packages
+        .iter()
+        .filter(|p| p.purl() != root.purl())
+        .map(|p| {
+            let mut component = json!({
+                "type": if p.source.is_some() { "library" } else { "application" },
+                "bom-ref": p.purl(),
+                "name": p.name,
+                "version": p.version,
+                "purl": p.purl(),
+            });
+            if let Some(checksum) = &p.checksum {
+                component["hashes"] = json!([{ "alg": "SHA-256", "content": checksum }]);
+            }
+            component
+        })
+        .collect();
+
+    let dependencies: Vec<Value> = packages
+        .iter()
+        .map(|p| {
+            let depends_on: Vec<String> = p
+                .dependencies
+                .iter()
+                .filter_map(|d| resolve_dependency(packages, d))
+                .map(LockedPackage::purl)
+                .collect();
+            json!({ "ref": p.purl(), "dependsOn": depends_on })
+        })
+        .collect();
+
+    let mut properties = vec![json!({ "name": "kipper:toolchain", "value": version })];
+    if let Some(commit) = commit {
+        properties.push(json!({ "name": "kipper:commit", "value": commit }));
+    }
+
+    json!({
+        "bomFormat": "CycloneDX",
+        "specVersion": "1.5",
+        "version": 1,
+        "metadata": {
+            "timestamp": created,
+            "tools": [{ "name": "kipper", "version": env!("CARGO_PKG_VERSION") }],
+            "component": {
+                "type": "application",
+                "bom-ref": root.purl(),
+                "name": root.name,
+                "version": root.version,
+                "purl": root.purl(),
+                "properties": properties,
+            },
+        },
+        "components": components,
+        "dependencies": dependencies,
+    })
+}
+
+fn spdx(version: &str, commit: Option<&str>, root: &LockedPackage, packages: &[LockedPackage], created: &str) -> Value {
+    let spdx_packages: Vec<Value> = packages
+        .iter()
+        .map(|p| {
+            let mut package = json!({
+                "SPDXID": p.spdx_id(),
+                "name": p.name,
+                "versionInfo": p.version,
+                "downloadLocation": p.download_location(),
+                "filesAnalyzed": false,
+                "licenseConcluded": "NOASSERTION",
+                "licenseDeclared": "NOASSERTION",
+                "copyrightText": "NOASSERTION",
+                "externalRefs": [{
+                    "referenceCategory": "PACKAGE-MANAGER",
+                    "referenceType": "purl",
+                    "referenceLocator": p.purl(),
+                }],
+            });
+            if let Some(checksum) = &p.checksum {
+                package["checksums"] = json!([{ "algorithm": "SHA256", "checksumValue": checksum }]);
+            }
+            package
+        })
+        .collect();
+
+    let mut relationships = vec![json!({
+        "spdxElementId": "SPDXRef-DOCUMENT",
+        "relationshipType": "DESCRIBES",
+        "relatedSpdxElement": root.spdx_id(),
+    })];
+    for package in packages {
+        for dependency in package.dependencies.iter().filter_map(|d| resolve_dependency(packages, d)) {
+            relationships.push(json!({
+                "spdxElementId": package.spdx_id(),
+                "relationshipType": "DEPENDS_ON",
+                "relatedSpdxElement": dependency.spdx_id(),
+            }));
+        }
+    }
+
+    json!({
+        "spdxVersion": "SPDX-2.3",
+        "dataLicense": "CC0-1.0",
+        "SPDXID": "SPDXRef-DOCUMENT",
+        "name": format!("kopi-{}", version),
+        "documentNamespace": format!("urn:kipper:kopi:{}:{}", version, commit.unwrap_or(&root.version)),
+        "creationInfo": {
+            "created": created,
+            "creators": [format!("Tool: kipper-{}", env!("CARGO_PKG_VERSION"))],
+        },
+        "packages": spdx_packages,
+        "relationships": relationships,
+    })
+}
+
+impl Installer {
+    /// `kipper sbom`: describe the crates built into `version` (or the version
+    /// selected for `cwd`).
+    pub fn sbom(&self, version: Option<&str>, cwd: &Path, format: SbomFormat) -> Result<Value, InstallerError> {
+        let version = match version {
+            Some(version) => toolchain::check_version_name(version).map(|()| version.to_string())?,
+            None => self.resolve_version(cwd)
+                .map(|resolved| resolved.name)
+                .ok_or_else(|| InstallerError::PathError("No Kopi version selected".to_string()))?,
+        };
+
+        let version_dir = self.version_dir(&version);
+        if !version_dir.exists() {
+            return Err(self.not_installed(&version));
+        }
+        let contents = fs::read_to_string(version_dir.join(LOCKFILE)).map_err(|_| {
+            InstallerError::PathError(format!(
+                "No Cargo.lock recorded for Kopi {}; reinstall it to generate an SBOM",
+                version
+            ))
+        })?;
+        let packages = parse_lockfile(&contents)?;
+
+        let root = packages
+            .iter()
+            .find(|p| p.source.is_none() && p.name == "kopi")
+            .or_else(|| packages.iter().find(|p| p.source.is_none()))
+            .cloned()
+            .ok_or_else(|| InstallerError::PathError("Cargo.lock has no workspace package".to_string()))?;
+
+        let state = State::load(&self.install_dir);
+        let commit = state.versions.get(&version).map(|record| record.commit.as_str());
+        let created = timestamp::now_utc();
+
+        Ok(match format {
+            SbomFormat::CycloneDx => cyclonedx(&version, commit, &root, &packages, &created),
+            SbomFormat::Spdx => spdx(&version, commit, &root, &packages, &created),
+        })
+    }
+}
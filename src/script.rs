@@ -0,0 +1,115 @@
+// Quoting for text kipper writes into scripts and shell startup files, so
+// paths with spaces, quotes, `$`, `%` or non-ASCII characters survive intact.
+
+use std::path::Path;
+
+use crate::platform::extended_length_path;
+
+/// `text` as a single POSIX shell word. Inside single quotes nothing is
+/// special except the quote itself, which is closed, escaped and reopened.
+pub(crate) fn sh_quote(text: &str) -> String {
+    format!("'{}'", text.replace('\'', r"'\''"))
+}
+
+/// `text` for use between double quotes in sh/bash/zsh, where `$`, `` ` ``,
+/// `"` and `\` keep their meaning.
+pub(crate) fn sh_double_quoted(text: &str) -> String {
+    escape_with_backslash(text, &['\\', '"', '$', '`'])
+}
+
+/// `text` for use between double quotes in fish, which only treats `\`, `"`
+/// and `$` specially there.
+pub(crate) fn fish_double_quoted(text: &str) -> String {
+    escape_with_backslash(text, &['\\', '"', '$'])
+}
+
+fn escape_with_backslash(text: &str, special: &[char]) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if special.contains(&c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// `text` as a single-quoted PowerShell string. Nothing is special inside
+/// but the quote, which is doubled; PowerShell takes the typographic single
+/// quotes for it too.
+pub(crate) fn ps_quote(text: &str) -> String {
+    let mut quoted = String::with_capacity(text.len() + 2);
+    quoted.push('\'');
+    for c in text.chars() {
+        if matches!(c, '\'' | '\u{2018}' | '\u{2019}' | '\u{201a}' | '\u{201b}') {
+            quoted.push(c);
+        }
+        quoted.push(c);
+    }
+    quoted.push('\'');
+    quoted
+}
+
+/// `path` as a double-quoted batch-file argument: extended-length so
+/// `rmdir /s` can reach deeply nested files, with `%` doubled so cmd doesn't
+/// expand it. Windows file names can't contain `"`, and `&`, `^`, `(` and `)`
+/// are literal inside quotes.
+pub(crate) fn batch_quote(path: &Path) -> String {
+    format!("\"{}\"", extended_length_path(path).display().to_string().replace('%', "%%"))
+}
+
+/// `text` as an unquoted `echo` argument in a batch file, with cmd's
+/// operators caret-escaped.
+pub(crate) fn batch_echo_text(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '%' => escaped.push_str("%%"),
+            '^' | '&' | '|' | '<' | '>' => {
+                escaped.push('^');
+                escaped.push(c);
+            }
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sh_quote_keeps_paths_one_word() {
+        assert_eq!(sh_quote("/home/a b/.kopi"), "'/home/a b/.kopi'");
+        assert_eq!(sh_quote("/home/o'brien"), r"'/home/o'\''brien'");
+        assert_eq!(sh_quote("/home/$USER/`x`"), "'/home/$USER/`x`'");
+        assert_eq!(sh_quote("/home/josé/コピ"), "'/home/josé/コピ'");
+        assert_eq!(sh_quote(""), "''");
+    }
+
+    #[test]
+    fn double_quoted_escapes_what_each_shell_expands() {
+        let path = r#"/home/a b/"q"/$HOME/`cmd`/back\slash/josé"#;
+        assert_eq!(sh_double_quoted(path), r#"/home/a b/\"q\"/\$HOME/\`cmd\`/back\\slash/josé"#);
+        assert_eq!(fish_double_quoted(path), r#"/home/a b/\"q\"/\$HOME/`cmd`/back\\slash/josé"#);
+        assert_eq!(sh_double_quoted("/home/o'brien"), "/home/o'brien");
+    }
+
+    #[test]
+    fn ps_quote_doubles_single_quotes() {
+        assert_eq!(ps_quote(r"C:\Users\a b"), r"'C:\Users\a b'");
+        assert_eq!(ps_quote(r"C:\Users\o'brien"), r"'C:\Users\o''brien'");
+        assert_eq!(ps_quote("C:\\Users\\o\u{2019}brien\\$env:X"), "'C:\\Users\\o\u{2019}\u{2019}brien\\$env:X'");
+        assert_eq!(ps_quote("C:\\Users\\José"), "'C:\\Users\\José'");
+    }
+
+    #[test]
+    fn batch_quoting_keeps_percent_and_operators_literal() {
+        assert_eq!(batch_echo_text("50% a&b | c <d> ^e"), "50%% a^&b ^| c ^<d^> ^^e");
+        assert_eq!(batch_echo_text("José's (x)"), "José's (x)");
+        if !cfg!(windows) {
+            assert_eq!(batch_quote(Path::new("/a b/%PATH%/é")), "\"/a b/%%PATH%%/é\"");
+        }
+    }
+}
@@ -0,0 +1,254 @@
+// Local HTTP API (`kipper serve`)
+//
+// A deliberately small HTTP/1.1 server bound to localhost. Every request must
+// carry `Authorization: Bearer <token>`; the token is printed on startup and
+// written to the install dir so local dashboards and editor extensions can
+// pick it up.
+
+use std::fs;
+use std::io::{self, Write};
+use std::net::{Ipv4Addr, SocketAddr};
+
+use serde_json::json;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::async_api::AsyncInstaller;
+use crate::redact::Redacted;
+use crate::theme::Theme;
+use crate::toolchain::{self, NIGHTLY};
+use crate::{Installer, InstallerError, LogLevel};
+
+pub const DEFAULT_PORT: u16 = 7878;
+const TOKEN_ENV: &str = "KIPPER_SERVE_TOKEN";
+const TOKEN_FILE: &str = "serve-token";
+const MAX_REQUEST_HEAD: usize = 8 * 1024;
+
+pub(crate) struct Request {
+    pub(crate) method: String,
+    pub(crate) path: String,
+    version: Option<String>,
+    token: Option<String>,
+}
+
+impl Installer {
+    /// What is left to `main` once `serve` has taken this installer: the same
+    /// install, temporary dir, settings and logging, for cleaning up and
+    /// reporting errors, without any of the build configuration.
+    pub fn reporting_handle(&self) -> Installer {
+        let mut handle = Installer::at(self.install_dir.clone(), self.bin_dir.clone());
+        handle.temp_dir = self.temp_dir.clone();
+        handle.config = self.config.clone();
+        handle.policy = self.policy.clone();
+        handle.theme = self.theme.clone();
+        handle.log_format = self.log_format;
+        handle.log_filter = self.log_filter.clone();
+        handle.unattended = self.unattended;
+        handle.ci_environment = self.ci_environment;
+        handle
+    }
+}
+
+/// Serve the HTTP API until the process is interrupted.
+pub fn serve(installer: Installer, port: u16) -> Result<(), InstallerError> {
+    let token = match std::env::var(TOKEN_ENV) {
+        Ok(token) if !token.is_empty() => token,
+        _ => generate_token()?,
+    };
+    write_token_file(&installer, &token)?;
+
+    let theme = installer.theme().clone();
+    let runtime = tokio::runtime::Runtime::new()?;
+    runtime.block_on(run(AsyncInstaller::new(installer), &theme, port, token))
+}
+
+async fn run(installer: AsyncInstaller, theme: &Theme, port: u16, token: String) -> Result<(), InstallerError> {
+    let addr = SocketAddr::from((Ipv4Addr::LOCALHOST, port));
+    let listener = TcpListener::bind(addr).await?;
+
+    eprintln!("{}", theme.message(LogLevel::Info, &format!("Listening on http://{}", addr)));
+    eprintln!("{}", theme.message(LogLevel::Info, &format!("API token: {}", token)));
+
+    loop {
+        let (stream, _) = listener.accept().await?;
+        let installer = installer.clone();
+        let token = token.clone();
+        tokio::spawn(async move {
+            let _ = handle(stream, installer, &token).await;
+        });
+    }
+}
+
+async fn handle(mut stream: TcpStream, installer: AsyncInstaller, token: &str) -> io::Result<()> {
+    let request = match read_request(&mut stream).await? {
+        Some(request) => request,
+        None => return respond(&mut stream, 400, &json!({ "error": "malformed request" })).await,
+    };
+
+    if !request.token.as_deref().is_some_and(|given| tokens_match(given, token)) {
+        return respond(&mut stream, 401, &json!({ "error": "missing or invalid token" })).await;
+    }
+
+    match (request.method.as_str(), request.path.as_str()) {
+        ("GET", "/status") => {
+            let body = json!({
+                "installed": installer.is_installed(),
+                "busy": installer.is_busy(),
+                "version": env!("CARGO_PKG_VERSION"),
+            });
+            respond(&mut stream, 200, &body).await
+        }
+        ("GET", "/toolchains") => match installer.list().await {
+            Ok(toolchains) => {
+                let list: Vec<_> = toolchains
+                    .iter()
+                    .map(|t| json!({ "name": t.name, "binary": t.binary.display().to_string() }))
+                    .collect();
+                respond(&mut stream, 200, &json!(list)).await
+            }
+            Err(e) => respond(&mut stream, 500, &json!({ "error": format!("{:?}", Redacted(e)) })).await,
+        },
+        ("POST", "/install") | ("POST", "/update") => {
+            if installer.is_busy() {
+                return respond(&mut stream, 409, &json!({ "error": "another operation is running" })).await;
+            }
+            let update = request.path == "/update";
+            let version = request.version.unwrap_or_else(|| NIGHTLY.to_string());
+            if let Err(e) = toolchain::check_version_name(&version) {
+                return respond(&mut stream, 400, &json!({ "error": format!("{:?}", Redacted(e)) })).await;
+            }
+            tokio::spawn(async move {
+                let _ = if update {
+                    installer.update().await
+                } else {
+                    installer.install(version).await
+                };
+            });
+            respond(&mut stream, 202, &json!({ "started": true })).await
+        }
+        ("GET", "/events") => stream_events(&mut stream, &installer).await,
+        _ => respond(&mut stream, 404, &json!({ "error": "not found" })).await,
+    }
+}
+
+/// Forward progress messages as server-sent events until the client goes away.
+async fn stream_events(stream: &mut TcpStream, installer: &AsyncInstaller) -> io::Result<()> {
+    let mut events = installer.subscribe();
+    stream
+        .write_all(b"HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nCache-Control: no-cache\r\nConnection: keep-alive\r\n\r\n")
+        .await?;
+
+    loop {
+        match events.recv().await {
+            Ok(event) => {
+                let data = json!(event);
+                stream
+                    .write_all(format!("event: progress\ndata: {}\n\n", data).as_bytes())
+                    .await?;
+            }
+            Err(RecvError::Lagged(_)) => continue,
+            Err(RecvError::Closed) => return Ok(()),
+        }
+    }
+}
+
+pub(crate) async fn read_request(stream: &mut TcpStream) -> io::Result<Option<Request>> {
+    let mut buf = Vec::new();
+    let mut chunk = [0u8; 1024];
+
+    while !buf.windows(4).any(|w| w == b"\r\n\r\n") {
+        if buf.len() > MAX_REQUEST_HEAD {
+            return Ok(None);
+        }
+        let n = stream.read(&mut chunk).await?;
+        if n == 0 {
+            return Ok(None);
+        }
+        buf.extend_from_slice(&chunk[..n]);
+    }
+
+    let head = String::from_utf8_lossy(&buf);
+    let mut lines = head.lines();
+    let mut request_line = lines.next().unwrap_or("").split_whitespace();
+    let (method, target) = match (request_line.next(), request_line.next()) {
+        (Some(method), Some(target)) => (method.to_string(), target),
+        _ => return Ok(None),
+    };
+
+    // The only query parameter the API understands is `version=`
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let version = query
+        .split('&')
+        .find_map(|pair| pair.strip_prefix("version="))
+        .filter(|v| !v.is_empty())
+        .map(str::to_string);
+
+    let token = lines
+        .filter_map(|line| line.split_once(':'))
+        .find(|(name, _)| name.trim().eq_ignore_ascii_case("authorization"))
+        .and_then(|(_, value)| value.trim().strip_prefix("Bearer ").map(|t| t.trim().to_string()));
+
+    Ok(Some(Request {
+        method,
+        path: path.to_string(),
+        version,
+        token,
+    }))
+}
+
+async fn respond(stream: &mut TcpStream, status: u16, body: &serde_json::Value) -> io::Result<()> {
+    let reason = match status {
+        200 => "OK",
+        202 => "Accepted",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        404 => "Not Found",
+        409 => "Conflict",
+        _ => "Internal Server Error",
+    };
+    let body = body.to_string();
+    let response = format!(
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        reason,
+        body.len(),
+        body
+    );
+    stream.write_all(response.as_bytes()).await
+}
+
+/// 128 random bits from the OS's cryptographic random source, as hex.
+fn generate_token() -> io::Result<String> {
+    let mut bytes = [0u8; 16];
+    getrandom::fill(&mut bytes).map_err(|e| io::Error::other(format!("no random source for the token: {}", e)))?;
+    Ok(bytes.iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+/// Compare a request's token with ours in time that doesn't depend on
+/// where they differ, so it can't be guessed a character at a time.
+fn tokens_match(given: &str, token: &str) -> bool {
+    given.len() == token.len() && given.bytes().zip(token.bytes()).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+fn write_token_file(installer: &Installer, token: &str) -> Result<(), InstallerError> {
+    fs::create_dir_all(installer.install_dir())?;
+    let path = installer.install_dir().join(TOKEN_FILE);
+
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options.open(&path)?;
+    // The mode only applies to a new file; an old one may be readable by others
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o600))?;
+    }
+    file.write_all(token.as_bytes())?;
+    Ok(())
+}
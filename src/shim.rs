@@ -0,0 +1,410 @@
+// Shims: `kopi` and its component tools in the bin dir all point at kipper,
+// which works out the version for the current directory and hands off to that
+// version's binary.
+
+use std::env;
+use std::ffi::OsString;
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::{Duration, Instant};
+
+use crate::config::BinStrategy;
+use crate::path::find_on_path;
+use crate::platform::{case_insensitive, names_fold_case};
+use crate::stamp::stamp_requested;
+use crate::state::{Profile, State};
+use crate::toolchain::{COMPONENTS, VERSION_ENV, exe_name};
+use crate::{Installer, InstallerError};
+
+/// Name of the kipper copy in the install dir that every shim points at.
+pub const SHIM_HOST: &str = "kipper";
+/// Set by shim scripts (`[bin] strategy = "shim"`) to the tool they stand
+/// for, since they run the shim host under its own name.
+pub const SHIM_ENV: &str = "KIPPER_SHIM";
+
+/// Tools that are dispatched through shims.
+pub fn shimmed_tools() -> impl Iterator<Item = &'static str> {
+    std::iter::once("kopi").chain(COMPONENTS.iter().copied())
+}
+
+/// If kipper was invoked through a shim or an alias, the name of the tool it
+/// stands in for.
+pub fn invoked_as(installer: &Installer, argv0: &str) -> Option<String> {
+    if let Some(tool) = env::var(SHIM_ENV).ok().filter(|tool| !tool.is_empty()) {
+        return Some(tool);
+    }
+    tool_named(installer, argv0)
+}
+
+/// The tool a shim or alias called `argv0` stands in for, going by the name alone.
+fn tool_named(installer: &Installer, argv0: &str) -> Option<String> {
+    let stem = Path::new(argv0).file_stem()?.to_str()?;
+    // `Kopi` typed at a Windows or macOS prompt still runs the `kopi` shim
+    let same = |name: &str| name == stem || (names_fold_case() && name.eq_ignore_ascii_case(stem));
+    if same(SHIM_HOST) {
+        return None;
+    }
+    // The built-in tools don't need the state file read
+    if let Some(tool) = shimmed_tools().find(|tool| same(tool)) {
+        return Some(tool.to_string());
+    }
+    let state = State::load(installer.install_dir());
+    if let Some(tool) = state.tools.into_iter().find(|tool| same(tool)) {
+        return Some(tool);
+    }
+    state.aliases.into_iter().find_map(|(alias, tool)| same(&alias).then_some(tool))
+}
+
+impl Installer {
+    /// Every tool that gets a shim: the built-in ones and any added by
+    /// kopi-lang's install manifest.
+    pub fn tools(&self) -> Vec<String> {
+        let mut tools: Vec<String> = shimmed_tools().map(str::to_string).collect();
+        tools.extend(State::load(&self.install_dir).tools);
+        tools
+    }
+
+    /// Aliases recorded in the install state, as `(alias, tool)` pairs.
+    pub fn aliases(&self) -> Vec<(String, String)> {
+        State::load(&self.install_dir).aliases.into_iter().collect()
+    }
+
+    /// Add `name` as another command for `tool`, e.g. `kp` for `kopi`.
+    ///
+    /// Refuses names that would shadow or replace an existing command unless
+    /// `force` is set.
+    pub fn add_alias(&self, name: &str, tool: &str, force: bool) -> Result<(), InstallerError> {
+        let tools = self.tools();
+        if !tools.iter().any(|t| t == tool) {
+            return Err(InstallerError::PathError(format!(
+                "'{}' is not a Kopi tool (expected one of: {})",
+                tool,
+                tools.join(", ")
+            )));
+        }
+        if self.bin_strategy() == BinStrategy::Direct {
+            return Err(InstallerError::PathError(
+                "Aliases need the bin dir, and [bin] strategy = \"none\" leaves it empty".to_string(),
+            ));
+        }
+        if name.is_empty() || name.contains(['/', '\\']) || name.starts_with('.') {
+            return Err(InstallerError::PathError(format!("'{}' is not a valid command name", name)));
+        }
+        // On a case-insensitive bin dir, `Kopi` would overwrite the `kopi` shim
+        fs::create_dir_all(&self.bin_dir)?;
+        let folded = case_insensitive(&self.bin_dir);
+        let same = |other: &str| other == name || (folded && other.eq_ignore_ascii_case(name));
+        if same(SHIM_HOST) || tools.iter().any(|tool| same(tool)) {
+            return Err(InstallerError::PathError(format!("'{}' is already a kipper command", name)));
+        }
+
+        let mut state = State::load(&self.install_dir);
+        if let Some(existing) = state.aliases.keys().find(|alias| *alias != name && same(alias)) {
+            return Err(InstallerError::PathError(format!(
+                "'{}' would replace the alias '{}' on this case-insensitive filesystem",
+                name, existing
+            )));
+        }
+        let shim_path = self.exposed_path(name);
+        if !force && !state.aliases.contains_key(name) {
+            if shim_path.symlink_metadata().is_ok() {
+                self.log_info("Use --force to replace it");
+                return Err(InstallerError::PathError(format!("{} already exists", shim_path.display())));
+            }
+            if let Some(existing) = find_on_path(name, Some(&self.bin_dir)) {
+                self.log_info("Use --force to add the alias anyway; which one runs depends on PATH order");
+                return Err(InstallerError::PathError(format!(
+                    "'{}' would collide with {}",
+                    name,
+                    existing.display()
+                )));
+            }
+        }
+
+        self.expose(name)?;
+        state.aliases.insert(name.to_string(), tool.to_string());
+        state.save(&self.install_dir)?;
+        self.refresh_uninstaller()?;
+
+        self.log_success(&format!("'{}' now runs {}", name, tool));
+        Ok(())
+    }
+
+    /// Rewrite an existing uninstaller so it knows about the current aliases.
+    fn refresh_uninstaller(&self) -> Result<(), InstallerError> {
+        if self.uninstaller_path().exists() {
+            self.create_uninstaller()?;
+        }
+        Ok(())
+    }
+
+    pub fn remove_alias(&self, name: &str) -> Result<(), InstallerError> {
+        let mut state = State::load(&self.install_dir);
+        if state.aliases.remove(name).is_none() {
+            return Err(InstallerError::PathError(format!("'{}' is not an alias", name)));
+        }
+
+        let shim_path = self.exposed_path(name);
+        if shim_path.symlink_metadata().is_ok() {
+            fs::remove_file(&shim_path)?;
+        }
+        state.save(&self.install_dir)?;
+        self.refresh_uninstaller()?;
+
+        self.log_success(&format!("Removed alias '{}'", name));
+        Ok(())
+    }
+}
+
+/// Why a shim can't run its tool, found before handing off so the user
+/// gets a way out instead of the shell's "No such file or directory".
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Broken {
+    /// The selected version isn't installed; `true` when it was and its
+    /// directory has since been deleted.
+    VersionMissing(bool),
+    /// The version is there without this tool.
+    ToolMissing,
+    /// The binary is there but can't be run: empty, truncated or not an executable.
+    Damaged(String),
+}
+
+impl Broken {
+    /// One line saying what is wrong and the command that fixes it.
+    pub fn hint(&self, tool: &str, version: &str) -> String {
+        match self {
+            Broken::VersionMissing(true) => format!(
+                "Kopi {} was installed but its directory is gone; run `kipper install {}`",
+                version, version
+            ),
+            Broken::VersionMissing(false) => {
+                format!("Kopi {} is selected but not installed; run `kipper install {}`", version, version)
+            }
+            Broken::ToolMissing if Profile::Full.components().contains(&tool) => format!(
+                "{} isn't installed for Kopi {}; run `kipper install {} --profile full`",
+                tool, version, version
+            ),
+            Broken::ToolMissing => {
+                format!("{} isn't installed for Kopi {}; run `kipper install {}`", tool, version, version)
+            }
+            Broken::Damaged(reason) => format!(
+                "{} from Kopi {} is damaged ({}); run `kipper install {}` to rebuild it",
+                tool, version, reason, version
+            ),
+        }
+    }
+
+    /// The exit status shells use for a missing (127) or unrunnable (126) command.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Broken::Damaged(_) => 126,
+            _ => 127,
+        }
+    }
+}
+
+/// Magic numbers of the binary formats a version's tools come in: ELF,
+/// Mach-O (thin or universal) and PE.
+const BINARY_MAGIC: [&[u8]; 5] = [b"\x7fELF", b"\xcf\xfa\xed\xfe", b"\xce\xfa\xed\xfe", b"\xca\xfe\xba\xbe", b"MZ"];
+/// Shorter than any of those formats' headers.
+const MIN_HEADER: usize = 64;
+
+/// Why the start of a tool's file can't be an executable, if it can't.
+/// A binary cut off inside its header would otherwise be handed to
+/// /bin/sh by execvp and fail with a confusing message.
+fn check_header(head: &[u8]) -> Option<&'static str> {
+    if head.is_empty() {
+        Some("the file is empty")
+    } else if head.starts_with(b"#!") {
+        None
+    } else if !BINARY_MAGIC.iter().any(|magic| head.starts_with(magic)) {
+        Some("not an executable")
+    } else if head.len() < MIN_HEADER {
+        Some("the file is truncated")
+    } else {
+        None
+    }
+}
+
+/// Where an ELF file's section header table ends, which is where the file
+/// ends unless something was cut off. `None` for other formats.
+fn elf_end(head: &[u8]) -> Option<u64> {
+    if !head.starts_with(b"\x7fELF") {
+        return None;
+    }
+    let little = match head.get(5)? {
+        1 => true,
+        2 => false,
+        _ => return None,
+    };
+    let read = |at: usize, len: usize| -> Option<u64> {
+        let bytes = head.get(at..at + len)?;
+        let fold = |acc: u64, byte: &u8| acc << 8 | u64::from(*byte);
+        Some(if little { bytes.iter().rev().fold(0, fold) } else { bytes.iter().fold(0, fold) })
+    };
+    // e_shoff, e_shentsize and e_shnum sit at different offsets in 32- and 64-bit headers
+    let (offset, entry_size, entries) = match head.get(4)? {
+        1 => (read(0x20, 4)?, read(0x2e, 2)?, read(0x30, 2)?),
+        2 => (read(0x28, 8)?, read(0x3a, 2)?, read(0x3c, 2)?),
+        _ => return None,
+    };
+    Some(offset + entry_size * entries)
+}
+
+impl Installer {
+    /// What is wrong with running `tool` from installed `version`, if
+    /// anything. Reads only the start of the binary, so shims can afford it
+    /// on every run.
+    pub fn tool_health(&self, version: &str, tool: &str) -> Option<Broken> {
+        let version_dir = self.version_dir(version);
+        if !version_dir.is_dir() {
+            let recorded = State::load(&self.install_dir).versions.contains_key(version);
+            return Some(Broken::VersionMissing(recorded));
+        }
+        let file = match fs::File::open(version_dir.join(exe_name(tool))) {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Some(Broken::ToolMissing),
+            Err(e) => return Some(Broken::Damaged(e.to_string())),
+        };
+        let len = file.metadata().map(|metadata| metadata.len()).unwrap_or(0);
+        let mut head = Vec::with_capacity(MIN_HEADER);
+        if let Err(e) = file.take(MIN_HEADER as u64).read_to_end(&mut head) {
+            return Some(Broken::Damaged(e.to_string()));
+        }
+        if let Some(reason) = check_header(&head) {
+            return Some(Broken::Damaged(reason.to_string()));
+        }
+        // A binary cut short past its header still starts like a good one
+        match elf_end(&head) {
+            Some(end) if end > len => {
+                Some(Broken::Damaged(format!("the file is truncated at {} of {} bytes", len, end)))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// The version and binary `tool` runs in `cwd`, or the line to print and
+/// the status to exit with when it can't run. Touches only the version files
+/// and the install dir, never the network.
+/// `requested` is the value of `KOPI_VERSION`.
+fn resolve_target(installer: &Installer, tool: &str, cwd: &Path, requested: Option<&str>) -> Result<(String, PathBuf), (String, i32)> {
+    let Some(resolved) = installer.select_version(requested, cwd) else {
+        return Err(("no Kopi version selected; run `kipper install`".to_string(), 127));
+    };
+    if let Some(broken) = installer.tool_health(&resolved.name, tool) {
+        return Err((broken.hint(tool, &resolved.name), broken.exit_code()));
+    }
+    let target = installer.version_dir(&resolved.name).join(exe_name(tool));
+    Ok((resolved.name, target))
+}
+
+/// What a shim adds before kopi starts is expected to stay under this.
+pub const SHIM_BUDGET: Duration = Duration::from_millis(5);
+
+impl Installer {
+    /// The average time a `kopi` shim spends before handing off, from
+    /// setting up to resolving the binary, measured in this process over
+    /// `runs` runs. `None` when no version is selected here.
+    pub fn shim_overhead(&self, runs: u32) -> Option<Duration> {
+        let cwd = env::current_dir().ok()?;
+        let requested = env::var(VERSION_ENV).ok();
+        let started = Instant::now();
+        for _ in 0..runs {
+            let installer = Installer::for_shim().ok()?;
+            let tool = invoked_as(&installer, "kopi")?;
+            resolve_target(&installer, &tool, &cwd, requested.as_deref()).ok()?;
+        }
+        Some(started.elapsed() / runs.max(1))
+    }
+}
+
+/// Run `tool` from the version selected for the working directory, replacing
+/// this process where the platform allows it. A missing or damaged binary is
+/// reported on one line with the command that fixes it.
+pub fn dispatch(installer: &Installer, tool: &str, args: Vec<OsString>) -> Result<i32, InstallerError> {
+    let requested = env::var(VERSION_ENV).ok();
+    let (version, target) = match resolve_target(installer, tool, &env::current_dir()?, requested.as_deref()) {
+        Ok(found) => found,
+        Err((hint, code)) => {
+            eprintln!("{}: {}", tool, hint);
+            return Ok(code);
+        }
+    };
+    if stamp_requested()
+        && let Some(stamp) = installer.stamp(&version)
+    {
+        eprintln!("{}: {}", tool, stamp);
+    }
+
+    let mut command = Command::new(&target);
+    // kipper run from inside kopi is kipper again
+    command.args(args).env_remove(SHIM_ENV);
+
+    #[cfg(unix)]
+    let error = {
+        use std::os::unix::process::CommandExt;
+        // exec only returns on failure
+        command.exec()
+    };
+
+    #[cfg(not(unix))]
+    let error = match command.status() {
+        Ok(status) => return Ok(status.code().unwrap_or(1)),
+        Err(e) => e,
+    };
+
+    // Passed the checks above but still wouldn't start, e.g. built for
+    // another architecture or a missing dynamic loader
+    eprintln!("{}: {}", tool, Broken::Damaged(error.to_string()).hint(tool, &version));
+    Ok(126)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Removes the test's install dir however the test ends.
+    struct TempRoot(PathBuf);
+
+    impl Drop for TempRoot {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    /// Everything a `kopi` shim does before handing off, against a real
+    /// install dir, stays within `SHIM_BUDGET` on average. KIPPER_SHIM and
+    /// KOPI_VERSION are left out, so a shell inside a kipper shim or with a
+    /// version set doesn't change what is resolved.
+    #[test]
+    fn dispatch_fits_the_budget() {
+        let root = TempRoot(env::temp_dir().join(format!("kipper-shim-test-{}", std::process::id())));
+        let root = &root.0;
+        let install_dir = root.join(".kopi");
+        let version_dir = install_dir.join("versions").join("v1.0.0");
+        fs::create_dir_all(&version_dir).unwrap();
+        // Any real binary passes the health check's header reads
+        fs::copy(env::current_exe().unwrap(), version_dir.join(exe_name("kopi"))).unwrap();
+        fs::write(install_dir.join(crate::DEFAULT_FILE), "v1.0.0\n").unwrap();
+
+        let run = || {
+            let installer = Installer::at(install_dir.clone(), root.join("bin"));
+            let tool = tool_named(&installer, "kopi").unwrap();
+            resolve_target(&installer, &tool, root, None).unwrap()
+        };
+        let (version, target) = run();
+        assert_eq!(version, "v1.0.0");
+        assert_eq!(target, version_dir.join(exe_name("kopi")));
+
+        let runs = 100;
+        let started = Instant::now();
+        for _ in 0..runs {
+            run();
+        }
+        let overhead = started.elapsed() / runs;
+        assert!(overhead <= SHIM_BUDGET, "{:?} per run, budget {:?}", overhead, SHIM_BUDGET);
+    }
+}
@@ -0,0 +1,217 @@
+// Tamper evidence for kipper's own metadata: state.json, the `default` file
+// and each version's stamp and `kipper-manifest.toml`, the files that decide
+// which build a `kopi` runs and what it is said to be.
+// An edit by hand or by another program that gets them wrong would otherwise
+// pass unnoticed until the wrong version runs.
+//
+// It is off until `kipper verify --enable`, which creates a random key in
+// `signing.key` (readable only by its owner, and never exported or copied
+// anywhere by kipper) and signs the files there are. From then on every
+// kipper that writes one of them also writes `<file>.hmac` next to it: the
+// HMAC-SHA256 of the file's path under the install dir and its contents, so
+// a signature can't be moved to another file either. `kipper verify` checks
+// them all and exits 1 when any was changed, removed or lost its signature.
+//
+// This catches edits made without the key, not someone who can read it:
+// whoever can write the files as this user can usually read the key too.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+use crate::manifest::MANIFEST_FILE;
+use crate::stamp::STAMP_FILE;
+use crate::state::STATE_FILE;
+use crate::{DEFAULT_FILE, Installer, InstallerError};
+
+pub const KEY_FILE: &str = "signing.key";
+/// Appended to a file's name for its signature.
+pub const SIGNATURE_SUFFIX: &str = ".hmac";
+const KEY_LEN: usize = 32;
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn unhex(text: &str) -> Option<Vec<u8>> {
+    let text = text.trim();
+    if !text.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..text.len()).step_by(2).map(|i| u8::from_str_radix(text.get(i..i + 2)?, 16).ok()).collect()
+}
+
+/// The signing key of the install in `install_dir`, when signing is on.
+fn key(install_dir: &Path) -> Option<Vec<u8>> {
+    unhex(&fs::read_to_string(install_dir.join(KEY_FILE)).ok()?).filter(|key| key.len() == KEY_LEN)
+}
+
+/// A new random key, from the OS's cryptographic random source
+/// (getrandom(2), BCryptGenRandom, ...).
+fn new_key() -> io::Result<Vec<u8>> {
+    let mut key = vec![0; KEY_LEN];
+    getrandom::fill(&mut key).map_err(|e| io::Error::other(format!("no random source for the key: {}", e)))?;
+    Ok(key)
+}
+
+/// Write `contents` to `path` so that only its owner can read it.
+fn write_private(path: &Path, contents: &str) -> io::Result<()> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    io::Write::write_all(&mut options.open(path)?, contents.as_bytes())
+}
+
+/// The signature file of `path`.
+fn signature_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(SIGNATURE_SUFFIX);
+    PathBuf::from(name)
+}
+
+/// The MAC of `path`'s contents, bound to where it is under `install_dir`.
+fn mac(key: &[u8], install_dir: &Path, path: &Path) -> io::Result<String> {
+    let relative = path.strip_prefix(install_dir).unwrap_or(path);
+    let name = relative.to_string_lossy().replace('\\', "/");
+    let mut hmac = hmac_sha256::HMAC::new(key);
+    hmac.update(name.as_bytes());
+    hmac.update([0]);
+    hmac.update(fs::read(path)?);
+    Ok(hex(&hmac.finalize()))
+}
+
+/// Sign `path`, one of the metadata files of the install in `install_dir`,
+/// if signing is on there.
+pub fn sign_file(install_dir: &Path, path: &Path) -> io::Result<()> {
+    match key(install_dir) {
+        Some(key) => fs::write(signature_path(path), mac(&key, install_dir, path)? + "\n"),
+        None => Ok(()),
+    }
+}
+
+/// What `kipper verify` found for one file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Verdict {
+    Valid,
+    /// The file doesn't match its signature.
+    Modified,
+    /// The file is there, its signature isn't.
+    Unsigned,
+    /// The signature is there, the file isn't.
+    Removed,
+}
+
+impl Verdict {
+    pub fn describe(self) -> &'static str {
+        match self {
+            Verdict::Valid => "signature matches",
+            Verdict::Modified => "changed since kipper wrote it",
+            Verdict::Unsigned => "has no signature",
+            Verdict::Removed => "was removed, but its signature is there",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FileVerdict {
+    /// The file's path under the install dir.
+    pub file: String,
+    pub verdict: Verdict,
+}
+
+impl Installer {
+    /// Sign `path` if signing is on. A failure to is reported, not returned:
+    /// the write it follows went through.
+    pub(crate) fn sign(&self, path: &Path) {
+        if let Err(e) = sign_file(&self.install_dir, path) {
+            self.log_warning(&format!("Could not sign {}: {}", path.display(), e));
+        }
+    }
+
+    pub fn signing_enabled(&self) -> bool {
+        key(&self.install_dir).is_some()
+    }
+
+    /// The metadata files signing covers, and those that have only a
+    /// signature left.
+    fn signed_files(&self) -> Vec<PathBuf> {
+        let mut files = vec![self.install_dir.join(STATE_FILE), self.install_dir.join(DEFAULT_FILE)];
+        if let Ok(entries) = fs::read_dir(self.install_dir.join("versions")) {
+            let mut versions: Vec<PathBuf> = entries
+                .flatten()
+                .flat_map(|entry| [entry.path().join(STAMP_FILE), entry.path().join(MANIFEST_FILE)])
+                .collect();
+            versions.sort();
+            files.extend(versions);
+        }
+        files.retain(|file| file.exists() || signature_path(file).exists());
+        files
+    }
+
+    /// `kipper verify --enable`: create the key and sign what is there.
+    /// Returns how many files were signed.
+    pub fn enable_signing(&self) -> Result<usize, InstallerError> {
+        // Signing what is there again would hide changes made since
+        if self.signing_enabled() {
+            self.log_info("Signing is already on; `kipper verify` checks the signatures");
+            return Ok(0);
+        }
+        fs::create_dir_all(&self.install_dir)?;
+        write_private(&self.install_dir.join(KEY_FILE), &(hex(&new_key()?) + "\n"))?;
+        let files: Vec<PathBuf> = self.signed_files().into_iter().filter(|file| file.exists()).collect();
+        for file in &files {
+            sign_file(&self.install_dir, file)?;
+        }
+        self.log_success(&format!(
+            "Signing is on: {} files signed with the key in {}",
+            files.len(),
+            self.install_dir.join(KEY_FILE).display()
+        ));
+        Ok(files.len())
+    }
+
+    /// `kipper verify --disable`: remove the key and every signature.
+    pub fn disable_signing(&self) -> Result<(), InstallerError> {
+        for file in self.signed_files() {
+            let signature = signature_path(&file);
+            if signature.exists() {
+                fs::remove_file(signature)?;
+            }
+        }
+        let key = self.install_dir.join(KEY_FILE);
+        if key.exists() {
+            fs::remove_file(key)?;
+        }
+        self.log_success("Signing is off, and the key and signatures are removed");
+        Ok(())
+    }
+
+    /// `kipper verify`: check every metadata file against its signature.
+    pub fn verify_metadata(&self) -> Result<Vec<FileVerdict>, InstallerError> {
+        let Some(key) = key(&self.install_dir) else {
+            return Err(InstallerError::PathError(
+                "Metadata isn't signed here; turn signing on with `kipper verify --enable`".to_string(),
+            ));
+        };
+        let mut verdicts = Vec::new();
+        for file in self.signed_files() {
+            let signature = fs::read_to_string(signature_path(&file)).ok();
+            let verdict = match (file.exists(), signature) {
+                (false, _) => Verdict::Removed,
+                (true, None) => Verdict::Unsigned,
+                (true, Some(signature)) if signature.trim() == mac(&key, &self.install_dir, &file)? => Verdict::Valid,
+                (true, Some(_)) => Verdict::Modified,
+            };
+            let relative = file.strip_prefix(&self.install_dir).unwrap_or(&file);
+            verdicts.push(FileVerdict {
+                file: relative.to_string_lossy().replace('\\', "/"),
+                verdict,
+            });
+        }
+        Ok(verdicts)
+    }
+}
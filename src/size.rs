@@ -0,0 +1,139 @@
+// `kipper size`: where kipper's disk space goes, and what to remove when it
+// grows past a threshold.
+
+use std::env;
+
+use serde::Serialize;
+
+use crate::cache::{dir_size, format_size, stale_temp_dirs};
+use crate::logging::log_dir;
+use crate::{Installer, InstallerError};
+
+/// Overrides the total above which `kipper size` suggests cleaning up, as a
+/// byte count with an optional K, M or G suffix (e.g. `2G`).
+pub const SIZE_WARN_ENV: &str = "KIPPER_SIZE_WARN";
+const DEFAULT_SIZE_WARN: u64 = 5 * 1024 * 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum UsageKind {
+    Version,
+    Cache,
+    Temp,
+    Logs,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct UsageEntry {
+    pub name: String,
+    pub kind: UsageKind,
+    pub bytes: u64,
+}
+
+/// Parse `2G`, `500M`, `64k` or a plain byte count, in binary units.
+pub fn parse_size(text: &str) -> Option<u64> {
+    let text = text.trim();
+    let (digits, multiplier) = match text.char_indices().last()? {
+        (i, 'k' | 'K') => (&text[..i], 1024),
+        (i, 'm' | 'M') => (&text[..i], 1024 * 1024),
+        (i, 'g' | 'G') => (&text[..i], 1024 * 1024 * 1024),
+        _ => (text, 1),
+    };
+    digits.trim().parse::<u64>().ok()?.checked_mul(multiplier)
+}
+
+/// The total above which cleaning up is suggested.
+pub fn size_threshold() -> u64 {
+    env::var(SIZE_WARN_ENV)
+        .ok()
+        .and_then(|value| parse_size(&value))
+        .unwrap_or(DEFAULT_SIZE_WARN)
+}
+
+impl Installer {
+    /// Disk usage of each installed version, the source cache, leftover
+    /// build dirs and logs. Locations that don't exist are left out.
+    pub fn disk_usage(&self) -> Result<Vec<UsageEntry>, InstallerError> {
+        let mut entries: Vec<UsageEntry> = self
+            .list()?
+            .into_iter()
+            .map(|toolchain| UsageEntry {
+                bytes: dir_size(&self.version_dir(&toolchain.name)).unwrap_or(0),
+                name: toolchain.name,
+                kind: UsageKind::Version,
+            })
+            .collect();
+
+        let cache = self.cache_dir();
+        let build_dir = self.build_cache_dir();
+        let build_bytes = if build_dir.exists() { dir_size(&build_dir)? } else { 0 };
+        if cache.exists() {
+            entries.push(UsageEntry {
+                name: "source cache".to_string(),
+                kind: UsageKind::Cache,
+                bytes: dir_size(&cache)?.saturating_sub(build_bytes),
+            });
+        }
+        if build_bytes > 0 {
+            entries.push(UsageEntry {
+                name: "build artifacts".to_string(),
+                kind: UsageKind::Cache,
+                bytes: build_bytes,
+            });
+        }
+
+        let stale = stale_temp_dirs().unwrap_or_default();
+        if !stale.is_empty() {
+            entries.push(UsageEntry {
+                name: format!("leftover build dirs ({})", stale.len()),
+                kind: UsageKind::Temp,
+                bytes: stale.iter().map(|dir| dir_size(dir).unwrap_or(0)).sum(),
+            });
+        }
+
+        let logs = log_dir(&self.install_dir);
+        if logs.exists() {
+            entries.push(UsageEntry {
+                name: "logs".to_string(),
+                kind: UsageKind::Logs,
+                bytes: dir_size(&logs)?,
+            });
+        }
+
+        Ok(entries)
+    }
+
+    /// When `entries` add up to more than the threshold, log what could be removed.
+    pub fn suggest_cleanup(&self, entries: &[UsageEntry]) {
+        let total: u64 = entries.iter().map(|e| e.bytes).sum();
+        let threshold = size_threshold();
+        if total <= threshold {
+            return;
+        }
+
+        self.log_warning(&format!(
+            "kipper is using {}, more than {} (set {} to change this)",
+            format_size(total),
+            format_size(threshold),
+            SIZE_WARN_ENV
+        ));
+        let default = self.default_version();
+        let unused: Vec<&str> = entries
+            .iter()
+            .filter(|e| e.kind == UsageKind::Version && default.as_deref() != Some(e.name.as_str()))
+            .map(|e| e.name.as_str())
+            .collect();
+        if !unused.is_empty() {
+            self.log_info(&format!(
+                "Installed besides the default: {}; remove any you no longer use with `kipper uninstall VERSION`",
+                unused.join(", ")
+            ));
+        }
+        if entries.iter().any(|e| e.kind == UsageKind::Cache && e.name == "build artifacts") {
+            self.log_info("Remove the artifacts kept for faster rebuilds with `kipper cache clean --build`");
+        }
+        if entries.iter().any(|e| e.kind == UsageKind::Temp) {
+            self.log_info("Remove build dirs left by interrupted installs with `kipper cache clean --temp`");
+        }
+    }
+}
@@ -0,0 +1,416 @@
+// Where kopi-lang's source comes from. Each way of getting it is a
+// `SourceProvider`, picked with `--source`:
+//
+//     (none) or git          the kopi-lang repository or a mirror, through the
+//                            source cache (GitClone)
+//     github[:OWNER/REPO]    the source archive GitHub makes of a release tag
+//                            (GitHubRelease)
+//     https://.../X.tar.gz   a source archive on a web server (HttpTarball)
+//     PATH/X.tar.gz          a source archive on disk (Archive)
+//     PATH                   a directory, e.g. a kopi-lang working tree with
+//                            changes not committed yet (LocalPath)
+//
+// A provider only puts the tree in the scratch checkout; the install does
+// the rest the same way whatever the source. Trees without git history are
+// committed into a new repository there, with a fixed author and date, so
+// the recorded commit, Cargo.lock and signature checks (which find them
+// unsigned) work as for a clone, and the same tree always gets the same
+// commit id. Archives of .tar, .tar.gz, .tgz, .tar.xz and .tar.bz2 are
+// unpacked with the system's tar. Adding another way to get the source is a
+// new provider and a line in `select`.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::remote_cache::sibling;
+use crate::toolchain::NIGHTLY;
+use crate::{Installer, InstallerError};
+
+/// The GitHub repository `--source github` downloads from.
+const GITHUB_REPO: &str = "kinoite/kopi-lang";
+/// Endings of the archives tar can unpack.
+const ARCHIVE_SUFFIXES: [&str; 5] = [".tar", ".tar.gz", ".tgz", ".tar.xz", ".tar.bz2"];
+/// Author and date of the commit made of a tree without history.
+const SNAPSHOT_IDENTITY: [(&str, &str); 6] = [
+    ("GIT_AUTHOR_NAME", "kipper"),
+    ("GIT_AUTHOR_EMAIL", "kipper@localhost"),
+    ("GIT_AUTHOR_DATE", "@0 +0000"),
+    ("GIT_COMMITTER_NAME", "kipper"),
+    ("GIT_COMMITTER_EMAIL", "kipper@localhost"),
+    ("GIT_COMMITTER_DATE", "@0 +0000"),
+];
+
+/// A way of getting kopi-lang's source.
+pub trait SourceProvider: Send + Sync {
+    /// What the source is, for messages and the install record.
+    fn describe(&self) -> String;
+
+    /// Whether the source is kopi-lang's git history, so that one fetch can
+    /// serve several versions and commits are upstream's own.
+    fn has_history(&self) -> bool {
+        false
+    }
+
+    /// Where the source comes from, as the admin policy's `allowed-sources`
+    /// lists it. `None` for git, whose URLs are the allowed ones already.
+    fn origin(&self) -> Option<String>;
+
+    /// Put the source of `version` in `dest`, which doesn't exist yet.
+    /// `None` asks for every version at once, which needs history.
+    fn fetch(&self, installer: &Installer, version: Option<&str>, dest: &Path) -> Result<(), InstallerError>;
+}
+
+/// The provider `--source VALUE` names.
+pub fn select(value: &str) -> Result<Box<dyn SourceProvider>, String> {
+    let is_archive = |name: &str| ARCHIVE_SUFFIXES.iter().any(|suffix| name.ends_with(suffix));
+    if value == "git" {
+        return Ok(Box::new(GitClone));
+    }
+    if value == "github" {
+        return Ok(Box::new(GitHubRelease { repo: GITHUB_REPO.to_string() }));
+    }
+    if let Some(repo) = value.strip_prefix("github:") {
+        return match repo.split_once('/') {
+            Some((owner, name)) if !owner.is_empty() && !name.is_empty() && !name.contains('/') => {
+                Ok(Box::new(GitHubRelease { repo: repo.to_string() }))
+            }
+            _ => Err(format!("'{}' should be github:OWNER/REPO", value)),
+        };
+    }
+    if value.starts_with("https://") || value.starts_with("http://") {
+        return if is_archive(value) {
+            Ok(Box::new(HttpTarball { url: value.to_string() }))
+        } else {
+            Err(format!(
+                "{} isn't a source archive ({}); add git repositories with `kipper mirror add`",
+                value,
+                ARCHIVE_SUFFIXES.join(", ")
+            ))
+        };
+    }
+    let path = fs::canonicalize(value).unwrap_or_else(|_| PathBuf::from(value));
+    if path.is_dir() {
+        Ok(Box::new(LocalPath { path }))
+    } else if path.is_file() && is_archive(value) {
+        Ok(Box::new(Archive { path }))
+    } else if path.exists() {
+        Err(format!("{} is neither a directory nor a source archive ({})", value, ARCHIVE_SUFFIXES.join(", ")))
+    } else {
+        Err(format!("unknown source '{}' (use git, github, an archive URL, or a directory or archive on disk)", value))
+    }
+}
+
+impl Installer {
+    /// Get kopi-lang's source from `source` instead of git.
+    pub fn with_source(mut self, source: Box<dyn SourceProvider>) -> Self {
+        self.source = source;
+        self
+    }
+
+    /// Whether versions come from kopi-lang's git history.
+    pub(crate) fn source_has_history(&self) -> bool {
+        self.source.has_history()
+    }
+}
+
+/// kopi-lang's repository or the chosen mirror, cloned from the source cache.
+pub struct GitClone;
+
+impl SourceProvider for GitClone {
+    fn describe(&self) -> String {
+        "git".to_string()
+    }
+
+    fn origin(&self) -> Option<String> {
+        None
+    }
+
+    fn has_history(&self) -> bool {
+        true
+    }
+
+    fn fetch(&self, installer: &Installer, version: Option<&str>, dest: &Path) -> Result<(), InstallerError> {
+        installer.refresh_source_cache(version)?;
+        installer.touch_cache_entry(&installer.source_cache());
+
+        // A local clone of the cache hardlinks its objects instead of downloading
+        let mut git = Command::new("git");
+        git.args(["clone", "--quiet"]);
+        // Git for Windows otherwise refuses to check out files past MAX_PATH
+        if cfg!(windows) {
+            git.args(["--config", "core.longpaths=true"]);
+        }
+        if let Some(version) = version.filter(|v| *v != NIGHTLY) {
+            git.args(["--branch", version]);
+        }
+        let output = installer.run_command(git.arg(installer.source_cache()).arg(dest))?;
+
+        if !output.status.success() {
+            let error = String::from_utf8_lossy(&output.stderr);
+            if let Some(version) = version
+                && error.contains("not found in upstream")
+                && let Some(suggestion) = installer.suggest_ref(version)
+            {
+                return Err(InstallerError::Git(format!(
+                    "Failed to clone repository: {}; did you mean {}?",
+                    error.trim_end(),
+                    suggestion
+                )));
+            }
+            return Err(InstallerError::Git(format!("Failed to clone repository: {}", error)));
+        }
+        Ok(())
+    }
+}
+
+/// A directory on disk, copied without its `.git` and `target`.
+pub struct LocalPath {
+    path: PathBuf,
+}
+
+impl SourceProvider for LocalPath {
+    fn describe(&self) -> String {
+        format!("directory {}", self.path.display())
+    }
+
+    fn origin(&self) -> Option<String> {
+        Some(self.path.display().to_string())
+    }
+
+    fn fetch(&self, installer: &Installer, _version: Option<&str>, dest: &Path) -> Result<(), InstallerError> {
+        if !self.path.join("Cargo.toml").exists() {
+            return Err(InstallerError::PathError(format!("{} has no Cargo.toml", self.path.display())));
+        }
+        installer.log_info(&format!("Copying the source from {}...", self.path.display()));
+        fs::create_dir_all(dest)?;
+        for entry in fs::read_dir(&self.path)? {
+            let entry = entry?;
+            let name = entry.file_name();
+            if name == ".git" || name == "target" {
+                continue;
+            }
+            if entry.file_type()?.is_dir() {
+                crate::copy_dir(&entry.path(), &dest.join(&name))?;
+            } else {
+                fs::copy(entry.path(), dest.join(&name))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A source archive on disk.
+pub struct Archive {
+    path: PathBuf,
+}
+
+impl SourceProvider for Archive {
+    fn describe(&self) -> String {
+        format!("archive {}", self.path.display())
+    }
+
+    fn origin(&self) -> Option<String> {
+        Some(self.path.display().to_string())
+    }
+
+    fn fetch(&self, installer: &Installer, _version: Option<&str>, dest: &Path) -> Result<(), InstallerError> {
+        unpack(installer, &self.path, dest)
+    }
+}
+
+/// A source archive on a web server.
+pub struct HttpTarball {
+    url: String,
+}
+
+impl SourceProvider for HttpTarball {
+    fn describe(&self) -> String {
+        self.url.clone()
+    }
+
+    fn origin(&self) -> Option<String> {
+        Some(self.url.clone())
+    }
+
+    fn fetch(&self, installer: &Installer, _version: Option<&str>, dest: &Path) -> Result<(), InstallerError> {
+        download_and_unpack(installer, &self.url, None, dest)
+    }
+}
+
+/// The archive GitHub serves of a release tag.
+pub struct GitHubRelease {
+    repo: String,
+}
+
+impl SourceProvider for GitHubRelease {
+    fn describe(&self) -> String {
+        format!("github:{}", self.repo)
+    }
+
+    /// The repository's git URL: allowing a repository allows its archives.
+    fn origin(&self) -> Option<String> {
+        Some(format!("https://github.com/{}.git", self.repo))
+    }
+
+    fn fetch(&self, installer: &Installer, version: Option<&str>, dest: &Path) -> Result<(), InstallerError> {
+        let Some(tag) = version.filter(|v| *v != NIGHTLY) else {
+            return Err(InstallerError::PathError(format!(
+                "{} has archives of release tags only; name a version, or use --source git",
+                self.describe()
+            )));
+        };
+        let url = format!("https://github.com/{}/archive/refs/tags/{}.tar.gz", self.repo, tag);
+        download_and_unpack(installer, &url, installer.credential("github").as_deref(), dest)
+    }
+}
+
+/// Unpack `archive` into `dest`. Archives usually hold one top-level dir
+/// (`kopi-lang-0.2.0/`); its contents become `dest`.
+fn unpack(installer: &Installer, archive: &Path, dest: &Path) -> Result<(), InstallerError> {
+    installer.log_info(&format!("Unpacking {}...", archive.display()));
+    let staging = sibling(dest, ".unpack");
+    if staging.exists() {
+        fs::remove_dir_all(&staging)?;
+    }
+    fs::create_dir_all(&staging)?;
+    let output = installer.run_command(Command::new("tar").arg("-xf").arg(archive).arg("-C").arg(&staging))?;
+    if !output.status.success() {
+        let error = String::from_utf8_lossy(&output.stderr);
+        return Err(InstallerError::PathError(format!("Failed to unpack {}: {}", archive.display(), error.trim_end())));
+    }
+
+    let entries: Vec<fs::DirEntry> = fs::read_dir(&staging)?.collect::<Result<_, _>>()?;
+    let root = match entries.as_slice() {
+        [only] if only.file_type()?.is_dir() => only.path(),
+        _ => staging.clone(),
+    };
+    if !root.join("Cargo.toml").exists() {
+        return Err(InstallerError::PathError(format!("{} has no Cargo.toml at its top", archive.display())));
+    }
+    fs::rename(&root, dest)?;
+    if staging.exists() {
+        fs::remove_dir_all(&staging)?;
+    }
+    Ok(())
+}
+
+#[cfg(feature = "http")]
+fn download_and_unpack(installer: &Installer, url: &str, token: Option<&str>, dest: &Path) -> Result<(), InstallerError> {
+    use std::io::Read;
+    use std::time::Duration;
+
+    installer.log_info(&format!("Downloading {}...", url));
+    let client = installer
+        .http_client(Duration::from_secs(600))
+        .map_err(|e| InstallerError::PathError(e.to_string()))?;
+    let mut request = client.get(url);
+    // reqwest drops it when redirected to another host, like GitHub's codeload
+    if let Some(token) = token {
+        request = request.bearer_auth(token);
+    }
+    let mut archive = Vec::new();
+    request
+        .send()
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| InstallerError::PathError(format!("Failed to download {}: {}", url, e)))?
+        .read_to_end(&mut archive)?;
+
+    // Named like the URL so tar sees the compression
+    let name = url.rsplit('/').next().unwrap_or("source.tar.gz");
+    let file = sibling(dest, &format!("-{}", name));
+    fs::write(&file, archive)?;
+    let result = unpack(installer, &file, dest);
+    let _ = fs::remove_file(&file);
+    result
+}
+
+#[cfg(not(feature = "http"))]
+fn download_and_unpack(_installer: &Installer, url: &str, _token: Option<&str>, _dest: &Path) -> Result<(), InstallerError> {
+    Err(InstallerError::PathError(crate::features::without_feature(
+        &format!("Downloading {}", url),
+        "http",
+    )))
+}
+
+/// Commit the tree in `dir` into a new repository, as the only commit.
+pub(crate) fn commit_tree(installer: &Installer, dir: &Path) -> Result<(), InstallerError> {
+    let _ = fs::remove_dir_all(dir.join(".git"));
+    let steps: [&[&str]; 3] = [
+        &["init", "--quiet"],
+        &["add", "--all"],
+        &["-c", "commit.gpgsign=false", "commit", "--quiet", "--no-verify", "-m", "Source tree"],
+    ];
+    for args in steps {
+        let output = installer.run_command(
+            Command::new("git")
+                .args(["-c", "init.defaultBranch=main", "-c", "core.autocrlf=false"])
+                .args(args)
+                .envs(SNAPSHOT_IDENTITY)
+                .current_dir(dir),
+        )?;
+        if !output.status.success() {
+            let error = String::from_utf8_lossy(&output.stderr);
+            return Err(InstallerError::Git(format!("Failed to record the source tree: {}", error.trim_end())));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn selected(value: &str) -> (String, Option<String>) {
+        let provider = select(value).unwrap_or_else(|e| panic!("{}: {}", value, e));
+        (provider.describe(), provider.origin())
+    }
+
+    #[test]
+    fn selects_git_and_github() {
+        assert_eq!(selected("git"), ("git".to_string(), None));
+        assert!(select("git").is_ok_and(|provider| provider.has_history()));
+        assert_eq!(
+            selected("github"),
+            ("github:kinoite/kopi-lang".to_string(), Some("https://github.com/kinoite/kopi-lang.git".to_string()))
+        );
+        assert_eq!(
+            selected("github:someone/kopi-fork"),
+            ("github:someone/kopi-fork".to_string(), Some("https://github.com/someone/kopi-fork.git".to_string()))
+        );
+        for value in ["github:", "github:someone", "github:/repo", "github:someone/", "github:a/b/c"] {
+            assert!(select(value).is_err(), "{}", value);
+        }
+    }
+
+    #[test]
+    fn selects_archives_by_url() {
+        for url in ["https://example.com/kopi-lang-0.2.0.tar.gz", "http://example.com/src.tgz", "https://example.com/a.tar.xz"] {
+            assert_eq!(selected(url), (url.to_string(), Some(url.to_string())));
+        }
+        assert!(select("https://example.com/kopi-lang.git").is_err());
+        assert!(select("https://example.com/kopi-lang.zip").is_err());
+    }
+
+    #[test]
+    fn selects_paths_on_disk() {
+        let dir = std::env::temp_dir().join(format!("kipper-source-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let dir = fs::canonicalize(&dir).unwrap();
+        let archive = dir.join("kopi-lang.tar.gz");
+        let other = dir.join("notes.txt");
+        fs::write(&archive, b"").unwrap();
+        fs::write(&other, b"").unwrap();
+
+        let (describe, origin) = selected(dir.to_str().unwrap());
+        assert_eq!(describe, format!("directory {}", dir.display()));
+        assert_eq!(origin, Some(dir.display().to_string()));
+        let (describe, origin) = selected(archive.to_str().unwrap());
+        assert_eq!(describe, format!("archive {}", archive.display()));
+        assert_eq!(origin, Some(archive.display().to_string()));
+        assert!(select(other.to_str().unwrap()).is_err());
+        assert!(select(dir.join("missing").to_str().unwrap()).is_err());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,119 @@
+// Stamps: where an installed version came from, written next to its binaries
+// as `.kipper-stamp.json`. kopi-lang's own `--version` prints the package
+// version at best, which says nothing of the commit or signature a nightly
+// or a rebuilt tag was built from. The stamp travels with the version dir,
+// so it still answers when the state file is gone or the dir was restored
+// from a cache, and it is cheap enough for shims to read on every run:
+// `kipper which --verbose` shows it, and with KIPPER_STAMP=1 set the shims
+// print it on stderr before handing off.
+
+use std::env;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::provenance::Provenance;
+use crate::state::{State, VersionRecord};
+use crate::timestamp;
+use crate::upstream::KIPPER_VERSION;
+use crate::Installer;
+
+/// The stamp's file name inside a version dir.
+pub const STAMP_FILE: &str = ".kipper-stamp.json";
+/// Set to make shims print the stamp of what they run on stderr.
+pub const STAMP_ENV: &str = "KIPPER_STAMP";
+
+/// What a version dir was built from and by which kipper.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Stamp {
+    pub version: String,
+    pub commit: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kopi_version: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provenance: Option<Provenance>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub installed_at: Option<String>,
+    /// The kipper version that installed it.
+    pub installed_by: String,
+}
+
+impl Stamp {
+    pub fn new(version: &str, record: &VersionRecord) -> Stamp {
+        Stamp {
+            version: version.to_string(),
+            commit: record.commit.clone(),
+            kopi_version: record.kopi_version.clone(),
+            provenance: record.provenance.clone(),
+            installed_at: record.installed_at.clone(),
+            installed_by: format!("kipper {}", KIPPER_VERSION),
+        }
+    }
+
+    /// The stamp in `version_dir`, if it has a readable one.
+    pub fn load(version_dir: &Path) -> Option<Stamp> {
+        let contents = fs::read_to_string(version_dir.join(STAMP_FILE)).ok()?;
+        serde_json::from_str(&contents).ok()
+    }
+
+    pub fn save(&self, version_dir: &Path) -> io::Result<()> {
+        let contents = serde_json::to_string_pretty(self).map_err(io::Error::other)?;
+        fs::write(version_dir.join(STAMP_FILE), contents + "\n")
+    }
+
+    /// The stamp as `Name: value` lines, as `kipper which --verbose` shows it.
+    pub fn lines(&self) -> Vec<String> {
+        let mut lines = vec![
+            format!("Version:     {}", self.version),
+            format!("Kopi:        {}", self.kopi_version.as_deref().unwrap_or("unknown")),
+            format!("Commit:      {}", self.commit),
+        ];
+        if let Some(provenance) = &self.provenance {
+            lines.push(format!("Provenance:  {}", provenance));
+        }
+        if let Some(installed_at) = &self.installed_at {
+            lines.push(format!("Installed:   {}", timestamp::display_local(installed_at)));
+        }
+        lines.push(format!("Built by:    {}", self.installed_by));
+        lines
+    }
+}
+
+impl fmt::Display for Stamp {
+    /// The one line shims print: version, kopi-lang version, commit and signature.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Kopi {}", self.version)?;
+        if let Some(kopi_version) = &self.kopi_version
+            && kopi_version.trim_start_matches('v') != self.version.trim_start_matches('v')
+        {
+            write!(f, " ({})", kopi_version)?;
+        }
+        write!(f, " from {}", self.commit.get(..10).unwrap_or(&self.commit))?;
+        if let Some(provenance) = &self.provenance {
+            write!(f, ", {}", provenance)?;
+        }
+        Ok(())
+    }
+}
+
+/// Whether KIPPER_STAMP asks shims to print stamps.
+pub fn stamp_requested() -> bool {
+    env::var(STAMP_ENV).is_ok_and(|value| !value.is_empty() && value != "0")
+}
+
+impl Installer {
+    /// The stamp of installed `version`: its sidecar file, or failing that
+    /// the state file's record (installs from before stamps).
+    pub fn stamp(&self, version: &str) -> Option<Stamp> {
+        let version_dir = self.version_dir(version);
+        Stamp::load(&version_dir).or_else(|| {
+            let record = State::load(&self.install_dir).versions.remove(version)?;
+            let mut stamp = Stamp::new(version, &record);
+            stamp.installed_by = "unknown".to_string();
+            Some(stamp)
+        })
+    }
+}
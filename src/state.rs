@@ -0,0 +1,194 @@
+// Persistent installer state (`~/.kopi/state.json`): choices made at install
+// time that later commands such as `kipper update` need to repeat.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::lock::{FileLock, LOCK_DIR, STATE_LOCK};
+use crate::metrics::RunRecord;
+use crate::provenance::{Attestation, Provenance};
+use crate::signing;
+
+pub const STATE_FILE: &str = "state.json";
+/// Longest a save waits for another process to finish with the state.
+const SAVE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Which parts of a toolchain get installed, like rustup's profiles.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Profile {
+    /// Just the `kopi` interpreter; only that binary is built.
+    Minimal,
+    /// The interpreter plus the language server and formatter.
+    Default,
+    /// Every component, plus docs and shell completions when upstream ships them.
+    Full,
+}
+
+impl Profile {
+    /// Components (besides `kopi` itself) that this profile installs.
+    pub fn components(self) -> &'static [&'static str] {
+        match self {
+            Profile::Minimal => &[],
+            Profile::Default => &["kopi-lsp", "kopi-fmt"],
+            Profile::Full => &["kopi-lsp", "kopi-fmt", "kopi-doc"],
+        }
+    }
+
+    pub fn includes_extras(self) -> bool {
+        self == Profile::Full
+    }
+}
+
+impl fmt::Display for Profile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Profile::Minimal => "minimal",
+            Profile::Default => "default",
+            Profile::Full => "full",
+        })
+    }
+}
+
+impl FromStr for Profile {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "minimal" => Ok(Profile::Minimal),
+            "default" => Ok(Profile::Default),
+            "full" => Ok(Profile::Full),
+            _ => Err(format!("unknown profile '{}' (expected minimal, default or full)", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct State {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub profile: Option<Profile>,
+    /// Extra command names in the bin dir, each mapped to the tool it runs.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub aliases: BTreeMap<String, String>,
+    /// Tools kopi-lang's install manifest added besides the built-in ones,
+    /// which get shims like the built-ins.
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub tools: BTreeSet<String>,
+    /// Extra kopi-lang git URLs to consider besides the official repository.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub mirrors: Vec<String>,
+    /// A mirror used whenever it responds, without comparing sources, e.g. a
+    /// classroom's `kipper mirror serve`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub preferred_mirror: Option<String>,
+    /// What each installed version was built from.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub versions: BTreeMap<String, VersionRecord>,
+    /// Builds made with `kipper update --prepare`, waiting in the prepared
+    /// dir for `kipper update --commit` to switch to them.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub prepared: BTreeMap<String, VersionRecord>,
+    /// Completion scripts `kipper completions --install` wrote, removed on uninstall.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub completions: Vec<PathBuf>,
+    /// The last run of each measured command, for metrics.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub runs: BTreeMap<String, RunRecord>,
+    /// Moving average of each install phase's duration in seconds, for estimates.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub phase_seconds: BTreeMap<String, f64>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct VersionRecord {
+    /// kopi-lang commit the version was built from.
+    pub commit: String,
+    /// Package version from kopi-lang's Cargo.toml at that commit.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kopi_version: Option<String>,
+    /// Signature status of the source, checked at install time.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provenance: Option<Provenance>,
+    /// Build options chosen with `--with-NAME` / `--without-NAME`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub options: BTreeMap<String, bool>,
+    /// When the version was installed (RFC 3339, UTC).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub installed_at: Option<String>,
+    /// Seconds each phase of that install took, by phase name.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub phase_seconds: BTreeMap<String, f64>,
+    /// Git's id for the Cargo.lock at that commit, which pins every dependency.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lockfile: Option<String>,
+    /// The rustc release and target it was built with.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rustc: Option<String>,
+    /// Where the source came from when it wasn't kopi-lang's git history
+    /// (see `source`); `commit` is then of the tree as kipper committed it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+    /// Set when the binaries came prebuilt from the remote cache rather than
+    /// being compiled here: whether their build attestation was checked.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prebuilt: Option<Attestation>,
+    /// Built with `--quick`: unoptimized, for now.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub unoptimized: bool,
+}
+
+impl State {
+    /// Load the state file, treating a missing one as empty. One that can't
+    /// be parsed is moved aside to `state.json.corrupt`, with a warning, so
+    /// that the next save doesn't lose what can still be recovered from it.
+    pub fn load(install_dir: &Path) -> State {
+        let path = install_dir.join(STATE_FILE);
+        let contents = match fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return State::default(),
+            Err(e) => {
+                eprintln!("kipper: could not read {}: {}", path.display(), e);
+                return State::default();
+            }
+        };
+        match serde_json::from_str(&contents) {
+            Ok(state) => state,
+            Err(e) => {
+                let corrupt = install_dir.join(format!("{}.corrupt", STATE_FILE));
+                match fs::rename(&path, &corrupt) {
+                    Ok(()) => eprintln!("kipper: {} is corrupt ({}); moved it to {}", path.display(), e, corrupt.display()),
+                    Err(_) => eprintln!("kipper: {} is corrupt ({})", path.display(), e),
+                }
+                State::default()
+            }
+        }
+    }
+
+    /// Write the state file under the state lock, replacing it whole so a
+    /// reader never sees it half written.
+    pub fn save(&self, install_dir: &Path) -> io::Result<()> {
+        let contents = serde_json::to_string_pretty(self).map_err(io::Error::other)?;
+        // Never create the install dir just to save into it
+        fs::metadata(install_dir)?;
+        let _lock = FileLock::acquire(&install_dir.join(LOCK_DIR).join(STATE_LOCK), Some(SAVE_TIMEOUT))?;
+        // A state file that is there but can't be read isn't replaced by what was made without it
+        match fs::read_to_string(install_dir.join(STATE_FILE)) {
+            Ok(contents) if serde_json::from_str::<State>(&contents).is_err() => {
+                return Err(io::Error::other(format!("{} is corrupt; move it aside first", STATE_FILE)));
+            }
+            Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+            _ => {}
+        }
+        let partial = install_dir.join(format!("{}.partial", STATE_FILE));
+        fs::write(&partial, contents + "\n")?;
+        fs::rename(&partial, install_dir.join(STATE_FILE))?;
+        signing::sign_file(install_dir, &install_dir.join(STATE_FILE))
+    }
+}
@@ -0,0 +1,77 @@
+// Did-you-mean: when a subcommand, option or version isn't known, the
+// nearest known one by edit distance is suggested, so `kipper isntall`
+// points at `install` and `kipper use 0.31` at `v0.3.1`.
+
+use crate::{Installer, InstallerError};
+
+/// Edits (insertions, deletions, substitutions and swaps of neighbouring
+/// characters) that turn `a` into `b`.
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    // Rows for the two previous prefixes of `a` and the current one
+    let mut before: Vec<usize> = Vec::new();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for i in 1..=a.len() {
+        let mut current = vec![i; b.len() + 1];
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            current[j] = (previous[j] + 1).min(current[j - 1] + 1).min(previous[j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                current[j] = current[j].min(before[j - 2] + 1);
+            }
+        }
+        before = previous;
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// The candidate nearest to `input`, if it is near enough to be a typo of
+/// it: a third of its length in edits, at least one.
+pub fn closest<'a>(input: &str, candidates: impl IntoIterator<Item = &'a str>) -> Option<&'a str> {
+    let limit = (input.chars().count() / 3).max(1);
+    candidates
+        .into_iter()
+        .map(|candidate| (edit_distance(input, candidate), candidate))
+        .filter(|(distance, _)| *distance <= limit)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate)
+}
+
+/// Like `closest`, for versions: a leading `v` doesn't count, so `0.31`
+/// is one edit from `v0.3.1`, and a version left short (`0.2`) suggests
+/// the last one it is the start of (`v0.2.1`).
+pub fn closest_version<'a>(input: &str, candidates: impl IntoIterator<Item = &'a str>) -> Option<&'a str> {
+    fn bare(version: &str) -> &str {
+        version.strip_prefix(['v', 'V']).unwrap_or(version)
+    }
+    let candidates: Vec<&str> = candidates.into_iter().collect();
+    if let Some(nearest) = closest(bare(input), candidates.iter().copied().map(bare)) {
+        return candidates.into_iter().find(|candidate| bare(candidate) == nearest);
+    }
+    let prefix = format!("{}.", bare(input));
+    candidates.into_iter().filter(|candidate| bare(candidate).starts_with(&prefix)).max()
+}
+
+impl Installer {
+    /// The error for a version that isn't installed, suggesting the
+    /// installed version it is closest to.
+    pub fn not_installed(&self, version: &str) -> InstallerError {
+        let installed: Vec<String> = self.list().unwrap_or_default().into_iter().map(|t| t.name).collect();
+        let message = match closest_version(version, installed.iter().map(String::as_str)) {
+            Some(suggestion) => format!("Kopi {} is not installed; did you mean {}?", version, suggestion),
+            None => format!("Kopi {} is not installed", version),
+        };
+        InstallerError::PathError(message)
+    }
+
+    /// The tag or branch in the source cache closest to `version`.
+    pub(crate) fn suggest_ref(&self, version: &str) -> Option<String> {
+        let output = self
+            .git_in_cache(&["for-each-ref", "--format=%(refname:short)", "refs/tags", "refs/heads"])
+            .ok()?;
+        let refs = String::from_utf8_lossy(&output.stdout).into_owned();
+        closest_version(version, refs.lines()).map(str::to_string)
+    }
+}
@@ -0,0 +1,148 @@
+// The report printed at the end of an install or update: what was installed,
+// how big it is, where the time went and whether `kopi` is reachable on PATH.
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use serde::Serialize;
+
+use crate::cache::{dir_size, format_size};
+use crate::path::PathStatus;
+use crate::state::State;
+use crate::timing::{Phase, format_duration};
+use crate::toolchain::exe_name;
+use crate::{Installer, InstallerError};
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PhaseTime {
+    pub phase: &'static str,
+    pub seconds: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct InstallSummary {
+    pub version: String,
+    /// kopi-lang commit the version was built from.
+    pub commit: Option<String>,
+    pub kopi_version: Option<String>,
+    /// When the install finished (RFC 3339, UTC).
+    pub installed_at: Option<String>,
+    /// A `--quick` build, not optimized.
+    pub unoptimized: bool,
+    pub binary: PathBuf,
+    pub binary_size: u64,
+    /// Everything in the version's directory.
+    pub disk_used: u64,
+    /// Phases timed in this run, in the order they ran.
+    pub phases: Vec<PhaseTime>,
+    /// Shims in the bin dir that now run this install's tools.
+    pub shims: Vec<String>,
+    /// `ok`, `missing` (bin dir not on PATH) or `shadowed`.
+    pub path: &'static str,
+    /// The other `kopi` that runs first, when `path` is `shadowed`.
+    pub shadowed_by: Option<PathBuf>,
+    pub uninstaller: PathBuf,
+}
+
+impl InstallSummary {
+    pub fn total_time(&self) -> Duration {
+        Duration::from_secs_f64(self.phases.iter().map(|p| p.seconds).sum())
+    }
+}
+
+impl Installer {
+    /// Keep the phase times of the install of `version` in its state record.
+    pub(crate) fn record_phase_seconds(&self, version: &str) -> Result<(), InstallerError> {
+        let mut state = State::load(&self.install_dir);
+        let Some(record) = state.versions.get_mut(version) else {
+            return Ok(());
+        };
+        record.phase_seconds = self.phase_seconds();
+        state.save(&self.install_dir)?;
+        Ok(())
+    }
+
+    /// Seconds each phase recorded so far took, by phase name.
+    pub(crate) fn phase_seconds(&self) -> BTreeMap<String, f64> {
+        self.phase_times
+            .lock()
+            .map(|times| times.iter().map(|time| (time.phase.to_string(), time.seconds)).collect())
+            .unwrap_or_default()
+    }
+
+    pub(crate) fn record_phase_time(&self, phase: Phase, elapsed: Duration) {
+        if let Ok(mut times) = self.phase_times.lock() {
+            times.push(PhaseTime {
+                phase: phase.name(),
+                seconds: elapsed.as_secs_f64(),
+            });
+        }
+    }
+
+    /// Summarise the installed `version`, consuming the phase times recorded so far.
+    pub(crate) fn install_summary(&self, version: &str) -> Result<InstallSummary, InstallerError> {
+        let version_dir = self.version_dir(version);
+        let binary = version_dir.join(exe_name("kopi"));
+        let record = State::load(&self.install_dir).versions.remove(version);
+        let (path, shadowed_by) = match self.path_status("kopi") {
+            PathStatus::Ok => ("ok", None),
+            PathStatus::Missing => ("missing", None),
+            PathStatus::Shadowed(other) => ("shadowed", Some(other)),
+        };
+
+        Ok(InstallSummary {
+            version: version.to_string(),
+            commit: record.as_ref().map(|r| r.commit.clone()),
+            installed_at: record.as_ref().and_then(|r| r.installed_at.clone()),
+            unoptimized: record.as_ref().is_some_and(|r| r.unoptimized),
+            kopi_version: record.and_then(|r| r.kopi_version),
+            binary_size: binary.metadata()?.len(),
+            binary,
+            disk_used: dir_size(&version_dir)?,
+            phases: self.phase_times.lock().map(|mut times| std::mem::take(&mut *times)).unwrap_or_default(),
+            shims: self
+                .tools()
+                .into_iter()
+                .filter(|tool| version_dir.join(exe_name(tool)).exists())
+                .collect(),
+            path,
+            shadowed_by,
+            uninstaller: self.uninstaller_path(),
+        })
+    }
+
+    pub(crate) fn print_summary(&self, summary: &InstallSummary) {
+        self.log_success(&format!("Kopi {} installed successfully!", summary.version));
+
+        let row = |label: &str, value: String| self.print_line(&format!("  {:<11} {}", label, value));
+        if let Some(commit) = &summary.commit {
+            let short = &commit[..commit.len().min(12)];
+            match &summary.kopi_version {
+                Some(kopi) => row("Commit", format!("{} (kopi {})", short, kopi)),
+                None => row("Commit", short.to_string()),
+            }
+        }
+        let build = if summary.unoptimized { ", unoptimized" } else { "" };
+        row("Binary", format!("{} ({}{})", summary.binary.display(), format_size(summary.binary_size), build));
+        row("Disk used", format_size(summary.disk_used));
+        if !summary.phases.is_empty() {
+            let phases: Vec<String> = summary
+                .phases
+                .iter()
+                .map(|p| format!("{} {}", p.phase, format_duration(Duration::from_secs_f64(p.seconds))))
+                .collect();
+            row("Time", format!("{} (total {})", phases.join(", "), format_duration(summary.total_time())));
+        }
+        row("Shims", format!("{} in {}", summary.shims.join(", "), self.bin_dir.display()));
+        row(
+            "PATH",
+            match (summary.path, &summary.shadowed_by) {
+                ("ok", _) => "ready: run `kopi --help`".to_string(),
+                (_, Some(other)) => format!("{} runs first", other.display()),
+                _ => format!("{} is not on PATH yet", self.bin_dir.display()),
+            },
+        );
+        row("Uninstall", summary.uninstaller.display().to_string());
+    }
+}
@@ -0,0 +1,192 @@
+// Build targets (`--target`): build Kopi for a target other than rustc's
+// default host. On Windows Rust has two host toolchains, MSVC and GNU
+// (MinGW-w64), and `--target msvc` or `--target gnu` picks one for this
+// machine's architecture. Each links with its own tools, MSVC's link.exe
+// from the Visual Studio Build Tools and GNU's gcc from MinGW-w64, so the
+// one the build needs is looked for before cargo gets to the link step, and
+// a missing one is reported with what to install or which target to use
+// instead.
+
+use std::env;
+use std::process::Command;
+
+use crate::check::CheckResult;
+use crate::vsbuild::BuildTools;
+use crate::{Installer, InstallerError};
+
+/// The two Windows host toolchains.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WindowsAbi {
+    Msvc,
+    Gnu,
+}
+
+impl WindowsAbi {
+    pub const ALL: [WindowsAbi; 2] = [WindowsAbi::Msvc, WindowsAbi::Gnu];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            WindowsAbi::Msvc => "msvc",
+            WindowsAbi::Gnu => "gnu",
+        }
+    }
+
+    /// The ABI of a Windows target triple.
+    pub fn of_triple(triple: &str) -> Option<WindowsAbi> {
+        WindowsAbi::ALL
+            .into_iter()
+            .find(|abi| triple.ends_with(&format!("-windows-{}", abi.name())))
+    }
+
+    /// The target triple for this machine's architecture.
+    pub fn triple(self) -> String {
+        format!("{}-pc-windows-{}", env::consts::ARCH, self.name())
+    }
+
+    fn linker(self) -> &'static str {
+        match self {
+            WindowsAbi::Msvc => "link.exe",
+            WindowsAbi::Gnu => "gcc",
+        }
+    }
+
+    fn other(self) -> WindowsAbi {
+        match self {
+            WindowsAbi::Msvc => WindowsAbi::Gnu,
+            WindowsAbi::Gnu => WindowsAbi::Msvc,
+        }
+    }
+}
+
+/// The triple `--target VALUE` stands for: `msvc` and `gnu` are short for
+/// the Windows targets of this machine's architecture.
+pub fn resolve_target(value: &str) -> Result<String, String> {
+    match value {
+        "msvc" => Ok(WindowsAbi::Msvc.triple()),
+        "gnu" => Ok(WindowsAbi::Gnu.triple()),
+        triple if triple.split('-').count() >= 3 && !triple.contains(char::is_whitespace) => Ok(triple.to_string()),
+        _ => Err(format!(
+            "unknown target '{}' (use msvc, gnu or a target triple such as x86_64-pc-windows-msvc)",
+            value
+        )),
+    }
+}
+
+impl Installer {
+    /// Build for `target` (a triple) instead of rustc's default host.
+    pub fn with_target(mut self, target: String) -> Self {
+        self.target = Some(target);
+        self
+    }
+
+    /// The `--target` given, if any.
+    pub(crate) fn build_target(&self) -> Option<&str> {
+        self.target.as_deref()
+    }
+
+    /// rustc's default host triple.
+    pub(crate) fn rustc_host(&self) -> Option<String> {
+        let output = self.run_command(Command::new("rustc").arg("-vV")).ok()?;
+        let info = String::from_utf8_lossy(&output.stdout).into_owned();
+        info.lines().find_map(|line| line.strip_prefix("host:")).map(|host| host.trim().to_string())
+    }
+
+    /// The Windows toolchains Rust can build with here: a host toolchain or
+    /// an added target of either ABI, in rustup's order.
+    pub fn windows_toolchains(&self) -> Vec<WindowsAbi> {
+        let mut names = Vec::new();
+        if self.has_rustup() {
+            let dir = env::temp_dir();
+            names.extend(self.rustup_list(&dir, &["toolchain", "list"]).unwrap_or_default());
+            names.extend(self.rustup_list(&dir, &["target", "list", "--installed"]).unwrap_or_default());
+        } else {
+            names.extend(self.rustc_host());
+        }
+        let mut found: Vec<WindowsAbi> = Vec::new();
+        for abi in names.iter().filter_map(|name| WindowsAbi::of_triple(name)) {
+            if !found.contains(&abi) {
+                found.push(abi);
+            }
+        }
+        found
+    }
+
+    /// Whether the tools `abi` links with are installed.
+    pub fn has_linker(&self, abi: WindowsAbi) -> bool {
+        match abi {
+            WindowsAbi::Msvc => self.build_tools() == BuildTools::Ready,
+            WindowsAbi::Gnu => {
+                self.command_exists("gcc") || self.command_exists(&format!("{}-w64-mingw32-gcc", env::consts::ARCH))
+            }
+        }
+    }
+
+    /// What to install to get the linker for `abi`.
+    fn linker_advice(&self, abi: WindowsAbi) -> String {
+        match abi {
+            WindowsAbi::Msvc => self.build_tools().advice(),
+            WindowsAbi::Gnu => "Install MinGW-w64 and put its bin dir on PATH, e.g. with MSYS2:\n  \
+                 pacman -S mingw-w64-x86_64-gcc"
+                .to_string(),
+        }
+    }
+
+    /// Check that the linker the Windows toolchain in use needs is there.
+    pub(crate) fn probe_windows_linker(&self) -> Result<(), InstallerError> {
+        let Some(triple) = self.build_target().map(str::to_string).or_else(|| self.rustc_host()) else {
+            return Ok(());
+        };
+        let Some(abi) = WindowsAbi::of_triple(&triple) else {
+            return Ok(());
+        };
+        if self.has_linker(abi) {
+            return Ok(());
+        }
+        self.log_error(&format!("Building for {} needs {}, which wasn't found", triple, abi.linker()));
+        self.print_line(&format!("  {}", self.linker_advice(abi)));
+        if abi == WindowsAbi::Msvc && self.build_tools() == BuildTools::Missing && self.offer_build_tools_install()? {
+            return Ok(());
+        }
+        let other = abi.other();
+        if self.windows_toolchains().contains(&other) && self.has_linker(other) {
+            self.log_info(&format!(
+                "Or build with the {} toolchain, which this machine has what it needs for: --target {}",
+                other.name(),
+                other.name()
+            ));
+        }
+        Err(InstallerError::Cargo(format!("{} not available for {}", abi.linker(), triple)))
+    }
+
+    /// Doctor's view of the Windows toolchains: which are installed and
+    /// whether each can link.
+    pub(crate) fn windows_toolchain_check(&self) -> CheckResult {
+        let toolchains = self.windows_toolchains();
+        if toolchains.is_empty() {
+            return CheckResult::fail(
+                "Rust toolchains",
+                "no msvc or gnu Rust toolchain found",
+                "Install Rust from https://rustup.rs",
+            );
+        }
+        let (usable, unusable): (Vec<WindowsAbi>, Vec<WindowsAbi>) =
+            toolchains.into_iter().partition(|abi| self.has_linker(*abi));
+        let names = |abis: &[WindowsAbi]| abis.iter().map(|abi| abi.name()).collect::<Vec<_>>().join(", ");
+        let Some(missing) = unusable.first() else {
+            return CheckResult::pass("Rust toolchains", format!("{} (linkers found)", names(&usable)));
+        };
+        if usable.is_empty() {
+            CheckResult::fail(
+                "Rust toolchains",
+                format!("{} installed, but {} wasn't found", names(&unusable), missing.linker()),
+                self.linker_advice(*missing),
+            )
+        } else {
+            CheckResult::note(
+                "Rust toolchains",
+                format!("{} can link; {} is missing {}", names(&usable), names(&unusable), missing.linker()),
+                format!("Build with --target {}", usable[0].name()),
+            )
+        }
+    }
+}
@@ -0,0 +1,208 @@
+// Output themes: the prefixes and colors kipper marks its messages with.
+// The default look isn't readable everywhere (yellow on a white terminal),
+// so `[theme]` in kipper.toml can pick another one or adjust its parts.
+
+use crate::LogLevel;
+use crate::capabilities::colors_supported;
+use crate::config::{StyleConfig, ThemeConfig};
+
+pub const THEMES: [&str; 3] = ["default", "boring", "high-contrast"];
+
+const COLORS: [(&str, &str); 8] = [
+    ("black", "30"),
+    ("red", "31"),
+    ("green", "32"),
+    ("yellow", "33"),
+    ("blue", "34"),
+    ("magenta", "35"),
+    ("cyan", "36"),
+    ("white", "37"),
+];
+
+/// How messages of one level are marked.
+#[derive(Debug, Clone)]
+pub struct Style {
+    pub prefix: String,
+    /// SGR parameters, e.g. `1;31`; `None` leaves the terminal's own color.
+    pub color: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Theme {
+    pub debug: Style,
+    pub info: Style,
+    pub success: Style,
+    pub warning: Style,
+    pub error: Style,
+    pub banner: Option<String>,
+    pub tagline: Option<String>,
+    pub highlight: Option<String>,
+    /// Off where colors aren't supported (see `capabilities`): prefixes
+    /// are kept, escapes are not.
+    colors: bool,
+}
+
+fn style(prefix: &str, color: Option<&str>) -> Style {
+    Style {
+        prefix: prefix.to_string(),
+        color: color.map(str::to_string),
+    }
+}
+
+/// SGR parameters for a color name: one of the eight terminal colors,
+/// `bold`, `bold-<color>` or `none`.
+fn parse_color(name: &str) -> Result<Option<String>, String> {
+    let name = name.trim().to_ascii_lowercase();
+    if name == "none" {
+        return Ok(None);
+    }
+    if name == "bold" {
+        return Ok(Some("1".to_string()));
+    }
+    let (bold, base) = match name.strip_prefix("bold-") {
+        Some(base) => (true, base),
+        None => (false, name.as_str()),
+    };
+    let code = COLORS
+        .iter()
+        .find(|(color, _)| *color == base)
+        .map(|(_, code)| *code)
+        .ok_or_else(|| {
+            let names: Vec<&str> = COLORS.iter().map(|(color, _)| *color).collect();
+            format!("unknown color '{}' (use {}, bold, bold-<color> or none)", name, names.join(", "))
+        })?;
+    Ok(Some(if bold { format!("1;{}", code) } else { code.to_string() }))
+}
+
+impl Theme {
+    /// The look kipper has always had.
+    pub fn default_theme() -> Theme {
+        Theme {
+            debug: style("[DEBUG]", Some("36")),
+            info: style("[INFO]", Some("34")),
+            success: style("[YAY!]", Some("32")),
+            warning: style("[WARN]", Some("33")),
+            error: style("[ERR]", Some("31")),
+            banner: Some("34".to_string()),
+            tagline: Some("33".to_string()),
+            highlight: Some("32".to_string()),
+            colors: true,
+        }
+    }
+
+    /// Plain words and no colors, for logs and screenshots in documentation.
+    pub fn boring() -> Theme {
+        Theme {
+            debug: style("debug:", None),
+            info: style("info:", None),
+            success: style("done:", None),
+            warning: style("warning:", None),
+            error: style("error:", None),
+            banner: None,
+            tagline: None,
+            highlight: None,
+            colors: true,
+        }
+    }
+
+    /// Bold text in the terminal's own foreground color, which stays readable
+    /// on light and dark backgrounds; only errors get a color of their own.
+    pub fn high_contrast() -> Theme {
+        Theme {
+            debug: style("[DEBUG]", None),
+            info: style("[INFO]", Some("1")),
+            success: style("[DONE]", Some("1")),
+            warning: style("[WARNING]", Some("1")),
+            error: style("[ERROR]", Some("1;31")),
+            banner: Some("1".to_string()),
+            tagline: None,
+            highlight: Some("1".to_string()),
+            colors: true,
+        }
+    }
+
+    pub fn named(name: &str) -> Option<Theme> {
+        match name {
+            "default" => Some(Theme::default_theme()),
+            "boring" => Some(Theme::boring()),
+            "high-contrast" => Some(Theme::high_contrast()),
+            _ => None,
+        }
+    }
+
+    /// The theme `config` describes: its base theme with the configured parts replaced.
+    pub fn from_config(config: &ThemeConfig) -> Result<Theme, String> {
+        let name = config.name.as_deref().unwrap_or("default");
+        let mut theme = Theme::named(name)
+            .ok_or_else(|| format!("unknown theme '{}' (use {})", name, THEMES.join(", ")))?;
+
+        for (style, custom) in [
+            (&mut theme.debug, &config.debug),
+            (&mut theme.info, &config.info),
+            (&mut theme.success, &config.success),
+            (&mut theme.warning, &config.warning),
+            (&mut theme.error, &config.error),
+        ] {
+            let Some(StyleConfig { prefix, color }) = custom else {
+                continue;
+            };
+            if let Some(prefix) = prefix {
+                style.prefix = prefix.clone();
+            }
+            if let Some(color) = color {
+                style.color = parse_color(color)?;
+            }
+        }
+        for (slot, color) in [
+            (&mut theme.banner, &config.banner),
+            (&mut theme.tagline, &config.tagline),
+            (&mut theme.highlight, &config.highlight),
+        ] {
+            if let Some(color) = color {
+                *slot = parse_color(color)?;
+            }
+        }
+
+        theme.colors = colors_supported();
+        Ok(theme)
+    }
+
+    /// The same theme without color escapes, as where colors aren't supported.
+    pub fn without_colors(mut self) -> Theme {
+        self.colors = false;
+        self
+    }
+
+    pub fn has_colors(&self) -> bool {
+        self.colors
+    }
+
+    /// `text` in `color`, or unchanged when there's no color to apply.
+    pub fn paint(&self, color: Option<&str>, text: &str) -> String {
+        match color {
+            Some(code) if self.colors => format!("\x1b[{}m{}\x1b[0m", code, text),
+            _ => text.to_string(),
+        }
+    }
+
+    pub fn style(&self, level: LogLevel) -> &Style {
+        match level {
+            LogLevel::Debug => &self.debug,
+            LogLevel::Info => &self.info,
+            LogLevel::Success => &self.success,
+            LogLevel::Warning => &self.warning,
+            LogLevel::Error => &self.error,
+        }
+    }
+
+    /// A message line marked with `level`'s prefix.
+    pub fn message(&self, level: LogLevel, msg: &str) -> String {
+        let style = self.style(level);
+        let prefix = self.paint(style.color.as_deref(), &style.prefix);
+        if prefix.is_empty() {
+            msg.to_string()
+        } else {
+            format!("{} {}", prefix, msg)
+        }
+    }
+}
@@ -0,0 +1,182 @@
+// Timestamps kipper writes to its metadata: always UTC in RFC 3339
+// (`2024-05-01T12:00:00Z`), so state and summaries from different machines
+// and CI runs compare directly. Local time is only for showing them.
+
+use std::env;
+use std::fs;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Seconds since the Unix epoch.
+pub fn unix_now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs())
+}
+
+/// The current time as an RFC 3339 UTC timestamp.
+pub fn now_utc() -> String {
+    utc_timestamp(unix_now())
+}
+
+/// Year, month and day of the day `days` after 1970-01-01 (Howard Hinnant's
+/// civil-from-days).
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    (yoe + era * 400 + i64::from(month <= 2), month, day)
+}
+
+/// Days from 1970-01-01 to the given date; the inverse of `civil_from_days`.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400);
+    let mp = if month > 2 { month - 3 } else { month + 9 };
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+/// `secs` as date and time separated by `separator`.
+fn format_time(secs: i64, separator: char) -> String {
+    let (year, month, day) = civil_from_days(secs.div_euclid(86_400));
+    let rem = secs.rem_euclid(86_400);
+    format!(
+        "{:04}-{:02}-{:02}{}{:02}:{:02}:{:02}",
+        year,
+        month,
+        day,
+        separator,
+        rem / 3600,
+        rem % 3600 / 60,
+        rem % 60
+    )
+}
+
+/// RFC 3339 UTC timestamp for seconds since the Unix epoch.
+pub fn utc_timestamp(secs: u64) -> String {
+    format!("{}Z", format_time(secs as i64, 'T'))
+}
+
+/// Seconds since the Unix epoch for an RFC 3339 timestamp. Fractional
+/// seconds are dropped; offsets other than `Z` are honoured.
+pub fn parse_timestamp(timestamp: &str) -> Option<i64> {
+    let timestamp = timestamp.trim();
+    let number = |range: std::ops::Range<usize>| timestamp.get(range)?.parse::<i64>().ok();
+    let bytes = timestamp.as_bytes();
+    if bytes.len() < 20 || bytes[4] != b'-' || bytes[7] != b'-' || !matches!(bytes[10], b'T' | b't' | b' ') {
+        return None;
+    }
+    let (year, month, day) = (number(0..4)?, number(5..7)?, number(8..10)?);
+    let (hour, minute, second) = (number(11..13)?, number(14..16)?, number(17..19)?);
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) || hour > 23 || minute > 59 || second > 60 {
+        return None;
+    }
+
+    let mut zone = &timestamp[19..];
+    if let Some(fraction) = zone.strip_prefix('.') {
+        zone = fraction.trim_start_matches(|c: char| c.is_ascii_digit());
+    }
+    let offset = match zone {
+        "Z" | "z" => 0,
+        _ => {
+            let sign = match zone.as_bytes().first()? {
+                b'+' => 1,
+                b'-' => -1,
+                _ => return None,
+            };
+            let (hours, minutes) = zone[1..].split_once(':')?;
+            sign * (hours.parse::<i64>().ok()? * 3600 + minutes.parse::<i64>().ok()? * 60)
+        }
+    };
+    Some(days_from_civil(year, month, day) * 86_400 + hour * 3600 + minute * 60 + second - offset)
+}
+
+/// Offset from UTC in seconds at `at` according to a TZif file (RFC 8536):
+/// the type of the last transition at or before `at`.
+fn tzif_offset(data: &[u8], at: i64) -> Option<i64> {
+    let count = |data: &[u8], field: usize| -> Option<usize> {
+        let bytes = data.get(20 + field * 4..24 + field * 4)?;
+        Some(u32::from_be_bytes(bytes.try_into().ok()?) as usize)
+    };
+    if data.get(..4)? != b"TZif" {
+        return None;
+    }
+    // Version 2+ files repeat the data with 64-bit times after the version 1 block
+    let (data, time_size) = if *data.get(4)? >= b'2' {
+        let [isut, isstd, leap, time, kinds, chars] = [0, 1, 2, 3, 4, 5].map(|field| count(data, field));
+        let v1_len = time? * 5 + kinds? * 6 + chars? + leap? * 8 + isstd? + isut?;
+        (data.get(44 + v1_len..)?, 8)
+    } else {
+        (data, 4)
+    };
+
+    let (times, kinds) = (count(data, 3)?, count(data, 4)?);
+    let body = data.get(44..)?;
+    let indices = body.get(times * time_size..times * (time_size + 1))?;
+    let types = body.get(times * (time_size + 1)..times * (time_size + 1) + kinds * 6)?;
+    let transition = |i: usize| -> Option<i64> {
+        let bytes = body.get(i * time_size..(i + 1) * time_size)?;
+        Some(match time_size {
+            8 => i64::from_be_bytes(bytes.try_into().ok()?),
+            _ => i64::from(i32::from_be_bytes(bytes.try_into().ok()?)),
+        })
+    };
+
+    let mut kind = 0;
+    for (i, index) in indices.iter().enumerate() {
+        if transition(i)? > at {
+            break;
+        }
+        kind = usize::from(*index);
+    }
+    let offset = types.get(kind * 6..kind * 6 + 4)?;
+    Some(i64::from(i32::from_be_bytes(offset.try_into().ok()?)))
+}
+
+/// The local offset from UTC at `at`, from `TZ` or /etc/localtime. `None`
+/// when the zone can't be determined, e.g. a POSIX rule in `TZ` or Windows.
+fn local_offset(at: i64) -> Option<i64> {
+    let zone = match env::var("TZ") {
+        Ok(tz) => {
+            let tz = tz.trim_start_matches(':').to_string();
+            if tz.is_empty() || tz == "UTC" || tz == "UTC0" || tz == "GMT" {
+                return Some(0);
+            }
+            if tz.starts_with('/') { tz } else { format!("/usr/share/zoneinfo/{}", tz) }
+        }
+        Err(_) => "/etc/localtime".to_string(),
+    };
+    tzif_offset(&fs::read(zone).ok()?, at)
+}
+
+/// `timestamp` in local time for display, e.g. `2024-05-01 14:00:00 +02:00`,
+/// or in UTC when the local zone is unknown. Unparseable input is shown as it is.
+pub fn display_local(timestamp: &str) -> String {
+    let Some(secs) = parse_timestamp(timestamp) else {
+        return timestamp.to_string();
+    };
+    match local_offset(secs) {
+        Some(offset) => format!(
+            "{} {}{:02}:{:02}",
+            format_time(secs + offset, ' '),
+            if offset < 0 { '-' } else { '+' },
+            offset.abs() / 3600,
+            offset.abs() % 3600 / 60
+        ),
+        None => format!("{} UTC", format_time(secs, ' ')),
+    }
+}
+
+/// Day of the week (0 is Monday) and minute of the day at `at` in local
+/// time, or in UTC when the local zone is unknown.
+pub fn local_weekday_minute(at: i64) -> (usize, u32) {
+    let local = at + local_offset(at).unwrap_or(0);
+    // 1970-01-01 was a Thursday
+    let weekday = (local.div_euclid(86_400) + 3).rem_euclid(7) as usize;
+    (weekday, (local.rem_euclid(86_400) / 60) as u32)
+}
@@ -0,0 +1,118 @@
+// Install phase timings: how long fetching, building and installing took on
+// this machine, kept in state so the next install can say how long it will take.
+
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+use crate::announce::ProgressMode;
+use crate::metrics::PhaseRun;
+use crate::state::State;
+use crate::{Installer, InstallerError};
+
+/// Weight of the latest run in the moving average, so one unusually slow
+/// build (cold cargo cache, busy machine) doesn't dominate later estimates.
+const LATEST_WEIGHT: f64 = 0.5;
+
+/// The steps of an install, in the order they run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Phase {
+    Fetch,
+    Build,
+    Install,
+}
+
+impl Phase {
+    pub const ALL: [Phase; 3] = [Phase::Fetch, Phase::Build, Phase::Install];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Phase::Fetch => "fetch",
+            Phase::Build => "build",
+            Phase::Install => "install",
+        }
+    }
+}
+
+/// A duration rounded for people: `45s`, `3m 20s`, `1h 05m`.
+pub fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    if secs < 60 {
+        format!("{}s", secs.max(1))
+    } else if secs < 3600 {
+        format!("{}m {:02}s", secs / 60, secs % 60)
+    } else {
+        format!("{}h {:02}m", secs / 3600, secs % 3600 / 60)
+    }
+}
+
+impl Installer {
+    /// Builds are timed per profile, since a minimal build compiles one
+    /// binary, and quick and `kipper optimize` builds apart from the rest.
+    fn phase_key(&self, phase: Phase) -> String {
+        match phase {
+            Phase::Build if self.is_optimizing() => format!("build-{}-optimized", self.effective_profile()),
+            Phase::Build if self.builds_quick() => format!("build-{}-quick", self.effective_profile()),
+            Phase::Build => format!("build-{}", self.effective_profile()),
+            _ => phase.name().to_string(),
+        }
+    }
+
+    /// How long `phase` usually takes here, if it has run before.
+    pub fn phase_estimate(&self, phase: Phase) -> Option<Duration> {
+        let secs = *State::load(&self.install_dir).phase_seconds.get(&self.phase_key(phase))?;
+        Some(Duration::from_secs_f64(secs))
+    }
+
+    /// Expected time from the start of `phase` to the end of the install, when
+    /// every phase still to come has been timed before.
+    pub fn remaining_estimate(&self, phase: Phase) -> Option<Duration> {
+        Phase::ALL
+            .into_iter()
+            .skip_while(|p| *p != phase)
+            .map(|p| self.phase_estimate(p))
+            .sum()
+    }
+
+    /// Run `phase`, announcing the estimated time left before it starts and
+    /// folding its duration into the estimate once it succeeds.
+    pub(crate) fn timed<T>(&self, phase: Phase, run: impl FnOnce() -> Result<T, InstallerError>) -> Result<T, InstallerError> {
+        let announce = self.effective_progress_mode() == ProgressMode::Announce;
+        if !announce && let Some(remaining) = self.remaining_estimate(phase) {
+            self.log_info(&format!("Estimated time remaining: about {}", format_duration(remaining)));
+        }
+        self.set_current_phase(Some(phase));
+        let started = Instant::now();
+        let result = if announce { self.announced(phase, run) } else { run() };
+        self.set_current_phase(None);
+        let elapsed = started.elapsed();
+        self.record_phase_run(PhaseRun {
+            phase,
+            seconds: elapsed.as_secs_f64(),
+            succeeded: result.is_ok(),
+        });
+        let result = result?;
+        self.record_phase(phase, elapsed);
+        self.record_phase_time(phase, elapsed);
+        Ok(result)
+    }
+
+    fn set_current_phase(&self, phase: Option<Phase>) {
+        if let Ok(mut current) = self.current_phase.lock() {
+            *current = phase;
+        }
+    }
+
+    fn record_phase(&self, phase: Phase, elapsed: Duration) {
+        let mut state = State::load(&self.install_dir);
+        let secs = elapsed.as_secs_f64();
+        let average = state
+            .phase_seconds
+            .get(&self.phase_key(phase))
+            .map_or(secs, |previous| previous * (1.0 - LATEST_WEIGHT) + secs * LATEST_WEIGHT);
+        state.phase_seconds.insert(self.phase_key(phase), average);
+        // Estimates are a nicety; never fail an install over saving them
+        let _ = state.save(&self.install_dir);
+    }
+}
@@ -0,0 +1,162 @@
+// Toolchain version selection: environment, project pin, or user default
+
+use std::fmt;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+use crate::InstallerError;
+
+/// Per-project pin file, looked up from the working directory towards the root.
+pub const VERSION_FILE: &str = ".kopi-version";
+/// Environment override that beats both the project pin and the default.
+pub const VERSION_ENV: &str = "KOPI_VERSION";
+/// Set by `kipper exec` to the directory of the toolchain in use.
+pub const HOME_ENV: &str = "KOPI_HOME";
+/// The version built from the default branch of kopi-lang.
+pub const NIGHTLY: &str = "nightly";
+
+/// Extra tools kopi-lang may build alongside the interpreter.
+pub const COMPONENTS: &[&str] = &["kopi-lsp", "kopi-fmt", "kopi-doc"];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VersionSource {
+    Env,
+    Project(PathBuf),
+    Default,
+}
+
+impl VersionSource {
+    pub fn describe(&self) -> &'static str {
+        match self {
+            VersionSource::Env => "env",
+            VersionSource::Project(_) => "project",
+            VersionSource::Default => "default",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ResolvedVersion {
+    pub name: String,
+    pub source: VersionSource,
+}
+
+/// Platform file name of an executable, e.g. `kopi` or `kopi.exe`.
+pub fn exe_name(tool: &str) -> String {
+    if cfg!(windows) {
+        format!("{}.exe", tool)
+    } else {
+        tool.to_string()
+    }
+}
+
+/// Whether `name` can name a version: it is a directory under `versions/`,
+/// so a single plain path component that doesn't read as an option.
+pub fn is_version_name(name: &str) -> bool {
+    let mut components = Path::new(name).components();
+    let single = matches!((components.next(), components.next()), (Some(Component::Normal(c)), None) if c == name);
+    single && !name.contains(['/', '\\', ':']) && !name.starts_with('-')
+}
+
+/// Refuse a version name that would reach outside `versions/`.
+pub fn check_version_name(name: &str) -> Result<(), InstallerError> {
+    if is_version_name(name) {
+        Ok(())
+    } else {
+        Err(InstallerError::PathError(format!("'{}' is not a Kopi version name", name)))
+    }
+}
+
+/// A version setting that was skipped because it can't name a version.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RejectedVersion {
+    /// Where it was read from: `KOPI_VERSION`, a project file or `default`.
+    pub from: String,
+    pub name: String,
+}
+
+impl fmt::Display for RejectedVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Ignoring {}: '{}' is not a Kopi version name", self.from, self.name)
+    }
+}
+
+/// `name` as read from `from`, or its rejection.
+fn usable(name: &str, from: &str) -> Result<String, RejectedVersion> {
+    if is_version_name(name) {
+        Ok(name.to_string())
+    } else {
+        Err(RejectedVersion {
+            from: from.to_string(),
+            name: name.to_string(),
+        })
+    }
+}
+
+/// Find the nearest `.kopi-version` at or above `start`.
+pub fn find_project_version(start: &Path) -> Option<(String, PathBuf)> {
+    for dir in start.ancestors() {
+        let candidate = dir.join(VERSION_FILE);
+        if let Ok(contents) = fs::read_to_string(&candidate) {
+            let name = contents.trim();
+            if !name.is_empty() {
+                return Some((name.to_string(), candidate));
+            }
+        }
+    }
+    None
+}
+
+/// Pick the version for `cwd`: `requested` (the value of `KOPI_VERSION`),
+/// then the project pin, then `default`. Settings that can't name a version
+/// are skipped and handed to `reject`.
+pub fn resolve_version(
+    requested: Option<&str>,
+    cwd: &Path,
+    default: Option<String>,
+    mut reject: impl FnMut(RejectedVersion),
+) -> Option<ResolvedVersion> {
+    if let Some(name) = requested.map(str::trim).filter(|name| !name.is_empty()) {
+        match usable(name, VERSION_ENV) {
+            Ok(name) => {
+                return Some(ResolvedVersion {
+                    name,
+                    source: VersionSource::Env,
+                });
+            }
+            Err(rejected) => reject(rejected),
+        }
+    }
+
+    if let Some((name, file)) = find_project_version(cwd) {
+        match usable(&name, &file.display().to_string()) {
+            Ok(name) => {
+                return Some(ResolvedVersion {
+                    name,
+                    source: VersionSource::Project(file),
+                });
+            }
+            Err(rejected) => reject(rejected),
+        }
+    }
+
+    default.map(|name| ResolvedVersion {
+        name,
+        source: VersionSource::Default,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn version_names_stay_in_versions_dir() {
+        for name in ["nightly", "v1.2.0", "v2.0.0-rc.1", "my-build"] {
+            assert!(is_version_name(name), "{}", name);
+        }
+        for name in ["", ".", "..", "../..", "a/b", "a\\b", "/tmp", "C:\\x", "C:", "-rf", "v1/"] {
+            assert!(!is_version_name(name), "{}", name);
+        }
+    }
+}
@@ -0,0 +1,127 @@
+// Command tracing (`--trace-commands`): every command kipper runs is shown,
+// and logged, as a line that can be pasted into a shell to repeat the step by
+// hand, whatever `KIPPER_LOG` says:
+//
+//     + (cd '/tmp/kopi-install-42/kopi-lang' && RUSTFLAGS='-C debuginfo=0' cargo build --release)
+//
+// The line holds the working directory, the variables kipper set or removed
+// for the command and those it passes on from your environment that change
+// what git, cargo or rustup do. Secrets are redacted as everywhere else (see
+// redact.rs), including variables kipper sets only for the command. In the
+// JSON format the event also has `argv`, `cwd` and `env` fields.
+
+use std::env;
+use std::ffi::OsStr;
+use std::process::Command;
+
+use serde_json::{Map, Value, json};
+
+use crate::logging::LogFormat;
+use crate::redact::{REDACTED, is_secret, redact};
+use crate::script::sh_quote;
+use crate::verbosity::program_subsystem;
+use crate::Installer;
+
+/// The `level` of traced commands in the log.
+const TRACE: &str = "trace";
+
+/// Inherited variables worth repeating with a command, by name prefix: only
+/// git's own for git, everything that steers a build for the rest.
+const GIT_PREFIXES: [&str; 1] = ["GIT_"];
+const BUILD_PREFIXES: [&str; 5] = ["CARGO", "RUST", "PKG_CONFIG", "CC", "CXX"];
+
+fn is_relevant(subsystem: &str, name: &str) -> bool {
+    let prefixes: &[&str] = if subsystem == "git" { &GIT_PREFIXES } else { &BUILD_PREFIXES };
+    prefixes.iter().any(|prefix| name.starts_with(prefix))
+}
+
+/// `text` as one shell word, quoted only when it has to be.
+fn shell_word(text: &str) -> String {
+    let plain = !text.is_empty()
+        && text
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "_-./:=@%+,".contains(c));
+    if plain { text.to_string() } else { sh_quote(text) }
+}
+
+fn lossy(text: &OsStr) -> String {
+    text.to_string_lossy().into_owned()
+}
+
+/// The variables a trace shows for `command`: the relevant inherited ones,
+/// then those set for it. `None` means the variable is removed.
+fn traced_env(command: &Command, subsystem: &str) -> Vec<(String, Option<String>)> {
+    let explicit: Vec<(String, Option<String>)> = command
+        .get_envs()
+        .map(|(name, value)| (lossy(name), value.map(lossy)))
+        .collect();
+    let mut traced: Vec<(String, Option<String>)> = env::vars()
+        .filter(|(name, _)| is_relevant(subsystem, name) && !explicit.iter().any(|(set, _)| set == name))
+        .map(|(name, value)| (name, Some(value)))
+        .collect();
+    traced.sort();
+    traced.extend(explicit);
+    for (name, value) in &mut traced {
+        if is_secret(name) && value.is_some() {
+            *value = Some(REDACTED.to_string());
+        }
+    }
+    traced
+}
+
+/// `command` as a line to paste into sh, bash or zsh.
+fn command_line(command: &Command, env: &[(String, Option<String>)]) -> String {
+    let removed: Vec<&str> = env.iter().filter(|(_, value)| value.is_none()).map(|(name, _)| name.as_str()).collect();
+    let mut words: Vec<String> = Vec::new();
+    if !removed.is_empty() {
+        words.push("env".to_string());
+        for name in removed {
+            words.push(format!("-u {}", name));
+        }
+    }
+    for (name, value) in env {
+        if let Some(value) = value {
+            words.push(format!("{}={}", name, shell_word(value)));
+        }
+    }
+    words.push(shell_word(&lossy(command.get_program())));
+    words.extend(command.get_args().map(|arg| shell_word(&lossy(arg))));
+    let line = words.join(" ");
+    match command.get_current_dir() {
+        Some(dir) => format!("(cd {} && {})", shell_word(&dir.display().to_string()), line),
+        None => line,
+    }
+}
+
+impl Installer {
+    /// Show every command kipper runs, see above.
+    pub fn with_trace_commands(mut self, trace: bool) -> Self {
+        self.trace_commands = trace;
+        self
+    }
+
+    /// Show and log `command` when tracing.
+    pub(crate) fn trace_command(&self, command: &Command) {
+        if !self.trace_commands {
+            return;
+        }
+        let program = lossy(command.get_program());
+        let subsystem = program_subsystem(&program).unwrap_or_else(|| self.subsystem());
+        let env = traced_env(command, subsystem);
+        let line = redact(&format!("+ {}", command_line(command, &env)));
+
+        let mut fields = Map::new();
+        if self.log_format == LogFormat::Json {
+            let mut argv = vec![Value::String(program)];
+            argv.extend(command.get_args().map(|arg| Value::String(lossy(arg))));
+            fields.insert("argv".to_string(), Value::Array(argv));
+            fields.insert("cwd".to_string(), json!(command.get_current_dir().map(|dir| dir.display().to_string())));
+            fields.insert("env".to_string(), env.into_iter().map(|(name, value)| (name, json!(value))).collect());
+            fields.insert("pid".to_string(), std::process::id().into());
+            eprintln!("{}", self.json_record(subsystem, TRACE, &line, &fields));
+        } else {
+            eprintln!("{}", line);
+        }
+        self.append_to_log(subsystem, TRACE, &line, &fields);
+    }
+}
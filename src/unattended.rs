@@ -0,0 +1,67 @@
+// `--unattended`: one flag for ephemeral CI runners and build farms. It
+// answers yes to confirmations (without overriding refusals the way --force
+// does), never offers to set up PATH, turns colors off, cuts huge build errors
+// down to their start with the rest in the log file, kills any external
+// command still running after an hour (or `--timeout`), says it is still
+// working every minute so watchdogs that kill silent jobs leave the build
+// alone, and builds with half the CPUs so a shared runner stays responsive.
+
+use std::thread;
+use std::time::Duration;
+
+use serde_json::Map;
+
+use crate::logging::{LOG_FILE, OUTPUT, log_dir};
+use crate::Installer;
+
+/// How long an external command may run in unattended mode, unless `--timeout` says otherwise.
+pub const UNATTENDED_TIMEOUT: Duration = Duration::from_secs(60 * 60);
+
+/// How often a long-running command is reported as still running.
+pub const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Lines of a failed command's output kept in the error.
+const MAX_ERROR_LINES: usize = 40;
+
+impl Installer {
+    pub fn with_unattended(mut self, unattended: bool) -> Self {
+        self.unattended = unattended;
+        if unattended {
+            self.theme = self.theme.without_colors();
+            self.command_timeout.get_or_insert(UNATTENDED_TIMEOUT);
+        }
+        self
+    }
+
+    /// Kill external commands that run longer than `timeout`.
+    pub fn with_command_timeout(mut self, timeout: Duration) -> Self {
+        self.command_timeout = Some(timeout);
+        self
+    }
+
+    pub fn is_unattended(&self) -> bool {
+        self.unattended
+    }
+
+    /// The number of cargo jobs to build with, when not cargo's default.
+    pub(crate) fn build_jobs(&self) -> Option<usize> {
+        self.unattended
+            .then(|| thread::available_parallelism().map_or(1, |cpus| (cpus.get() / 2).max(1)))
+    }
+
+    /// `output` of a failed command as it goes into an error. Unattended, only
+    /// the first lines are kept and the whole output goes to the log file.
+    pub(crate) fn bounded_output(&self, command: &str, output: &str) -> String {
+        let lines: Vec<&str> = output.lines().collect();
+        if !self.unattended || lines.len() <= MAX_ERROR_LINES {
+            return output.to_string();
+        }
+        self.append_to_log(self.subsystem(), OUTPUT, &format!("Full output of {}:\n{}", command, output), &Map::new());
+        format!(
+            "{}\n... {} more lines; the full output is in {}",
+            lines[..MAX_ERROR_LINES].join("\n"),
+            lines.len() - MAX_ERROR_LINES,
+            log_dir(&self.install_dir).join(LOG_FILE).display()
+        )
+    }
+}
@@ -0,0 +1,276 @@
+// `kipper update`: fetch the default version's latest source, show what
+// changed since the installed build, and rebuild only when it's worth it.
+//
+// An update can also happen in two steps: `--prepare` builds into the
+// prepared dir (`~/.kopi/prepared/<version>`) while the installed build keeps
+// running, and `--commit` later swaps the prepared build into the versions
+// store with a rename, so the switch itself takes no time.
+
+use std::fs;
+use std::io::{self, IsTerminal};
+use std::path::PathBuf;
+
+use crate::platform::extended_length_path;
+use crate::state::{State, VersionRecord};
+use crate::timestamp::{local_weekday_minute, unix_now};
+use crate::timing::Phase;
+use crate::toolchain::exe_name;
+use crate::{Installer, InstallerError};
+
+/// Commit subjects shown before the rest are summarised as a count.
+const SHORTLOG_LIMIT: usize = 20;
+
+pub const PREPARED_DIR: &str = "prepared";
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct UpdateOptions {
+    /// Ask before rebuilding when running interactively.
+    pub confirm: bool,
+    /// List the subjects of the incoming commits.
+    pub shortlog: bool,
+    /// Build into the prepared dir instead of replacing the installed build.
+    pub prepare: bool,
+}
+
+fn short(commit: &str) -> &str {
+    &commit[..commit.len().min(7)]
+}
+
+impl Installer {
+    /// Rebuild the default version from the latest source, without prompting.
+    pub fn update(&self) -> Result<(), InstallerError> {
+        self.update_with(UpdateOptions::default())
+    }
+
+    pub fn update_with(&self, options: UpdateOptions) -> Result<(), InstallerError> {
+        self.refuse_when_locked("update")?;
+        let version = self
+            .default_version()
+            .ok_or_else(|| InstallerError::PathError("Kopi is not installed".to_string()))?;
+
+        self.log_info(&format!("Checking for updates to Kopi {}...", version));
+        self.prepare_install()?;
+        self.rollback_on_cancel(self.timed(Phase::Fetch, || self.clone_source(Some(&version))))?;
+
+        let state = State::load(&self.install_dir);
+        let installed = state.versions.get(&version);
+        let latest = self.source_record()?;
+        let profile_changed = state.profile != Some(self.effective_profile());
+
+        if installed.is_some_and(|old| old.commit == latest.commit) && !profile_changed && !self.options_changed(&version) {
+            self.log_success(&format!("Kopi {} is already up to date ({})", version, short(&latest.commit)));
+            return Ok(());
+        }
+        if options.prepare
+            && state.prepared.get(&version).is_some_and(|prepared| prepared.commit == latest.commit)
+            && self.prepared_dir(&version).join(exe_name("kopi")).exists()
+        {
+            self.log_success(&format!(
+                "An update of Kopi {} to {} is already prepared; run 'kipper update --commit' to switch to it",
+                version,
+                short(&latest.commit)
+            ));
+            return Ok(());
+        }
+
+        self.print_update_summary(&version, installed, &latest, options.shortlog);
+
+        // Unlike other questions, no terminal means yes: updating is what was asked for
+        let question = if options.prepare { "Build now?" } else { "Rebuild now?" };
+        if options.confirm && io::stdin().is_terminal() && !self.ask(question, true)? {
+            self.log_info("Update cancelled");
+            return Ok(());
+        }
+
+        self.rollback_on_cancel(self.timed(Phase::Build, || self.build_source(&version)))?;
+        if options.prepare {
+            return self.prepare_update(&version, &latest);
+        }
+        self.finish_install(&version)?;
+        // Superseded by the build just installed
+        self.discard_prepared(&version)
+    }
+
+    /// The update a timer runs: switch versions only inside a maintenance
+    /// window from `[update] windows`, and outside one, prepare the update
+    /// for the next run inside a window to commit.
+    pub fn auto_update(&self) -> Result<(), InstallerError> {
+        let windows = self.config.update.maintenance_windows();
+        let (weekday, minute) = local_weekday_minute(unix_now() as i64);
+        if !windows.is_empty() && !windows.iter().any(|window| window.contains(weekday, minute)) {
+            let list: Vec<String> = windows.iter().map(ToString::to_string).collect();
+            self.log_info(&format!(
+                "Outside the maintenance windows ({}); preparing the update without switching to it",
+                list.join(", ")
+            ));
+            return self.update_with(UpdateOptions {
+                prepare: true,
+                ..UpdateOptions::default()
+            });
+        }
+
+        let version = self
+            .default_version()
+            .ok_or_else(|| InstallerError::PathError("Kopi is not installed".to_string()))?;
+        let prepared = State::load(&self.install_dir).prepared.remove(&version);
+        if let Some(prepared) = prepared
+            && self.prepared_dir(&version).join(exe_name("kopi")).exists()
+            && self.upstream_commit(&version)? == prepared.commit
+        {
+            return self.commit_update();
+        }
+        self.update()
+    }
+
+    /// Where `kipper update --prepare` keeps its build of `version`.
+    fn prepared_dir(&self, version: &str) -> PathBuf {
+        self.install_dir.join(PREPARED_DIR).join(version)
+    }
+
+    /// Copy the build into the prepared dir and record it, leaving the
+    /// installed build alone.
+    fn prepare_update(&self, version: &str, latest: &VersionRecord) -> Result<(), InstallerError> {
+        self.log_info(&format!("Preparing Kopi {} without switching to it...", version));
+        let prepared_dir = self.prepared_dir(version);
+        if prepared_dir.exists() {
+            fs::remove_dir_all(extended_length_path(&prepared_dir))?;
+        }
+        let manifest = self.timed(Phase::Install, || self.copy_build(&prepared_dir))?;
+        let mut record = self.build_record(version, manifest.as_ref())?;
+        record.phase_seconds = self.phase_seconds();
+
+        let mut state = State::load(&self.install_dir);
+        state.prepared.insert(version.to_string(), record);
+        state.save(&self.install_dir)?;
+
+        self.log_success(&format!("Kopi {} ({}) is prepared", version, short(&latest.commit)));
+        self.log_info("Run 'kipper update --commit' to switch to it");
+        Ok(())
+    }
+
+    /// Switch the default version to the build `kipper update --prepare`
+    /// made. The old build is moved aside and the prepared one renamed into
+    /// its place, so `kopi` is never missing for more than an instant.
+    pub fn commit_update(&self) -> Result<(), InstallerError> {
+        let version = self
+            .default_version()
+            .ok_or_else(|| InstallerError::PathError("Kopi is not installed".to_string()))?;
+        let prepared_dir = self.prepared_dir(&version);
+        let mut state = State::load(&self.install_dir);
+        let Some(record) = state
+            .prepared
+            .remove(&version)
+            .filter(|_| prepared_dir.join(exe_name("kopi")).exists())
+        else {
+            return Err(InstallerError::PathError(format!(
+                "No update of Kopi {} is prepared; run 'kipper update --prepare' first",
+                version
+            )));
+        };
+
+        let version_dir = self.version_dir(&version);
+        let previous = self.install_dir.join(PREPARED_DIR).join(format!(".{}.previous", version));
+        if previous.exists() {
+            fs::remove_dir_all(extended_length_path(&previous))?;
+        }
+        if version_dir.exists() {
+            fs::rename(&version_dir, &previous)?;
+        }
+        if let Err(e) = fs::rename(&prepared_dir, &version_dir) {
+            if previous.exists() {
+                fs::rename(&previous, &version_dir)?;
+            }
+            return Err(e.into());
+        }
+
+        let commit = record.commit.clone();
+        state.versions.insert(version.clone(), record);
+        state.save(&self.install_dir)?;
+        self.install_shims(&version_dir)?;
+        self.create_uninstaller()?;
+        self.record_profile()?;
+        if previous.exists() {
+            fs::remove_dir_all(extended_length_path(&previous))?;
+        }
+
+        self.log_success(&format!("Switched Kopi {} to {}", version, short(&commit)));
+        self.verify_installation(&version)
+    }
+
+    /// Throw away a prepared build of `version`, e.g. once it is uninstalled
+    /// or replaced by a plain update.
+    pub(crate) fn discard_prepared(&self, version: &str) -> Result<(), InstallerError> {
+        let prepared_dir = self.prepared_dir(version);
+        if prepared_dir.exists() {
+            fs::remove_dir_all(extended_length_path(&prepared_dir))?;
+        }
+        let mut state = State::load(&self.install_dir);
+        if state.prepared.remove(version).is_some() {
+            state.save(&self.install_dir)?;
+        }
+        Ok(())
+    }
+
+    fn print_update_summary(&self, version: &str, installed: Option<&VersionRecord>, latest: &VersionRecord, shortlog: bool) {
+        let Some(installed) = installed else {
+            self.log_info(&format!(
+                "Kopi {}: installed commit unknown, latest is {}",
+                version,
+                short(&latest.commit)
+            ));
+            return;
+        };
+
+        if installed.commit == latest.commit {
+            self.log_info(&format!(
+                "Kopi {} is at the latest commit ({}); rebuilding for the {} profile",
+                version,
+                short(&latest.commit),
+                self.effective_profile()
+            ));
+            return;
+        }
+
+        self.log_info(&format!(
+            "Kopi {}: {} → {}",
+            version,
+            short(&installed.commit),
+            short(&latest.commit)
+        ));
+
+        let range = format!("{}..{}", installed.commit, latest.commit);
+        let count = self
+            .git_in_clone(&["rev-list", "--count", &range])
+            .and_then(|out| out.trim().parse::<usize>().ok());
+        match count {
+            Some(count) => self.print_line(&format!("  {} new commit{}", count, if count == 1 { "" } else { "s" })),
+            // The installed commit may be gone upstream, e.g. after a force push
+            None => self.print_line("  number of new commits unknown"),
+        }
+
+        match (&installed.kopi_version, &latest.kopi_version) {
+            (Some(old), Some(new)) if old != new => self.print_line(&format!("  version {} → {}", old, new)),
+            (_, Some(new)) => self.print_line(&format!("  version {} (unchanged)", new)),
+            _ => {}
+        }
+
+        if shortlog && let Some(log) = self.git_in_clone(&["log", "--no-decorate", "--format=%h %s", &range]) {
+            let lines: Vec<&str> = log.lines().collect();
+            for line in lines.iter().take(SHORTLOG_LIMIT) {
+                self.print_line(&format!("    {}", line));
+            }
+            if lines.len() > SHORTLOG_LIMIT {
+                self.print_line(&format!("    ... and {} more", lines.len() - SHORTLOG_LIMIT));
+            }
+        }
+    }
+
+    /// Stdout of a git command in the scratch clone, or `None` if it failed.
+    fn git_in_clone(&self, args: &[&str]) -> Option<String> {
+        let output = self.git_in_checkout(args).ok()?;
+        output
+            .status
+            .success()
+            .then(|| String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+}
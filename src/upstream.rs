@@ -0,0 +1,64 @@
+// Requirements kopi-lang places on the installer, from a `kipper.toml` at the
+// root of the checkout being built:
+//
+//     min-kipper-version = "0.2.0"
+//
+// A release that changes its layout can raise the minimum so an older
+// kipper stops before installing it wrongly. Unknown keys are ignored, so
+// upstream can add settings older kippers don't know about.
+
+use std::fs;
+use std::path::Path;
+
+use serde::Deserialize;
+
+use crate::release::Release;
+use crate::{Installer, InstallerError};
+
+const UPSTREAM_FILE: &str = "kipper.toml";
+/// The version of the running kipper.
+pub const KIPPER_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct UpstreamConfig {
+    pub min_kipper_version: Option<String>,
+}
+
+impl UpstreamConfig {
+    /// Read `kipper.toml` from `checkout`; a missing file means no requirements.
+    pub fn load(checkout: &Path) -> Result<UpstreamConfig, InstallerError> {
+        let path = checkout.join(UPSTREAM_FILE);
+        if !path.exists() {
+            return Ok(UpstreamConfig::default());
+        }
+        let contents = fs::read_to_string(&path)?;
+        toml::from_str(&contents)
+            .map_err(|e| InstallerError::PathError(format!("Invalid {} in kopi-lang: {}", UPSTREAM_FILE, e)))
+    }
+}
+
+impl Installer {
+    /// Stop before building `version` when it needs a newer kipper than this one.
+    pub(crate) fn enforce_min_kipper(&self, version: &str, checkout: &Path) -> Result<(), InstallerError> {
+        let Some(required) = UpstreamConfig::load(checkout)?.min_kipper_version else {
+            return Ok(());
+        };
+        let (Some(minimum), Some(running)) = (Release::parse(&required), Release::parse(KIPPER_VERSION)) else {
+            return Err(InstallerError::PathError(format!(
+                "kopi-lang {} asks for kipper '{}', which isn't a version",
+                version, required
+            )));
+        };
+        if running >= minimum {
+            return Ok(());
+        }
+
+        self.log_error(&format!(
+            "Kopi {} needs kipper {} or newer, but this is kipper {}",
+            version, required, KIPPER_VERSION
+        ));
+        self.log_info("Update kipper to its latest release, then run the install again");
+        Err(InstallerError::PathError(format!("kipper {} is too old for Kopi {}", KIPPER_VERSION, version)))
+    }
+}
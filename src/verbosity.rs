@@ -0,0 +1,134 @@
+// Per-subsystem verbosity: `KIPPER_LOG` picks which messages are shown and
+// logged, as a default level and/or `subsystem=level` pairs:
+//
+//     KIPPER_LOG=debug                  # everything, external commands included
+//     KIPPER_LOG=git=debug,build=warning
+//     KIPPER_LOG=error,git=debug        # only errors, except from git
+//
+// Levels are debug, info, warning, error and off; success messages count as
+// info. Messages belong to the subsystem of the phase running at the time
+// (fetch is `git`, then `build` and `install`), or to `kipper` outside the
+// phases. The commands kipper runs are debug messages of the subsystem they
+// serve: git of `git`, cargo and rustc of `build`, rustup of `toolchain`.
+// Without `KIPPER_LOG` everything but debug messages is shown.
+
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+use crate::LogLevel;
+use crate::timing::Phase;
+
+pub const LOG_FILTER_ENV: &str = "KIPPER_LOG";
+
+pub const SUBSYSTEMS: [&str; 5] = ["kipper", "git", "toolchain", "build", "install"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Verbosity {
+    Debug,
+    Info,
+    Warning,
+    Error,
+    Off,
+}
+
+impl FromStr for Verbosity {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "debug" | "trace" => Ok(Verbosity::Debug),
+            "info" => Ok(Verbosity::Info),
+            "warning" | "warn" => Ok(Verbosity::Warning),
+            "error" => Ok(Verbosity::Error),
+            "off" => Ok(Verbosity::Off),
+            other => Err(format!("unknown level '{}' (use debug, info, warning, error or off)", other)),
+        }
+    }
+}
+
+impl From<LogLevel> for Verbosity {
+    fn from(level: LogLevel) -> Self {
+        match level {
+            LogLevel::Debug => Verbosity::Debug,
+            LogLevel::Info | LogLevel::Success => Verbosity::Info,
+            LogLevel::Warning => Verbosity::Warning,
+            LogLevel::Error => Verbosity::Error,
+        }
+    }
+}
+
+/// The least severe level shown, overall and for each subsystem.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogFilter {
+    default: Verbosity,
+    subsystems: BTreeMap<&'static str, Verbosity>,
+}
+
+impl Default for LogFilter {
+    fn default() -> Self {
+        LogFilter {
+            default: Verbosity::Info,
+            subsystems: BTreeMap::new(),
+        }
+    }
+}
+
+impl FromStr for LogFilter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut filter = LogFilter::default();
+        for directive in s.split(',').map(str::trim).filter(|d| !d.is_empty()) {
+            match directive.split_once('=') {
+                None => filter.default = directive.parse()?,
+                Some((name, level)) => {
+                    let name = name.trim();
+                    let subsystem = SUBSYSTEMS.iter().find(|s| **s == name).ok_or_else(|| {
+                        format!("unknown subsystem '{}' (use {})", name, SUBSYSTEMS.join(", "))
+                    })?;
+                    filter.subsystems.insert(subsystem, level.parse()?);
+                }
+            }
+        }
+        Ok(filter)
+    }
+}
+
+impl LogFilter {
+    /// The filter `KIPPER_LOG` sets; an invalid value falls back to the
+    /// default (`main` reports it before anything runs).
+    pub fn from_env() -> LogFilter {
+        std::env::var(LOG_FILTER_ENV)
+            .ok()
+            .and_then(|spec| spec.parse().ok())
+            .unwrap_or_default()
+    }
+
+    /// Whether a `level` message from `subsystem` is shown.
+    pub fn allows(&self, subsystem: &str, level: LogLevel) -> bool {
+        let threshold = self.subsystems.get(subsystem).copied().unwrap_or(self.default);
+        // No message is as severe as `off`
+        Verbosity::from(level) >= threshold
+    }
+}
+
+/// The subsystem messages logged during `phase` belong to.
+pub fn phase_subsystem(phase: Option<Phase>) -> &'static str {
+    match phase {
+        Some(Phase::Fetch) => "git",
+        Some(Phase::Build) => "build",
+        Some(Phase::Install) => "install",
+        None => "kipper",
+    }
+}
+
+/// The subsystem running `program` serves, if it is one kipper knows.
+pub fn program_subsystem(program: &str) -> Option<&'static str> {
+    let name = std::path::Path::new(program).file_stem()?.to_str()?;
+    match name {
+        "git" => Some("git"),
+        "cargo" | "rustc" => Some("build"),
+        "rustup" => Some("toolchain"),
+        _ => None,
+    }
+}
@@ -0,0 +1,144 @@
+// Visual Studio Build Tools: the MSVC toolchain links with link.exe and the
+// Windows SDK's import libraries, which come with the Build Tools rather
+// than with Rust. Without them every build fails at the very end with
+// "linker `link.exe` not found", so the install is inspected through vswhere
+// beforehand, and what is missing is named down to the installer component.
+// With consent, and winget available, kipper runs the Build Tools installer
+// with just the C++ workload.
+
+use std::env;
+use std::io::{self, IsTerminal};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::check::CheckResult;
+use crate::{Installer, InstallerError};
+
+/// The Build Tools package in winget.
+pub const BUILD_TOOLS_PACKAGE: &str = "Microsoft.VisualStudio.2022.BuildTools";
+/// The installer workload with the C++ compiler, linker and a Windows SDK.
+pub const CPP_WORKLOAD: &str = "Microsoft.VisualStudio.Workload.VCTools";
+/// The component with the x64/x86 compilers and link.exe.
+pub const CPP_TOOLS_COMPONENT: &str = "Microsoft.VisualStudio.Component.VC.Tools.x86.x64";
+
+/// What the Visual Studio install has of what MSVC builds need.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BuildTools {
+    /// The C++ tools and a Windows SDK are installed.
+    Ready,
+    /// Visual Studio is at `PathBuf`, without the C++ build tools.
+    NoCppTools(PathBuf),
+    /// The C++ tools are there but no Windows SDK.
+    NoWindowsSdk,
+    /// No Visual Studio or Build Tools at all.
+    Missing,
+}
+
+impl BuildTools {
+    /// What to do about it, naming the component to add.
+    pub fn advice(&self) -> String {
+        match self {
+            BuildTools::Ready => String::new(),
+            BuildTools::NoCppTools(path) => format!(
+                "Visual Studio at {} lacks the C++ build tools; in the Visual Studio Installer, modify it and \
+                 select \"Desktop development with C++\" (component {})",
+                path.display(),
+                CPP_TOOLS_COMPONENT
+            ),
+            BuildTools::NoWindowsSdk => "The C++ build tools are installed without a Windows SDK; in the Visual \
+                 Studio Installer, add a \"Windows 11 SDK\" (or Windows 10 SDK) component"
+                .to_string(),
+            BuildTools::Missing => format!(
+                "Install the Visual Studio Build Tools with the \"Desktop development with C++\" workload:\n  \
+                 winget install --id {} --override \"--passive --wait --add {} --includeRecommended\"\n  \
+                 or download them from https://visualstudio.microsoft.com/visual-cpp-build-tools/",
+                BUILD_TOOLS_PACKAGE, CPP_WORKLOAD
+            ),
+        }
+    }
+}
+
+fn program_files() -> Option<PathBuf> {
+    env::var_os("ProgramFiles(x86)").or_else(|| env::var_os("ProgramFiles")).map(PathBuf::from)
+}
+
+/// vswhere, which every Visual Studio install (Build Tools included) puts
+/// in the same place.
+fn vswhere() -> Option<PathBuf> {
+    let path = program_files()?.join("Microsoft Visual Studio").join("Installer").join("vswhere.exe");
+    path.exists().then_some(path)
+}
+
+/// Whether a Windows 10 or 11 SDK is installed (both live under `10`).
+fn has_windows_sdk() -> bool {
+    env::var_os("WindowsSdkDir").is_some()
+        || program_files().is_some_and(|dir| dir.join("Windows Kits").join("10").join("Lib").is_dir())
+}
+
+impl Installer {
+    /// The first installation path vswhere reports for `args`.
+    fn vswhere_path(&self, vswhere: &Path, args: &[&str]) -> Option<PathBuf> {
+        let mut command = Command::new(vswhere);
+        command.args(["-latest", "-products", "*"]).args(args).args(["-property", "installationPath"]);
+        let output = self.run_command(&mut command).ok().filter(|output| output.status.success())?;
+        let stdout = String::from_utf8_lossy(&output.stdout).into_owned();
+        stdout.lines().map(str::trim).find(|line| !line.is_empty()).map(PathBuf::from)
+    }
+
+    /// What the Visual Studio install has for MSVC builds. A developer
+    /// prompt (VCINSTALLDIR set) counts as ready.
+    pub fn build_tools(&self) -> BuildTools {
+        if env::var_os("VCINSTALLDIR").is_some() {
+            return BuildTools::Ready;
+        }
+        let Some(vswhere) = vswhere() else {
+            return BuildTools::Missing;
+        };
+        if self.vswhere_path(&vswhere, &["-requires", CPP_TOOLS_COMPONENT]).is_none() {
+            return match self.vswhere_path(&vswhere, &[]) {
+                Some(path) => BuildTools::NoCppTools(path),
+                None => BuildTools::Missing,
+            };
+        }
+        if has_windows_sdk() { BuildTools::Ready } else { BuildTools::NoWindowsSdk }
+    }
+
+    /// Offer to install the Build Tools with winget when there are none.
+    /// Returns whether they are ready afterwards. Only asks at a terminal
+    /// (or with --force): the download is several gigabytes.
+    pub(crate) fn offer_build_tools_install(&self) -> Result<bool, InstallerError> {
+        if self.unattended || !(io::stdin().is_terminal() || self.is_forced()) || !self.command_exists("winget") {
+            return Ok(false);
+        }
+        if !self.ask("Install the Visual Studio Build Tools (C++ workload) with winget now?", false)? {
+            return Ok(false);
+        }
+        self.log_info("Running the Visual Studio Build Tools installer; this takes a while...");
+        let mut winget = Command::new("winget");
+        winget
+            .args(["install", "--id", BUILD_TOOLS_PACKAGE, "--exact", "--accept-package-agreements", "--override"])
+            .arg(format!("--passive --wait --add {} --includeRecommended", CPP_WORKLOAD));
+        // Its progress goes straight to the terminal
+        self.trace_command(&winget);
+        let status = winget.status()?;
+        if !status.success() {
+            self.log_warning("winget did not finish installing the Build Tools");
+            return Ok(false);
+        }
+        let ready = self.build_tools() == BuildTools::Ready;
+        if ready {
+            self.log_success("Visual Studio Build Tools installed");
+        }
+        Ok(ready)
+    }
+
+    /// Doctor's check of the Build Tools, for MSVC builds.
+    pub(crate) fn build_tools_check(&self) -> CheckResult {
+        match self.build_tools() {
+            BuildTools::Ready => CheckResult::pass("MSVC build tools", "C++ build tools and a Windows SDK found"),
+            missing => {
+                CheckResult::fail("MSVC build tools", "link.exe won't be found by MSVC builds", missing.advice())
+            }
+        }
+    }
+}
@@ -0,0 +1,108 @@
+// `kipper warm [VERSION]`: keep a build box ready for the next update. The
+// source mirror is fetched, `cargo fetch` downloads every dependency, and
+// the dependencies alone are compiled into the shared target dir, so the
+// `kipper update` that follows only compiles kopi-lang's own crates.
+//
+// Cargo can't build just the dependencies of a package, so the checkout's
+// own crates are replaced with empty ones first (the way cargo-chef does
+// it): the build then compiles the dependency graph with the same features,
+// profile and target as a real build, and the few empty crates it finishes
+// with are rebuilt from the real sources later anyway.
+//
+// Nothing here is tied to an init system: it is one command that exits when
+// done, to run from cron, a systemd timer, launchd or a CI schedule shortly
+// before updates happen, e.g. `0 2 * * * kipper warm` for a 03:00 window.
+
+use std::fs;
+use std::path::Path;
+use std::time::Instant;
+
+use crate::manifest::Manifest;
+use crate::timing::format_duration;
+use crate::toolchain::NIGHTLY;
+use crate::{Installer, InstallerError};
+
+/// The source an empty crate of `kind` needs, or None for targets a
+/// release build doesn't compile or that must stay (build scripts).
+fn stub_source(kind: &[String]) -> Option<&'static str> {
+    if kind.iter().any(|k| k == "bin") {
+        Some("fn main() {}\n")
+    } else if kind.iter().any(|k| matches!(k.as_str(), "lib" | "rlib" | "dylib" | "cdylib" | "staticlib" | "proc-macro")) {
+        Some("")
+    } else {
+        None
+    }
+}
+
+impl Installer {
+    /// Fetch `version` (the default version, or nightly when none is
+    /// installed) and compile its dependencies into the shared target dir.
+    pub fn warm(&self, version: Option<&str>) -> Result<(), InstallerError> {
+        let started = Instant::now();
+        let version = match version {
+            Some(version) => self.resolve_release(version)?,
+            None => self.default_version().unwrap_or_else(|| NIGHTLY.to_string()),
+        };
+        self.log_info(&format!("Warming the build cache for Kopi {}...", version));
+        self.prepare_install()?;
+        self.clone_source(Some(&version))?;
+        self.verify_pin(&version)?;
+        let clone_dir = self.temp_dir.join("kopi-lang");
+        let commit = self.source_record()?.commit;
+        self.probe_build_dependencies(&clone_dir)?;
+        self.check_rust_version(&clone_dir)?;
+
+        self.log_info("Downloading dependencies...");
+        let mut fetch = self.cargo_command(&clone_dir)?;
+        fetch.arg("fetch");
+        if self.locked {
+            fetch.arg("--locked");
+        }
+        if let Some(target) = self.build_target() {
+            fetch.args(["--target", target]);
+        }
+        let output = self.run_command(&mut fetch)?;
+        if !output.status.success() {
+            let error = String::from_utf8_lossy(&output.stderr);
+            return Err(InstallerError::Cargo(format!("cargo fetch failed: {}", self.bounded_output("cargo fetch", &error))));
+        }
+
+        let manifest = Manifest::load(&clone_dir)?;
+        self.stub_own_crates(&clone_dir)?;
+        self.prepare_build_dir(&clone_dir, &version)?;
+        self.log_info("Building dependencies (this may take a few minutes)...");
+        let output = self.run_command(&mut self.release_build(&clone_dir, &version, manifest.as_ref())?)?;
+        self.finish_build_dir(&clone_dir);
+        if !output.status.success() {
+            let error = String::from_utf8_lossy(&output.stderr);
+            return Err(InstallerError::Cargo(format!(
+                "Building the dependencies failed: {}",
+                self.bounded_output("cargo build", &error)
+            )));
+        }
+
+        self.log_success(&format!(
+            "Dependencies of Kopi {} ({}) are built in {} ({}); an update to it only compiles kopi-lang itself",
+            version,
+            &commit[..commit.len().min(7)],
+            self.cargo_target_dir(&clone_dir).display(),
+            format_duration(started.elapsed())
+        ));
+        Ok(())
+    }
+
+    /// Replace the source of every library and binary in the checkout's
+    /// workspace with an empty one.
+    fn stub_own_crates(&self, clone_dir: &Path) -> Result<(), InstallerError> {
+        let metadata = self.workspace_metadata(clone_dir)?;
+        let mut stubbed = 0;
+        for target in metadata.packages.iter().flat_map(|package| &package.targets) {
+            if let Some(source) = stub_source(&target.kind) {
+                fs::write(&target.src_path, source)?;
+                stubbed += 1;
+            }
+        }
+        self.log_debug("build", &format!("Replaced {} of kopi-lang's crates with empty ones", stubbed));
+        Ok(())
+    }
+}
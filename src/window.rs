@@ -0,0 +1,114 @@
+// Maintenance windows (`[update] windows` in kipper.toml): when a timer runs
+// `kipper update --auto`, it only switches to a new build inside one of these
+// windows. Outside them the update is built and left prepared, for the first
+// run inside a window to commit. Each window is a local time range, on every
+// day or on the days given:
+//
+//     [update]
+//     windows = ["03:00-05:00", "Sat,Sun 10:00-18:00", "Mon-Fri 22:00-02:00"]
+//
+// A range that ends before it starts runs past midnight into the next day.
+
+use std::fmt;
+use std::str::FromStr;
+
+const DAYS: [&str; 7] = ["monday", "tuesday", "wednesday", "thursday", "friday", "saturday", "sunday"];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MaintenanceWindow {
+    /// The days the window starts on, Monday first.
+    days: [bool; 7],
+    /// Minutes after midnight.
+    start: u32,
+    end: u32,
+}
+
+/// A day as `Mon`, `monday` or anything in between.
+fn parse_day(day: &str) -> Result<usize, String> {
+    let lower = day.to_ascii_lowercase();
+    DAYS.iter()
+        .position(|name| lower.len() >= 3 && name.starts_with(&lower))
+        .ok_or_else(|| format!("unknown day '{}'", day))
+}
+
+fn parse_time(time: &str) -> Result<u32, String> {
+    let invalid = || format!("invalid time '{}' (expected HH:MM)", time);
+    let (hours, minutes) = time.split_once(':').ok_or_else(invalid)?;
+    let hours: u32 = hours.parse().map_err(|_| invalid())?;
+    let minutes: u32 = minutes.parse().map_err(|_| invalid())?;
+    if minutes >= 60 || hours > 24 || (hours == 24 && minutes > 0) {
+        return Err(invalid());
+    }
+    Ok(hours * 60 + minutes)
+}
+
+impl FromStr for MaintenanceWindow {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (days_part, times) = match s.trim().rsplit_once(char::is_whitespace) {
+            Some((days, times)) => (Some(days.trim()), times),
+            None => (None, s.trim()),
+        };
+
+        let mut days = [days_part.is_none(); 7];
+        for item in days_part.into_iter().flat_map(|d| d.split(',')) {
+            match item.trim().split_once('-') {
+                Some((first, last)) => {
+                    let (first, last) = (parse_day(first.trim())?, parse_day(last.trim())?);
+                    let mut day = first;
+                    loop {
+                        days[day] = true;
+                        if day == last {
+                            break;
+                        }
+                        day = (day + 1) % 7;
+                    }
+                }
+                None => days[parse_day(item.trim())?] = true,
+            }
+        }
+
+        let (start, end) = times
+            .split_once('-')
+            .ok_or_else(|| format!("invalid time range '{}' (expected HH:MM-HH:MM)", times))?;
+        let (start, end) = (parse_time(start)?, parse_time(end)?);
+        if start == end {
+            return Err(format!("time range '{}' is empty", times));
+        }
+        Ok(MaintenanceWindow { days, start, end })
+    }
+}
+
+impl MaintenanceWindow {
+    /// Whether the window is open on `weekday` (0 is Monday) at `minute`
+    /// after midnight.
+    pub fn contains(&self, weekday: usize, minute: u32) -> bool {
+        if self.start < self.end {
+            return self.days[weekday] && (self.start..self.end).contains(&minute);
+        }
+        (self.days[weekday] && minute >= self.start) || (self.days[(weekday + 6) % 7] && minute < self.end)
+    }
+}
+
+impl fmt::Display for MaintenanceWindow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if !self.days.iter().all(|&open| open) {
+            let names: Vec<String> = DAYS
+                .iter()
+                .zip(self.days)
+                .filter(|(_, open)| *open)
+                .map(|(name, _)| format!("{}{}", name[..1].to_uppercase(), &name[1..3]))
+                .collect();
+            write!(f, "{} ", names.join(","))?;
+        }
+        write!(
+            f,
+            "{:02}:{:02}-{:02}:{:02}",
+            self.start / 60,
+            self.start % 60,
+            self.end / 60,
+            self.end % 60
+        )
+    }
+}
@@ -0,0 +1,108 @@
+// Cargo layout of the kopi-lang checkout: whether it is a workspace, which
+// packages and binaries to build, and where cargo puts what it built.
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+use crate::manifest::Manifest;
+use crate::state::Profile;
+use crate::{Installer, InstallerError};
+
+/// What `cargo metadata --no-deps` says about the checkout's own packages.
+#[derive(Debug, Deserialize)]
+pub struct Metadata {
+    pub packages: Vec<Package>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Package {
+    pub name: String,
+    pub manifest_path: PathBuf,
+    pub targets: Vec<Target>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Target {
+    pub name: String,
+    pub kind: Vec<String>,
+    pub src_path: PathBuf,
+}
+
+impl Package {
+    pub fn has_binaries(&self) -> bool {
+        self.targets.iter().any(|target| target.kind.iter().any(|kind| kind == "bin"))
+    }
+}
+
+/// Whether the checkout's root Cargo.toml declares a workspace.
+pub fn is_workspace(checkout: &Path) -> bool {
+    fs::read_to_string(checkout.join("Cargo.toml"))
+        .ok()
+        .and_then(|contents| contents.parse::<toml::Table>().ok())
+        .is_some_and(|manifest| manifest.contains_key("workspace"))
+}
+
+/// The `build.target-dir` set in the checkout's cargo config, resolved
+/// against the directory holding `.cargo`, as cargo does.
+fn configured_target_dir(checkout: &Path) -> Option<PathBuf> {
+    ["config.toml", "config"].iter().find_map(|name| {
+        let config = fs::read_to_string(checkout.join(".cargo").join(name)).ok()?;
+        let table = config.parse::<toml::Table>().ok()?;
+        let dir = table.get("build")?.get("target-dir")?.as_str()?;
+        Some(checkout.join(dir))
+    })
+}
+
+/// The target dir set for the checkout: `CARGO_TARGET_DIR` when the build
+/// sees the environment's, else the checkout's cargo config.
+pub fn chosen_target_dir(checkout: &Path, from_env: bool) -> Option<PathBuf> {
+    env::var_os("CARGO_TARGET_DIR")
+        .or_else(|| env::var_os("CARGO_BUILD_TARGET_DIR"))
+        .filter(|dir| from_env && !dir.is_empty())
+        .map(|dir| checkout.join(dir))
+        .or_else(|| configured_target_dir(checkout))
+}
+
+/// Arguments selecting what `cargo build` compiles for `profile`. Binaries
+/// the manifest places in a package are built with `-p`; otherwise a
+/// workspace is built whole, narrowed to `kopi` for the minimal profile.
+pub fn build_selection(checkout: &Path, profile: Profile, manifest: Option<&Manifest>) -> Vec<String> {
+    let mut args = Vec::new();
+    let packages = manifest.map(|m| m.packages(profile)).unwrap_or_default();
+    if !packages.is_empty() {
+        for package in packages {
+            args.extend(["-p".to_string(), package]);
+        }
+    } else if is_workspace(checkout) {
+        args.push("--workspace".to_string());
+    }
+
+    // Skip compiling tools the profile won't install
+    if profile == Profile::Minimal {
+        match manifest {
+            Some(manifest) => {
+                for binary in manifest.binaries(profile) {
+                    args.extend(["--bin".to_string(), binary.to_string()]);
+                }
+            }
+            None => args.extend(["--bin".to_string(), "kopi".to_string()]),
+        }
+    }
+    args
+}
+
+impl Installer {
+    /// The packages of the checkout's workspace, as cargo sees them.
+    pub(crate) fn workspace_metadata(&self, checkout: &Path) -> Result<Metadata, InstallerError> {
+        let mut cargo = self.cargo_command(checkout)?;
+        let output = self.run_command(cargo.args(["metadata", "--no-deps", "--offline", "--format-version", "1"]))?;
+        if !output.status.success() {
+            let error = String::from_utf8_lossy(&output.stderr);
+            return Err(InstallerError::Cargo(format!("cargo metadata failed: {}", error.trim_end())));
+        }
+        serde_json::from_slice(&output.stdout).map_err(|e| InstallerError::Cargo(format!("Can't read cargo metadata: {}", e)))
+    }
+}
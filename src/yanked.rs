@@ -0,0 +1,79 @@
+// Yanked versions: kopi-lang lists releases found to be broken in
+// `yanked.toml` on its default branch, e.g.
+//
+//     [[yanked]]
+//     version = "v0.3.0"
+//     reason = "miscompiles closures captured by value"
+//
+// The list comes with every fetch of the source cache, so mirrors carry it
+// too. Yanked versions aren't installed without `--force`, are never what
+// `latest` or `stable` resolve to, and are reported by `kipper doctor`.
+
+use serde::Deserialize;
+
+use crate::{Installer, InstallerError};
+
+const YANKED_FILE: &str = "yanked.toml";
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Yanked {
+    pub version: String,
+    #[serde(default)]
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct YankedList {
+    #[serde(default)]
+    yanked: Vec<Yanked>,
+}
+
+impl Yanked {
+    pub fn describe(&self) -> String {
+        match &self.reason {
+            Some(reason) => format!("Kopi {} has been yanked: {}", self.version, reason),
+            None => format!("Kopi {} has been yanked", self.version),
+        }
+    }
+}
+
+impl Installer {
+    /// Versions yanked as of the last fetch of the source cache. Empty when
+    /// there is no cache yet or kopi-lang doesn't publish the list.
+    pub fn yanked_versions(&self) -> Vec<Yanked> {
+        if !self.source_cache().exists() {
+            return Vec::new();
+        }
+        let Ok(output) = self.git_in_cache(&["show", &format!("HEAD:{}", YANKED_FILE)]) else {
+            return Vec::new();
+        };
+        if !output.status.success() {
+            return Vec::new();
+        }
+        match toml::from_str::<YankedList>(&String::from_utf8_lossy(&output.stdout)) {
+            Ok(list) => list.yanked,
+            Err(e) => {
+                self.log_warning(&format!("Ignoring kopi-lang's {}: {}", YANKED_FILE, e));
+                Vec::new()
+            }
+        }
+    }
+
+    pub fn yanked(&self, version: &str) -> Option<Yanked> {
+        self.yanked_versions().into_iter().find(|y| y.version == version)
+    }
+
+    /// Refuse to build a yanked `version` unless `--force` was given.
+    pub(crate) fn refuse_yanked(&self, version: &str) -> Result<(), InstallerError> {
+        let Some(yanked) = self.yanked(version) else {
+            return Ok(());
+        };
+        if self.is_forced() {
+            self.log_warning(&format!("{}; installing anyway because of --force", yanked.describe()));
+            return Ok(());
+        }
+        self.log_error(&yanked.describe());
+        self.log_info("Install another version, or re-run with --force to install it anyway");
+        Err(InstallerError::Git(format!("Kopi {} is yanked", version)))
+    }
+}